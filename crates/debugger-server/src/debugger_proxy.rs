@@ -1,24 +1,149 @@
 use futures::SinkExt;
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::mpsc, usize};
+use lazy_static::lazy_static;
 use std::{
-    sync::{Arc, Mutex},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    rc::Rc,
+    sync::mpsc,
+    usize,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     thread,
+    time::{Duration, Instant},
 };
 use tokio_tungstenite::tungstenite::Message;
 use wasmparser::FuncType;
 
+use crate::output::{GuestOutput, Stream};
 use crate::rpc::{self, WasmExport};
 use crate::serialization;
 use wasminspect_debugger::{
-    try_load_dwarf, CommandContext, CommandResult, Debugger, Interactive, MainDebugger, Process,
+    try_load_dwarf, Breakpoint, BreakpointId, CommandContext, Debugger, FrameInfo, Interactive,
+    MainDebugger, OffsetBase, Process,
 };
-use wasminspect_vm::{HostFuncBody, HostValue, MemoryAddr, NumVal, Trap, WasmValue};
+use wasminspect_vm::{HostFuncBody, HostValue, MemoryAddr, ModuleIndex, Trap, WasmValue};
 
 static VERSION: &str = "0.2.0";
+/// Protocol features which clients can negotiate by `Handshake`
+const SERVER_FEATURES: &[&str] = &[
+    "breakpoint-events",
+    "breakpoints",
+    "line-breakpoints",
+    "lz4-memory",
+    "multi-memory",
+    "progress",
+    "simd",
+    "stats",
+    "typed-memory",
+];
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// `LoadMemory` results larger than this are compressed for clients supporting
+/// `lz4-memory`, unless overridden by `WASMINSPECT_SERVER_COMPRESS_THRESHOLD`
+const DEFAULT_COMPRESS_THRESHOLD: usize = 64 * 1024;
+
+lazy_static! {
+    static ref COMPRESS_THRESHOLD: usize = std::env::var("WASMINSPECT_SERVER_COMPRESS_THRESHOLD")
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .unwrap_or(DEFAULT_COMPRESS_THRESHOLD);
+}
 
 pub type ProcessRef = Rc<RefCell<Process<MainDebugger>>>;
 pub type CommandCtxRef = Rc<RefCell<CommandContext>>;
 
+/// Protocol negotiation of a session
+enum HandshakeState {
+    /// No request has been handled yet
+    Pending,
+    /// The client sent `Handshake` with a compatible version
+    Accepted { features: Vec<String> },
+    /// The client started without `Handshake`, so every feature is assumed
+    Skipped,
+    /// The client version is incompatible, and no more requests are served
+    Rejected,
+}
+
+thread_local! {
+    /// Each connection runs its debugger on its own thread
+    static GUEST_OUTPUT: RefCell<GuestOutput> = RefCell::new(GuestOutput::default());
+    static HANDSHAKE: RefCell<HandshakeState> = RefCell::new(HandshakeState::Pending);
+    /// Chunks uploaded by `InitChunk` until `InitFinalize`
+    static INIT_CHUNKS: RefCell<Option<rpc::ChunkBuffer>> = RefCell::new(None);
+}
+
+/// Accepts `Handshake` only as the first request, and rejects requests after a failed one
+fn negotiate(req: &rpc::Request) -> Result<Option<rpc::TextResponse>, rpc::RequestError> {
+    HANDSHAKE.with(|state| {
+        let mut state = state.borrow_mut();
+        match (&*state, &req.body) {
+            (HandshakeState::Rejected, _) => Err(rpc::RequestError::HandshakeRejected),
+            (
+                HandshakeState::Pending,
+                rpc::RequestBody::Text(rpc::TextRequest::Handshake {
+                    client_version,
+                    supported_features,
+                }),
+            ) => {
+                if !rpc::is_compatible_version(client_version, VERSION) {
+                    *state = HandshakeState::Rejected;
+                    return Err(rpc::RequestError::IncompatibleClientVersion {
+                        client_version: client_version.clone(),
+                        server_version: VERSION.to_string(),
+                    });
+                }
+                let accepted_features = supported_features
+                    .iter()
+                    .filter(|feature| SERVER_FEATURES.contains(&feature.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                *state = HandshakeState::Accepted {
+                    features: accepted_features.clone(),
+                };
+                Ok(Some(rpc::TextResponse::HandshakeResult {
+                    server_version: VERSION.to_string(),
+                    accepted_features,
+                }))
+            }
+            (_, rpc::RequestBody::Text(rpc::TextRequest::Handshake { .. })) => {
+                Err(rpc::RequestError::LateHandshake)
+            }
+            (HandshakeState::Pending, _) => {
+                *state = HandshakeState::Skipped;
+                Ok(None)
+            }
+            (HandshakeState::Accepted { .. }, _) | (HandshakeState::Skipped, _) => Ok(None),
+        }
+    })
+}
+
+/// Whether the client advertised `feature` by `Handshake`, for features
+/// changing the format of responses which older clients can't read
+fn is_feature_accepted(feature: &str) -> bool {
+    HANDSHAKE.with(|state| match &*state.borrow() {
+        HandshakeState::Accepted { features } => features.iter().any(|f| f == feature),
+        _ => false,
+    })
+}
+
+/// Responds to `LoadMemory` with `bytes`, which are compressed if they are large
+/// and the client supports it
+fn load_memory_response(offset: usize, bytes: Vec<u8>) -> rpc::Response {
+    if bytes.len() > *COMPRESS_THRESHOLD && is_feature_accepted("lz4-memory") {
+        rpc::ResponseBody::Binary {
+            kind: rpc::BinaryResponseKind::LoadMemoryCompressed,
+            bytes: rpc::compress_memory(offset, &bytes),
+        }
+        .into()
+    } else {
+        rpc::TextResponse::LoadMemoryResult { bytes }.into()
+    }
+}
+
 pub fn handle_request<S: futures::Sink<Message> + Unpin + Send + 'static>(
     req: rpc::Request,
     process: ProcessRef,
@@ -29,33 +154,83 @@ pub fn handle_request<S: futures::Sink<Message> + Unpin + Send + 'static>(
 where
     S::Error: std::error::Error,
 {
-    match req {
-        rpc::Request::Text(ref req) => {
+    match req.body {
+        rpc::RequestBody::Text(ref req) => {
             log::debug!("Received TextRequest: {:?}", req);
         }
-        rpc::Request::Binary(ref req) => {
+        rpc::RequestBody::Binary(ref req) => {
             log::debug!("Received BinaryRequest: {:?}", req.kind);
         }
     };
-    let res = match _handle_request(req, process, context, tx, rx) {
+    let request_id = req.request_id;
+    let res = match negotiate(&req) {
+        Ok(Some(handshake)) => Ok(handshake.into()),
+        Ok(None) => _handle_request(req, process, context, tx.clone(), rx),
+        Err(err) => Err(err.into()),
+    };
+    let res = match res {
         Ok(res) => res,
         Err(err) => rpc::TextResponse::Error {
             message: err.to_string(),
         }
         .into(),
     };
+    let res = res.with_request_id(request_id);
+    // The process has halted, so send the rest of its output before the response
+    if let Err(err) = flush_guest_output(tx) {
+        log::error!("Failed to send guest output: {:?}", err);
+    }
 
-    match res {
-        rpc::Response::Text(ref req) => {
+    match res.body {
+        rpc::ResponseBody::Text(ref req) => {
             log::debug!("Sending TextResponse: {:?}", req);
         }
-        rpc::Response::Binary { ref kind, .. } => {
+        rpc::ResponseBody::Binary { ref kind, .. } => {
             log::debug!("Sending BinaryResponse: {:?}", kind);
         }
     };
     res
 }
 
+/// Returns the range of `length` bytes from `offset` if it is within the memory
+fn memory_range(
+    offset: usize,
+    length: usize,
+    memory_size: usize,
+) -> Result<std::ops::Range<usize>, rpc::RequestError> {
+    offset
+        .checked_add(length)
+        .filter(|end| *end <= memory_size)
+        .map(|end| offset..end)
+        .ok_or(rpc::RequestError::MemoryOutOfBounds {
+            offset,
+            length,
+            memory_size,
+        })
+}
+
+/// Decodes `count` values of `ty` from `memory` at `offset`
+fn load_memory_typed(
+    memory: &[u8],
+    offset: usize,
+    count: usize,
+    ty: rpc::ValueType,
+) -> Result<rpc::TextResponse, rpc::RequestError> {
+    let out_of_bounds = || rpc::RequestError::MemoryOutOfBounds {
+        offset,
+        length: count.saturating_mul(ty.size()),
+        memory_size: memory.len(),
+    };
+    let end = count
+        .checked_mul(ty.size())
+        .and_then(|length| offset.checked_add(length))
+        .ok_or_else(out_of_bounds)?;
+    let bytes = memory.get(offset..end).ok_or_else(out_of_bounds)?;
+    Ok(rpc::TextResponse::LoadMemoryTypedResult {
+        values: ty.decode(bytes),
+    })
+}
+
 fn from_js_number(value: rpc::JSNumber, ty: &wasmparser::Type) -> WasmValue {
     match ty {
         wasmparser::Type::I32 => wasminspect_vm::WasmValue::I32(value as i32),
@@ -70,30 +245,45 @@ fn from_js_number(value: rpc::JSNumber, ty: &wasmparser::Type) -> WasmValue {
     }
 }
 
-#[allow(dead_code)]
+fn arg_matches(arg: &rpc::WasmValue, ty: &wasmparser::Type) -> bool {
+    matches!(
+        (arg, ty),
+        (rpc::WasmValue::I32 { .. }, wasmparser::Type::I32)
+            | (rpc::WasmValue::I64 { .. }, wasmparser::Type::I64)
+            | (rpc::WasmValue::F32 { .. }, wasmparser::Type::F32)
+            | (rpc::WasmValue::F64 { .. }, wasmparser::Type::F64)
+            | (rpc::WasmValue::V128 { .. }, wasmparser::Type::V128)
+    )
+}
+
 fn to_vm_wasm_value(value: &rpc::WasmValue) -> WasmValue {
-    match value {
-        rpc::WasmValue::F32 { value } => WasmValue::F32((*value).to_bits()),
-        rpc::WasmValue::F64 { value } => WasmValue::F64((*value).to_bits()),
-        rpc::WasmValue::I32 { value } => WasmValue::I32(*value),
-        rpc::WasmValue::I64 { value } => WasmValue::I64(*value),
-    }
+    WasmValue::from(*value)
 }
 
 fn from_vm_wasm_value(value: &WasmValue) -> rpc::WasmValue {
-    match value {
-        WasmValue::Num(NumVal::F32(v)) => rpc::WasmValue::F32 {
-            value: v.to_float(),
-        },
-        WasmValue::Num(NumVal::F64(v)) => rpc::WasmValue::F64 {
-            value: v.to_float(),
-        },
-        WasmValue::Num(NumVal::I32(v)) => rpc::WasmValue::I32 { value: *v },
-        WasmValue::Num(NumVal::I64(v)) => rpc::WasmValue::I64 { value: *v },
-        WasmValue::Ref(_) => todo!("reference type is not supported yet"),
+    match rpc::WasmValue::try_from(*value) {
+        Ok(value) => value,
+        Err(_) => todo!("reference type is not supported yet"),
     }
 }
 
+fn named_results(values: &[WasmValue], return_type: Option<&str>) -> Vec<rpc::NamedValue> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let name = match return_type {
+                Some(ty) if values.len() == 1 => ty.to_string(),
+                _ => format!("result_{}", index),
+            };
+            rpc::NamedValue {
+                name: Some(name),
+                value: from_vm_wasm_value(value),
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 struct RemoteCallError(String);
 impl std::fmt::Display for RemoteCallError {
@@ -163,35 +353,87 @@ where
                 .map_err(|e| Trap::HostFunctionError(Box::new(e)))?;
             let request = serialization::deserialize_request(&message)
                 .map_err(|e| Trap::HostFunctionError(Box::new(e)))?;
-            match request {
-                rpc::Request::Text(rpc::TextRequest::CallResult { values }) => break values,
-                rpc::Request::Text(rpc::TextRequest::StoreMemory {
+            let request_id = request.request_id;
+            match request.body {
+                rpc::RequestBody::Text(rpc::TextRequest::CallResult { values }) => break values,
+                rpc::RequestBody::Text(rpc::TextRequest::StoreMemory {
                     name: _,
                     offset,
                     bytes,
                 }) => {
-                    for (idx, byte) in bytes.iter().enumerate() {
-                        ctx.mem[offset + idx] = *byte;
-                    }
-                    blocking_send_response(
-                        rpc::TextResponse::StoreMemoryResult.into(),
-                        tx.clone(),
-                    )?;
+                    let mem: &mut [u8] = match ctx.mems.first_mut() {
+                        Some(mem) => mem,
+                        None => &mut [],
+                    };
+                    let response = match memory_range(offset, bytes.len(), mem.len()) {
+                        Ok(range) => {
+                            mem[range].copy_from_slice(&bytes);
+                            rpc::TextResponse::StoreMemoryResult
+                        }
+                        Err(err) => rpc::TextResponse::Error {
+                            message: err.to_string(),
+                        },
+                    };
+                    let response = rpc::Response::from(response);
+                    blocking_send_response(response.with_request_id(request_id), tx.clone())?;
                 }
-                rpc::Request::Text(rpc::TextRequest::LoadMemory {
+                rpc::RequestBody::Text(rpc::TextRequest::LoadMemory {
                     name: _,
                     offset,
                     length,
                 }) => {
-                    let bytes = ctx.mem[offset..offset + length].to_vec();
-                    blocking_send_response(
-                        rpc::TextResponse::LoadMemoryResult { bytes }.into(),
-                        tx.clone(),
-                    )?;
+                    let mem: &[u8] = ctx.mems.first().map_or(&[], |mem| mem);
+                    let response = match memory_range(offset, length, mem.len()) {
+                        Ok(range) => load_memory_response(offset, mem[range].to_vec()),
+                        Err(err) => rpc::TextResponse::Error {
+                            message: err.to_string(),
+                        }
+                        .into(),
+                    };
+                    blocking_send_response(response.with_request_id(request_id), tx.clone())?;
                 }
-                rpc::Request::Text(rpc::TextRequest::CallExported { name, args }) => {
-                    let res = call_exported(name, args, process.clone(), context.clone()).unwrap();
-                    blocking_send_response(res, tx.clone())?;
+                rpc::RequestBody::Text(rpc::TextRequest::LoadMemoryTyped {
+                    name: _,
+                    offset,
+                    count,
+                    ty,
+                }) => {
+                    let mem: &[u8] = ctx.mems.first().map_or(&[], |mem| mem);
+                    let response = match load_memory_typed(mem, offset, count, ty) {
+                        Ok(response) => response,
+                        Err(err) => rpc::TextResponse::Error {
+                            message: err.to_string(),
+                        },
+                    };
+                    let response = rpc::Response::from(response);
+                    blocking_send_response(response.with_request_id(request_id), tx.clone())?;
+                }
+                rpc::RequestBody::Text(rpc::TextRequest::CallExported {
+                    name,
+                    args,
+                    progress_interval_instructions,
+                    include_stats,
+                }) => {
+                    let options = CallOptions {
+                        request_id: request_id.unwrap_or_default(),
+                        progress_interval: progress_interval_instructions,
+                        include_stats,
+                    };
+                    let res = match call_exported(
+                        name,
+                        args,
+                        options,
+                        process.clone(),
+                        context.clone(),
+                        tx.clone(),
+                    ) {
+                        Ok(res) => res,
+                        Err(err) => rpc::TextResponse::Error {
+                            message: err.to_string(),
+                        }
+                        .into(),
+                    };
+                    blocking_send_response(res.with_request_id(request_id), tx.clone())?;
                 }
                 other => {
                     let error = RemoteCallError(format!(
@@ -211,6 +453,84 @@ where
     })
 }
 
+fn output_response(stream: Stream, data: String) -> rpc::Response {
+    match stream {
+        Stream::Stdout => rpc::TextResponse::Stdout { data },
+        Stream::Stderr => rpc::TextResponse::Stderr { data },
+    }
+    .into()
+}
+
+fn flush_guest_output<S: futures::Sink<Message> + Unpin + Send + 'static>(
+    tx: Arc<Mutex<S>>,
+) -> Result<(), Trap> {
+    let flushed = GUEST_OUTPUT.with(|output| output.borrow_mut().flush());
+    for (stream, data) in flushed {
+        blocking_send_response(output_response(stream, data), tx.clone())?;
+    }
+    Ok(())
+}
+
+/// Wraps the remote `fd_write` to send writes to stdout and stderr as
+/// `Stdout`/`Stderr` messages, forwarding the other file descriptors
+fn fd_write_fn<S: futures::Sink<Message> + Unpin + Send + 'static>(
+    remote: HostFuncBody,
+    tx: Arc<Mutex<S>>,
+) -> HostFuncBody {
+    const ERRNO_SUCCESS: i32 = 0;
+    const ERRNO_FAULT: i32 = 21;
+    HostFuncBody::new(remote.ty().clone(), move |args, results, ctx, store| {
+        let (stream, iovs, iovs_len, nwritten) = match args {
+            [fd, iovs, iovs_len, nwritten] => match fd.as_i32().and_then(Stream::from_fd) {
+                Some(stream) => (stream, *iovs, *iovs_len, *nwritten),
+                None => return remote.call_with_context(args, results, ctx, store),
+            },
+            _ => return remote.call_with_context(args, results, ctx, store),
+        };
+        let address = |value: &WasmValue| value.as_i32().unwrap_or_default() as u32 as usize;
+        // fd_write only reads and writes the memory 0
+        let mem: &mut [u8] = match ctx.mems.first_mut() {
+            Some(mem) => mem,
+            None => &mut [],
+        };
+        let read_u32 = |mem: &[u8], offset: usize| -> Option<usize> {
+            let bytes = mem.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        };
+        let mut data = Vec::new();
+        let mut errno = ERRNO_SUCCESS;
+        for index in 0..address(&iovs_len) {
+            // An iovec is a pair of a pointer and a length
+            let iov = address(&iovs) + index * 8;
+            let bytes = read_u32(mem, iov)
+                .zip(read_u32(mem, iov + 4))
+                .and_then(|(buf, len)| mem.get(buf..buf + len));
+            match bytes {
+                Some(bytes) => data.extend_from_slice(bytes),
+                None => {
+                    errno = ERRNO_FAULT;
+                    break;
+                }
+            }
+        }
+        if errno == ERRNO_SUCCESS {
+            let nwritten = address(&nwritten);
+            match mem.get_mut(nwritten..nwritten + 4) {
+                Some(dest) => dest.copy_from_slice(&(data.len() as u32).to_le_bytes()),
+                None => errno = ERRNO_FAULT,
+            }
+        }
+        if errno == ERRNO_SUCCESS {
+            let line = GUEST_OUTPUT.with(|output| output.borrow_mut().write(stream, &data));
+            if let Some(line) = line {
+                blocking_send_response(output_response(stream, line), tx.clone())?;
+            }
+        }
+        *results = vec![WasmValue::I32(errno)];
+        Ok(())
+    })
+}
+
 type ImportModule = HashMap<String, HostValue>;
 
 fn remote_import_module<S: futures::Sink<Message> + Unpin + Send + 'static>(
@@ -255,7 +575,7 @@ where
                         None => continue,
                     };
 
-                    let func = remote_call_fn(
+                    let mut func = remote_call_fn(
                         field_name.to_string(),
                         import.module.to_string(),
                         process.clone(),
@@ -264,6 +584,11 @@ where
                         tx.clone(),
                         rx.clone(),
                     );
+                    let is_wasi =
+                        matches!(import.module, "wasi_snapshot_preview1" | "wasi_unstable");
+                    if is_wasi && field_name == "fd_write" {
+                        func = fd_write_fn(func, tx.clone());
+                    }
                     modules
                         .entry(import.module.to_string())
                         .or_default()
@@ -281,9 +606,23 @@ fn module_exports(bytes: &[u8]) -> anyhow::Result<Vec<WasmExport>> {
     let parser = wasmparser::Parser::new(0);
     let mut exports = Vec::<WasmExport>::new();
     let mut mems = Vec::new();
+    // Imported globals come first in the index space
+    let mut globals = Vec::new();
 
     for payload in parser.parse_all(bytes) {
         match payload? {
+            wasmparser::Payload::ImportSection(iter) => {
+                for import in iter {
+                    if let wasmparser::ImportSectionEntryType::Global(ty) = import?.ty {
+                        globals.push(ty);
+                    }
+                }
+            }
+            wasmparser::Payload::GlobalSection(iter) => {
+                for global in iter {
+                    globals.push(global?.ty);
+                }
+            }
             wasmparser::Payload::MemorySection(iter) => {
                 for mem in iter {
                     let mem = mem?;
@@ -304,6 +643,14 @@ fn module_exports(bytes: &[u8]) -> anyhow::Result<Vec<WasmExport>> {
                         wasmparser::ExternalKind::Function => exports.push(WasmExport::Function {
                             name: export.field.to_string(),
                         }),
+                        wasmparser::ExternalKind::Global => {
+                            let ty = globals[export.index as usize];
+                            exports.push(WasmExport::Global {
+                                name: export.field.to_string(),
+                                mutable: ty.mutable,
+                                ty: format!("{:?}", ty.content_type).to_lowercase(),
+                            })
+                        }
                         _ => unimplemented!("unsupported export kind {:?}", export.kind),
                     }
                 }
@@ -314,59 +661,155 @@ fn module_exports(bytes: &[u8]) -> anyhow::Result<Vec<WasmExport>> {
     Ok(exports)
 }
 
-fn call_exported(
+/// Periodically reports the progress of a running call until dropped
+struct ProgressReporter {
+    stop_tx: mpsc::Sender<()>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    fn start<S: futures::Sink<Message> + Unpin + Send + 'static>(
+        request_id: u64,
+        interval: u64,
+        counter: Arc<AtomicU64>,
+        tx: Arc<Mutex<S>>,
+    ) -> Self {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let base = counter.load(Ordering::Relaxed);
+            let mut last_reported = 0;
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stop_rx.recv_timeout(PROGRESS_POLL_INTERVAL)
+            {
+                let executed = counter.load(Ordering::Relaxed) - base;
+                if executed - last_reported < interval {
+                    continue;
+                }
+                last_reported = executed;
+                let progress = rpc::TextResponse::Progress {
+                    request_id,
+                    instructions_executed: executed,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                };
+                if blocking_send_response(progress.into(), tx.clone()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            stop_tx,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        let _ = self.stop_tx.send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct CallOptions {
+    request_id: u64,
+    /// Instructions between progress notifications, or 0 to disable them
+    progress_interval: u64,
+    include_stats: bool,
+}
+
+/// Measures a call for `ExecutionStats`
+struct StatsRecorder {
+    started: Instant,
+    instructions_before: u64,
+}
+
+impl StatsRecorder {
+    fn start(debugger: &MainDebugger) -> Self {
+        debugger.reset_call_stats();
+        Self {
+            started: Instant::now(),
+            instructions_before: debugger.executed_instructions().load(Ordering::Relaxed),
+        }
+    }
+
+    fn finish(&self, debugger: &MainDebugger) -> rpc::ExecutionStats {
+        let instructions = debugger.executed_instructions().load(Ordering::Relaxed);
+        rpc::ExecutionStats {
+            instructions_executed: instructions - self.instructions_before,
+            functions_called: debugger.functions_called(),
+            peak_call_depth: debugger.peak_call_depth(),
+            wall_time_ms: self.started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+fn call_exported<S: futures::Sink<Message> + Unpin + Send + 'static>(
     name: String,
-    args: Vec<f64>,
+    args: Vec<rpc::WasmValue>,
+    options: CallOptions,
     process: ProcessRef,
     context: CommandCtxRef,
+    tx: Arc<Mutex<S>>,
 ) -> Result<rpc::Response, anyhow::Error> {
     use rpc::*;
     use wasminspect_debugger::RunResult;
 
     let func = process.borrow().debugger.lookup_func(&name)?;
+    let return_type = context
+        .borrow()
+        .subroutine
+        .return_type_name(&name)
+        .ok()
+        .flatten();
     let func_ty = process.borrow().debugger.func_type(func)?;
     if func_ty.params.len() != args.len() {
         return Err(RequestError::CallArgumentLengthMismatch.into());
     }
-    let args = args
-        .iter()
-        .zip(func_ty.params.iter())
-        .map(|(arg, ty)| from_js_number(*arg, ty))
-        .collect();
-    let result = { process.borrow_mut().debugger.execute_func(func, args) };
+    for (index, (arg, ty)) in args.iter().zip(func_ty.params.iter()).enumerate() {
+        if !arg_matches(arg, ty) {
+            return Err(RequestError::CallArgumentTypeMismatch { index }.into());
+        }
+    }
+    let args = args.iter().map(to_vm_wasm_value).collect();
+    let recorder = if options.include_stats {
+        Some(StatsRecorder::start(&process.borrow().debugger))
+    } else {
+        None
+    };
+    let stats = |process: &ProcessRef| {
+        recorder
+            .as_ref()
+            .map(|recorder| recorder.finish(&process.borrow().debugger))
+    };
+    let result = {
+        let _reporter = if options.progress_interval > 0 {
+            let counter = process.borrow().debugger.executed_instructions();
+            Some(ProgressReporter::start(
+                options.request_id,
+                options.progress_interval,
+                counter,
+                tx.clone(),
+            ))
+        } else {
+            None
+        };
+        process.borrow_mut().debugger.execute_func(func, args)
+    };
     match result {
         Ok(RunResult::Finish(values)) => {
-            let values = values.iter().map(from_vm_wasm_value).collect();
-            Ok(TextResponse::CallResult { values }.into())
+            let values = named_results(&values, return_type.as_deref());
+            let stats = stats(&process);
+            Ok(TextResponse::CallResult { values, stats }.into())
         }
-        Ok(RunResult::Breakpoint) => {
-            // use std::borrow::{Borrow, BorrowMut};
-            let mut interactive = Interactive::new_with_loading_history().unwrap();
-            let mut result = { interactive.run_loop(&*context.borrow(), process.clone())? };
-            loop {
-                match result {
-                    CommandResult::ProcessFinish(values) => {
-                        let values = values.iter().map(from_vm_wasm_value).collect();
-                        return Ok(TextResponse::CallResult { values }.into());
-                    }
-                    CommandResult::Exit => {
-                        let cmd_result = {
-                            process
-                                .borrow_mut()
-                                .dispatch_command("process continue", &*context.borrow())?
-                        };
-                        match cmd_result {
-                            Some(r) => {
-                                result = r;
-                            }
-                            None => {
-                                result =
-                                    interactive.run_loop(&*context.borrow(), process.clone())?;
-                            }
-                        }
-                    }
-                }
-            }
+        Ok(RunResult::Breakpoint)
+        | Ok(RunResult::Watchpoint(_))
+        | Ok(RunResult::MemoryWatchpoint(_)) => {
+            // The client resumes the process by Continue or Step
+            notify_breakpoint_hit(&process.borrow().debugger, tx)?;
+            Ok(halted_breakpoint(&process.borrow().debugger).into())
         }
         Err(msg) => {
             let mut interactive = Interactive::new_with_loading_history().unwrap();
@@ -380,6 +823,83 @@ fn call_exported(
     }
 }
 
+/// Loads modules given by `Init`, where the last one is the main module
+fn init_modules<S: futures::Sink<Message> + Unpin + Send + 'static>(
+    modules: Vec<(String, Vec<u8>)>,
+    process: ProcessRef,
+    context: CommandCtxRef,
+    tx: Arc<Mutex<S>>,
+    rx: Arc<mpsc::Receiver<Option<Message>>>,
+) -> Result<rpc::Response, anyhow::Error>
+where
+    S::Error: std::error::Error,
+{
+    GUEST_OUTPUT.with(|output| *output.borrow_mut() = GuestOutput::default());
+    let mut imports = HashMap::new();
+    let mut loaded_names = HashSet::new();
+    for (name, bytes) in &modules {
+        let module_imports = remote_import_module(
+            bytes,
+            process.clone(),
+            context.clone(),
+            tx.clone(),
+            rx.clone(),
+        )?;
+        // Imports from modules loaded earlier are resolved by the store
+        for (module, fields) in module_imports {
+            if !loaded_names.contains(&module) {
+                imports
+                    .entry(module)
+                    .or_insert_with(ImportModule::new)
+                    .extend(fields);
+            }
+        }
+        loaded_names.insert(name.clone());
+    }
+    let (main_name, main_bytes) = match modules.split_last() {
+        Some(((name, bytes), rest)) => {
+            for (name, bytes) in rest {
+                process
+                    .borrow_mut()
+                    .debugger
+                    .load_module_named(bytes, name.clone())?;
+            }
+            (name, bytes)
+        }
+        None => return Err(anyhow::anyhow!("no module is given")),
+    };
+    process
+        .borrow_mut()
+        .debugger
+        .load_main_module(main_bytes, main_name.clone())?;
+    process.borrow_mut().debugger.instantiate(imports, None)?;
+    match try_load_dwarf(
+        main_bytes,
+        &mut *Clone::clone(&context).borrow_mut(),
+        &mut process.borrow_mut().debugger,
+    ) {
+        Ok(_) => (),
+        Err(err) => {
+            log::warn!("Failed to load dwarf info: {}", err);
+        }
+    }
+    let loaded = modules
+        .iter()
+        .map(|(name, bytes)| {
+            Ok(rpc::ModuleExports {
+                name: name.clone(),
+                exports: module_exports(bytes)?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let exports = module_exports(main_bytes)?;
+    Ok(rpc::TextResponse::Init {
+        exports,
+        modules: loaded,
+    }
+    .into())
+}
+
 fn _handle_request<S: futures::Sink<Message> + Unpin + Send + 'static>(
     req: rpc::Request,
     process: ProcessRef,
@@ -391,46 +911,69 @@ where
     S::Error: std::error::Error,
 {
     use rpc::BinaryRequestKind::*;
-    use rpc::Request::*;
+    use rpc::RequestBody::*;
     use rpc::TextRequest::*;
     use rpc::*;
 
-    match req {
+    let request_id = req.request_id;
+    match req.body {
         Binary(req) => match req.kind {
-            Init => {
-                let imports =
-                    remote_import_module(req.bytes, process.clone(), context.clone(), tx, rx)?;
-                process
-                    .borrow_mut()
-                    .debugger
-                    .load_main_module(req.bytes, "_remote_main".to_string())?;
-                process.borrow_mut().debugger.instantiate(imports, None)?;
-                match try_load_dwarf(
-                    &req.bytes.to_vec(),
-                    &mut *Clone::clone(&context).borrow_mut(),
-                ) {
-                    Ok(_) => (),
-                    Err(err) => {
-                        log::warn!("Failed to load dwarf info: {}", err);
-                    }
+            Init => init_modules(req.bytes, process, context, tx, rx),
+            InitChunk => {
+                let chunk = req.chunk.ok_or_else(|| {
+                    RequestError::MalformedBinaryRequest("no chunk is given".to_string())
+                })?;
+                let index = chunk.index;
+                INIT_CHUNKS.with(|buffer| {
+                    buffer
+                        .borrow_mut()
+                        .get_or_insert_with(|| ChunkBuffer::new(chunk.total))
+                        .insert(chunk)
+                })?;
+                Ok(rpc::ResponseBody::Binary {
+                    kind: rpc::BinaryResponseKind::InitChunkAck,
+                    bytes: index.to_le_bytes().to_vec(),
                 }
-                let exports = module_exports(req.bytes)?;
-                Ok(rpc::Response::Text(TextResponse::Init { exports }))
+                .into())
+            }
+            InitFinalize => {
+                let buffer = INIT_CHUNKS
+                    .with(|buffer| buffer.borrow_mut().take())
+                    .ok_or(RequestError::NoChunks)?;
+                let modules = decode_init_payload(&buffer.assemble()?)?;
+                init_modules(modules, process, context, tx, rx)
             }
         },
         Text(InitMemory) => {
-            let init_memory = rpc::Response::Binary {
+            let init_memory = rpc::ResponseBody::Binary {
                 kind: rpc::BinaryResponseKind::InitMemory,
                 bytes: process.borrow().debugger.memory()?,
             };
-            Ok(init_memory)
+            Ok(init_memory.into())
         }
         Text(Version) => Ok(TextResponse::Version {
             value: VERSION.to_string(),
         }
         .into()),
         Text(CallResult { .. }) => unreachable!(),
-        Text(CallExported { name, args }) => call_exported(name, args, process, context),
+        Text(Handshake { .. }) => unreachable!("handled by negotiate"),
+        Text(CallExported {
+            name,
+            args,
+            progress_interval_instructions,
+            include_stats,
+        }) => call_exported(
+            name,
+            args,
+            CallOptions {
+                request_id: request_id.unwrap_or_default(),
+                progress_interval: progress_interval_instructions,
+                include_stats,
+            },
+            process,
+            context,
+            tx,
+        ),
         Text(LoadMemory {
             name,
             offset,
@@ -439,8 +982,22 @@ where
             let process = process.borrow();
             let memory_addr = memory_addr_by_name(&name, &process.debugger)?;
             let memory = process.debugger.store()?.memory(memory_addr);
-            let bytes = memory.borrow().raw_data()[offset..offset + length].to_vec();
-            Ok(TextResponse::LoadMemoryResult { bytes }.into())
+            let memory = memory.borrow();
+            let range = memory_range(offset, length, memory.data_len())?;
+            let bytes = memory.raw_data()[range].to_vec();
+            Ok(load_memory_response(offset, bytes))
+        }
+        Text(LoadMemoryTyped {
+            name,
+            offset,
+            count,
+            ty,
+        }) => {
+            let process = process.borrow();
+            let memory_addr = memory_addr_by_name(&name, &process.debugger)?;
+            let memory = process.debugger.store()?.memory(memory_addr);
+            let memory = memory.borrow();
+            Ok(load_memory_typed(memory.raw_data(), offset, count, ty)?.into())
         }
         Text(StoreMemory {
             name,
@@ -450,14 +1007,275 @@ where
             let process = process.borrow();
             let memory_addr = memory_addr_by_name(&name, &process.debugger)?;
             let memory = process.debugger.store()?.memory(memory_addr);
-            for (idx, byte) in bytes.iter().enumerate() {
-                memory.borrow_mut().raw_data_mut()[offset + idx] = *byte;
-            }
+            let mut memory = memory.borrow_mut();
+            let range = memory_range(offset, bytes.len(), memory.data_len())?;
+            memory.raw_data_mut()[range].copy_from_slice(&bytes);
             Ok(TextResponse::StoreMemoryResult.into())
         }
+        Text(MemoryChecksum {
+            name,
+            offset,
+            length,
+        }) => {
+            let process = process.borrow();
+            let memory_addr = memory_addr_by_name(&name, &process.debugger)?;
+            let memory = process.debugger.store()?.memory(memory_addr);
+            let memory = memory.borrow();
+            let bytes = memory
+                .raw_data()
+                .get(offset..offset + length)
+                .ok_or_else(|| anyhow::anyhow!("memory range out of bounds"))?;
+            let checksum = crc32fast::hash(bytes);
+            Ok(TextResponse::MemoryChecksumResult { checksum }.into())
+        }
+        Text(MemorySize { name }) => {
+            let process = process.borrow();
+            let memory_addr = memory_addr_by_name(&name, &process.debugger)?;
+            let memory = process.debugger.store()?.memory(memory_addr);
+            let size = memory.borrow().raw_data().len();
+            Ok(TextResponse::MemorySizeResult { size }.into())
+        }
+        Text(Continue) => {
+            use wasminspect_debugger::RunResult;
+            let result = { process.borrow_mut().debugger.process()? };
+            match result {
+                RunResult::Finish(values) => {
+                    let values = named_results(&values, None);
+                    Ok(TextResponse::CallResult {
+                        values,
+                        stats: None,
+                    }
+                    .into())
+                }
+                RunResult::Breakpoint
+                | RunResult::Watchpoint(_)
+                | RunResult::MemoryWatchpoint(_) => {
+                    let process = process.borrow();
+                    notify_breakpoint_hit(&process.debugger, tx)?;
+                    Ok(TextResponse::Stopped {
+                        inst_offset: next_inst_offset(&process.debugger),
+                    }
+                    .into())
+                }
+            }
+        }
+        Text(Step { style }) => {
+            use wasminspect_debugger::StepStyle as DebuggerStepStyle;
+            let style = match style {
+                StepStyle::In => DebuggerStepStyle::InstIn,
+                StepStyle::Over => DebuggerStepStyle::InstOver,
+                StepStyle::Out => DebuggerStepStyle::Out,
+            };
+            let process = process.borrow();
+            process.debugger.step(style)?;
+            Ok(TextResponse::Stopped {
+                inst_offset: next_inst_offset(&process.debugger),
+            }
+            .into())
+        }
+        Text(ReadGlobal {
+            module_index,
+            global_index,
+        }) => {
+            let process = process.borrow();
+            let globals = process.debugger.globals(Some(ModuleIndex(module_index)))?;
+            let (mutable, value) = globals.get(global_index as usize).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} is out of range, globals length is {}",
+                    global_index,
+                    globals.len()
+                )
+            })?;
+            Ok(TextResponse::ReadGlobalResult {
+                value: from_vm_wasm_value(value),
+                mutable: *mutable,
+            }
+            .into())
+        }
+        Text(WriteGlobal {
+            module_index,
+            global_index,
+            value,
+        }) => {
+            process.borrow_mut().debugger.set_global(
+                Some(ModuleIndex(module_index)),
+                global_index as usize,
+                to_vm_wasm_value(&value),
+            )?;
+            Ok(TextResponse::WriteGlobalResult.into())
+        }
+        Text(ReadGlobals { module_index }) => {
+            let process = process.borrow();
+            let module_index = match module_index {
+                Some(module_index) => ModuleIndex(module_index),
+                None => process.debugger.main_module_index()?,
+            };
+            let globals = process.debugger.globals(Some(module_index))?;
+            let (mutable, values) = globals
+                .iter()
+                .map(|(mutable, value)| (*mutable, from_vm_wasm_value(value)))
+                .unzip();
+            Ok(TextResponse::ReadGlobalsResult { values, mutable }.into())
+        }
+        Text(ReadGlobalByName { name }) => {
+            let value = process.borrow().debugger.global_by_name(&name)?;
+            Ok(TextResponse::ReadGlobalByNameResult {
+                value: value.as_ref().map(from_vm_wasm_value),
+            }
+            .into())
+        }
+        Text(SetBreakpoint { name, hit_count }) => {
+            if hit_count == Some(0) {
+                return Err(anyhow::anyhow!("hitCount should be greater than 0"));
+            }
+            let id = process
+                .borrow_mut()
+                .debugger
+                .set_breakpoint(Breakpoint::Function {
+                    name,
+                    condition: None,
+                    hit_count,
+                })?;
+            Ok(TextResponse::SetBreakpointResult { id: id.0 }.into())
+        }
+        Text(SetBreakpointAtLine { file, line }) => {
+            let inst_offset = context
+                .borrow()
+                .sourcemap
+                .find_line_address(&file, line)
+                .ok_or(RequestError::NoCodeAtLine { file, line })?;
+            let id = process
+                .borrow_mut()
+                .debugger
+                .set_breakpoint(Breakpoint::Instruction {
+                    base: OffsetBase::Absolute,
+                    offset: inst_offset,
+                    condition: None,
+                })?;
+            Ok(TextResponse::SetBreakpointResult { id: id.0 }.into())
+        }
+        Text(RemoveBreakpoint { id }) => {
+            if !process
+                .borrow_mut()
+                .debugger
+                .delete_breakpoint(BreakpointId(id))
+            {
+                return Err(RequestError::NoSuchBreakpoint(id).into());
+            }
+            Ok(TextResponse::RemoveBreakpointResult.into())
+        }
+        Text(ListBreakpoints) => {
+            let breakpoints = process
+                .borrow()
+                .debugger
+                .list_breakpoints()
+                .into_iter()
+                .map(|(id, breakpoint)| BreakpointInfo {
+                    id: id.0,
+                    location: breakpoint_location(&breakpoint),
+                })
+                .collect();
+            Ok(TextResponse::ListBreakpointsResult { breakpoints }.into())
+        }
+    }
+}
+
+fn breakpoint_location(breakpoint: &Breakpoint) -> String {
+    match breakpoint {
+        Breakpoint::Function { name, .. }
+        | Breakpoint::FunctionConditional { name, .. }
+        | Breakpoint::FunctionOnce { name } => name.clone(),
+        Breakpoint::Instruction {
+            base: OffsetBase::Absolute,
+            offset,
+            ..
+        } => format!("{:#x}", offset),
+        Breakpoint::Instruction {
+            base: OffsetBase::Function(func),
+            offset,
+            ..
+        } => format!("{}+{:#x}", func, offset),
+        Breakpoint::WatchLocal { local_index, .. } => format!("local {}", local_index),
+        Breakpoint::MemoryWrite { offset, len } => {
+            format!("memory {:#x}..{:#x}", offset, offset + len)
+        }
+    }
+}
+
+/// Describes where the process halted, with the ID of the breakpoint that
+/// matches the innermost frame
+/// Finds the breakpoint set over RPC which halted the process in `frame`
+fn halted_breakpoint_id(debugger: &MainDebugger, frame: &FrameInfo) -> Option<u32> {
+    debugger
+        .list_breakpoints()
+        .into_iter()
+        .find(|(_, breakpoint)| match breakpoint {
+            Breakpoint::Function { name, .. }
+            | Breakpoint::FunctionConditional { name, .. }
+            | Breakpoint::FunctionOnce { name } => *name == frame.func_name,
+            Breakpoint::Instruction {
+                base: OffsetBase::Absolute,
+                offset,
+                ..
+            } => *offset == frame.instruction_offset,
+            _ => false,
+        })
+        .map(|(id, _)| id.0)
+}
+
+/// Pushes `BreakpointHit` with the locals of the halted frame, so that the
+/// client observes them without requesting
+fn notify_breakpoint_hit<S: futures::Sink<Message> + Unpin + Send + 'static>(
+    debugger: &MainDebugger,
+    tx: Arc<Mutex<S>>,
+) -> Result<(), Trap> {
+    let frame = match debugger.frame().pop() {
+        Some(frame) => frame,
+        None => return Ok(()),
+    };
+    let id = match halted_breakpoint_id(debugger, &frame) {
+        Some(id) => id,
+        // Halted by a watchpoint or a breakpoint set in the interactive session
+        None => return Ok(()),
+    };
+    let locals = debugger.locals().iter().map(from_vm_wasm_value).collect();
+    let event = rpc::TextResponse::BreakpointHit {
+        id,
+        func_name: frame.func_name,
+        offset: frame.instruction_offset,
+        locals,
+    };
+    blocking_send_response(event.into(), tx)
+}
+
+fn halted_breakpoint(debugger: &MainDebugger) -> rpc::TextResponse {
+    let inst_offset = next_inst_offset(debugger);
+    let frame = debugger.frame().pop();
+    let id = frame
+        .as_ref()
+        .and_then(|frame| halted_breakpoint_id(debugger, frame));
+    let location = match frame {
+        Some(frame) => match frame.source_location {
+            Some(source) => format!(
+                "{} at {}:{}:{}",
+                frame.func_name, source.file, source.line, source.column
+            ),
+            None => format!("{} at {:#x}", frame.func_name, frame.instruction_offset),
+        },
+        None => "unknown".to_string(),
+    };
+    rpc::TextResponse::Breakpoint {
+        id,
+        location,
+        inst_offset,
     }
 }
 
+fn next_inst_offset(debugger: &MainDebugger) -> Option<usize> {
+    let (insts, next_index) = debugger.selected_instructions().ok()?;
+    insts.get(next_index).map(|inst| inst.offset)
+}
+
 fn memory_addr_by_name(name: &str, debugger: &MainDebugger) -> Result<MemoryAddr, anyhow::Error> {
     let addr = debugger
         .main_module()?
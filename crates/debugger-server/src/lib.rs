@@ -1,4 +1,5 @@
 mod debugger_proxy;
+mod output;
 mod rpc;
 mod serialization;
 mod socket;
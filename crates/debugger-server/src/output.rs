@@ -0,0 +1,126 @@
+/// A standard stream of the guest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn from_fd(fd: i32) -> Option<Self> {
+        match fd {
+            1 => Some(Self::Stdout),
+            2 => Some(Self::Stderr),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the length of `bytes` without a trailing incomplete UTF-8 sequence
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        // Skip continuation bytes to find the first byte of the last sequence
+        if byte & 0xc0 == 0x80 {
+            continue;
+        }
+        let sequence_len = match byte {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        if sequence_len > back {
+            return bytes.len() - back;
+        }
+        break;
+    }
+    bytes.len()
+}
+
+#[derive(Default)]
+struct LineBuffer {
+    bytes: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Appends `bytes` and takes the text up to the last newline
+    fn write(&mut self, bytes: &[u8]) -> Option<String> {
+        self.bytes.extend_from_slice(bytes);
+        let end = self.bytes.iter().rposition(|byte| *byte == b'\n')? + 1;
+        Some(self.take(end))
+    }
+
+    /// Takes the buffered text, keeping a trailing incomplete UTF-8 sequence
+    /// until the next write completes it
+    fn flush(&mut self) -> Option<String> {
+        match complete_utf8_len(&self.bytes) {
+            0 => None,
+            end => Some(self.take(end)),
+        }
+    }
+
+    fn take(&mut self, end: usize) -> String {
+        let rest = self.bytes.split_off(end);
+        let bytes = std::mem::replace(&mut self.bytes, rest);
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/// Buffers stdout and stderr of the guest to send them line by line
+#[derive(Default)]
+pub struct GuestOutput {
+    stdout: LineBuffer,
+    stderr: LineBuffer,
+}
+
+impl GuestOutput {
+    fn buffer(&mut self, stream: Stream) -> &mut LineBuffer {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    /// Returns the text to send now, which ends with a newline
+    pub fn write(&mut self, stream: Stream, bytes: &[u8]) -> Option<String> {
+        self.buffer(stream).write(bytes)
+    }
+
+    /// Returns the buffered text of each stream, e.g. when the process halts
+    pub fn flush(&mut self) -> Vec<(Stream, String)> {
+        [Stream::Stdout, Stream::Stderr]
+            .iter()
+            .filter_map(|stream| Some((*stream, self.buffer(*stream).flush()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_lines_and_split_utf8() {
+        let mut output = GuestOutput::default();
+        assert_eq!(output.write(Stream::Stdout, b"hello"), None);
+        assert_eq!(
+            output.write(Stream::Stdout, b" world\nnext"),
+            Some("hello world\n".to_string())
+        );
+        assert_eq!(output.write(Stream::Stderr, b"error"), None);
+
+        // "é" is split across writes
+        let bytes = "é".as_bytes();
+        assert_eq!(output.write(Stream::Stdout, &bytes[..1]), None);
+        assert_eq!(
+            output.flush(),
+            vec![
+                (Stream::Stdout, "next".to_string()),
+                (Stream::Stderr, "error".to_string())
+            ]
+        );
+        assert_eq!(output.write(Stream::Stdout, &bytes[1..]), None);
+        assert_eq!(output.flush(), vec![(Stream::Stdout, "é".to_string())]);
+        assert!(output.flush().is_empty());
+    }
+}
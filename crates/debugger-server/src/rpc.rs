@@ -2,52 +2,50 @@
 
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum WasmValue {
-    I32 { value: i32 },
-    I64 { value: i64 },
-    F32 { value: f32 },
-    F64 { value: f64 },
-}
-
-pub type JSNumber = f64;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub enum WasmImport {
-    Func { name: String },
-    Global { name: String },
-    Mem { name: String },
-    Table { name: String },
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum WasmExport {
-    Memory {
-        name: String,
-        #[serde(rename = "memorySize")]
-        memory_size: usize,
-    },
-    Function {
-        name: String,
-    },
-    Global {
-        name: String,
-    },
-    Table {
-        name: String,
-    },
-}
+pub use wasminspect_debugger::rpc::*;
 
 #[derive(Debug)]
 pub enum RequestError {
     InvalidBinaryRequestKind(u8),
+    MalformedBinaryRequest(String),
     InvalidTextRequestJSON(Box<dyn std::error::Error + Send + Sync>),
     InvalidMessageType(String),
     CallArgumentLengthMismatch,
+    CallArgumentTypeMismatch {
+        index: usize,
+    },
+    NoSuchBreakpoint(u32),
+    NoCodeAtLine {
+        file: String,
+        line: u64,
+    },
+    MemoryOutOfBounds {
+        offset: usize,
+        length: usize,
+        memory_size: usize,
+    },
+    IncompatibleClientVersion {
+        client_version: String,
+        server_version: String,
+    },
+    /// `Handshake` is sent after other requests of the session
+    LateHandshake,
+    /// A request is sent after the handshake of the session failed
+    HandshakeRejected,
+    ChunkIndexOutOfRange {
+        index: u32,
+        total: u32,
+    },
+    /// `InitChunk` requests of an upload disagree on the number of chunks
+    ChunkCountMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    MissingChunks {
+        indices: Vec<u32>,
+    },
+    /// `InitFinalize` is sent without any `InitChunk`
+    NoChunks,
 }
 
 impl std::fmt::Display for RequestError {
@@ -57,100 +55,223 @@ impl std::fmt::Display for RequestError {
 }
 impl std::error::Error for RequestError {}
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum TextRequest {
-    Version,
-    InitMemory,
-    CallExported {
-        name: String,
-        args: Vec<JSNumber>,
-    },
-    CallResult {
-        values: Vec<JSNumber>,
-    },
-    LoadMemory {
-        name: String,
-        offset: usize,
-        length: usize,
-    },
-    StoreMemory {
-        name: String,
-        offset: usize,
-        bytes: Vec<u8>,
-    },
+/// Whether a client speaking the protocol `client_version` can talk to the server.
+/// Versions are compatible if their major versions match, or their minor versions too before 1.0
+pub fn is_compatible_version(client_version: &str, server_version: &str) -> bool {
+    fn major_minor(version: &str) -> Option<(u64, u64)> {
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().unwrap_or("0").parse().ok()?;
+        Some((major, minor))
+    }
+    match (major_minor(client_version), major_minor(server_version)) {
+        (Some((0, client_minor)), Some((0, server_minor))) => client_minor == server_minor,
+        (Some((client_major, _)), Some((server_major, _))) => client_major == server_major,
+        _ => false,
+    }
 }
 
 #[derive(FromPrimitive, Debug)]
 pub enum BinaryRequestKind {
     Init = 0,
+    /// A piece of an `Init` payload, prefixed with `(index: u32le, total: u32le)`
+    InitChunk = 1,
+    /// Loads the payload assembled from `InitChunk` requests as `Init`
+    InitFinalize = 2,
 }
 
 const WASM_MAGIC: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
 
+/// The module name used when a bare wasm binary is sent as `Init`
+pub const DEFAULT_MODULE_NAME: &str = "_remote_main";
+
 #[derive(Debug)]
-pub struct BinaryRequest<'a> {
+pub struct BinaryRequest {
     pub kind: BinaryRequestKind,
-    pub bytes: &'a [u8],
+    /// Named modules in loading order. The last one is the main module.
+    pub bytes: Vec<(String, Vec<u8>)>,
+    /// The piece of the payload sent by `InitChunk`
+    pub chunk: Option<UploadChunk>,
+}
+
+#[derive(Debug)]
+pub struct UploadChunk {
+    pub index: u32,
+    pub total: u32,
+    pub data: Vec<u8>,
 }
 
-impl<'a> BinaryRequest<'a> {
-    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, RequestError> {
+/// Chunks of an `Init` payload uploaded by `InitChunk` requests, which may arrive in any order
+#[derive(Debug)]
+pub struct ChunkBuffer {
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl ChunkBuffer {
+    pub fn new(total: u32) -> Self {
+        Self {
+            chunks: vec![None; total as usize],
+        }
+    }
+
+    /// Stores a chunk, replacing the one with the same index if it's sent again
+    pub fn insert(&mut self, chunk: UploadChunk) -> Result<(), RequestError> {
+        let total = self.chunks.len() as u32;
+        if chunk.total != total {
+            return Err(RequestError::ChunkCountMismatch {
+                expected: total,
+                actual: chunk.total,
+            });
+        }
+        let slot = self.chunks.get_mut(chunk.index as usize).ok_or(
+            RequestError::ChunkIndexOutOfRange {
+                index: chunk.index,
+                total,
+            },
+        )?;
+        *slot = Some(chunk.data);
+        Ok(())
+    }
+
+    /// Concatenates the chunks if all of them have arrived
+    pub fn assemble(self) -> Result<Vec<u8>, RequestError> {
+        let missing = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_none())
+            .map(|(index, _)| index as u32)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(RequestError::MissingChunks { indices: missing });
+        }
+        Ok(self.chunks.into_iter().flatten().flatten().collect())
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], RequestError> {
+    let end = pos.saturating_add(len);
+    let chunk = bytes.get(*pos..end).ok_or_else(|| {
+        RequestError::MalformedBinaryRequest("unexpected end of request".to_string())
+    })?;
+    *pos = end;
+    Ok(chunk)
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, RequestError> {
+    let value = take(bytes, pos, 4)?;
+    Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn read_chunk<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8], RequestError> {
+    let len = read_u32(bytes, pos)? as usize;
+    take(bytes, pos, len)
+}
+
+/// Decodes a list of `(name length: u32le, name, bytes length: u32le, bytes)` entries
+fn decode_named_modules(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, RequestError> {
+    let mut modules = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let name = read_chunk(bytes, &mut pos)?;
+        let name = String::from_utf8(name.to_vec())
+            .map_err(|e| RequestError::MalformedBinaryRequest(e.to_string()))?;
+        let module = read_chunk(bytes, &mut pos)?;
+        modules.push((name, module.to_vec()));
+    }
+    if modules.is_empty() {
+        return Err(RequestError::MalformedBinaryRequest(
+            "no module is given".to_string(),
+        ));
+    }
+    Ok(modules)
+}
+
+/// Decodes the payload of `Init`, which is a bare wasm binary or a list of named modules
+pub fn decode_init_payload(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, RequestError> {
+    if bytes.len() >= 4 && bytes[0..4].eq(&WASM_MAGIC) {
+        Ok(vec![(DEFAULT_MODULE_NAME.to_string(), bytes.to_vec())])
+    } else {
+        decode_named_modules(bytes)
+    }
+}
+
+impl BinaryRequest {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RequestError> {
         if bytes.len() >= 4 && bytes[0..4].eq(&WASM_MAGIC) {
-            Ok(Self {
+            return Ok(Self {
                 kind: BinaryRequestKind::Init,
-                bytes,
-            })
-        } else if let Some(kind) = FromPrimitive::from_u8(bytes[0]) {
-            Ok(Self {
-                kind,
-                bytes: &bytes[1..],
-            })
-        } else {
-            Err(RequestError::InvalidBinaryRequestKind(bytes[0]))
+                bytes: decode_init_payload(bytes)?,
+                chunk: None,
+            });
+        }
+        let kind = bytes.first().and_then(|kind| FromPrimitive::from_u8(*kind));
+        match kind {
+            Some(BinaryRequestKind::Init) => Ok(Self {
+                kind: BinaryRequestKind::Init,
+                bytes: decode_named_modules(&bytes[1..])?,
+                chunk: None,
+            }),
+            Some(BinaryRequestKind::InitChunk) => {
+                let mut pos = 1;
+                let index = read_u32(bytes, &mut pos)?;
+                let total = read_u32(bytes, &mut pos)?;
+                Ok(Self {
+                    kind: BinaryRequestKind::InitChunk,
+                    bytes: vec![],
+                    chunk: Some(UploadChunk {
+                        index,
+                        total,
+                        data: bytes[pos..].to_vec(),
+                    }),
+                })
+            }
+            Some(BinaryRequestKind::InitFinalize) => Ok(Self {
+                kind: BinaryRequestKind::InitFinalize,
+                bytes: vec![],
+                chunk: None,
+            }),
+            None => Err(RequestError::InvalidBinaryRequestKind(
+                bytes.first().copied().unwrap_or_default(),
+            )),
         }
     }
 }
 
 #[derive(Debug)]
-pub enum Request<'a> {
+pub enum RequestBody {
     Text(TextRequest),
-    Binary(BinaryRequest<'a>),
+    Binary(BinaryRequest),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum TextResponse {
-    Version {
-        value: String,
-    },
-    Init {
-        exports: Vec<WasmExport>,
-    },
-    CallResult {
-        values: Vec<WasmValue>,
-    },
-    CallHost {
-        module: String,
-        field: String,
-        args: Vec<WasmValue>,
-    },
-    LoadMemoryResult {
-        bytes: Vec<u8>,
-    },
-    StoreMemoryResult,
-    Error {
-        message: String,
-    },
+#[derive(Debug)]
+pub struct Request {
+    /// Echoed back in the response to correlate them. Binary requests have no id
+    /// and their responses are matched by arrival order.
+    pub request_id: Option<u64>,
+    pub body: RequestBody,
 }
+
 #[derive(Debug)]
 #[repr(u8)]
 pub enum BinaryResponseKind {
     InitMemory = 0,
+    /// Acknowledges an `InitChunk` request with its index in u32le
+    InitChunkAck = 1,
+    /// `LoadMemoryResult` for clients supporting `lz4-memory`, see `compress_memory`
+    LoadMemoryCompressed = 2,
+}
+
+/// Encodes `bytes` loaded from `offset` as the offset in u64le followed by the
+/// bytes in the LZ4 block format, prepended with their length in u32le
+pub fn compress_memory(offset: usize, bytes: &[u8]) -> Vec<u8> {
+    let mut payload = (offset as u64).to_le_bytes().to_vec();
+    payload.extend(lz4_flex::compress_prepend_size(bytes));
+    payload
 }
 
 #[derive(Debug)]
-pub enum Response {
+pub enum ResponseBody {
     Text(TextResponse),
     Binary {
         kind: BinaryResponseKind,
@@ -158,8 +279,81 @@ pub enum Response {
     },
 }
 
+#[derive(Debug)]
+pub struct Response {
+    /// The id of the request this responds to. `None` for messages which are
+    /// not responses, e.g. `Progress` and `CallHost`.
+    pub request_id: Option<u64>,
+    pub body: ResponseBody,
+}
+
+impl Response {
+    pub fn with_request_id(self, request_id: Option<u64>) -> Self {
+        Self { request_id, ..self }
+    }
+}
+
+impl From<ResponseBody> for Response {
+    fn from(body: ResponseBody) -> Self {
+        Response {
+            request_id: None,
+            body,
+        }
+    }
+}
+
 impl From<TextResponse> for Response {
     fn from(val: TextResponse) -> Self {
-        Response::Text(val)
+        ResponseBody::Text(val).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_chunks_in_any_order() {
+        let chunk = |index, data: &[u8]| UploadChunk {
+            index,
+            total: 3,
+            data: data.to_vec(),
+        };
+        let mut buffer = ChunkBuffer::new(3);
+        buffer.insert(chunk(2, b"ef")).unwrap();
+        buffer.insert(chunk(0, b"ab")).unwrap();
+        assert!(matches!(
+            buffer.insert(chunk(3, b"gh")),
+            Err(RequestError::ChunkIndexOutOfRange { index: 3, total: 3 })
+        ));
+        let mut incomplete = ChunkBuffer::new(3);
+        incomplete.insert(chunk(1, b"cd")).unwrap();
+        assert!(matches!(
+            incomplete.assemble(),
+            Err(RequestError::MissingChunks { indices }) if indices == vec![0, 2]
+        ));
+        buffer.insert(chunk(1, b"cd")).unwrap();
+        assert_eq!(buffer.assemble().unwrap(), b"abcdef".to_vec());
+    }
+
+    #[test]
+    fn compressed_memory_round_trip() {
+        let bytes = b"wasminspect".repeat(1000);
+        let payload = compress_memory(0x400, &bytes);
+        assert!(payload.len() < bytes.len());
+        assert_eq!(payload[..8], 0x400u64.to_le_bytes());
+        assert_eq!(
+            lz4_flex::decompress_size_prepended(&payload[8..]).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn compatible_versions() {
+        assert!(is_compatible_version("0.2.1", "0.2.0"));
+        assert!(!is_compatible_version("0.1.0", "0.2.0"));
+        assert!(is_compatible_version("1.4.0", "1.0.0"));
+        assert!(!is_compatible_version("2.0.0", "1.0.0"));
+        assert!(!is_compatible_version("latest", "0.2.0"));
     }
 }
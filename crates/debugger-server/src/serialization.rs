@@ -3,18 +3,28 @@ use tokio_tungstenite::tungstenite::Message;
 
 pub fn deserialize_request(message: &Message) -> Result<rpc::Request, rpc::RequestError> {
     match message {
-        Message::Binary(bytes) => rpc::BinaryRequest::from_bytes(bytes).map(rpc::Request::Binary),
-        Message::Text(text) => match serde_json::from_str::<rpc::TextRequest>(text) {
-            Ok(req) => Ok(rpc::Request::Text(req)),
+        Message::Binary(bytes) => rpc::BinaryRequest::from_bytes(bytes).map(|req| rpc::Request {
+            request_id: None,
+            body: rpc::RequestBody::Binary(req),
+        }),
+        Message::Text(text) => match serde_json::from_str::<rpc::TextRequestMessage>(text) {
+            Ok(message) => Ok(rpc::Request {
+                request_id: message.request_id,
+                body: rpc::RequestBody::Text(message.request),
+            }),
             Err(e) => Err(rpc::RequestError::InvalidTextRequestJSON(Box::new(e))),
         },
         msg => Err(rpc::RequestError::InvalidMessageType(format!("{:?}", msg))),
     }
 }
 pub fn serialize_response(response: rpc::Response) -> Message {
-    match response {
-        rpc::Response::Text(response) => {
-            let json = match serde_json::to_string(&response) {
+    match response.body {
+        rpc::ResponseBody::Text(body) => {
+            let message = rpc::TextResponseMessage {
+                request_id: response.request_id,
+                response: &body,
+            };
+            let json = match serde_json::to_string(&message) {
                 Ok(json) => json,
                 Err(e) => {
                     log::error!("Failed to serialize error response: {}", e);
@@ -23,7 +33,7 @@ pub fn serialize_response(response: rpc::Response) -> Message {
             };
             Message::Text(json)
         }
-        rpc::Response::Binary { kind, bytes } => {
+        rpc::ResponseBody::Binary { kind, bytes } => {
             let mut bin = vec![kind as u8];
             bin.extend(bytes);
             Message::binary(bin)
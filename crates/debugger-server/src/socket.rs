@@ -153,7 +153,7 @@ async fn _establish_connection(upgraded: Upgraded) -> Result<(), anyhow::Error>
         rt.block_on(async move {
             log::debug!("Start debugger thread");
             let (process, dbg_context) =
-                wasminspect_debugger::start_debugger(None, vec![], vec![]).unwrap();
+                wasminspect_debugger::start_debugger(None, vec![], vec![], true).unwrap();
             let process = Rc::new(RefCell::new(process));
 
             let mut last_line: Option<String> = None;
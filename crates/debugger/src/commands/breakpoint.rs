@@ -1,7 +1,13 @@
+use super::command::AliasCommand;
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::{Breakpoint, Debugger};
+use super::debugger::{
+    BreakCondition, Breakpoint, BreakpointId, CompareOp, ConditionOperand, Debugger, FunctionRef,
+    OffsetBase, ValueFormat,
+};
+use super::format::format_value;
 use anyhow::{anyhow, Result};
 use structopt::StructOpt;
+use wasminspect_vm::WasmValue;
 
 pub struct BreakpointCommand {}
 
@@ -16,6 +22,12 @@ enum Opts {
     /// Sets a breakpoint for the given symbol in executable
     #[structopt(name = "set")]
     Set(SetOpts),
+    /// Lists all breakpoints with their IDs
+    #[structopt(name = "list")]
+    List,
+    /// Deletes the breakpoint with the given ID
+    #[structopt(name = "delete")]
+    Delete { id: u32 },
 }
 
 #[derive(StructOpt)]
@@ -24,21 +36,76 @@ struct SetOpts {
     name: Option<String>,
     #[structopt(short, long)]
     address: Option<String>,
+    /// Halts only if the condition holds (e.g. `arg0==42`, `local2!=0`, `global0>10`, `stack<0`)
+    #[structopt(long = "if")]
+    condition: Option<String>,
+    /// Deletes the breakpoint after its first hit
+    #[structopt(long)]
+    once: bool,
+    /// Halts only on the Nth hit of the function
+    #[structopt(long)]
+    hit_count: Option<u32>,
+    /// Halts at the instruction at this offset from the first instruction of the
+    /// function given by --name
+    #[structopt(long)]
+    offset: Option<String>,
 }
 
 impl SetOpts {
     fn breakpoint(self) -> Result<Breakpoint> {
+        let condition = match self.condition {
+            Some(condition) => Some(parse_condition(&condition)?),
+            None => None,
+        };
+        if let Some(offset) = self.offset {
+            let func_name = self
+                .name
+                .ok_or_else(|| anyhow!("--offset is only supported with --name"))?;
+            if self.once || self.hit_count.is_some() {
+                return Err(anyhow!(
+                    "--offset cannot be combined with --once or --hit-count"
+                ));
+            }
+            return Ok(Breakpoint::Instruction {
+                base: OffsetBase::Function(FunctionRef::Name(func_name)),
+                offset: parse_offset(&offset)?,
+                condition,
+            });
+        }
+        if self.once {
+            return match (self.name, condition, self.hit_count) {
+                (Some(name), None, None) => Ok(Breakpoint::FunctionOnce { name }),
+                (None, _, _) => Err(anyhow!("--once is only supported with --name")),
+                _ => Err(anyhow!(
+                    "--once cannot be combined with --if or --hit-count"
+                )),
+            };
+        }
+        if self.hit_count.is_some() && self.name.is_none() {
+            return Err(anyhow!("--hit-count is only supported with --name"));
+        }
+        if self.hit_count == Some(0) {
+            return Err(anyhow!("--hit-count should be greater than 0"));
+        }
         if let Some(name) = self.name {
-            Ok(Breakpoint::Function { name })
+            Ok(Breakpoint::Function {
+                name,
+                condition,
+                hit_count: self.hit_count,
+            })
         } else if let Some(address) = self.address {
-            let address = if address.starts_with("0x") {
-                let raw = address.trim_start_matches("0x");
-                usize::from_str_radix(raw, 16)?
-            } else {
-                address.parse::<usize>()?
-            };
+            if address.starts_with("func[") {
+                let (func_index, offset) = parse_func_offset(&address)?;
+                return Ok(Breakpoint::Instruction {
+                    base: OffsetBase::Function(FunctionRef::Index(func_index)),
+                    offset,
+                    condition,
+                });
+            }
             Ok(Breakpoint::Instruction {
-                inst_offset: address,
+                base: OffsetBase::Absolute,
+                offset: parse_offset(&address)?,
+                condition,
             })
         } else {
             Err(anyhow!("no breakpoint option"))
@@ -46,6 +113,27 @@ impl SetOpts {
     }
 }
 
+fn parse_offset(offset: &str) -> Result<usize> {
+    if let Some(raw) = offset.strip_prefix("0x") {
+        Ok(usize::from_str_radix(raw, 16)?)
+    } else {
+        Ok(offset.parse::<usize>()?)
+    }
+}
+
+/// Parses `func[INDEX]+OFFSET`, where `OFFSET` is counted from the first instruction
+fn parse_func_offset(address: &str) -> Result<(u32, usize)> {
+    let invalid = || anyhow!("'{}' is not in the form of 'func[INDEX]+OFFSET'", address);
+    let rest = address.strip_prefix("func[").ok_or_else(invalid)?;
+    let (index, rest) = rest.split_once(']').ok_or_else(invalid)?;
+    let func_index = index.trim().parse::<u32>().map_err(|_| invalid())?;
+    let offset = match rest.trim() {
+        "" => 0,
+        rest => parse_offset(rest.strip_prefix('+').ok_or_else(invalid)?.trim())?,
+    };
+    Ok((func_index, offset))
+}
+
 impl<D: Debugger> Command<D> for BreakpointCommand {
     fn name(&self) -> &'static str {
         "breakpoint"
@@ -58,15 +146,221 @@ impl<D: Debugger> Command<D> for BreakpointCommand {
     fn run(
         &self,
         debugger: &mut D,
-        _context: &CommandContext,
+        context: &CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Set(opts) => {
-                debugger.set_breakpoint(opts.breakpoint()?);
+                let breakpoint = opts.breakpoint()?;
+                let description = describe_breakpoint(&breakpoint, debugger);
+                let id = debugger.set_breakpoint(breakpoint)?;
+                context
+                    .printer
+                    .println(&format!("Breakpoint {}: {}", id.0, description));
+                Ok(None)
+            }
+            Opts::List => {
+                let breakpoints = debugger.list_breakpoints();
+                if breakpoints.is_empty() {
+                    context.printer.println("No breakpoints");
+                }
+                for (id, breakpoint) in breakpoints {
+                    let description = describe_breakpoint(&breakpoint, debugger);
+                    context
+                        .printer
+                        .println(&format!("{}: {}", id.0, description));
+                }
                 Ok(None)
             }
+            Opts::Delete { id } => {
+                if !debugger.delete_breakpoint(BreakpointId(id)) {
+                    context
+                        .printer
+                        .println(&format!("No breakpoint with ID {}", id));
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn describe_condition(condition: &BreakCondition, format: ValueFormat) -> String {
+    let operand = match condition.operand {
+        ConditionOperand::Local(index) => format!("local{}", index),
+        ConditionOperand::Global(index) => format!("global{}", index),
+        ConditionOperand::StackTop => "stack".to_string(),
+    };
+    let op = match condition.op {
+        CompareOp::Eq => "==",
+        CompareOp::Ne => "!=",
+        CompareOp::Lt => "<",
+        CompareOp::Le => "<=",
+        CompareOp::Gt => ">",
+        CompareOp::Ge => ">=",
+    };
+    let value = format_value(&condition.value, format);
+    format!("{} {} {}", operand, op, value)
+}
+
+/// `func[INDEX]` followed by the function name if the name section has it
+fn describe_func<D: Debugger>(func_index: u32, debugger: &D) -> String {
+    match debugger.function_name(func_index) {
+        Some(name) => format!("func[{}] '{}'", func_index, name),
+        None => format!("func[{}]", func_index),
+    }
+}
+
+fn describe_breakpoint<D: Debugger>(breakpoint: &Breakpoint, debugger: &D) -> String {
+    let format = debugger.get_opts().value_format;
+    let (mut description, condition) = match breakpoint {
+        Breakpoint::Function {
+            name,
+            condition,
+            hit_count,
+        } => {
+            let mut description = format!("function '{}'", name);
+            if let Some(hit_count) = hit_count {
+                description += &format!(" on hit {}", hit_count);
+            }
+            (description, condition)
+        }
+        Breakpoint::FunctionConditional { name, .. } => {
+            (format!("function '{}' with a predicate", name), &None)
+        }
+        Breakpoint::FunctionOnce { name } => (format!("function '{}' once", name), &None),
+        Breakpoint::Instruction {
+            base,
+            offset,
+            condition,
+        } => {
+            let description = match base {
+                OffsetBase::Absolute => format!("address {:#x}", offset),
+                OffsetBase::Function(FunctionRef::Index(func_index)) => {
+                    format!("{}+{:#x}", describe_func(*func_index, debugger), offset)
+                }
+                OffsetBase::Function(func) => format!("{}+{:#x}", func, offset),
+            };
+            (description, condition)
+        }
+        Breakpoint::WatchLocal {
+            local_index,
+            func_index,
+        } => {
+            let mut description = format!("watch local {}", local_index);
+            if let Some(func_index) = func_index {
+                description += &format!(" in {}", describe_func(*func_index, debugger));
+            }
+            (description, &None)
+        }
+        Breakpoint::MemoryWrite { offset, len } => (
+            format!("watch memory {:#x}..{:#x}", offset, offset + len),
+            &None,
+        ),
+    };
+    if let Some(condition) = condition {
+        description += &format!(" if {}", describe_condition(condition, format));
+    }
+    description
+}
+
+/// Parses a condition in the form of `OPERAND <op> <number>`, where `OPERAND` is
+/// `argN`, `localN`, `globalN` or `stack`
+fn parse_condition(condition: &str) -> Result<BreakCondition> {
+    const OPERATORS: [(&str, CompareOp); 6] = [
+        ("==", CompareOp::Eq),
+        ("!=", CompareOp::Ne),
+        ("<=", CompareOp::Le),
+        (">=", CompareOp::Ge),
+        ("<", CompareOp::Lt),
+        (">", CompareOp::Gt),
+    ];
+    let (operator, op, position) = OPERATORS
+        .iter()
+        .find_map(|(operator, op)| {
+            condition
+                .find(operator)
+                .map(|position| (*operator, *op, position))
+        })
+        .ok_or_else(|| anyhow!("no comparison operator in condition '{}'", condition))?;
+    let lhs = condition[..position].trim();
+    let rhs = condition[position + operator.len()..].trim();
+    let index = |prefix: &str| lhs.strip_prefix(prefix)?.parse().ok();
+    let operand = if lhs == "stack" {
+        ConditionOperand::StackTop
+    } else if let Some(index) = index("arg").or_else(|| index("local")) {
+        // Arguments are the first locals of a frame
+        ConditionOperand::Local(index)
+    } else if let Some(index) = index("global") {
+        ConditionOperand::Global(index as u32)
+    } else {
+        return Err(anyhow!(
+            "'{}' should be one of 'argN', 'localN', 'globalN' or 'stack'",
+            lhs
+        ));
+    };
+    let value = parse_number(rhs).map_err(|_| anyhow!("'{}' is not a number", rhs))?;
+    Ok(BreakCondition { operand, op, value })
+}
+
+fn parse_number(s: &str) -> Result<WasmValue> {
+    let int = match s.strip_prefix("0x") {
+        Some(hex) => Some(i64::from_str_radix(hex, 16)?),
+        None => s.parse::<i64>().ok(),
+    };
+    Ok(match int {
+        Some(v) if (i32::MIN as i64..=i32::MAX as i64).contains(&v) => WasmValue::from(v as i32),
+        Some(v) => WasmValue::from(v),
+        None => WasmValue::from(s.parse::<f64>()?),
+    })
+}
+
+/// `break [--once] FUNCTION [--if CONDITION] [--hit-count N]`, `break FUNCTION+OFFSET`,
+/// `break *func[INDEX]+OFFSET`, `break list` or `break delete ID`
+pub struct BreakCommand {}
+
+impl BreakCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for BreakCommand {
+    fn name(&self) -> &'static str {
+        "break"
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let once = args.contains(&"--once");
+        let args: Vec<&str> = args.into_iter().filter(|arg| *arg != "--once").collect();
+        let target = args.get(1).ok_or_else(|| {
+            anyhow!(
+                "Usage: break [--once] FUNCTION [--if CONDITION] [--hit-count N] | break FUNCTION+OFFSET | break *func[INDEX]+OFFSET | break list | break delete ID"
+            )
+        })?;
+        match *target {
+            "list" => return Ok(shell_words::join(vec!["breakpoint", "list"])),
+            "delete" => {
+                let mut line = vec!["breakpoint", "delete"];
+                line.extend(&args[2..]);
+                return Ok(shell_words::join(line));
+            }
+            _ => {}
+        }
+        let mut line = match target.strip_prefix('*') {
+            Some(address) => vec!["breakpoint", "set", "--address", address],
+            None => match target.rsplit_once('+') {
+                // `FUNCTION+OFFSET`
+                Some((name, offset)) if parse_offset(offset).is_ok() => {
+                    vec!["breakpoint", "set", "--name", name, "--offset", offset]
+                }
+                _ => vec!["breakpoint", "set", "--name", target],
+            },
+        };
+        line.extend(&args[2..]);
+        if once {
+            line.push("--once");
         }
+        Ok(shell_words::join(line))
     }
 }
@@ -1,13 +1,17 @@
 use super::debugger::{Debugger, OutputPrinter};
 use super::sourcemap::SourceMap;
 use super::subroutine::SubroutineMap;
+use super::watch::WatchList;
 use anyhow::Result;
+use std::cell::RefCell;
 use wasminspect_vm::WasmValue;
 
 pub struct CommandContext {
     pub sourcemap: Box<dyn SourceMap>,
     pub subroutine: Box<dyn SubroutineMap>,
     pub printer: Box<dyn OutputPrinter>,
+    /// Watch expressions printed after each step or stop
+    pub watches: RefCell<WatchList>,
 }
 
 #[derive(Debug)]
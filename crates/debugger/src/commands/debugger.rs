@@ -1,19 +1,266 @@
-use anyhow::Result;
-use wasminspect_vm::{HostValue, Instruction, ModuleIndex, Signal, Store, WasmValue};
+use super::format::type_name;
+use super::sourcemap::{LineInfo, SourceLocation, SourceMap};
+use super::subroutine::SubroutineMap;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+use wasminspect_vm::{
+    ExecutionLimit, HostValue, Instruction, ModuleIndex, NumVal, Signal, Store, WasmValue,
+};
+use wasmparser::ValType;
+
+/// The environment variable holding options in JSON
+pub static OPTS_ENV_VAR: &str = "WASMINSPECT_OPTS";
 
 #[derive(Default, Clone)]
 pub struct DebuggerOpts {
     pub watch_memory: bool,
+    pub track_locals: bool,
+    pub profile: bool,
+    pub value_format: ValueFormat,
+    pub trace_format: TraceFormat,
+    /// Shows function names as they are instead of demangling them
+    pub raw_symbols: bool,
+    /// The number of executed instructions recorded for `Debugger::step_back`, 0 disables it.
+    ///
+    /// Each instruction records its program counter, the stack entries it popped, the
+    /// old values of locals and globals it set and the old bytes of memory it wrote,
+    /// which are about 100 bytes for most instructions. Full memory snapshots are not taken.
+    pub history_limit: usize,
+}
+
+/// Options given by a config file or `WASMINSPECT_OPTS`, where `Some` fields
+/// override lower layers even if they are the default value
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct DebuggerOptsLayer {
+    pub watch_memory: Option<bool>,
+    pub track_locals: Option<bool>,
+    pub profile: Option<bool>,
+    pub value_format: Option<ValueFormat>,
+    pub trace_format: Option<TraceFormat>,
+    pub raw_symbols: Option<bool>,
+    pub history_limit: Option<usize>,
+}
+
+impl DebuggerOptsLayer {
+    /// Reads options from `WASMINSPECT_OPTS`, returning an empty layer when it is not set
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(OPTS_ENV_VAR) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|err| anyhow!("invalid {}: {}", OPTS_ENV_VAR, err)),
+            Err(std::env::VarError::NotPresent) => Ok(Self::default()),
+            Err(err) => Err(anyhow!("invalid {}: {}", OPTS_ENV_VAR, err)),
+        }
+    }
+
+    /// Reads options from a JSON file if it has `.json` extension, otherwise from a TOML file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        let opts = if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            serde_json::from_str(&content).map_err(|err| anyhow!("{}: {}", path.display(), err))?
+        } else {
+            toml::from_str(&content).map_err(|err| anyhow!("{}: {}", path.display(), err))?
+        };
+        Ok(opts)
+    }
+
+    /// Takes the fields set in `self`, falling back to `base` for the others
+    pub fn or(self, base: Self) -> Self {
+        Self {
+            watch_memory: self.watch_memory.or(base.watch_memory),
+            track_locals: self.track_locals.or(base.track_locals),
+            profile: self.profile.or(base.profile),
+            value_format: self.value_format.or(base.value_format),
+            trace_format: self.trace_format.or(base.trace_format),
+            raw_symbols: self.raw_symbols.or(base.raw_symbols),
+            history_limit: self.history_limit.or(base.history_limit),
+        }
+    }
+
+    /// Fills the fields not set in any layer with the default
+    pub fn into_opts(self) -> DebuggerOpts {
+        let default = DebuggerOpts::default();
+        DebuggerOpts {
+            watch_memory: self.watch_memory.unwrap_or(default.watch_memory),
+            track_locals: self.track_locals.unwrap_or(default.track_locals),
+            profile: self.profile.unwrap_or(default.profile),
+            value_format: self.value_format.unwrap_or(default.value_format),
+            trace_format: self.trace_format.unwrap_or(default.trace_format),
+            raw_symbols: self.raw_symbols.unwrap_or(default.raw_symbols),
+            history_limit: self.history_limit.unwrap_or(default.history_limit),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueFormat {
+    #[default]
+    Default,
+    Hex,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFormat {
+    #[default]
+    Text,
+    /// Fixed-size 8-byte records of `[u32 offset][u8 category][u8 call depth][u16 reserved]`
+    Binary,
+    /// `offset,instruction,call_depth,stack_depth` rows with a header line
+    Csv,
+}
+
+/// A predicate on the locals of a newly entered frame, which start with the arguments
+pub type BreakpointPredicate = Rc<dyn Fn(&[WasmValue]) -> bool>;
+
+/// A value referenced by a `BreakCondition`
+#[derive(Clone, Copy, Debug)]
+pub enum ConditionOperand {
+    /// A local of the current frame
+    Local(usize),
+    /// A global of the main module
+    Global(u32),
+    /// The value on the top of the stack
+    StackTop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A condition evaluated when a breakpoint is reached
+#[derive(Clone, Copy, Debug)]
+pub struct BreakCondition {
+    pub operand: ConditionOperand,
+    pub op: CompareOp,
+    pub value: WasmValue,
+}
+
+impl BreakCondition {
+    /// Compares numerically, so integers of different widths can be compared
+    pub fn holds(&self, actual: &WasmValue) -> bool {
+        let ordering = match (Number::from_value(actual), Number::from_value(&self.value)) {
+            (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs),
+            _ => None,
+        };
+        match ordering {
+            Some(ordering) => match self.op {
+                CompareOp::Eq => ordering == Ordering::Equal,
+                CompareOp::Ne => ordering != Ordering::Equal,
+                CompareOp::Lt => ordering == Ordering::Less,
+                CompareOp::Le => ordering != Ordering::Greater,
+                CompareOp::Gt => ordering == Ordering::Greater,
+                CompareOp::Ge => ordering != Ordering::Less,
+            },
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &WasmValue) -> Option<Self> {
+        match value {
+            WasmValue::Num(NumVal::I32(v)) => Some(Self::Int(*v as i64)),
+            WasmValue::Num(NumVal::I64(v)) => Some(Self::Int(*v)),
+            WasmValue::Num(NumVal::F32(v)) => Some(Self::Float(v.to_float() as f64)),
+            WasmValue::Num(NumVal::F64(v)) => Some(Self::Float(v.to_float())),
+            WasmValue::Ref(_) | WasmValue::V128(_) => None,
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (*self, *other) {
+            (Self::Int(lhs), Self::Int(rhs)) => Some(lhs.cmp(&rhs)),
+            (Self::Int(lhs), Self::Float(rhs)) => (lhs as f64).partial_cmp(&rhs),
+            (Self::Float(lhs), Self::Int(rhs)) => lhs.partial_cmp(&(rhs as f64)),
+            (Self::Float(lhs), Self::Float(rhs)) => lhs.partial_cmp(&rhs),
+        }
+    }
+}
+
+/// Identifies a breakpoint within a session. IDs are never reused.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct BreakpointId(pub u32);
+
+/// A function referred by a breakpoint
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FunctionRef {
+    Index(u32),
+    /// A name in the name section
+    Name(String),
+}
+
+impl std::fmt::Display for FunctionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "func[{}]", index),
+            Self::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// What the offset of an instruction breakpoint is counted from
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OffsetBase {
+    /// The offset is the same as `Instruction::offset`
+    Absolute,
+    /// The offset is counted from the first instruction of the function
+    Function(FunctionRef),
+}
+
+#[derive(Clone)]
 pub enum Breakpoint {
-    Function { name: String },
-    Instruction { inst_offset: usize },
+    Function {
+        name: String,
+        condition: Option<BreakCondition>,
+        /// Halts only on the Nth hit where the condition holds
+        hit_count: Option<u32>,
+    },
+    /// Halts at the function entry only if the predicate holds
+    FunctionConditional {
+        name: String,
+        predicate: BreakpointPredicate,
+    },
+    /// Halts at the first entry to the function, then removes itself
+    FunctionOnce { name: String },
+    Instruction {
+        base: OffsetBase,
+        offset: usize,
+        condition: Option<BreakCondition>,
+    },
+    WatchLocal {
+        local_index: usize,
+        func_index: Option<u32>,
+    },
+    /// Halts after a write touching `len` bytes from `offset` of the linear memory
+    MemoryWrite { offset: usize, len: usize },
 }
 
 pub enum RunResult {
     Finish(Vec<WasmValue>),
     Breakpoint,
+    Watchpoint(LocalChange),
+    MemoryWatchpoint(MemoryChange),
 }
 
 #[derive(Clone, Copy)]
@@ -21,11 +268,216 @@ pub enum StepStyle {
     InstIn,
     InstOver,
     Out,
+    /// Steps instructions, descending into calls, until the source line changes
+    StepLineIn,
+    /// Steps over instructions and calls until the source line changes
+    StepLineOver,
+}
+
+/// A frame of the call stack
+#[derive(Clone)]
+pub struct FrameInfo {
+    pub func_name: String,
+    pub demangled_name: String,
+    /// The index of the function in its module, `None` for host functions
+    pub func_index: Option<usize>,
+    pub module_index: ModuleIndex,
+    /// The offset of the executing instruction, or the call instruction for callers
+    pub instruction_offset: usize,
+    pub source_location: Option<SourceLocation>,
+    /// Whether the frame is a subroutine inlined into the physical frame below it
+    pub is_inlined: bool,
+}
+
+/// A trap which stopped the execution, with the call stack at that time
+#[derive(Clone)]
+pub struct TrapInfo {
+    pub message: String,
+    /// The offset of the trapping instruction
+    pub instruction_offset: usize,
+    /// Frames from the outermost one as `Debugger::backtrace`
+    pub backtrace: Vec<FrameInfo>,
 }
 
 pub struct FunctionFrame {
     pub module_index: ModuleIndex,
+    pub func_index: u32,
     pub argument_count: usize,
+    pub result_types: Vec<ValType>,
+}
+
+pub struct LocalInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub wasm_type: ValType,
+    pub value: WasmValue,
+    pub is_param: bool,
+    pub source_location: Option<LineInfo>,
+}
+
+/// A source variable in scope at the selected frame
+pub struct VariableInfo {
+    pub name: String,
+    pub type_name: String,
+    /// `None` if the variable doesn't live in a wasm local at the current instruction
+    pub value: Option<WasmValue>,
+    /// Whether the variable is a parameter of the function
+    pub is_param: bool,
+}
+
+/// An error of `Debugger::set_local`
+#[derive(Debug)]
+pub enum SetLocalError {
+    OutOfRange {
+        index: usize,
+        count: usize,
+    },
+    TypeMismatch {
+        index: usize,
+        expected: ValType,
+        actual: ValType,
+    },
+}
+
+impl std::fmt::Display for SetLocalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange { index, count } => {
+                write!(f, "{} is out of range, locals length is {}", index, count)
+            }
+            Self::TypeMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "local {} has type {}, but a {} value is given",
+                index,
+                type_name(*expected),
+                type_name(*actual)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SetLocalError {}
+
+/// An error of `Debugger::set_global`
+#[derive(Debug)]
+pub enum SetGlobalError {
+    OutOfRange {
+        index: usize,
+        count: usize,
+    },
+    Immutable {
+        index: usize,
+    },
+    TypeMismatch {
+        index: usize,
+        expected: ValType,
+        actual: ValType,
+    },
+}
+
+impl std::fmt::Display for SetGlobalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange { index, count } => {
+                write!(f, "{} is out of range, globals length is {}", index, count)
+            }
+            Self::Immutable { index } => write!(f, "global {} is const", index),
+            Self::TypeMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "global {} has type {}, but a {} value is given",
+                index,
+                type_name(*expected),
+                type_name(*actual)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SetGlobalError {}
+
+#[derive(Clone, Debug)]
+pub struct LocalChange {
+    pub func_index: u32,
+    pub local_index: usize,
+    pub old_value: WasmValue,
+    pub new_value: WasmValue,
+    pub instruction_offset: usize,
+}
+
+/// A write to a watched memory range, limited to the watched bytes
+#[derive(Clone, Debug)]
+pub struct MemoryChange {
+    pub addr: usize,
+    pub old_bytes: Vec<u8>,
+    pub new_bytes: Vec<u8>,
+    pub instruction_offset: usize,
+}
+
+/// Call stacks recorded by the sampling profiler
+#[derive(Clone, Debug, Default)]
+pub struct ProfilingData {
+    /// Function names from the outermost frame, followed by the offset of the executing instruction
+    pub samples: Vec<Vec<String>>,
+}
+
+impl ProfilingData {
+    /// Serializes samples in the folded format read by `inferno-flamegraph`,
+    /// where each line is a `;`-separated stack followed by its sample count
+    pub fn to_folded(&self) -> String {
+        let mut counts = BTreeMap::new();
+        for sample in &self.samples {
+            *counts.entry(sample.join(";")).or_insert(0u64) += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+}
+
+/// Contents of the `producers` custom section
+#[derive(Clone, Debug, Default)]
+pub struct ProducersSection {
+    pub language: Vec<(String, String)>,
+    pub processed_by: Vec<(String, String)>,
+    pub sdk: Vec<(String, String)>,
+}
+
+/// An active data segment placed at a constant offset of the linear memory
+#[derive(Clone, Debug)]
+pub struct DataSegment {
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// A host function whose body is replaced by a fixed result
+#[derive(Clone, Debug)]
+pub struct FunctionMock {
+    pub module: String,
+    pub name: String,
+    pub return_value: Option<String>,
+}
+
+/// A named range of the linear memory
+#[derive(Clone, Debug)]
+pub struct LabeledRegion {
+    pub start: usize,
+    pub len: usize,
+    pub label: String,
+}
+
+impl LabeledRegion {
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.start + self.len
+    }
 }
 
 pub trait OutputPrinter {
@@ -37,6 +489,8 @@ pub type RawHostModule = std::collections::HashMap<String, HostValue>;
 pub trait Debugger {
     fn get_opts(&self) -> DebuggerOpts;
     fn set_opts(&mut self, opts: DebuggerOpts);
+    /// Aborts executions exceeding `limit` with a trap, or removes the limit with `None`
+    fn set_execution_limit(&mut self, limit: Option<ExecutionLimit>) -> Result<()>;
     fn instantiate(
         &mut self,
         host_modules: std::collections::HashMap<String, RawHostModule>,
@@ -44,15 +498,135 @@ pub trait Debugger {
     ) -> Result<()>;
     fn run(&mut self, name: Option<&str>, args: Vec<WasmValue>) -> Result<RunResult>;
     fn is_running(&self) -> bool;
-    fn frame(&self) -> Vec<String>;
+    /// Frames of the call stack from the outermost one
+    fn frame(&self) -> Vec<FrameInfo>;
+    /// Same as `frame`, but inlined subroutines are expanded into their own frames
+    fn backtrace(&self) -> Vec<FrameInfo>;
+    /// The trap of the last run, if it stopped with one
+    fn last_trap(&self) -> Option<TrapInfo>;
     fn current_frame(&self) -> Option<FunctionFrame>;
-    fn locals(&self) -> Vec<WasmValue>;
-    fn memory(&self) -> Result<Vec<u8>>;
+    fn frame_locals(&self) -> Result<Vec<LocalInfo>>;
+    /// Source variables in scope at the selected frame, resolved from debug info
+    fn local_variables(&self) -> Vec<VariableInfo>;
+    /// Overwrites a local of the selected frame. Fails with `SetLocalError` if
+    /// the index is out of range or the value doesn't match the local's type
+    fn set_local(&mut self, index: usize, value: WasmValue) -> Result<()>;
+    /// Mutability and values of the globals of `module_index`, or of the
+    /// selected frame's module if it's `None`
+    fn globals(&self, module_index: Option<ModuleIndex>) -> Result<Vec<(bool, WasmValue)>>;
+    /// Overwrites a global of `module_index`, or of the selected frame's module if it's `None`.
+    /// Fails with `SetGlobalError` if the global doesn't exist, is const or has another type
+    fn set_global(
+        &mut self,
+        module_index: Option<ModuleIndex>,
+        index: usize,
+        value: WasmValue,
+    ) -> Result<()>;
+    fn locals(&self) -> Vec<WasmValue> {
+        self.frame_locals()
+            .map(|locals| locals.into_iter().map(|local| local.value).collect())
+            .unwrap_or_default()
+    }
+    /// Changes of a local recorded while `DebuggerOpts::track_locals` is set, from the oldest one.
+    /// This takes `&mut self` to lay out the recorded changes contiguously.
+    fn local_history(&mut self, func_index: u32, local_index: usize) -> &[LocalChange];
+    /// Execution counts of each instruction offset recorded while `profile` is enabled
+    fn profile_counts(&self) -> HashMap<usize, u64>;
+    /// Execution counts of instruction kinds keyed by mnemonics since the last `reset_histogram`
+    fn instruction_histogram(&self) -> HashMap<String, u64>;
+    fn reset_histogram(&mut self);
+    /// Starts writing executed instructions to `path` in `DebuggerOpts::trace_format`
+    fn start_trace(&mut self, path: &str) -> Result<()>;
+    fn stop_trace(&mut self) -> Result<()>;
+    /// Records the call stack once every `interval_instructions` executed instructions
+    fn start_sampling_profiler(&mut self, interval_instructions: u64) -> Result<()>;
+    /// Stops the sampling profiler and returns the recorded samples
+    fn stop_sampling_profiler(&mut self) -> Result<ProfilingData>;
+    /// Instructions of the main module keyed by their offsets
+    fn instruction_map(&self) -> Result<HashMap<usize, Instruction>>;
+    /// Contents of the memory 0 of the main module
+    fn memory(&self) -> Result<Vec<u8>> {
+        self.memory_at(0)
+    }
+    /// Contents of the memory `mem_index` of the main module, which is empty if it has no memory
+    fn memory_at(&self, mem_index: usize) -> Result<Vec<u8>>;
+    fn memory_checksum(&self, start: usize, len: usize) -> Result<u32>;
+    /// Writes `len` bytes of the memory from `offset` to a file
+    fn dump_memory(&self, path: &Path, offset: usize, len: usize) -> Result<()>;
+    /// Overwrites the memory from `offset` with the contents of a file
+    fn load_memory_from_file(&mut self, path: &Path, offset: usize) -> Result<()> {
+        let bytes = std::fs::read(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        self.write_memory(offset, bytes)
+    }
+    /// Overwrites the memory 0 from `offset` with `bytes`, failing if they do not fit
+    fn write_memory(&mut self, offset: usize, bytes: Vec<u8>) -> Result<()>;
+    fn find_in_memory(&self, pattern: &[u8], align: usize) -> Result<Vec<usize>>;
+    fn label_memory_region(&mut self, start: usize, len: usize, label: String);
+    /// Sets the line table used by `StepStyle::StepLineIn` and `StepStyle::StepLineOver`
+    fn set_line_table(&mut self, sourcemap: Rc<dyn SourceMap>);
+    /// Sets the subroutine map used by `backtrace` to find inlined subroutines
+    fn set_subroutine_map(&mut self, subroutine: Rc<dyn SubroutineMap>);
+    /// Replaces the results of a host function from the next instantiation.
+    /// Fails if the host function doesn't exist or the return value doesn't match its type
+    fn mock_function(&mut self, mock: FunctionMock) -> Result<()>;
+    /// Removes the mock of a host function from the next instantiation.
+    /// Returns `false` if the function isn't mocked
+    fn delete_mock(&mut self, module: &str, name: &str) -> bool;
+    /// Removes all mocks from the next instantiation
+    fn clear_mocks(&mut self);
+    /// Instantiates a wasm binary and makes its exports importable as `module_name`
+    fn load_host_module_from_file(&mut self, module_name: &str, path: &Path) -> Result<()>;
+    fn memory_labels(&self) -> Vec<LabeledRegion>;
+    /// Active data segments of the main module
+    fn data_segments(&self) -> Result<Vec<DataSegment>>;
+    /// Looks up a global of the main module by its export name or debug name
+    fn global_by_name(&self, name: &str) -> Result<Option<WasmValue>>;
     fn store(&self) -> Result<&Store>;
-    fn set_breakpoint(&mut self, breakpoint: Breakpoint);
+    fn set_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<BreakpointId>;
+    fn set_conditional_breakpoint(
+        &mut self,
+        name: String,
+        predicate: BreakpointPredicate,
+    ) -> Result<BreakpointId>;
+    fn instruction_breakpoints(&self) -> Vec<usize>;
+    fn list_breakpoints(&self) -> Vec<(BreakpointId, Breakpoint)>;
+    /// Returns false if no breakpoint has the ID
+    fn delete_breakpoint(&mut self, id: BreakpointId) -> bool;
+    fn producers(&self) -> Option<ProducersSection>;
     fn stack_values(&self) -> Vec<WasmValue>;
+    /// Locals of the frame at `depth` from the innermost one
+    fn locals_for_frame(&self, depth: usize) -> Vec<WasmValue>;
+    /// Operand stack values of the frame at `depth` from the innermost one
+    fn stack_values_for_frame(&self, depth: usize) -> Vec<WasmValue>;
+    /// The frame selected by `frame select`, where 0 is the innermost one
+    fn selected_frame_index(&self) -> usize;
     fn selected_instructions(&self) -> Result<(&[Instruction], usize)>;
+    /// Instructions of a function of the main module given by its name or index
+    fn func_instructions(&self, func: &str) -> Result<&[Instruction]>;
+    /// The name of a function of the main module given by the name section
+    fn function_name(&self, func_index: u32) -> Option<String>;
+    /// The binary of the main module
+    fn module_bytes(&self) -> Result<&[u8]>;
     fn step(&self, style: StepStyle) -> Result<Signal>;
+    /// Reverts the last instruction recorded while `DebuggerOpts::history_limit` is set.
+    /// Effects of `memory.grow`, table instructions and host functions are not reverted.
+    fn step_back(&mut self) -> Result<()>;
     fn process(&mut self) -> Result<RunResult>;
     fn select_frame(&mut self, frame_index: Option<usize>) -> Result<()>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opts_layer_resets_to_default() {
+        let file: DebuggerOptsLayer =
+            toml::from_str("watch_memory = true\nhistory_limit = 100").unwrap();
+        let env: DebuggerOptsLayer = serde_json::from_str(r#"{"watch_memory": false}"#).unwrap();
+        let opts = env.or(file).into_opts();
+        assert!(!opts.watch_memory);
+        assert_eq!(opts.history_limit, 100);
+        assert!(!opts.track_locals);
+    }
+}
@@ -1,7 +1,13 @@
 use super::command::{Command, CommandContext, CommandResult};
 use super::debugger::{Debugger, OutputPrinter};
-use anyhow::Result;
+use super::format::format_v128;
+use super::memory::parse_address;
+use super::symbol::demangle;
+use crate::disassembler::{disassemble_func, disassemble_module};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 use structopt::StructOpt;
+use wasminspect_vm::InstructionKind;
 
 pub struct DisassembleCommand {}
 
@@ -13,10 +19,25 @@ impl DisassembleCommand {
 
 #[derive(StructOpt)]
 struct Opts {
+    /// Function name or index to disassemble instead of the selected one
+    #[structopt(name = "FUNCTION")]
+    function: Option<String>,
     #[structopt(short, long)]
     count: Option<usize>,
     #[structopt(short, long)]
     pc: bool,
+    /// Disassemble from this instruction offset
+    #[structopt(short, long, parse(try_from_str = parse_address))]
+    start_offset: Option<usize>,
+    /// Disassemble up to this instruction offset (exclusive)
+    #[structopt(short, long, parse(try_from_str = parse_address))]
+    end_offset: Option<usize>,
+    /// Annotate each instruction with its execution count from the profiling run
+    #[structopt(long)]
+    annotate_profile: bool,
+    /// Print the function in the WebAssembly text format
+    #[structopt(long, requires = "FUNCTION")]
+    wat: bool,
 }
 
 impl<D: Debugger> Command<D> for DisassembleCommand {
@@ -25,7 +46,7 @@ impl<D: Debugger> Command<D> for DisassembleCommand {
     }
 
     fn description(&self) -> &'static str {
-        "Disassemble instructions in the current function."
+        "Disassemble instructions in the current function or the given function."
     }
 
     fn run(
@@ -35,12 +56,70 @@ impl<D: Debugger> Command<D> for DisassembleCommand {
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts: Opts = Opts::from_iter_safe(args)?;
-        let count = if opts.pc {
-            Some(opts.count.unwrap_or(4))
-        } else {
-            opts.count
+        if let (true, Some(function)) = (opts.wat, &opts.function) {
+            for line in disassemble_func(debugger.module_bytes()?, function)? {
+                context.printer.println(&line);
+            }
+            return Ok(None);
+        }
+        let range = AsmRange {
+            count: if opts.pc {
+                Some(opts.count.unwrap_or(4))
+            } else {
+                opts.count
+            },
+            pc_rel: opts.pc,
+            start_offset: opts.start_offset,
+            end_offset: opts.end_offset,
         };
-        display_asm(debugger, context.printer.as_ref(), count, opts.pc)?;
+        let function = opts.function.as_deref();
+        if opts.annotate_profile {
+            if !debugger.get_opts().profile {
+                return Err(anyhow!(
+                    "profiling is not enabled; run 'settings set profile true' first"
+                ));
+            }
+            let counts = debugger.profile_counts();
+            display_asm_with_counts(
+                debugger,
+                context.printer.as_ref(),
+                function,
+                range,
+                Some(&counts),
+            )?;
+        } else {
+            display_asm_with_counts(debugger, context.printer.as_ref(), function, range, None)?;
+        }
+        Ok(None)
+    }
+}
+
+pub struct DisassembleModuleCommand {}
+
+impl DisassembleModuleCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl<D: Debugger> Command<D> for DisassembleModuleCommand {
+    fn name(&self) -> &'static str {
+        "disassemble-module"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the main module in the WebAssembly text format."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        _args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        for line in disassemble_module(debugger.module_bytes()?)? {
+            context.printer.println(&line);
+        }
         Ok(None)
     }
 }
@@ -51,19 +130,143 @@ pub fn display_asm<D: Debugger>(
     count: Option<usize>,
     pc_rel: bool,
 ) -> Result<()> {
-    let (insts, inst_index) = debugger.selected_instructions()?;
-    let begin = if pc_rel { inst_index } else { 0 };
-    let end = if let Some(count) = count {
+    let range = AsmRange {
+        count,
+        pc_rel,
+        start_offset: None,
+        end_offset: None,
+    };
+    display_asm_with_counts(debugger, printer, None, range, None)
+}
+
+/// Instructions to be listed
+struct AsmRange {
+    count: Option<usize>,
+    /// Whether `count` instructions are listed from the pc or from the beginning
+    pc_rel: bool,
+    start_offset: Option<usize>,
+    end_offset: Option<usize>,
+}
+
+/// Returns the smallest count regarded as hot, i.e. in the top 10% of the given counts
+fn hot_threshold(counts: &[u64]) -> Option<u64> {
+    let mut counts = counts
+        .iter()
+        .copied()
+        .filter(|count| *count > 0)
+        .collect::<Vec<_>>();
+    if counts.is_empty() {
+        return None;
+    }
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    let hot_len = (counts.len() - 1) / 10 + 1;
+    Some(counts[hot_len - 1])
+}
+
+fn display_asm_with_counts<D: Debugger>(
+    debugger: &D,
+    printer: &dyn OutputPrinter,
+    function: Option<&str>,
+    range: AsmRange,
+    profile_counts: Option<&HashMap<usize, u64>>,
+) -> Result<()> {
+    let selected = debugger.selected_instructions();
+    let (insts, inst_index) = match function {
+        Some(function) => {
+            let insts = debugger.func_instructions(function)?;
+            // The pc is marked only in the function of the selected frame
+            let inst_index = match selected {
+                Ok((selected, inst_index)) if std::ptr::eq(selected, insts) => Some(inst_index),
+                _ => None,
+            };
+            (insts, inst_index)
+        }
+        None => {
+            let (insts, inst_index) = selected?;
+            (insts, Some(inst_index))
+        }
+    };
+    let begin = match inst_index {
+        Some(inst_index) if range.pc_rel => inst_index,
+        _ => 0,
+    };
+    let end = if let Some(count) = range.count {
         begin + count
     } else {
         insts.len()
     };
+    let inst_counts = profile_counts.map(|counts| {
+        insts
+            .iter()
+            .map(|inst| counts.get(&inst.offset).copied().unwrap_or(0))
+            .collect::<Vec<_>>()
+    });
+    let threshold = inst_counts.as_deref().and_then(hot_threshold);
+    let width = inst_counts
+        .as_ref()
+        .and_then(|counts| counts.iter().max())
+        .map(|max| max.to_string().len())
+        .unwrap_or(1);
+    let raw_symbols = debugger.get_opts().raw_symbols;
+    let mut depth: usize = 0;
     for (index, inst) in insts.iter().enumerate() {
-        if !(begin..end).contains(&index) {
+        let indent = match inst.kind {
+            InstructionKind::Else
+            | InstructionKind::Catch { .. }
+            | InstructionKind::CatchAll
+            | InstructionKind::End
+            | InstructionKind::Delegate { .. } => depth.saturating_sub(1),
+            _ => depth,
+        };
+        depth = match inst.kind {
+            InstructionKind::Block { .. }
+            | InstructionKind::Loop { .. }
+            | InstructionKind::If { .. }
+            | InstructionKind::Try { .. } => depth + 1,
+            InstructionKind::End | InstructionKind::Delegate { .. } => depth.saturating_sub(1),
+            _ => depth,
+        };
+        if !(begin..end).contains(&index)
+            || matches!(range.start_offset, Some(start) if inst.offset < start)
+            || matches!(range.end_offset, Some(end) if inst.offset >= end)
+        {
             continue;
         }
-        let prefix = if index == inst_index { "->" } else { "  " };
-        let output = format!("{} 0x{:>08x}: {:?}", prefix, inst.offset, inst.kind);
+        let prefix = if Some(index) == inst_index {
+            "->"
+        } else {
+            "  "
+        };
+        let mut output = format!(
+            "{} 0x{:>08x}: {}{}",
+            prefix,
+            inst.offset,
+            "  ".repeat(indent),
+            inst.kind
+        );
+        if let InstructionKind::V128Const { value } = &inst.kind {
+            output += &format!(" ;; {}", format_v128(u128::from_le_bytes(*value.bytes())));
+        }
+        if let InstructionKind::Call { function_index } = &inst.kind {
+            if let Some(name) = debugger.function_name(*function_index) {
+                let name = if raw_symbols { name } else { demangle(&name) };
+                output += &format!(" ;; {}", name);
+            }
+        }
+        if let Some(counts) = &inst_counts {
+            let count = counts[index];
+            let count_str = if count == 0 {
+                ".".to_string()
+            } else {
+                count.to_string()
+            };
+            let hot = if matches!(threshold, Some(threshold) if count >= threshold) {
+                "[HOT] "
+            } else {
+                "      "
+            };
+            output = format!("{:>width$} {}{}", count_str, hot, output, width = width);
+        }
         printer.println(&output);
     }
     Ok(())
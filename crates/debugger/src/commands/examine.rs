@@ -0,0 +1,240 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use super::memory::parse_address;
+use anyhow::{anyhow, Result};
+
+pub struct ExamineCommand {}
+
+impl ExamineCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Hex,
+    Signed,
+    Unsigned,
+    Float,
+    Char,
+    String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnitSize {
+    Byte,
+    Half,
+    Word,
+    Giant,
+}
+
+impl UnitSize {
+    fn bytes(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::Half => 2,
+            Self::Word => 4,
+            Self::Giant => 8,
+        }
+    }
+
+    fn units_per_row(self) -> usize {
+        match self {
+            Self::Byte | Self::Half => 8,
+            Self::Word => 4,
+            Self::Giant => 2,
+        }
+    }
+}
+
+/// `/NFU` of `x/NFU ADDRESS`
+#[derive(Debug, PartialEq, Eq)]
+struct Spec {
+    count: usize,
+    format: Format,
+    unit: UnitSize,
+}
+
+impl Default for Spec {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            format: Format::Hex,
+            unit: UnitSize::Word,
+        }
+    }
+}
+
+/// Parses `NFU` where each of the count, the format letter and the unit size is optional
+fn parse_spec(spec: &str) -> Result<Spec> {
+    let mut result = Spec::default();
+    let digits = spec.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        result.count = spec[..digits].parse()?;
+    }
+    for c in spec[digits..].chars() {
+        match c {
+            'x' => result.format = Format::Hex,
+            'd' => result.format = Format::Signed,
+            'u' => result.format = Format::Unsigned,
+            'f' => result.format = Format::Float,
+            'c' => result.format = Format::Char,
+            's' => result.format = Format::String,
+            'b' => result.unit = UnitSize::Byte,
+            'h' => result.unit = UnitSize::Half,
+            'w' => result.unit = UnitSize::Word,
+            'g' => result.unit = UnitSize::Giant,
+            _ => return Err(anyhow!("'{}' is not a valid format letter or unit size", c)),
+        }
+    }
+    match (result.format, result.unit) {
+        (Format::Char, _) => result.unit = UnitSize::Byte,
+        (Format::Float, UnitSize::Byte) | (Format::Float, UnitSize::Half) => {
+            return Err(anyhow!(
+                "floats must be examined in w (f32) or g (f64) units"
+            ))
+        }
+        _ => {}
+    }
+    Ok(result)
+}
+
+/// Formats a unit stored in little endian
+fn format_unit(bytes: &[u8], format: Format) -> String {
+    let size = bytes.len();
+    let mut buf = [0; 8];
+    buf[..size].copy_from_slice(bytes);
+    let unsigned = u64::from_le_bytes(buf);
+    let shift = 64 - 8 * size as u32;
+    let signed = ((unsigned << shift) as i64) >> shift;
+    match format {
+        Format::Hex => format!("0x{:0width$x}", unsigned, width = size * 2),
+        Format::Signed => signed.to_string(),
+        Format::Unsigned => unsigned.to_string(),
+        Format::Float if size == 4 => f32::from_bits(unsigned as u32).to_string(),
+        Format::Float => f64::from_bits(unsigned).to_string(),
+        Format::Char | Format::String => format!("{} {:?}", signed, bytes[0] as char),
+    }
+}
+
+impl<D: Debugger> Command<D> for ExamineCommand {
+    fn name(&self) -> &'static str {
+        "x"
+    }
+
+    fn description(&self) -> &'static str {
+        "Examine memory, e.g. x/16xb ADDRESS. Formats are x, d, u, f, c and s, and unit sizes are b, h, w and g."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let spec = match args.first().and_then(|name| name.split_once('/')) {
+            Some((_, spec)) => parse_spec(spec)?,
+            None => Spec::default(),
+        };
+        let address = args.get(1).ok_or_else(|| anyhow!("Usage: x/NFU ADDRESS"))?;
+        let address = parse_address(address)?;
+        let memory = debugger.memory()?;
+        let end_note = format!(
+            "The memory ends at 0x{:>08x}, the rest is not shown",
+            memory.len()
+        );
+
+        if spec.format == Format::String {
+            let mut offset = address;
+            for _ in 0..spec.count {
+                let rest = match memory.get(offset..) {
+                    Some(rest) if !rest.is_empty() => rest,
+                    _ => {
+                        context.printer.println(&end_note);
+                        break;
+                    }
+                };
+                let len = rest.iter().position(|b| *b == 0);
+                let string = String::from_utf8_lossy(&rest[..len.unwrap_or(rest.len())]);
+                context
+                    .printer
+                    .println(&format!("0x{:>08x}: {:?}", offset, string));
+                match len {
+                    Some(len) => offset += len + 1,
+                    None => {
+                        context.printer.println(&end_note);
+                        break;
+                    }
+                }
+            }
+            return Ok(None);
+        }
+
+        let unit = spec.unit.bytes();
+        let count = spec.count.min(memory.len().saturating_sub(address) / unit);
+        let items = (0..count)
+            .map(|index| {
+                let start = address + index * unit;
+                format_unit(&memory[start..start + unit], spec.format)
+            })
+            .collect::<Vec<_>>();
+        let width = items.iter().map(String::len).max().unwrap_or(0);
+        let units_per_row = match spec.format {
+            Format::Char => 8,
+            _ => spec.unit.units_per_row(),
+        };
+        for (row, units) in items.chunks(units_per_row).enumerate() {
+            let units = units
+                .iter()
+                .map(|item| format!("{:>width$}", item, width = width))
+                .collect::<Vec<_>>();
+            let output = format!(
+                "0x{:>08x}: {}",
+                address + row * units_per_row * unit,
+                units.join("  ")
+            );
+            context.printer.println(&output);
+        }
+        if count < spec.count {
+            context.printer.println(&end_note);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_examine_spec() {
+        let spec = parse_spec("16xb").unwrap();
+        assert_eq!(
+            spec,
+            Spec {
+                count: 16,
+                format: Format::Hex,
+                unit: UnitSize::Byte,
+            }
+        );
+        assert_eq!(parse_spec("").unwrap(), Spec::default());
+        assert_eq!(parse_spec("2c").unwrap().unit, UnitSize::Byte);
+        assert_eq!(parse_spec("fg").unwrap().unit, UnitSize::Giant);
+        assert!(parse_spec("fb").is_err());
+        assert!(parse_spec("4z").is_err());
+    }
+
+    #[test]
+    fn format_units() {
+        assert_eq!(format_unit(&[0xfe, 0xff], Format::Hex), "0xfffe");
+        assert_eq!(format_unit(&[0xfe, 0xff], Format::Signed), "-2");
+        assert_eq!(format_unit(&[0xfe, 0xff], Format::Unsigned), "65534");
+        assert_eq!(format_unit(&1.5f32.to_le_bytes(), Format::Float), "1.5");
+        assert_eq!(
+            format_unit(&(-0.25f64).to_le_bytes(), Format::Float),
+            "-0.25"
+        );
+        assert_eq!(format_unit(b"A", Format::Char), "65 'A'");
+    }
+}
@@ -55,7 +55,7 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
                         .get(idx as usize)
                         .with_context(|| "failed to get base local".to_string())?,
                     WasmLoc::Stack(idx) => *debugger
-                        .stack_values()
+                        .stack_values_for_frame(debugger.selected_frame_index())
                         .get(idx as usize)
                         .with_context(|| "failed to get base local".to_string())?,
                 };
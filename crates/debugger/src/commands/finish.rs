@@ -0,0 +1,69 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Debugger, StepStyle};
+use super::format::{format_value, type_name};
+use super::list::{display_source, next_line_info};
+use super::watch::print_changed_watches;
+use anyhow::{anyhow, Result};
+use wasminspect_vm::Signal;
+
+pub struct FinishCommand {}
+
+impl FinishCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl<D: Debugger> Command<D> for FinishCommand {
+    fn name(&self) -> &'static str {
+        "finish"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run until the current function returns and print the returned values."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        _args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        if debugger.selected_frame_index() != 0 {
+            // Only the innermost frame can be resumed
+            debugger.select_frame(None)?;
+        }
+        let frame = debugger
+            .current_frame()
+            .ok_or_else(|| anyhow!("No function frame to finish"))?;
+        if debugger.frame().len() < 2 {
+            context
+                .printer
+                .println("The outermost frame has no caller to return to");
+            return Ok(None);
+        }
+        match debugger.step(StepStyle::Out)? {
+            Signal::Breakpoint => {
+                // The function hasn't returned yet, so there are no values to print
+                context.printer.println("Hit breakpoint");
+            }
+            _ => {
+                let values = debugger.stack_values();
+                let results = &values[values.len().saturating_sub(frame.result_types.len())..];
+                let format = debugger.get_opts().value_format;
+                for (ty, value) in frame.result_types.iter().zip(results) {
+                    let output = format!(
+                        "Return value: ({}) {}",
+                        type_name(*ty),
+                        format_value(value, format)
+                    );
+                    context.printer.println(&output);
+                }
+                let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
+                display_source(line_info, context.printer.as_ref())?;
+            }
+        }
+        print_changed_watches(debugger, context);
+        Ok(None)
+    }
+}
@@ -0,0 +1,157 @@
+use super::debugger::ValueFormat;
+use anyhow::{anyhow, Result};
+use wasminspect_vm::{NumVal, WasmValue};
+use wasmparser::ValType;
+
+pub fn type_name(ty: ValType) -> &'static str {
+    match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::FuncRef => "funcref",
+        ValType::ExternRef => "externref",
+    }
+}
+
+pub fn format_value(value: &WasmValue, format: ValueFormat) -> String {
+    match (value, format) {
+        (WasmValue::Num(NumVal::I32(v)), ValueFormat::Default) => format!("{}", v),
+        (WasmValue::Num(NumVal::I64(v)), ValueFormat::Default) => format!("{}", v),
+        (WasmValue::Num(NumVal::F32(v)), ValueFormat::Default) => format!("{}", v.to_float()),
+        (WasmValue::Num(NumVal::F64(v)), ValueFormat::Default) => format!("{}", v.to_float()),
+        (WasmValue::Num(NumVal::I32(v)), ValueFormat::Hex) => format!("0x{:08x}", v),
+        (WasmValue::Num(NumVal::I64(v)), ValueFormat::Hex) => format!("0x{:016x}", v),
+        (WasmValue::Num(NumVal::F32(v)), ValueFormat::Hex) => format!("0x{:08x}", v.to_bits()),
+        (WasmValue::Num(NumVal::F64(v)), ValueFormat::Hex) => format!("0x{:016x}", v.to_bits()),
+        (WasmValue::V128(v), ValueFormat::Default) => format_v128(*v),
+        (WasmValue::V128(v), ValueFormat::Hex) => format!("0x{:032x}", v),
+        (WasmValue::Ref(r), _) => format!("{:?}", r),
+    }
+}
+
+/// An interpretation of a `v128` value as lanes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaneShape {
+    I8x16,
+    I16x8,
+    I32x4,
+    I64x2,
+    F32x4,
+    F64x2,
+}
+
+impl std::str::FromStr for LaneShape {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "i8x16" => Ok(Self::I8x16),
+            "i16x8" => Ok(Self::I16x8),
+            "i32x4" => Ok(Self::I32x4),
+            "i64x2" => Ok(Self::I64x2),
+            "f32x4" => Ok(Self::F32x4),
+            "f64x2" => Ok(Self::F64x2),
+            _ => Err(anyhow!(
+                "'{}' should be one of i8x16, i16x8, i32x4, i64x2, f32x4 or f64x2",
+                s
+            )),
+        }
+    }
+}
+
+impl LaneShape {
+    fn name(self) -> &'static str {
+        match self {
+            Self::I8x16 => "i8x16",
+            Self::I16x8 => "i16x8",
+            Self::I32x4 => "i32x4",
+            Self::I64x2 => "i64x2",
+            Self::F32x4 => "f32x4",
+            Self::F64x2 => "f64x2",
+        }
+    }
+
+    fn lane_bits(self) -> u32 {
+        match self {
+            Self::I8x16 => 8,
+            Self::I16x8 => 16,
+            Self::I32x4 | Self::F32x4 => 32,
+            Self::I64x2 | Self::F64x2 => 64,
+        }
+    }
+}
+
+/// Formats `value` like the operands of `v128.const`, where lane 0 is
+/// the least significant bits as in the SIMD spec
+pub fn format_lanes(value: u128, shape: LaneShape) -> String {
+    let bits = shape.lane_bits();
+    let lanes = (0..128 / bits)
+        .map(|index| {
+            let lane = (value >> (index * bits)) as u64;
+            match shape {
+                LaneShape::I8x16 => (lane as i8).to_string(),
+                LaneShape::I16x8 => (lane as i16).to_string(),
+                LaneShape::I32x4 => (lane as i32).to_string(),
+                LaneShape::I64x2 => (lane as i64).to_string(),
+                LaneShape::F32x4 => f32::from_bits(lane as u32).to_string(),
+                LaneShape::F64x2 => f64::from_bits(lane).to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{} {}", shape.name(), lanes.join(" "))
+}
+
+/// Formats `value` in every common lane layout to pick the relevant one from.
+/// `ValueFormat::Hex` keeps the raw bits instead.
+pub fn format_v128(value: u128) -> String {
+    [
+        LaneShape::I8x16,
+        LaneShape::I16x8,
+        LaneShape::I32x4,
+        LaneShape::F32x4,
+    ]
+    .iter()
+    .map(|shape| format_lanes(value, *shape))
+    .collect::<Vec<_>>()
+    .join("; ")
+}
+
+/// Parses a literal as a value of the given type
+pub fn parse_value(text: &str, ty: ValType) -> Result<WasmValue> {
+    let value = match ty {
+        ValType::I32 => WasmValue::from(text.parse::<i32>()?),
+        ValType::I64 => WasmValue::from(text.parse::<i64>()?),
+        ValType::F32 => WasmValue::from(text.parse::<f32>()?),
+        ValType::F64 => WasmValue::from(text.parse::<f64>()?),
+        _ => return Err(anyhow!("{} values are not supported", type_name(ty))),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_v128_lanes() {
+        let value = u128::from_le_bytes([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0xff]);
+        assert_eq!(
+            format_lanes(value, LaneShape::I8x16),
+            "i8x16 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 -1"
+        );
+        assert_eq!(
+            format_lanes(value, LaneShape::I32x4),
+            "i32x4 67305985 134678021 202050057 -15790579"
+        );
+        let value = (2.5f64.to_bits() as u128) << 64 | (-1.0f64).to_bits() as u128;
+        assert_eq!(format_lanes(value, LaneShape::F64x2), "f64x2 -1 2.5");
+        assert_eq!(
+            format_v128(0x4000_0000_3f80_0000),
+            "i8x16 0 0 -128 63 0 0 0 64 0 0 0 0 0 0 0 0; i16x8 0 16256 0 16384 0 0 0 0; \
+             i32x4 1065353216 1073741824 0 0; f32x4 1 2 0 0"
+        );
+        assert!("i4x32".parse::<LaneShape>().is_err());
+    }
+}
@@ -1,6 +1,8 @@
-use super::command::{Command, CommandContext, CommandResult};
+use super::command::{AliasCommand, Command, CommandContext, CommandResult};
 use super::debugger::Debugger;
-use anyhow::Result;
+use super::info::format_variable;
+use super::thread::format_frame;
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -21,6 +23,18 @@ enum Opts {
         #[structopt(name = "index")]
         frame_index: usize,
     },
+    /// Select the caller of the selected frame
+    #[structopt(name = "up")]
+    Up {
+        #[structopt(name = "COUNT", default_value = "1")]
+        count: usize,
+    },
+    /// Select the callee of the selected frame
+    #[structopt(name = "down")]
+    Down {
+        #[structopt(name = "COUNT", default_value = "1")]
+        count: usize,
+    },
 }
 
 impl<D: Debugger> Command<D> for FrameCommand {
@@ -38,23 +52,112 @@ impl<D: Debugger> Command<D> for FrameCommand {
         context: &CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
+        // `frame N` is a shorthand for `frame select N`
+        let args = match args.as_slice() {
+            [name, index] if index.parse::<usize>().is_ok() => vec![*name, "select", *index],
+            _ => args,
+        };
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Variable => {
-                let (insts, next_index) = debugger.selected_instructions()?;
-                let current_index = if next_index == 0 { 0 } else { next_index - 1 };
-                let current_inst = insts[current_index].clone();
-                let variable_names = context.subroutine.variable_name_list(current_inst.offset)?;
-                for variable in variable_names {
-                    let output = format!("{}: {}", variable.name, variable.type_name);
-                    context.printer.println(&output);
+                let format = debugger.get_opts().value_format;
+                for variable in debugger.local_variables() {
+                    context.printer.println(&format_variable(&variable, format));
                 }
                 Ok(None)
             }
             Opts::Select { frame_index } => {
-                debugger.select_frame(Some(frame_index))?;
+                select_frame(debugger, context, frame_index)?;
+                Ok(None)
+            }
+            Opts::Up { count } => {
+                let frame_index = debugger.selected_frame_index().saturating_add(count);
+                select_frame(debugger, context, frame_index)?;
+                Ok(None)
+            }
+            Opts::Down { count } => {
+                let selected = debugger.selected_frame_index();
+                if count > selected {
+                    context
+                        .printer
+                        .eprintln("Already at the innermost frame, selecting frame 0");
+                }
+                select_frame(debugger, context, selected.saturating_sub(count))?;
                 Ok(None)
             }
         }
     }
 }
+
+/// Selects the frame at `frame_index` from the innermost one, clamping it to the outermost frame
+fn select_frame<D: Debugger>(
+    debugger: &mut D,
+    context: &CommandContext,
+    frame_index: usize,
+) -> Result<()> {
+    let frames = debugger.frame();
+    let outermost = frames
+        .len()
+        .checked_sub(1)
+        .ok_or_else(|| anyhow!("No frames in the call stack"))?;
+    if frame_index > outermost {
+        context.printer.eprintln(&format!(
+            "Frame index {} is out of range, valid range is 0-{}",
+            frame_index, outermost
+        ));
+    }
+    let frame_index = frame_index.min(outermost);
+    let frame = &frames[outermost - frame_index];
+    context.printer.println(&format_frame(frame_index, frame));
+    debugger.select_frame(Some(frame_index))
+}
+
+/// `up [COUNT]`
+pub struct UpCommand {}
+
+impl UpCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for UpCommand {
+    fn name(&self) -> &'static str {
+        "up"
+    }
+
+    fn description(&self) -> &'static str {
+        "Select the caller of the selected frame."
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let mut line = vec!["frame", "up"];
+        line.extend(args.iter().skip(1));
+        Ok(shell_words::join(line))
+    }
+}
+
+/// `down [COUNT]`
+pub struct DownCommand {}
+
+impl DownCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for DownCommand {
+    fn name(&self) -> &'static str {
+        "down"
+    }
+
+    fn description(&self) -> &'static str {
+        "Select the callee of the selected frame."
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let mut line = vec!["frame", "down"];
+        line.extend(args.iter().skip(1));
+        Ok(shell_words::join(line))
+    }
+}
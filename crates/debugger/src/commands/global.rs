@@ -1,6 +1,8 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
+use super::debugger::{Debugger, SetGlobalError};
+use super::format::{format_value, parse_value, type_name};
 use anyhow::{anyhow, Result};
+use wasminspect_vm::ModuleIndex;
 
 use structopt::StructOpt;
 
@@ -14,13 +16,101 @@ impl GlobalCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Print a global, or all globals if INDEX is omitted
     #[structopt(name = "read")]
     Read {
+        #[structopt(name = "INDEX")]
+        index: Option<usize>,
+        #[structopt(flatten)]
+        module: ModuleOpts,
+    },
+    /// List all globals with their mutability, types and values
+    #[structopt(name = "list")]
+    List {
+        #[structopt(flatten)]
+        module: ModuleOpts,
+    },
+    /// Print a global
+    #[structopt(name = "get")]
+    Get {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(flatten)]
+        module: ModuleOpts,
+    },
+    /// Overwrite a mutable global
+    #[structopt(name = "set")]
+    Set {
         #[structopt(name = "INDEX")]
         index: usize,
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        value: String,
+        #[structopt(flatten)]
+        module: ModuleOpts,
     },
 }
 
+#[derive(StructOpt)]
+struct ModuleOpts {
+    /// The index of the module, the selected frame's module by default
+    #[structopt(long)]
+    module: Option<u32>,
+}
+
+impl ModuleOpts {
+    fn module_index<D: Debugger>(&self, debugger: &D) -> Result<ModuleIndex> {
+        match self.module {
+            Some(index) => Ok(ModuleIndex(index)),
+            None => match debugger.current_frame() {
+                Some(frame) => Ok(frame.module_index),
+                None => Err(anyhow!("function frame not found")),
+            },
+        }
+    }
+}
+
+fn print_globals<D: Debugger>(
+    debugger: &D,
+    context: &CommandContext,
+    module_index: ModuleIndex,
+) -> Result<()> {
+    let globals = debugger.globals(Some(module_index))?;
+    let module = debugger
+        .store()
+        .ok()
+        .and_then(|store| store.module(module_index).defined());
+    let format = debugger.get_opts().value_format;
+    for (index, (is_mutable, value)) in globals.iter().enumerate() {
+        let name = module.and_then(|m| m.global_name(index as u32));
+        let output = format!(
+            "{: <3} ({}) {}: {} = {}",
+            index,
+            if *is_mutable { "mut" } else { "const" },
+            name.map(String::as_str).unwrap_or("<unnamed>"),
+            type_name(value.value_type()),
+            format_value(value, format)
+        );
+        context.printer.println(&output);
+    }
+    Ok(())
+}
+
+fn print_global<D: Debugger>(
+    debugger: &D,
+    context: &CommandContext,
+    module_index: ModuleIndex,
+    index: usize,
+) -> Result<()> {
+    let globals = debugger.globals(Some(module_index))?;
+    let (_, value) = globals.get(index).ok_or(SetGlobalError::OutOfRange {
+        index,
+        count: globals.len(),
+    })?;
+    let format = debugger.get_opts().value_format;
+    context.printer.println(&format_value(value, format));
+    Ok(())
+}
+
 impl<D: Debugger> Command<D> for GlobalCommand {
     fn name(&self) -> &'static str {
         "global"
@@ -37,19 +127,37 @@ impl<D: Debugger> Command<D> for GlobalCommand {
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
-        use wasminspect_vm::*;
         match opts {
-            Opts::Read { index } => {
-                let store: &Store = debugger.store()?;
-                let mod_index = match debugger.current_frame() {
-                    Some(frame) => frame.module_index,
-                    None => return Err(anyhow!("function frame not found")),
-                };
-                let global = store.global(GlobalAddr::new_unsafe(mod_index, index));
-                let output = format!("{:?}", global.borrow().value());
-                context.printer.println(&output);
-                Ok(None)
+            Opts::Read { index, module } => {
+                let module_index = module.module_index(debugger)?;
+                match index {
+                    Some(index) => print_global(debugger, context, module_index, index)?,
+                    None => print_globals(debugger, context, module_index)?,
+                }
+            }
+            Opts::List { module } => {
+                let module_index = module.module_index(debugger)?;
+                print_globals(debugger, context, module_index)?;
+            }
+            Opts::Get { index, module } => {
+                let module_index = module.module_index(debugger)?;
+                print_global(debugger, context, module_index, index)?;
+            }
+            Opts::Set {
+                index,
+                value,
+                module,
+            } => {
+                let module_index = module.module_index(debugger)?;
+                let globals = debugger.globals(Some(module_index))?;
+                let (_, current) = globals.get(index).ok_or(SetGlobalError::OutOfRange {
+                    index,
+                    count: globals.len(),
+                })?;
+                let value = parse_value(&value, current.value_type())?;
+                debugger.set_global(Some(module_index), index, value)?;
             }
         }
+        Ok(None)
     }
 }
@@ -0,0 +1,71 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Debugger, ValueFormat, VariableInfo};
+use super::format::format_value;
+use anyhow::Result;
+
+use structopt::StructOpt;
+
+pub struct InfoCommand {}
+
+impl InfoCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Show parameters of the selected frame
+    #[structopt(name = "args")]
+    Args,
+    /// Show variables in scope at the selected frame, excluding parameters
+    #[structopt(name = "locals")]
+    Locals,
+}
+
+impl<D: Debugger> Command<D> for InfoCommand {
+    fn name(&self) -> &'static str {
+        "info"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for showing source variables of the selected frame."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        let params = matches!(opts, Opts::Args);
+        let format = debugger.get_opts().value_format;
+        let variables = debugger
+            .local_variables()
+            .into_iter()
+            .filter(|variable| variable.is_param == params)
+            .collect::<Vec<_>>();
+        if variables.is_empty() {
+            let output = if params {
+                "No arguments."
+            } else {
+                "No locals."
+            };
+            context.printer.println(output);
+        }
+        for variable in variables {
+            context.printer.println(&format_variable(&variable, format));
+        }
+        Ok(None)
+    }
+}
+
+/// Formats a variable as `name: type = value`
+pub fn format_variable(variable: &VariableInfo, format: ValueFormat) -> String {
+    let value = match &variable.value {
+        Some(value) => format_value(value, format),
+        None => "<unavailable>".to_string(),
+    };
+    format!("{}: {} = {}", variable.name, variable.type_name, value)
+}
@@ -3,6 +3,8 @@ use super::debugger::{Debugger, OutputPrinter};
 use super::sourcemap::{ColumnType, LineInfo, SourceMap};
 use anyhow::{anyhow, Result};
 
+use structopt::StructOpt;
+
 pub struct ListCommand {}
 
 impl ListCommand {
@@ -11,6 +13,13 @@ impl ListCommand {
     }
 }
 
+#[derive(StructOpt)]
+struct Opts {
+    /// List the source around the beginning of the function instead of the current position
+    #[structopt(name = "FUNCTION")]
+    function: Option<String>,
+}
+
 impl<D: Debugger> Command<D> for ListCommand {
     fn name(&self) -> &'static str {
         "list"
@@ -24,9 +33,22 @@ impl<D: Debugger> Command<D> for ListCommand {
         &self,
         debugger: &mut D,
         context: &CommandContext,
-        _args: Vec<&str>,
+        args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
-        let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
+        let opts = Opts::from_iter_safe(args)?;
+        let line_info = match opts.function {
+            Some(name) => {
+                let pc = context
+                    .subroutine
+                    .lookup_func_by_name(&name)
+                    .ok_or_else(|| anyhow!("Function '{}' not found", name))?;
+                context
+                    .sourcemap
+                    .find_line_info(pc.start as usize)
+                    .ok_or_else(|| anyhow!("Source info not found"))?
+            }
+            None => next_line_info(debugger, context.sourcemap.as_ref())?,
+        };
         display_source(line_info, context.printer.as_ref())?;
         Ok(None)
     }
@@ -0,0 +1,53 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use anyhow::Result;
+use std::path::Path;
+
+use structopt::StructOpt;
+
+pub struct LoadCommand {}
+
+impl LoadCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Instantiate a wasm file and provide its exports as imports of the given module name
+    #[structopt(name = "module")]
+    Module {
+        #[structopt(name = "NAME")]
+        name: String,
+        #[structopt(name = "FILE")]
+        file: String,
+    },
+}
+
+impl<D: Debugger> Command<D> for LoadCommand {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for loading additional modules."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Module { name, file } => {
+                debugger.load_host_module_from_file(&name, Path::new(&file))?;
+                let output = format!("Loaded {} as module '{}'", file, name);
+                context.printer.println(&output);
+            }
+        }
+        Ok(None)
+    }
+}
@@ -1,6 +1,7 @@
-use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
-use anyhow::Result;
+use super::command::{AliasCommand, Command, CommandContext, CommandResult};
+use super::debugger::{Debugger, LocalInfo};
+use super::format::{format_value, parse_value, type_name};
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -19,6 +20,23 @@ enum Opts {
         #[structopt(name = "INDEX")]
         index: Option<usize>,
     },
+    /// Overwrite a local of the selected frame
+    #[structopt(name = "set")]
+    Set {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Show the recorded changes of a local in the current function
+    #[structopt(name = "history")]
+    History {
+        #[structopt(name = "INDEX")]
+        index: usize,
+    },
+    /// Start recording changes of locals
+    #[structopt(name = "enable-history")]
+    EnableHistory,
 }
 
 impl<D: Debugger> Command<D> for LocalCommand {
@@ -39,20 +57,160 @@ impl<D: Debugger> Command<D> for LocalCommand {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Read { index: None } => {
-                for (index, value) in debugger.locals().iter().enumerate() {
-                    let output = format!("{: <3}: {:?}", index, value);
-                    context.printer.println(&output);
+                let format = debugger.get_opts().value_format;
+                for local in locals_with_debug_info(debugger, context)? {
+                    context.printer.println(&display_local(&local, format));
                 }
             }
             Opts::Read { index: Some(index) } => {
-                let locals = debugger.locals();
-                if index >= locals.len() {
-                    return Err(anyhow::anyhow!("{:?} is out of range, locals length is {:?}", index, locals.len()));
+                let locals = locals_with_debug_info(debugger, context)?;
+                let local = locals.get(index).ok_or_else(|| {
+                    anyhow!(
+                        "{:?} is out of range, locals length is {:?}",
+                        index,
+                        locals.len()
+                    )
+                })?;
+                let mut output = format_value(&local.value, debugger.get_opts().value_format);
+                if let Some(name) = &local.name {
+                    output = format!("{} = {}", name, output);
                 }
-                let output = format!("{:?}", locals[index]);
                 context.printer.println(&output);
             }
+            Opts::Set { index, value } => {
+                let locals = debugger.frame_locals()?;
+                let local = locals.get(index).ok_or_else(|| {
+                    anyhow!(
+                        "{:?} is out of range, locals length is {:?}",
+                        index,
+                        locals.len()
+                    )
+                })?;
+                let value = parse_value(&value, local.wasm_type)?;
+                debugger.set_local(index, value)?;
+            }
+            Opts::History { index } => {
+                if !debugger.get_opts().track_locals {
+                    return Err(anyhow!(
+                        "local history is not recorded, run 'local enable-history' first"
+                    ));
+                }
+                let frame = debugger
+                    .current_frame()
+                    .ok_or_else(|| anyhow!("function frame not found"))?;
+                for change in debugger.local_history(frame.func_index, index) {
+                    let output = format!(
+                        "0x{:>08x}: {:?} -> {:?}",
+                        change.instruction_offset, change.old_value, change.new_value
+                    );
+                    context.printer.println(&output);
+                }
+            }
+            Opts::EnableHistory => {
+                let mut opts = debugger.get_opts();
+                opts.track_locals = true;
+                debugger.set_opts(opts);
+            }
         }
         Ok(None)
     }
 }
+
+/// Returns locals of the selected frame with names and locations from debug info.
+/// Names from DWARF take priority, and the name section fills the rest
+fn locals_with_debug_info<D: Debugger>(
+    debugger: &D,
+    context: &CommandContext,
+) -> Result<Vec<LocalInfo>> {
+    let mut locals = debugger.frame_locals()?;
+    let mut variables = selected_code_offset(debugger)
+        .and_then(|offset| context.subroutine.local_variables(offset).ok())
+        .unwrap_or_default();
+    for local in locals.iter_mut() {
+        if let Some(variable) = variables.remove(&local.index) {
+            local.name = Some(variable.name);
+            local.source_location = variable.source_location;
+        }
+    }
+    Ok(locals)
+}
+
+/// The offset of the instruction being executed in the selected frame
+fn selected_code_offset<D: Debugger>(debugger: &D) -> Option<usize> {
+    let (insts, next_index) = debugger.selected_instructions().ok()?;
+    let current_index = if next_index == 0 { 0 } else { next_index - 1 };
+    insts.get(current_index).map(|inst| inst.offset)
+}
+
+fn display_local(local: &LocalInfo, format: super::debugger::ValueFormat) -> String {
+    let kind = if local.is_param { "param" } else { "local" };
+    let mut output = format!(
+        "{: <3} ({}) {}: {} = {}",
+        local.index,
+        kind,
+        local.name.as_deref().unwrap_or("<unnamed>"),
+        type_name(local.wasm_type),
+        format_value(&local.value, format)
+    );
+    if let Some(ref location) = local.source_location {
+        match location.line {
+            Some(line) => output += &format!(" at {}:{}", location.filepath, line),
+            None => output += &format!(" at {}", location.filepath),
+        }
+    }
+    output
+}
+
+/// `locals ...`, the same as `local ...`, e.g. `locals history INDEX`
+pub struct LocalsCommand {}
+
+impl LocalsCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for LocalsCommand {
+    fn name(&self) -> &'static str {
+        "locals"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating locals, the same as 'local'."
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let mut line = vec!["local"];
+        line.extend(&args[1..]);
+        Ok(shell_words::join(line))
+    }
+}
+
+/// `set local INDEX VALUE`
+pub struct SetCommand {}
+
+impl SetCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for SetCommand {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Overwrite a local of the selected frame or a global, e.g. set local 0 = 42."
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        match args.as_slice() {
+            [_, kind @ ("local" | "global"), index, "=", value]
+            | [_, kind @ ("local" | "global"), index, value] => {
+                Ok(shell_words::join(vec![kind, "set", index, value]))
+            }
+            _ => Err(anyhow!("Usage: set (local|global) INDEX = VALUE")),
+        }
+    }
+}
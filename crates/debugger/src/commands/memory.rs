@@ -1,6 +1,7 @@
-use super::command::{Command, CommandContext, CommandResult};
+use super::command::{AliasCommand, Command, CommandContext, CommandResult};
 use super::debugger::Debugger;
 use anyhow::{anyhow, Result};
+use std::path::Path;
 
 use structopt::StructOpt;
 
@@ -14,13 +15,84 @@ impl MemoryCommand {
 
 #[derive(StructOpt)]
 enum Opts {
-    #[structopt(name = "read")]
+    #[structopt(name = "read", alias = "hexdump")]
     Read {
         #[structopt(name = "ADDRESS")]
         address: String,
         #[structopt(short, long, default_value = "32")]
         count: u32,
+        /// How bytes are printed: hex, decimal or binary
+        #[structopt(long, default_value = "hex", parse(try_from_str = parse_dump_format))]
+        format: DumpFormat,
+        /// Bytes per row
+        #[structopt(long, default_value = "16")]
+        width: usize,
+        /// Index of the memory to read with multiple memories
+        #[structopt(long, default_value = "0")]
+        index: usize,
     },
+    /// Compute the CRC32 checksum of a memory range
+    #[structopt(name = "checksum")]
+    Checksum {
+        #[structopt(long, default_value = "0", parse(try_from_str = parse_address))]
+        offset: usize,
+        /// Defaults to the rest of the memory
+        #[structopt(long)]
+        length: Option<usize>,
+    },
+    /// Find all occurrences of a byte pattern
+    #[structopt(name = "find", alias = "search")]
+    Find {
+        /// Hex bytes like "DE AD BE EF", a typed integer like "i32:12345", or a string
+        #[structopt(name = "PATTERN", conflicts_with_all = &["hex", "string"])]
+        pattern: Option<String>,
+        /// Bytes in hex, e.g. "DE AD BE EF"
+        #[structopt(long, conflicts_with = "string")]
+        hex: Option<String>,
+        #[structopt(long)]
+        string: Option<String>,
+        #[structopt(long, alias = "from", parse(try_from_str = parse_address))]
+        start: Option<usize>,
+        #[structopt(long, alias = "to", parse(try_from_str = parse_address))]
+        end: Option<usize>,
+        #[structopt(long, default_value = "1")]
+        align: usize,
+        /// Maximum number of matches to print
+        #[structopt(long, default_value = "16")]
+        max_results: usize,
+    },
+    /// Write a range of the memory to a file
+    #[structopt(name = "dump")]
+    Dump {
+        #[structopt(name = "FILE")]
+        file: String,
+        #[structopt(long, default_value = "0", parse(try_from_str = parse_address))]
+        offset: usize,
+        /// Defaults to the rest of the memory
+        #[structopt(long)]
+        length: Option<usize>,
+    },
+    /// Write the contents of a file into the memory
+    #[structopt(name = "load")]
+    Load {
+        #[structopt(name = "FILE")]
+        file: String,
+        #[structopt(long, default_value = "0", parse(try_from_str = parse_address))]
+        offset: usize,
+    },
+    /// Give a name to a memory region to annotate dumps
+    #[structopt(name = "label")]
+    Label {
+        #[structopt(name = "START")]
+        start: String,
+        #[structopt(name = "LEN")]
+        len: usize,
+        #[structopt(name = "NAME")]
+        name: String,
+    },
+    /// Summarize data, stack and heap regions of the memory
+    #[structopt(name = "layout")]
+    Layout,
     #[structopt(name = "enable-watch")]
     EnableWatch,
 }
@@ -41,35 +113,128 @@ impl<D: Debugger> Command<D> for MemoryCommand {
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Read { address, count } => {
-                let address = if address.starts_with("0x") {
-                    let raw = address.trim_start_matches("0x");
-                    i64::from_str_radix(raw, 16)?
-                } else {
-                    address.parse::<i64>()?
-                };
-                let memory = debugger.memory()?;
+            Opts::Read {
+                address,
+                count,
+                format,
+                width,
+                index,
+            } => {
+                let address = parse_address(&address)?;
+                let memory = debugger.memory_at(index)?;
 
-                let begin = address as usize;
+                let begin = address;
                 let end = begin + (count as usize);
-                let chunk_size = 16;
-                if memory.len() <= end {
+                if width == 0 {
+                    return Err(anyhow!("width must be greater than 0"));
+                }
+                if memory.len() < end {
                     return Err(anyhow!(
                         "index {} out of range for slice of length {}",
                         end,
                         memory.len()
                     ));
                 }
-                for (offset, bytes) in memory[begin..end].chunks(chunk_size).enumerate() {
-                    let bytes_str = bytes
+                let labels = debugger
+                    .memory_labels()
+                    .into_iter()
+                    .filter(|region| region.overlaps(begin, end))
+                    .collect::<Vec<_>>();
+                let margin = labels.iter().map(|r| r.label.len() + 3).max().unwrap_or(0);
+                for (offset, bytes) in memory[begin..end].chunks(width).enumerate() {
+                    let row_begin = begin + offset * width;
+                    let label = labels
                         .iter()
-                        .map(|b| format!("{:>02x}", b))
-                        .collect::<Vec<String>>();
+                        .find(|region| region.overlaps(row_begin, row_begin + bytes.len()))
+                        .map(|region| format!("[{}]", region.label))
+                        .unwrap_or_default();
+                    let output = format!(
+                        "{:<margin$}{}",
+                        label,
+                        format_dump_row(row_begin, bytes, width, format),
+                        margin = margin
+                    );
+                    context.printer.println(&output);
+                }
+                Ok(None)
+            }
+            Opts::Checksum { offset, length } => {
+                let length = match length {
+                    Some(length) => length,
+                    None => debugger.memory()?.len().saturating_sub(offset),
+                };
+                let checksum = debugger.memory_checksum(offset, length)?;
+                let output = format!("0x{:>08x}", checksum);
+                context.printer.println(&output);
+                Ok(None)
+            }
+            Opts::Dump {
+                file,
+                offset,
+                length,
+            } => {
+                let length = match length {
+                    Some(length) => length,
+                    None => debugger.memory()?.len().saturating_sub(offset),
+                };
+                debugger.dump_memory(Path::new(&file), offset, length)?;
+                let output = format!("Wrote {} bytes to {}", length, file);
+                context.printer.println(&output);
+                Ok(None)
+            }
+            Opts::Load { file, offset } => {
+                debugger.load_memory_from_file(Path::new(&file), offset)?;
+                Ok(None)
+            }
+            Opts::Find {
+                pattern,
+                hex,
+                string,
+                start,
+                end,
+                align,
+                max_results,
+            } => {
+                let pattern = match (pattern, hex, string) {
+                    (Some(pattern), _, _) => parse_search_pattern(&pattern)?,
+                    (None, Some(hex), _) => parse_hex_bytes(&hex)?,
+                    (None, None, Some(string)) => string.into_bytes(),
+                    (None, None, None) => return Err(anyhow!("no pattern option")),
+                };
+                let start = start.unwrap_or(0);
+                let end = end.unwrap_or(usize::MAX);
+                let offsets = debugger
+                    .find_in_memory(&pattern, align)?
+                    .into_iter()
+                    .filter(|offset| start <= *offset && offset + pattern.len() <= end)
+                    .collect::<Vec<_>>();
+                for offset in offsets.iter().take(max_results) {
+                    let output = format!("0x{:>08x}", offset);
+                    context.printer.println(&output);
+                }
+                if offsets.len() > max_results {
+                    let output = format!(
+                        "... {} more matches, use --max-results to show them",
+                        offsets.len() - max_results
+                    );
+                    context.printer.println(&output);
+                }
+                Ok(None)
+            }
+            Opts::Label { start, len, name } => {
+                let start = parse_address(&start)?;
+                debugger.label_memory_region(start, len, name);
+                Ok(None)
+            }
+            Opts::Layout => {
+                for region in memory_layout(debugger)? {
+                    let end = match region.end {
+                        Some(end) => format!("0x{:>08x}", end),
+                        None => "...".to_string(),
+                    };
                     let output = format!(
-                        "0x{:>08x}: {} {}",
-                        begin + offset * chunk_size,
-                        bytes_str.join(" "),
-                        dump_memory_as_str(bytes)
+                        "[0x{:>08x}, {:<10}) {}",
+                        region.start, end, region.description
                     );
                     context.printer.println(&output);
                 }
@@ -85,6 +250,196 @@ impl<D: Debugger> Command<D> for MemoryCommand {
     }
 }
 
+struct MemoryRegion {
+    start: usize,
+    end: Option<usize>,
+    description: String,
+}
+
+fn global_address<D: Debugger>(debugger: &D, name: &str) -> Result<Option<usize>> {
+    let value = debugger.global_by_name(name)?;
+    Ok(value
+        .and_then(|value| value.as_i32())
+        .map(|value| value as u32 as usize))
+}
+
+/// Estimates regions following the wasm-ld conventions: the data segments, the stack growing
+/// down from `__heap_base` (or from the data start with `--stack-first`), and the heap.
+fn memory_layout<D: Debugger>(debugger: &D) -> Result<Vec<MemoryRegion>> {
+    let memory_len = debugger.memory()?.len();
+    let segments = debugger.data_segments()?;
+    let labels = debugger.memory_labels();
+    let data_start = segments
+        .iter()
+        .map(|s| s.offset)
+        .chain(labels.iter().map(|l| l.start))
+        .min();
+    let data_end = segments
+        .iter()
+        .map(|s| s.offset + s.len)
+        .chain(labels.iter().map(|l| l.start + l.len))
+        .max();
+    let data_end = global_address(debugger, "__data_end")?.or(data_end);
+    let stack_pointer = global_address(debugger, "__stack_pointer")?;
+    let heap_base = global_address(debugger, "__heap_base")?;
+
+    let mut regions = Vec::new();
+    if let (Some(start), Some(end)) = (data_start, data_end) {
+        regions.push(MemoryRegion {
+            start,
+            end: Some(end),
+            description: format!("data segments ({})", segments.len()),
+        });
+    }
+    if let Some(sp) = stack_pointer {
+        let stack = match (data_start, data_end, heap_base) {
+            (Some(data_start), _, _) if sp <= data_start => Some((0, data_start)),
+            (_, Some(data_end), Some(heap_base)) if data_end <= sp && sp <= heap_base => {
+                Some((data_end, heap_base))
+            }
+            _ => None,
+        };
+        match stack {
+            Some((start, end)) => regions.push(MemoryRegion {
+                start,
+                end: Some(end),
+                description: format!("stack (sp = 0x{:>08x})", sp),
+            }),
+            None => regions.push(MemoryRegion {
+                start: sp,
+                end: None,
+                description: "stack pointer".to_string(),
+            }),
+        }
+    }
+    if let Some(heap_base) = heap_base {
+        regions.push(MemoryRegion {
+            start: heap_base,
+            end: Some(memory_len).filter(|len| *len > heap_base),
+            description: "heap".to_string(),
+        });
+    }
+    if regions.is_empty() {
+        return Err(anyhow!("no data segments or linker globals found"));
+    }
+    regions.sort_by_key(|region| region.start);
+    Ok(regions)
+}
+
+pub(super) fn parse_address(address: &str) -> Result<usize> {
+    let address = if address.starts_with("0x") {
+        let raw = address.trim_start_matches("0x");
+        usize::from_str_radix(raw, 16)?
+    } else {
+        address.parse::<usize>()?
+    };
+    Ok(address)
+}
+
+fn parse_hex_bytes(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<char>>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err(anyhow!("'{}' is not a valid hex byte sequence", hex));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte = pair.iter().collect::<String>();
+            u8::from_str_radix(&byte, 16).map_err(|_| anyhow!("'{}' is not a valid hex byte", byte))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DumpFormat {
+    Hex,
+    Decimal,
+    Binary,
+}
+
+impl DumpFormat {
+    fn format_byte(self, byte: u8) -> String {
+        match self {
+            Self::Hex => format!("{:02x}", byte),
+            Self::Decimal => format!("{:>3}", byte),
+            Self::Binary => format!("{:08b}", byte),
+        }
+    }
+
+    fn byte_width(self) -> usize {
+        match self {
+            Self::Hex => 2,
+            Self::Decimal => 3,
+            Self::Binary => 8,
+        }
+    }
+}
+
+fn parse_dump_format(format: &str) -> Result<DumpFormat> {
+    match format {
+        "x" | "hex" => Ok(DumpFormat::Hex),
+        "d" | "decimal" => Ok(DumpFormat::Decimal),
+        "b" | "binary" => Ok(DumpFormat::Binary),
+        _ => Err(anyhow!("'{}' is not hex, decimal or binary", format)),
+    }
+}
+
+/// Formats a row of a dump like `0x00001000  48 65 6c 6c  6f 20 57 6f  |Hello Wo|`.
+/// Rows shorter than `width` are padded to keep the ASCII column aligned.
+fn format_dump_row(address: usize, bytes: &[u8], width: usize, format: DumpFormat) -> String {
+    let mut columns = String::new();
+    for index in 0..width {
+        if index > 0 {
+            columns.push(' ');
+            // Split the row in halves
+            if index == width / 2 {
+                columns.push(' ');
+            }
+        }
+        match bytes.get(index) {
+            Some(byte) => columns += &format.format_byte(*byte),
+            None => columns += &" ".repeat(format.byte_width()),
+        }
+    }
+    format!(
+        "0x{:>08x}  {}  |{}|",
+        address,
+        columns,
+        dump_memory_as_str(bytes)
+    )
+}
+
+/// Parses a pattern of `memory find`. `TYPE:VALUE` is encoded in little endian,
+/// hex digits are read as bytes and anything else is searched as a string.
+fn parse_search_pattern(pattern: &str) -> Result<Vec<u8>> {
+    if let Some((ty, value)) = pattern.split_once(':') {
+        let bytes = match ty {
+            "i8" => value.parse::<i8>()?.to_le_bytes().to_vec(),
+            "u8" => value.parse::<u8>()?.to_le_bytes().to_vec(),
+            "i16" => value.parse::<i16>()?.to_le_bytes().to_vec(),
+            "u16" => value.parse::<u16>()?.to_le_bytes().to_vec(),
+            "i32" => value.parse::<i32>()?.to_le_bytes().to_vec(),
+            "u32" => value.parse::<u32>()?.to_le_bytes().to_vec(),
+            "i64" => value.parse::<i64>()?.to_le_bytes().to_vec(),
+            "u64" => value.parse::<u64>()?.to_le_bytes().to_vec(),
+            "f32" => value.parse::<f32>()?.to_le_bytes().to_vec(),
+            "f64" => value.parse::<f64>()?.to_le_bytes().to_vec(),
+            _ => return Ok(pattern.as_bytes().to_vec()),
+        };
+        return Ok(bytes);
+    }
+    if pattern.len() >= 2 && pattern.starts_with('"') && pattern.ends_with('"') {
+        return Ok(pattern.as_bytes()[1..pattern.len() - 1].to_vec());
+    }
+    match parse_hex_bytes(pattern) {
+        Ok(bytes) => Ok(bytes),
+        Err(_) => Ok(pattern.as_bytes().to_vec()),
+    }
+}
+
 use std::str;
 fn dump_memory_as_str(bytes: &[u8]) -> String {
     let mut v = Vec::new();
@@ -100,3 +455,94 @@ fn dump_memory_as_str(bytes: &[u8]) -> String {
     }
     v.join("")
 }
+
+/// `mem dump OFFSET LENGTH [--format FORMAT] [--width WIDTH]`, `mem search PATTERN`,
+/// `mem dump-file OFFSET LENGTH PATH` or `mem load-file OFFSET PATH`
+pub struct MemCommand {}
+
+impl MemCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AliasCommand for MemCommand {
+    fn name(&self) -> &'static str {
+        "mem"
+    }
+
+    fn description(&self) -> &'static str {
+        "Dump, search, save or load the memory."
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        match args.as_slice() {
+            [_, "dump", offset, length, rest @ ..] => {
+                let mut line = vec!["memory", "read", offset, "--count", length];
+                line.extend(rest);
+                Ok(shell_words::join(line))
+            }
+            [_, "search", rest @ ..] => {
+                let mut line = vec!["memory", "search"];
+                line.extend(rest);
+                Ok(shell_words::join(line))
+            }
+            [_, "dump-file", offset, length, path] => Ok(shell_words::join([
+                "memory", "dump", path, "--offset", offset, "--length", length,
+            ])),
+            [_, "load-file", offset, path] => Ok(shell_words::join([
+                "memory", "load", path, "--offset", offset,
+            ])),
+            _ => Err(anyhow!(
+                "Usage: mem dump OFFSET LENGTH [--format hex|decimal|binary] [--width WIDTH] | mem search PATTERN [--from OFFSET] [--to OFFSET] | mem dump-file OFFSET LENGTH PATH | mem load-file OFFSET PATH"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_dump_row() {
+        let row = format_dump_row(0x1000, b"Hello World!\0\0\0\0", 16, DumpFormat::Hex);
+        assert_eq!(
+            row,
+            "0x00001000  48 65 6c 6c 6f 20 57 6f  72 6c 64 21 00 00 00 00  |Hello World!....|"
+        );
+    }
+
+    #[test]
+    fn search_patterns() {
+        let parse = |pattern| parse_search_pattern(pattern).unwrap();
+        assert_eq!(parse("DE AD BE EF"), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(parse("i32:12345"), 12345i32.to_le_bytes().to_vec());
+        assert_eq!(parse("u16:65535"), vec![0xff, 0xff]);
+        assert_eq!(parse("hello"), b"hello".to_vec());
+        assert_eq!(parse("\"cafe\""), b"cafe".to_vec());
+        assert!(parse_search_pattern("i32:x").is_err());
+    }
+
+    #[test]
+    fn mem_file_aliases() {
+        let expand = |line: &str| MemCommand::new().run(line.split(' ').collect());
+        assert_eq!(
+            expand("mem dump-file 0x10 32 heap.bin").unwrap(),
+            "memory dump heap.bin --offset 0x10 --length 32"
+        );
+        assert_eq!(
+            expand("mem load-file 0x10 heap.bin").unwrap(),
+            "memory load heap.bin --offset 0x10"
+        );
+        assert!(expand("mem load-file heap.bin").is_err());
+    }
+
+    #[test]
+    fn padded_dump_rows() {
+        let row = format_dump_row(0x10, b"AB", 4, DumpFormat::Decimal);
+        assert_eq!(row, "0x00000010   65  66           |AB|");
+        let row = format_dump_row(0, &[5], 2, DumpFormat::Binary);
+        assert_eq!(row, "0x00000000  00000101            |.|");
+    }
+}
@@ -0,0 +1,120 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Debugger, FunctionMock};
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
+
+pub struct MockCommand {}
+
+impl MockCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Make a host function return the given value instead of running it
+    #[structopt(name = "function")]
+    Function {
+        #[structopt(name = "MODULE")]
+        module: String,
+        #[structopt(name = "NAME")]
+        name: String,
+        #[structopt(name = "RETURN_VALUE")]
+        return_value: Option<String>,
+    },
+    /// Restore a mocked host function
+    #[structopt(name = "delete")]
+    Delete {
+        #[structopt(name = "MODULE")]
+        module: String,
+        #[structopt(name = "NAME")]
+        name: String,
+    },
+    /// Restore all mocked host functions
+    #[structopt(name = "clear")]
+    Clear,
+}
+
+/// Mocks are applied when the process is instantiated
+fn print_next_launch_note<D: Debugger>(debugger: &D, context: &CommandContext) {
+    if debugger.is_running() {
+        context
+            .printer
+            .println("The change takes effect from the next launch");
+    }
+}
+
+impl<D: Debugger> Command<D> for MockCommand {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for replacing host functions with test doubles."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Function {
+                module,
+                name,
+                return_value,
+            } => {
+                debugger.mock_function(FunctionMock {
+                    module,
+                    name,
+                    return_value,
+                })?;
+                print_next_launch_note(debugger, context);
+            }
+            Opts::Delete { module, name } => {
+                if !debugger.delete_mock(&module, &name) {
+                    return Err(anyhow!("'{}.{}' is not mocked", module, name));
+                }
+                print_next_launch_note(debugger, context);
+            }
+            Opts::Clear => {
+                debugger.clear_mocks();
+                print_next_launch_note(debugger, context);
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::MainDebugger;
+
+    fn mock(name: &str, return_value: Option<&str>) -> FunctionMock {
+        FunctionMock {
+            module: "wasi_snapshot_preview1".to_string(),
+            name: name.to_string(),
+            return_value: return_value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn validate_mocks_when_set() {
+        let mut debugger = MainDebugger::new(vec![], vec![], true).unwrap();
+        debugger.mock_function(mock("fd_write", Some("0"))).unwrap();
+        assert!(debugger.mock_function(mock("fd_write", None)).is_err());
+        assert!(debugger.mock_function(mock("fd_write", Some("x"))).is_err());
+        assert!(debugger.mock_function(mock("no_such_func", None)).is_err());
+
+        assert!(debugger.delete_mock("wasi_snapshot_preview1", "fd_write"));
+        assert!(!debugger.delete_mock("wasi_snapshot_preview1", "fd_write"));
+
+        let mut debugger = MainDebugger::new(vec![], vec![], false).unwrap();
+        assert!(debugger.mock_function(mock("fd_write", Some("0"))).is_err());
+    }
+}
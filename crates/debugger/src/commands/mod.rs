@@ -1,5 +1,6 @@
 pub mod command;
 pub mod debugger;
+pub mod format;
 pub mod sourcemap;
 pub mod subroutine;
 pub mod symbol;
@@ -8,14 +9,26 @@ pub mod symbol;
 pub mod backtrace;
 pub mod breakpoint;
 pub mod disassemble;
+pub mod examine;
 pub mod expression;
+pub mod finish;
 pub mod frame;
 pub mod global;
+pub mod info;
 pub mod list;
+pub mod load;
 pub mod local;
 pub mod memory;
+pub mod mock;
+pub mod module;
+pub mod print;
 pub mod process;
+pub mod profiler;
 pub mod run;
+pub mod run_to;
 pub mod settings;
 pub mod stack;
+pub mod stats;
 pub mod thread;
+pub mod trace;
+pub mod watch;
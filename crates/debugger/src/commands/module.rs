@@ -0,0 +1,62 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
+
+pub struct ModuleCommand {}
+
+impl ModuleCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Show the toolchains that produced the module
+    #[structopt(name = "producers")]
+    Producers,
+}
+
+impl<D: Debugger> Command<D> for ModuleCommand {
+    fn name(&self) -> &'static str {
+        "module"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting the main module."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Producers => {
+                let producers = debugger
+                    .producers()
+                    .ok_or_else(|| anyhow!("no producers section"))?;
+                let fields = [
+                    ("language", producers.language),
+                    ("processed-by", producers.processed_by),
+                    ("sdk", producers.sdk),
+                ];
+                for (field, values) in fields.iter() {
+                    if values.is_empty() {
+                        continue;
+                    }
+                    context.printer.println(&format!("{}:", field));
+                    for (name, version) in values {
+                        let output = format!("  {} {}", name, version);
+                        context.printer.println(&output);
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
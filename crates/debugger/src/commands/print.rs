@@ -0,0 +1,57 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use super::format::{format_lanes, format_value, LaneShape};
+use anyhow::{anyhow, Result};
+use structopt::StructOpt;
+use wasminspect_vm::WasmValue;
+
+pub struct PrintCommand {}
+
+impl PrintCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Reinterprets a v128 value as lanes of i8x16, i16x8, i32x4, i64x2, f32x4 or f64x2
+    #[structopt(long = "as")]
+    shape: Option<LaneShape>,
+    /// The index of the value as listed by `stack`
+    #[structopt(name = "STACK_INDEX")]
+    index: usize,
+}
+
+impl<D: Debugger> Command<D> for PrintCommand {
+    fn name(&self) -> &'static str {
+        "print"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print a value on the stack, e.g. print --as i8x16 0"
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        let values = match debugger.selected_frame_index() {
+            0 => debugger.stack_values(),
+            depth => debugger.stack_values_for_frame(depth),
+        };
+        let value = values
+            .get(opts.index)
+            .ok_or_else(|| anyhow!("Stack index {} is out of range", opts.index))?;
+        let output = match (value, opts.shape) {
+            (WasmValue::V128(v), Some(shape)) => format_lanes(*v, shape),
+            (_, Some(_)) => return Err(anyhow!("--as is only supported for v128 values")),
+            (value, None) => format_value(value, debugger.get_opts().value_format),
+        };
+        context.printer.println(&output);
+        Ok(None)
+    }
+}
@@ -2,6 +2,8 @@ use crate::RunResult;
 
 use super::command::{Command, CommandContext, CommandResult};
 use super::debugger::Debugger;
+use super::thread::format_frame;
+use super::watch::{memory_watchpoint_message, print_changed_watches, watchpoint_message};
 use anyhow::Result;
 
 use structopt::StructOpt;
@@ -48,14 +50,23 @@ impl<D: Debugger> Command<D> for ProcessCommand {
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Continue => match debugger.process()? {
-                RunResult::Finish(result) => {
-                    return Ok(Some(CommandResult::ProcessFinish(result)));
+            Opts::Continue => {
+                match debugger.process()? {
+                    RunResult::Finish(result) => {
+                        return Ok(Some(CommandResult::ProcessFinish(result)));
+                    }
+                    RunResult::Breakpoint => {
+                        context.printer.println("Hit breakpoint");
+                    }
+                    RunResult::Watchpoint(change) => {
+                        context.printer.println(&watchpoint_message(&change));
+                    }
+                    RunResult::MemoryWatchpoint(change) => {
+                        context.printer.println(&memory_watchpoint_message(&change));
+                    }
                 }
-                RunResult::Breakpoint => {
-                    context.printer.println("Hit breakpoint");
-                }
-            },
+                print_changed_watches(debugger, context);
+            }
             Opts::Launch { start, args } => {
                 return self.start_debugger(debugger, context, start, args);
             }
@@ -93,11 +104,23 @@ impl ProcessCommand {
             Ok(RunResult::Breakpoint) => {
                 context.printer.println("Hit breakpoint");
             }
+            Ok(RunResult::Watchpoint(change)) => {
+                context.printer.println(&watchpoint_message(&change));
+            }
+            Ok(RunResult::MemoryWatchpoint(change)) => {
+                context.printer.println(&memory_watchpoint_message(&change));
+            }
             Err(msg) => {
                 let output = format!("{}", msg);
                 context.printer.eprintln(&output);
+                if let Some(trap) = debugger.last_trap() {
+                    for (index, frame) in trap.backtrace.iter().rev().enumerate() {
+                        context.printer.eprintln(&format_frame(index, frame));
+                    }
+                }
             }
         }
+        print_changed_watches(debugger, context);
         Ok(None)
     }
 }
@@ -0,0 +1,71 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
+
+pub struct ProfilerCommand {}
+
+impl ProfilerCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Start sampling the call stack
+    #[structopt(name = "start")]
+    Start {
+        /// Executed instructions between samples
+        #[structopt(long, default_value = "1000")]
+        interval: u64,
+    },
+    /// Stop sampling and write the samples in the folded format for flamegraphs
+    #[structopt(name = "stop")]
+    Stop {
+        /// Print the samples instead if omitted
+        #[structopt(name = "FILE")]
+        file: Option<String>,
+    },
+}
+
+impl<D: Debugger> Command<D> for ProfilerCommand {
+    fn name(&self) -> &'static str {
+        "profiler"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for the sampling profiler."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Start { interval } => debugger.start_sampling_profiler(interval)?,
+            Opts::Stop { file } => {
+                let data = debugger.stop_sampling_profiler()?;
+                let folded = data.to_folded();
+                match file {
+                    Some(file) => {
+                        std::fs::write(&file, folded)
+                            .map_err(|err| anyhow!("{}: {}", file, err))?;
+                        let output = format!("Wrote {} samples to {}", data.samples.len(), file);
+                        context.printer.println(&output);
+                    }
+                    None => {
+                        for line in folded.lines() {
+                            context.printer.println(line);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,87 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Breakpoint, Debugger, FunctionRef, OffsetBase, RunResult};
+use super::disassemble::display_asm;
+use super::memory::parse_address;
+use super::watch::{memory_watchpoint_message, print_changed_watches, watchpoint_message};
+use anyhow::Result;
+use structopt::StructOpt;
+
+pub struct RunToCommand {}
+
+impl RunToCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Function name
+    #[structopt(name = "FUNCTION")]
+    func_name: String,
+    /// Offset from the first instruction of the function, as in `break FUNCTION+OFFSET`
+    #[structopt(name = "OFFSET", parse(try_from_str = parse_address))]
+    offset: usize,
+}
+
+impl<D: Debugger> Command<D> for RunToCommand {
+    fn name(&self) -> &'static str {
+        "run-to"
+    }
+
+    fn description(&self) -> &'static str {
+        "Continue until the instruction at the offset of the function is reached."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        let base = OffsetBase::Function(FunctionRef::Name(opts.func_name.clone()));
+        // A breakpoint already set by the user at the same place is kept
+        let existing = debugger
+            .list_breakpoints()
+            .into_iter()
+            .any(|(_, breakpoint)| {
+                matches!(breakpoint, Breakpoint::Instruction { base: existing, offset, .. }
+                if existing == base && offset == opts.offset)
+            });
+        let temporary = if existing {
+            None
+        } else {
+            Some(debugger.set_breakpoint(Breakpoint::Instruction {
+                base,
+                offset: opts.offset,
+                condition: None,
+            })?)
+        };
+        let result = debugger.process();
+        if let Some(id) = temporary {
+            debugger.delete_breakpoint(id);
+        }
+        match result? {
+            RunResult::Finish(values) => {
+                let output = format!(
+                    "warning: the process finished before reaching {}+{:#x}",
+                    opts.func_name, opts.offset
+                );
+                context.printer.eprintln(&output);
+                return Ok(Some(CommandResult::ProcessFinish(values)));
+            }
+            RunResult::Breakpoint => {
+                display_asm(debugger, context.printer.as_ref(), Some(4), true)?;
+            }
+            RunResult::Watchpoint(change) => {
+                context.printer.println(&watchpoint_message(&change));
+            }
+            RunResult::MemoryWatchpoint(change) => {
+                context.printer.println(&memory_watchpoint_message(&change));
+            }
+        }
+        print_changed_watches(debugger, context);
+        Ok(None)
+    }
+}
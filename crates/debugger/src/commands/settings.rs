@@ -1,6 +1,8 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
-use anyhow::Result;
+use super::debugger::{Debugger, ValueFormat};
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use wasminspect_vm::ExecutionLimit;
 
 use structopt::StructOpt;
 
@@ -18,7 +20,7 @@ enum Opts {
     Set {
         key: String,
         operand1: String,
-        operand2: String,
+        operand2: Option<String>,
     },
 }
 
@@ -33,7 +35,7 @@ impl<D: Debugger> Command<D> for SettingsCommand {
 
     fn run(
         &self,
-        _debugger: &mut D,
+        debugger: &mut D,
         context: &CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
@@ -45,8 +47,62 @@ impl<D: Debugger> Command<D> for SettingsCommand {
                 operand2,
             } => match key.as_str() {
                 "directory.map" => {
+                    let operand2 =
+                        operand2.ok_or_else(|| anyhow!("directory.map requires two operands"))?;
                     context.sourcemap.set_directory_map(operand1, operand2);
                 }
+                "print.format" => {
+                    let format = match operand1.as_str() {
+                        "x" | "hex" => ValueFormat::Hex,
+                        "default" => ValueFormat::Default,
+                        _ => return Err(anyhow!("'{}' is not valid format", operand1)),
+                    };
+                    let mut opts = debugger.get_opts();
+                    opts.value_format = format;
+                    debugger.set_opts(opts);
+                }
+                "profile" => {
+                    let profile = match operand1.as_str() {
+                        "true" | "on" => true,
+                        "false" | "off" => false,
+                        _ => return Err(anyhow!("'{}' is not valid boolean", operand1)),
+                    };
+                    let mut opts = debugger.get_opts();
+                    opts.profile = profile;
+                    debugger.set_opts(opts);
+                }
+                "symbols.demangle" => {
+                    let demangle = match operand1.as_str() {
+                        "true" | "on" => true,
+                        "false" | "off" => false,
+                        _ => return Err(anyhow!("'{}' is not valid boolean", operand1)),
+                    };
+                    let mut opts = debugger.get_opts();
+                    opts.raw_symbols = !demangle;
+                    debugger.set_opts(opts);
+                }
+                "history.limit" => {
+                    let mut opts = debugger.get_opts();
+                    opts.history_limit = operand1.parse()?;
+                    debugger.set_opts(opts);
+                }
+                "execution.limit" => {
+                    let limit = match (operand1.as_str(), operand2) {
+                        ("off", None) => None,
+                        ("instructions", Some(count)) => {
+                            Some(ExecutionLimit::InstructionCount(count.parse()?))
+                        }
+                        ("seconds", Some(seconds)) => Some(ExecutionLimit::WallClock(
+                            Duration::from_secs_f64(seconds.parse()?),
+                        )),
+                        _ => {
+                            return Err(anyhow!(
+                                "execution.limit should be 'off', 'instructions COUNT' or 'seconds SECONDS'"
+                            ))
+                        }
+                    };
+                    debugger.set_execution_limit(limit)?;
+                }
                 _ => {
                     let output = format!("'{}' is not valid key", key);
                     context.printer.eprintln(&output);
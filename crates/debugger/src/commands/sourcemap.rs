@@ -20,11 +20,62 @@ pub struct LineInfo {
     pub column: ColumnType,
 }
 
+/// A resolved position in a source file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl SourceLocation {
+    /// Returns `None` if the line is unknown
+    pub fn from_line_info(line_info: LineInfo) -> Option<Self> {
+        Some(Self {
+            line: line_info.line.filter(|line| *line != 0)? as u32,
+            column: u64::from(line_info.column) as u32,
+            file: line_info.filepath,
+        })
+    }
+}
+
 pub trait SourceMap {
     fn find_line_info(&self, offset: usize) -> Option<LineInfo>;
+    /// Returns the source location of the instruction at `offset`, if it has a line
+    fn lookup_pc(&self, offset: usize) -> Option<SourceLocation> {
+        SourceLocation::from_line_info(self.find_line_info(offset)?)
+    }
+    /// Same as `find_line_info`, but code of inlined subroutines is attributed
+    /// to the line they are inlined at
+    fn find_statement_line(&self, offset: usize) -> Option<LineInfo> {
+        self.find_line_info(offset)
+    }
+    /// Returns the lowest offset of the instructions at `line` in a file whose
+    /// path ends with `file`
+    fn find_line_address(&self, _file: &str, _line: u64) -> Option<usize> {
+        None
+    }
     fn set_directory_map(&self, from: String, to: String);
 }
 
+impl<T: SourceMap + ?Sized> SourceMap for std::rc::Rc<T> {
+    fn find_line_info(&self, offset: usize) -> Option<LineInfo> {
+        (**self).find_line_info(offset)
+    }
+    fn find_statement_line(&self, offset: usize) -> Option<LineInfo> {
+        (**self).find_statement_line(offset)
+    }
+    fn lookup_pc(&self, offset: usize) -> Option<SourceLocation> {
+        (**self).lookup_pc(offset)
+    }
+    fn find_line_address(&self, file: &str, line: u64) -> Option<usize> {
+        (**self).find_line_address(file, line)
+    }
+    fn set_directory_map(&self, from: String, to: String) {
+        (**self).set_directory_map(from, to)
+    }
+}
+
 pub struct EmptySourceMap {}
 
 impl EmptySourceMap {
@@ -1,6 +1,8 @@
 use super::command::{Command, CommandContext, CommandResult};
 use super::debugger::Debugger;
+use super::format::format_value;
 use anyhow::Result;
+use wasminspect_vm::WasmValue;
 
 pub struct StackCommand {}
 
@@ -25,8 +27,16 @@ impl<D: Debugger> Command<D> for StackCommand {
         context: &CommandContext,
         _args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
-        for (index, value) in debugger.stack_values().iter().enumerate() {
-            let output = format!("{}: {:?}", index, value);
+        let values = match debugger.selected_frame_index() {
+            0 => debugger.stack_values(),
+            depth => debugger.stack_values_for_frame(depth),
+        };
+        let format = debugger.get_opts().value_format;
+        for (index, value) in values.iter().enumerate() {
+            let output = match value {
+                WasmValue::V128(_) => format!("{}: {}", index, format_value(value, format)),
+                _ => format!("{}: {:?}", index, value),
+            };
             context.printer.println(&output);
         }
         Ok(None)
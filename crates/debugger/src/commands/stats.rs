@@ -0,0 +1,74 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use anyhow::Result;
+
+use structopt::StructOpt;
+
+pub struct StatsCommand {}
+
+impl StatsCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Show how many times each kind of instruction is executed
+    #[structopt(name = "instructions")]
+    Instructions {
+        /// Show only the most frequent ones
+        #[structopt(short, long)]
+        count: Option<usize>,
+    },
+    /// Clear the instruction counts
+    #[structopt(name = "reset")]
+    Reset,
+}
+
+impl<D: Debugger> Command<D> for StatsCommand {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for showing execution statistics."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Instructions { count } => {
+                let mut histogram = debugger
+                    .instruction_histogram()
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                histogram.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+                let total: u64 = histogram.iter().map(|(_, count)| count).sum();
+                let width = histogram
+                    .first()
+                    .map(|(_, max)| max.to_string().len())
+                    .unwrap_or(1);
+                for (name, executed) in histogram.iter().take(count.unwrap_or(usize::MAX)) {
+                    let output = format!(
+                        "{:>width$} {:>5.1}% {}",
+                        executed,
+                        *executed as f64 * 100.0 / total as f64,
+                        name,
+                        width = width
+                    );
+                    context.printer.println(&output);
+                }
+                let output = format!("{} instructions executed", total);
+                context.printer.println(&output);
+            }
+            Opts::Reset => debugger.reset_histogram(),
+        }
+        Ok(None)
+    }
+}
@@ -1,13 +1,66 @@
+use super::sourcemap::LineInfo;
 use crate::dwarf::{FrameBase, WasmLoc};
 use anyhow::Result;
+use std::collections::HashMap;
 
 pub struct Variable {
     pub name: String,
     pub type_name: String,
 }
 
+/// A source variable which lives in a wasm local
+pub struct LocalVariable {
+    pub name: String,
+    pub source_location: Option<LineInfo>,
+}
+
+/// A subroutine inlined at the code offset being looked up
+pub struct InlinedFrame {
+    pub name: Option<String>,
+    /// The location the subroutine is inlined at in its caller
+    pub call_site: LineInfo,
+}
+
+/// A source variable in scope at a code offset
+pub struct ScopedVariable {
+    pub name: String,
+    pub type_name: String,
+    /// `None` if the location can't be described as a wasm location at the offset
+    pub location: Option<WasmLoc>,
+    pub is_param: bool,
+}
+
 pub trait SubroutineMap {
+    /// Returns subroutines inlined at `code_offset` from the outermost one
+    fn inlined_frames(&self, _code_offset: usize) -> Vec<InlinedFrame> {
+        vec![]
+    }
+    /// Returns the source name of the function containing `code_offset`
+    fn function_name(&self, _code_offset: usize) -> Option<String> {
+        None
+    }
+    /// Returns the code range of the function with the given source name
+    fn lookup_func_by_name(&self, name: &str) -> Option<std::ops::Range<u64>>;
+    /// Returns the name of the return type of the function with the given source name
+    fn return_type_name(&self, name: &str) -> Result<Option<String>>;
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<Variable>>;
+    /// Returns variables of the function and lexical blocks enclosing `code_offset`,
+    /// from the outermost scope. Shadowed variables of outer scopes are excluded.
+    fn scoped_variables(&self, _code_offset: usize) -> Result<Vec<ScopedVariable>> {
+        Ok(vec![])
+    }
+    /// Returns variables which live in wasm locals, keyed by local index
+    fn local_variables(&self, code_offset: usize) -> Result<HashMap<usize, LocalVariable>>;
+    /// Returns source names of wasm locals at `code_offset`, indexed by local index
+    fn local_names(&self, code_offset: usize) -> Vec<Option<String>> {
+        let variables = self.local_variables(code_offset).unwrap_or_default();
+        let len = variables.keys().max().map(|index| index + 1).unwrap_or(0);
+        let mut names = vec![None; len];
+        for (index, variable) in variables {
+            names[index] = Some(variable.name);
+        }
+        names
+    }
     fn get_frame_base(&self, code_offset: usize) -> Result<Option<WasmLoc>>;
     fn display_variable(
         &self,
@@ -18,6 +71,42 @@ pub trait SubroutineMap {
     ) -> Result<()>;
 }
 
+impl<T: SubroutineMap + ?Sized> SubroutineMap for std::rc::Rc<T> {
+    fn inlined_frames(&self, code_offset: usize) -> Vec<InlinedFrame> {
+        (**self).inlined_frames(code_offset)
+    }
+    fn function_name(&self, code_offset: usize) -> Option<String> {
+        (**self).function_name(code_offset)
+    }
+    fn lookup_func_by_name(&self, name: &str) -> Option<std::ops::Range<u64>> {
+        (**self).lookup_func_by_name(name)
+    }
+    fn return_type_name(&self, name: &str) -> Result<Option<String>> {
+        (**self).return_type_name(name)
+    }
+    fn variable_name_list(&self, code_offset: usize) -> Result<Vec<Variable>> {
+        (**self).variable_name_list(code_offset)
+    }
+    fn scoped_variables(&self, code_offset: usize) -> Result<Vec<ScopedVariable>> {
+        (**self).scoped_variables(code_offset)
+    }
+    fn local_variables(&self, code_offset: usize) -> Result<HashMap<usize, LocalVariable>> {
+        (**self).local_variables(code_offset)
+    }
+    fn get_frame_base(&self, code_offset: usize) -> Result<Option<WasmLoc>> {
+        (**self).get_frame_base(code_offset)
+    }
+    fn display_variable(
+        &self,
+        code_offset: usize,
+        frame_base: FrameBase,
+        memory: &[u8],
+        name: String,
+    ) -> Result<()> {
+        (**self).display_variable(code_offset, frame_base, memory, name)
+    }
+}
+
 pub struct EmptySubroutineMap {}
 
 impl EmptySubroutineMap {
@@ -26,9 +115,18 @@ impl EmptySubroutineMap {
     }
 }
 impl SubroutineMap for EmptySubroutineMap {
+    fn lookup_func_by_name(&self, _name: &str) -> Option<std::ops::Range<u64>> {
+        None
+    }
+    fn return_type_name(&self, _name: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
     fn variable_name_list(&self, _code_offset: usize) -> Result<Vec<Variable>> {
         Ok(vec![])
     }
+    fn local_variables(&self, _code_offset: usize) -> Result<HashMap<usize, LocalVariable>> {
+        Ok(HashMap::new())
+    }
     fn get_frame_base(&self, _: usize) -> Result<Option<WasmLoc>> {
         Ok(Some(WasmLoc::Global(0)))
     }
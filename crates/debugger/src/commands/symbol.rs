@@ -1,23 +1,58 @@
 #[cfg(feature = "swift-extension")]
-use wasminspect_swift_runtime::demangle;
+use wasminspect_swift_runtime::demangle as demangle_swift;
 
-pub fn demangle_symbol(symbol: &str) -> &str {
+/// Demangles Swift, Rust (legacy and v0) and Itanium C++ symbols, returning
+/// the original name if it isn't mangled or fails to parse
+pub fn demangle(symbol: &str) -> String {
     if is_swift_symbol(symbol) {
-        demangle_swift_symbol(symbol)
-    } else {
-        symbol
+        return demangle_swift_symbol(symbol).to_string();
     }
+    if let Ok(demangled) = rustc_demangle::try_demangle(symbol) {
+        // The alternate format omits the hash of legacy symbols
+        return format!("{:#}", demangled);
+    }
+    if symbol.starts_with("_Z") {
+        if let Some(demangled) = demangle_cpp_symbol(symbol) {
+            return demangled;
+        }
+    }
+    symbol.to_string()
 }
 
 fn is_swift_symbol(symbol: &str) -> bool {
     symbol.starts_with("$s")
 }
 
+fn demangle_cpp_symbol(symbol: &str) -> Option<String> {
+    let symbol = cpp_demangle::Symbol::new(symbol).ok()?;
+    symbol
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
+}
+
 #[cfg(feature = "swift-extension")]
 fn demangle_swift_symbol(symbol: &str) -> &str {
-    demangle(symbol).unwrap_or(symbol)
+    demangle_swift(symbol).unwrap_or(symbol)
 }
 #[cfg(not(feature = "swift-extension"))]
 fn demangle_swift_symbol(symbol: &str) -> &str {
     symbol
 }
+
+#[cfg(test)]
+mod tests {
+    use super::demangle;
+
+    #[test]
+    fn demangle_symbols() {
+        assert_eq!(
+            demangle("_ZN4core3fmt5write17h4a2c3f9e8b7d6c5aE"),
+            "core::fmt::write"
+        );
+        assert_eq!(demangle("_RNvCs1234_7mycrate3foo"), "mycrate::foo");
+        assert_eq!(demangle("_Z3addii"), "add(int, int)");
+        assert_eq!(demangle("_ZN3foo3barEv"), "foo::bar()");
+        assert_eq!(demangle("__original_main"), "__original_main");
+        assert_eq!(demangle("_Zinvalid"), "_Zinvalid");
+    }
+}
@@ -1,8 +1,8 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::{Debugger, StepStyle};
+use super::debugger::{Debugger, FrameInfo, StepStyle};
 use super::disassemble::display_asm;
 use super::list::{display_source, next_line_info};
-use super::symbol::demangle_symbol;
+use super::watch::print_changed_watches;
 
 pub struct ThreadCommand {}
 
@@ -31,6 +31,9 @@ enum Opts {
     StepInstIn,
     #[structopt(name = "step-inst-over")]
     StepInstOver,
+    /// Reverts the last executed instruction recorded with `settings set history.limit`
+    #[structopt(name = "step-back")]
+    StepBack,
 }
 
 impl<D: Debugger> Command<D> for ThreadCommand {
@@ -49,10 +52,15 @@ impl<D: Debugger> Command<D> for ThreadCommand {
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args.clone())?;
+        let resumes = !matches!(opts, Opts::Info | Opts::Backtrace);
+        if resumes && debugger.selected_frame_index() != 0 {
+            // Frames other than the innermost one can't be stepped
+            debugger.select_frame(None)?;
+        }
         match opts {
             Opts::Info => {
                 let frames = debugger.frame();
-                let frame_name = frames.last().unwrap();
+                let frame_name = &frames.last().unwrap().func_name;
                 let (insts, next_index) = debugger.selected_instructions()?;
                 let current_index = if next_index == 0 { 0 } else { next_index - 1 };
                 let current_inst = insts[current_index].clone();
@@ -76,31 +84,38 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 context.printer.println(&output);
             }
             Opts::Backtrace => {
-                for (index, frame) in debugger.frame().iter().rev().enumerate() {
-                    let output = format!("{}: {}", index, demangle_symbol(frame));
+                // Inlined frames precede the physical frame they're inlined into
+                let selected = debugger.selected_frame_index();
+                let mut physical_index = 0;
+                for (index, frame) in debugger.backtrace().iter().rev().enumerate() {
+                    let marker = if !frame.is_inlined && physical_index == selected {
+                        "* "
+                    } else {
+                        "  "
+                    };
+                    let output = format!("{}{}", marker, format_frame(index, frame));
                     context.printer.println(&output);
+                    if !frame.is_inlined {
+                        physical_index += 1;
+                    }
                 }
             }
             Opts::StepIn | Opts::StepOver => {
                 let style = match opts {
-                    Opts::StepIn => StepStyle::InstIn,
-                    Opts::StepOver => StepStyle::InstOver,
+                    Opts::StepIn => StepStyle::StepLineIn,
+                    Opts::StepOver => StepStyle::StepLineOver,
                     _ => panic!(),
                 };
-                let initial_line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                while {
-                    debugger.step(style)?;
-                    let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                    initial_line_info.filepath == line_info.filepath
-                        && initial_line_info.line == line_info.line
-                } {}
+                debugger.step(style)?;
                 let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
                 display_source(line_info, context.printer.as_ref())?;
+                print_changed_watches(debugger, context);
             }
             Opts::StepOut => {
                 debugger.step(StepStyle::Out)?;
                 let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
                 display_source(line_info, context.printer.as_ref())?;
+                print_changed_watches(debugger, context);
             }
             Opts::StepInstIn | Opts::StepInstOver => {
                 let style = match opts {
@@ -110,8 +125,31 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 };
                 debugger.step(style)?;
                 display_asm(debugger, context.printer.as_ref(), Some(4), true)?;
+                print_changed_watches(debugger, context);
+            }
+            Opts::StepBack => {
+                debugger.step_back()?;
+                display_asm(debugger, context.printer.as_ref(), Some(4), true)?;
             }
         }
         Ok(None)
     }
 }
+
+/// Formats a frame as `INDEX: OFFSET NAME [inlined] [at FILE:LINE:COLUMN]`
+pub fn format_frame(index: usize, frame: &FrameInfo) -> String {
+    let mut output = format!(
+        "{}: 0x{:>08x} {}",
+        index, frame.instruction_offset, frame.demangled_name
+    );
+    if frame.is_inlined {
+        output += " [inlined]";
+    }
+    if let Some(location) = &frame.source_location {
+        output += &format!(
+            " at {}:{}:{}",
+            location.file, location.line, location.column
+        );
+    }
+    output
+}
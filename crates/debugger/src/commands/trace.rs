@@ -0,0 +1,85 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Debugger, TraceFormat};
+use crate::trace::convert_binary_trace;
+use anyhow::Result;
+
+use structopt::StructOpt;
+
+pub struct TraceCommand {}
+
+impl TraceCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Start recording executed instructions to the given file
+    #[structopt(name = "start")]
+    Start {
+        #[structopt(name = "FILE")]
+        file: String,
+        /// Write compact fixed-size records instead of text
+        #[structopt(long)]
+        binary: bool,
+        /// Write CSV rows of the offset, the instruction, the call depth and the stack depth
+        #[structopt(long, conflicts_with = "binary")]
+        csv: bool,
+    },
+    /// Stop recording and flush the trace file
+    #[structopt(name = "stop")]
+    Stop,
+    /// Decode a binary trace to human-readable text
+    #[structopt(name = "convert")]
+    Convert {
+        #[structopt(name = "BINARY_FILE")]
+        binary_file: String,
+        #[structopt(name = "TEXT_FILE")]
+        text_file: String,
+    },
+}
+
+impl<D: Debugger> Command<D> for TraceCommand {
+    fn name(&self) -> &'static str {
+        "trace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for recording execution traces."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Start { file, binary, csv } => {
+                let mut opts = debugger.get_opts();
+                opts.trace_format = if binary {
+                    TraceFormat::Binary
+                } else if csv {
+                    TraceFormat::Csv
+                } else {
+                    TraceFormat::Text
+                };
+                debugger.set_opts(opts);
+                debugger.start_trace(&file)?;
+            }
+            Opts::Stop => debugger.stop_trace()?,
+            Opts::Convert {
+                binary_file,
+                text_file,
+            } => {
+                let insts = debugger.instruction_map()?;
+                let count = convert_binary_trace(&binary_file, &text_file, &insts)?;
+                let output = format!("Converted {} records to {}", count, text_file);
+                context.printer.println(&output);
+            }
+        }
+        Ok(None)
+    }
+}
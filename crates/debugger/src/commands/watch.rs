@@ -0,0 +1,273 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::{Breakpoint, Debugger, LocalChange, MemoryChange};
+use super::format::format_value;
+use super::memory::parse_address;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use structopt::StructOpt;
+use wasminspect_vm::GlobalAddr;
+
+pub struct WatchCommand {}
+
+impl WatchCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Stop when a local is modified by local.set or local.tee
+    #[structopt(name = "local")]
+    Local {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        /// Only watch the local in the function with the given index
+        #[structopt(short, long)]
+        func: Option<u32>,
+    },
+    /// Stop after a store instruction writes to the memory range
+    #[structopt(name = "memory")]
+    Memory {
+        #[structopt(name = "OFFSET", parse(try_from_str = parse_address))]
+        offset: usize,
+        #[structopt(name = "LENGTH", default_value = "1", parse(try_from_str = parse_address))]
+        len: usize,
+    },
+    /// Print an expression whenever its value changes after a step or a stop.
+    /// EXPR is one of `local INDEX`, `global INDEX` or `mem OFFSET LENGTH`
+    #[structopt(name = "expr")]
+    Expr {
+        #[structopt(name = "EXPR", required = true)]
+        expr: Vec<String>,
+    },
+    /// List watch expressions
+    #[structopt(name = "list")]
+    List,
+    /// Delete a watch expression
+    #[structopt(name = "delete")]
+    Delete {
+        #[structopt(name = "ID")]
+        id: usize,
+    },
+}
+
+/// An expression evaluated by `watch expr`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchExpr {
+    Local(usize),
+    Global(usize),
+    Memory { offset: usize, len: usize },
+}
+
+impl WatchExpr {
+    pub fn parse(args: &[&str]) -> Result<Self> {
+        match args {
+            ["local", index] => Ok(Self::Local(index.parse()?)),
+            ["global", index] => Ok(Self::Global(index.parse()?)),
+            ["mem", offset, len] => Ok(Self::Memory {
+                offset: parse_address(offset)?,
+                len: parse_address(len)?,
+            }),
+            _ => Err(anyhow!(
+                "'{}' is not a valid watch expression, expected `local INDEX`, `global INDEX` or `mem OFFSET LENGTH`",
+                args.join(" ")
+            )),
+        }
+    }
+
+    fn evaluate<D: Debugger>(&self, debugger: &D) -> Result<String> {
+        let format = debugger.get_opts().value_format;
+        match self {
+            Self::Local(index) => {
+                let locals = debugger.locals();
+                let value = locals.get(*index).ok_or_else(|| {
+                    anyhow!(
+                        "{} is out of range, locals length is {}",
+                        index,
+                        locals.len()
+                    )
+                })?;
+                Ok(format_value(value, format))
+            }
+            Self::Global(index) => {
+                let store = debugger.store()?;
+                let mod_index = match debugger.current_frame() {
+                    Some(frame) => frame.module_index,
+                    None => return Err(anyhow!("function frame not found")),
+                };
+                if *index >= store.global_count(mod_index) {
+                    return Err(anyhow!(
+                        "{} is out of range, globals length is {}",
+                        index,
+                        store.global_count(mod_index)
+                    ));
+                }
+                let global = store.global(GlobalAddr::new_unsafe(mod_index, *index));
+                let value = global.borrow().value();
+                Ok(format_value(&value, format))
+            }
+            Self::Memory { offset, len } => {
+                let memory = debugger.memory()?;
+                let bytes = offset
+                    .checked_add(*len)
+                    .and_then(|end| memory.get(*offset..end))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "0x{:x}..0x{:x} is out of the memory of size 0x{:x}",
+                            offset,
+                            offset.saturating_add(*len),
+                            memory.len()
+                        )
+                    })?;
+                Ok(format!("{:02x?}", bytes))
+            }
+        }
+    }
+}
+
+impl fmt::Display for WatchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Local(index) => write!(f, "local {}", index),
+            Self::Global(index) => write!(f, "global {}", index),
+            Self::Memory { offset, len } => write!(f, "mem 0x{:x} {}", offset, len),
+        }
+    }
+}
+
+struct WatchExpression {
+    id: usize,
+    expr: WatchExpr,
+    /// The value printed last time
+    last_value: Option<String>,
+}
+
+/// Watch expressions registered by `watch expr`
+#[derive(Default)]
+pub struct WatchList {
+    next_id: usize,
+    watches: Vec<WatchExpression>,
+}
+
+impl WatchList {
+    pub fn add(&mut self, expr: WatchExpr) -> usize {
+        self.next_id += 1;
+        self.watches.push(WatchExpression {
+            id: self.next_id,
+            expr,
+            last_value: None,
+        });
+        self.next_id
+    }
+
+    /// Returns false if no watch expression has the ID
+    pub fn delete(&mut self, id: usize) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() != len
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.watches
+            .iter()
+            .map(|watch| {
+                let value = watch.last_value.as_deref().unwrap_or("<not evaluated>");
+                format!("{}: {} = {}", watch.id, watch.expr, value)
+            })
+            .collect()
+    }
+
+    /// Evaluates all watch expressions and returns lines for ones whose values
+    /// changed since the last call
+    pub fn changed_values<D: Debugger>(&mut self, debugger: &D) -> Vec<String> {
+        let mut lines = vec![];
+        for watch in self.watches.iter_mut() {
+            let value = match watch.expr.evaluate(debugger) {
+                Ok(value) => value,
+                Err(err) => format!("<unavailable: {}>", err),
+            };
+            if watch.last_value.as_ref() == Some(&value) {
+                continue;
+            }
+            lines.push(format!("{}: {} = {}", watch.id, watch.expr, value));
+            watch.last_value = Some(value);
+        }
+        lines
+    }
+}
+
+/// Prints watch expressions whose values changed, called after each step or stop
+pub fn print_changed_watches<D: Debugger>(debugger: &D, context: &CommandContext) {
+    let lines = context.watches.borrow_mut().changed_values(debugger);
+    for line in lines {
+        context.printer.println(&line);
+    }
+}
+
+impl<D: Debugger> Command<D> for WatchCommand {
+    fn name(&self) -> &'static str {
+        "watch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating on watchpoints."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Local { index, func } => {
+                debugger.set_breakpoint(Breakpoint::WatchLocal {
+                    local_index: index,
+                    func_index: func,
+                })?;
+            }
+            Opts::Memory { offset, len } => {
+                debugger.set_breakpoint(Breakpoint::MemoryWrite { offset, len })?;
+            }
+            Opts::Expr { expr } => {
+                let expr = expr.iter().map(String::as_str).collect::<Vec<_>>();
+                let expr = WatchExpr::parse(&expr)?;
+                let id = context.watches.borrow_mut().add(expr.clone());
+                let output = format!("Watch expression {}: {}", id, expr);
+                context.printer.println(&output);
+                print_changed_watches(debugger, context);
+            }
+            Opts::List => {
+                for line in context.watches.borrow().list() {
+                    context.printer.println(&line);
+                }
+            }
+            Opts::Delete { id } => {
+                if !context.watches.borrow_mut().delete(id) {
+                    return Err(anyhow!("No watch expression with ID {}", id));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub fn watchpoint_message(change: &LocalChange) -> String {
+    format!(
+        "Hit watchpoint: local {} of func {} at 0x{:>08x}: {:?} -> {:?}",
+        change.local_index,
+        change.func_index,
+        change.instruction_offset,
+        change.old_value,
+        change.new_value
+    )
+}
+
+pub fn memory_watchpoint_message(change: &MemoryChange) -> String {
+    format!(
+        "Hit watchpoint: memory 0x{:>08x} written at 0x{:>08x}: {:02x?} -> {:02x?}",
+        change.addr, change.instruction_offset, change.old_bytes, change.new_bytes
+    )
+}
@@ -1,20 +1,33 @@
-use crate::commands::debugger::{self, Debugger, DebuggerOpts, RawHostModule, RunResult};
+use crate::commands::debugger::{
+    self, BreakpointId, Debugger, DebuggerOpts, RawHostModule, RunResult,
+};
+use crate::commands::format::parse_value;
+use crate::commands::sourcemap::{SourceLocation, SourceMap};
+use crate::commands::subroutine::SubroutineMap;
+use crate::commands::symbol::demangle;
+use crate::dwarf::WasmLoc;
+use crate::trace::Tracer;
 use anyhow::{anyhow, Context, Result};
 use log::{trace, warn};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::Path;
 use std::rc::Rc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::{cell::RefCell, usize};
 use wasminspect_vm::{
-    CallFrame, DefinedModuleInstance, Executor, FuncAddr, FunctionInstance, InstIndex, Instruction,
-    Interceptor, MemoryAddr, ModuleIndex, ProgramCounter, Signal, Store, Trap, WasmValue,
+    CallFrame, DefinedModuleInstance, ExecutionLimit, Executor, FuncAddr, FunctionInstance,
+    GlobalAddr, HostMemory, InstIndex, Instruction, InstructionKind, Interceptor, MemoryAddr,
+    ModuleIndex, ProgramCounter, Signal, StackDelta, Store, Trap, TrapLocation, WasmValue,
 };
 use wasminspect_wasi::instantiate_wasi;
 use wasmparser::WasmFeatures;
 
 type RawModule = Vec<u8>;
 
+/// The number of changes kept for each local variable
+const LOCAL_HISTORY_CAPACITY: usize = 256;
+
 pub struct Instance {
     main_module_index: ModuleIndex,
     pub store: Store,
@@ -29,44 +42,407 @@ pub struct MainDebugger {
     opts: DebuggerOpts,
     preopen_dirs: Vec<(String, String)>,
     envs: Vec<(String, String)>,
+    /// Whether `wasi_snapshot_preview1` and `wasi_unstable` are provided to the process
+    wasi: bool,
 
     config: wasminspect_vm::Config,
     breakpoints: Breakpoints,
     is_interrupted: Arc<AtomicBool>,
+    executed_instructions: Arc<AtomicU64>,
+    functions_called: Cell<u64>,
+    peak_call_depth: Cell<usize>,
     selected_frame: Option<usize>,
+    local_history: RefCell<HashMap<(u32, usize), VecDeque<debugger::LocalChange>>>,
+    profile_counts: RefCell<HashMap<usize, u64>>,
+    /// Execution counts indexed by `InstructionKind::ordinal`
+    instruction_counts: Vec<Cell<u64>>,
+    watch_hit: RefCell<Option<debugger::LocalChange>>,
+    memory_watch_hit: RefCell<Option<debugger::MemoryChange>>,
+    /// The offset of the instruction being executed
+    current_inst_offset: Cell<usize>,
+    last_trap: RefCell<Option<debugger::TrapInfo>>,
+    step_out: RefCell<Option<StepOutTarget>>,
+    step_out_hit: Cell<bool>,
+    tracer: RefCell<Option<Tracer>>,
+    sampler: RefCell<Option<Sampler>>,
+    /// Instructions executed while `DebuggerOpts::history_limit` is set, from the oldest one
+    history: RefCell<VecDeque<HistoryEntry>>,
+    /// The entry of the instruction being executed
+    recording: RefCell<Option<HistoryEntry>>,
+    mocks: Vec<debugger::FunctionMock>,
+    import_modules: Vec<(String, RawModule)>,
+    memory_labels: Vec<debugger::LabeledRegion>,
+    line_table: Option<Rc<dyn SourceMap>>,
+    subroutine_map: Option<Rc<dyn SubroutineMap>>,
+}
+
+/// Records the call stack once every `interval` executed instructions
+struct Sampler {
+    interval: u64,
+    /// Instructions left until the next sample
+    remaining: u64,
+    data: debugger::ProfilingData,
+}
+
+/// Changes made by an executed instruction, recorded to step back over it
+struct HistoryEntry {
+    pc: ProgramCounter,
+    stack: Option<StackDelta>,
+    /// Old bytes of memory written by the instruction
+    memory: Vec<(MemoryAddr, usize, Vec<u8>)>,
+    /// Old values of globals set by the instruction
+    globals: Vec<(GlobalAddr, WasmValue)>,
+}
+
+impl HistoryEntry {
+    fn new(pc: ProgramCounter) -> Self {
+        Self {
+            pc,
+            stack: None,
+            memory: Vec::new(),
+            globals: Vec::new(),
+        }
+    }
+}
+
+/// A temporary breakpoint on instructions which may leave the function being stepped out
+struct StepOutTarget {
+    frame_depth: usize,
+    exit_offsets: HashSet<usize>,
+}
+
+impl StepOutTarget {
+    fn new(insts: &[Instruction], frame_depth: usize) -> Self {
+        use wasminspect_vm::InstructionKind::*;
+        let mut exit_offsets = insts
+            .iter()
+            .filter(|inst| matches!(inst.kind, Return | Br { .. } | BrIf { .. } | BrTable { .. }))
+            .map(|inst| inst.offset)
+            .collect::<HashSet<_>>();
+        // The last `End` closes the function body
+        if let Some(last) = insts.last() {
+            exit_offsets.insert(last.offset);
+        }
+        Self {
+            frame_depth,
+            exit_offsets,
+        }
+    }
+
+    fn should_break(&self, inst: &Instruction, executor: &Executor) -> bool {
+        // Check the depth only at exits not to stop in recursive calls
+        self.exit_offsets.contains(&inst.offset)
+            && executor.stack.peek_frames().len() == self.frame_depth
+    }
 }
 
 #[derive(Default)]
 struct Breakpoints {
-    function_map: HashMap<String, debugger::Breakpoint>,
-    inst_map: HashMap<usize, debugger::Breakpoint>,
+    next_id: u32,
+    entries: BTreeMap<BreakpointId, debugger::Breakpoint>,
+    function_map: HashMap<String, BreakpointId>,
+    inst_map: HashMap<usize, BreakpointId>,
+    /// Keyed by pairs of a function index and an offset from its first instruction
+    func_inst_offsets: HashMap<(u32, usize), BreakpointId>,
+    local_watches: Vec<(BreakpointId, usize, Option<u32>)>,
+    /// Offsets and lengths of watched memory ranges
+    memory_watches: Vec<(BreakpointId, usize, usize)>,
+    /// A one-shot breakpoint hit in the current run
+    fired_once: Cell<Option<BreakpointId>>,
+    /// How many times each function breakpoint with a hit count has been reached
+    hit_counts: RefCell<HashMap<BreakpointId, u32>>,
+}
+
+/// Evaluates a breakpoint condition, treating unavailable operands as false
+fn condition_holds(
+    condition: &Option<debugger::BreakCondition>,
+    executor: &Executor,
+    instance: Option<&Instance>,
+) -> bool {
+    use debugger::ConditionOperand;
+    let condition = match condition {
+        Some(condition) => condition,
+        None => return true,
+    };
+    let actual = match condition.operand {
+        ConditionOperand::Local(index) => match executor.stack.current_frame() {
+            Ok(frame) => frame.locals.get(index).copied(),
+            Err(_) => None,
+        },
+        ConditionOperand::Global(index) => instance.and_then(|instance| {
+            let module_index = instance.main_module_index;
+            if (index as usize) < instance.store.global_count(module_index) {
+                let addr = GlobalAddr::new_unsafe(module_index, index as usize);
+                Some(instance.store.global(addr).borrow().value())
+            } else {
+                None
+            }
+        }),
+        ConditionOperand::StackTop => executor.stack.peek_values().last().copied().copied(),
+    };
+    match actual {
+        Some(actual) => condition.holds(&actual),
+        None => false,
+    }
 }
 
 impl Breakpoints {
-    fn should_break_func(&self, name: &str) -> bool {
+    fn should_break_func(
+        &self,
+        name: &str,
+        executor: &Executor,
+        instance: Option<&Instance>,
+    ) -> Result<bool, Trap> {
         // FIXME
-        self.function_map
-            .keys()
-            .any(|k| name.contains(Clone::clone(&k)))
+        for (key, id) in &self.function_map {
+            if !name.contains(key.as_str()) {
+                continue;
+            }
+            match &self.entries[id] {
+                debugger::Breakpoint::FunctionConditional { predicate, .. } => {
+                    let locals = match executor.stack.current_frame() {
+                        Ok(frame) => frame.locals.as_slice(),
+                        Err(_) => &[],
+                    };
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        predicate(locals)
+                    }));
+                    match result {
+                        Ok(true) => return Ok(true),
+                        Ok(false) => continue,
+                        Err(payload) => {
+                            let message = payload
+                                .downcast_ref::<&str>()
+                                .map(|s| s.to_string())
+                                .or_else(|| payload.downcast_ref::<String>().cloned())
+                                .unwrap_or_else(|| "unknown panic".to_string());
+                            let err =
+                                anyhow!("breakpoint condition on '{}' panicked: {}", key, message);
+                            return Err(Trap::HostFunctionError(err.into()));
+                        }
+                    }
+                }
+                debugger::Breakpoint::Function {
+                    condition,
+                    hit_count,
+                    ..
+                } => {
+                    if condition_holds(condition, executor, instance)
+                        && self.count_hit(*id, *hit_count)
+                    {
+                        return Ok(true);
+                    }
+                }
+                debugger::Breakpoint::FunctionOnce { .. } => {
+                    self.fired_once.set(Some(*id));
+                    return Ok(true);
+                }
+                _ => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Counts a hit and returns true only on the `hit_count`th one
+    fn count_hit(&self, id: BreakpointId, hit_count: Option<u32>) -> bool {
+        let hit_count = match hit_count {
+            Some(hit_count) => hit_count,
+            None => return true,
+        };
+        let mut hit_counts = self.hit_counts.borrow_mut();
+        let hits = hit_counts.entry(id).or_insert(0);
+        *hits = hits.saturating_add(1);
+        *hits == hit_count
+    }
+
+    fn should_break_inst(
+        &self,
+        inst: &Instruction,
+        executor: &Executor,
+        instance: Option<&Instance>,
+    ) -> bool {
+        match self.inst_map.get(&inst.offset).map(|id| &self.entries[id]) {
+            Some(debugger::Breakpoint::Instruction { condition, .. }) => {
+                condition_holds(condition, executor, instance)
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    fn should_break_func_inst(
+        &self,
+        inst: &Instruction,
+        executor: &Executor,
+        instance: &Instance,
+    ) -> bool {
+        if self.func_inst_offsets.is_empty() {
+            return false;
+        }
+        let store = &instance.store;
+        let exec_addr = executor.pc.exec_addr();
+        let first_offset = match store.func_global(exec_addr).defined() {
+            Some(func) if func.module_index() == instance.main_module_index => {
+                match func.instructions().first() {
+                    Some(first) => first.offset,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        let func_index = match store.func_index(exec_addr) {
+            Some(func_index) => func_index as u32,
+            None => return false,
+        };
+        match self
+            .func_inst_offsets
+            .get(&(func_index, inst.offset - first_offset))
+            .map(|id| &self.entries[id])
+        {
+            Some(debugger::Breakpoint::Instruction { condition, .. }) => {
+                condition_holds(condition, executor, Some(instance))
+            }
+            Some(_) => true,
+            None => false,
+        }
     }
 
-    fn should_break_inst(&self, inst: &Instruction) -> bool {
-        self.inst_map.contains_key(&inst.offset)
+    fn should_break_local(&self, func_index: u32, local_index: usize) -> bool {
+        self.local_watches.iter().any(|(_, index, func)| {
+            *index == local_index && func.iter().all(|func| *func == func_index)
+        })
     }
 
-    fn insert(&mut self, breakpoint: debugger::Breakpoint) {
-        match &breakpoint {
-            debugger::Breakpoint::Function { name } => {
-                self.function_map.insert(name.clone(), breakpoint);
+    /// Registers a breakpoint, replacing an existing one at the same location
+    fn insert(&mut self, breakpoint: debugger::Breakpoint) -> BreakpointId {
+        self.next_id += 1;
+        let id = BreakpointId(self.next_id);
+        let replaced = match &breakpoint {
+            debugger::Breakpoint::Function { name, .. }
+            | debugger::Breakpoint::FunctionConditional { name, .. }
+            | debugger::Breakpoint::FunctionOnce { name } => {
+                self.function_map.insert(name.clone(), id)
+            }
+            debugger::Breakpoint::Instruction {
+                base: debugger::OffsetBase::Absolute,
+                offset,
+                ..
+            } => self.inst_map.insert(*offset, id),
+            debugger::Breakpoint::Instruction { .. } => {
+                // Resolved into `func_inst_offsets` by `MainDebugger::set_breakpoint`
+                None
             }
-            debugger::Breakpoint::Instruction { inst_offset } => {
-                self.inst_map.insert(*inst_offset, breakpoint);
+            debugger::Breakpoint::WatchLocal {
+                local_index,
+                func_index,
+            } => {
+                self.local_watches.push((id, *local_index, *func_index));
+                None
             }
+            debugger::Breakpoint::MemoryWrite { offset, len } => {
+                self.memory_watches.push((id, *offset, *len));
+                None
+            }
+        };
+        if let Some(replaced) = replaced {
+            self.entries.remove(&replaced);
+            self.hit_counts.get_mut().remove(&replaced);
+        }
+        self.entries.insert(id, breakpoint);
+        id
+    }
+
+    fn remove(&mut self, id: BreakpointId) -> bool {
+        if self.entries.remove(&id).is_none() {
+            return false;
         }
+        self.function_map.retain(|_, entry| *entry != id);
+        self.inst_map.retain(|_, entry| *entry != id);
+        self.func_inst_offsets.retain(|_, entry| *entry != id);
+        self.local_watches.retain(|(entry, _, _)| *entry != id);
+        self.memory_watches.retain(|(entry, _, _)| *entry != id);
+        self.hit_counts.get_mut().remove(&id);
+        true
     }
 }
 
 impl MainDebugger {
+    /// Executes an instruction, keeping the call stack when it traps
+    fn execute_step(&self, executor: &RefCell<Executor>, store: &Store) -> Result<Signal, Trap> {
+        if self.opts.history_limit > 0 {
+            let mut executor = executor.borrow_mut();
+            executor.stack.record_delta();
+            *self.recording.borrow_mut() = Some(HistoryEntry::new(executor.pc));
+        }
+        let result = executor
+            .borrow_mut()
+            .execute_step(store, self, &self.config)
+            .map_err(|trap| self.locate_trap(trap));
+        if let Some(mut entry) = self.recording.borrow_mut().take() {
+            entry.stack = executor.borrow_mut().stack.take_delta();
+            let mut history = self.history.borrow_mut();
+            while history.len() >= self.opts.history_limit {
+                history.pop_front();
+            }
+            history.push_back(entry);
+        }
+        if let Err(trap) = &result {
+            *self.last_trap.borrow_mut() = Some(debugger::TrapInfo {
+                message: trap.to_string(),
+                instruction_offset: trap
+                    .location()
+                    .map(|location| location.offset)
+                    .unwrap_or_else(|| self.current_inst_offset.get()),
+                backtrace: self.backtrace(),
+            });
+        }
+        result
+    }
+
+    /// Resolves the source line of the trapping instruction from debug info
+    fn locate_trap(&self, trap: Trap) -> Trap {
+        let offset = match trap.location() {
+            Some(location) => location.offset,
+            None => return trap,
+        };
+        let source = self
+            .line_table
+            .as_ref()
+            .and_then(|sourcemap| sourcemap.lookup_pc(offset))
+            .map(|location| (location.file, location.line as u64));
+        trap.with_location(TrapLocation { offset, source })
+    }
+
+    /// Executes instructions until the execution halts or finishes
+    fn execute_until_signal(&self) -> Result<RunResult> {
+        let store = self.store()?;
+        let executor = self.executor()?;
+        loop {
+            let result = self.execute_step(&executor, store);
+            match result {
+                Ok(Signal::Next) => continue,
+                Ok(Signal::Breakpoint) => {
+                    if let Some(change) = self.watch_hit.borrow_mut().take() {
+                        return Ok(RunResult::Watchpoint(change));
+                    }
+                    if let Some(change) = self.memory_watch_hit.borrow_mut().take() {
+                        return Ok(RunResult::MemoryWatchpoint(change));
+                    }
+                    return Ok(RunResult::Breakpoint);
+                }
+                Ok(Signal::End) => {
+                    let pc = executor.borrow().pc;
+                    let func = store.func_global(pc.exec_addr());
+                    let results = executor
+                        .borrow_mut()
+                        .pop_result(func.ty().results().to_vec())?;
+                    return Ok(RunResult::Finish(results));
+                }
+                Err(err) => return Err(anyhow!("trap: {}", err)),
+            }
+        }
+    }
+
     pub fn load_main_module(&mut self, module: &[u8], name: String) -> Result<()> {
         if let Err(err) = wasmparser::validate(module) {
             warn!("{}", err);
@@ -76,7 +452,23 @@ impl MainDebugger {
         Ok(())
     }
 
-    pub fn new(preopen_dirs: Vec<(String, String)>, envs: Vec<(String, String)>) -> Result<Self> {
+    /// Registers a module which can satisfy imports of modules loaded after it
+    pub fn load_module_named(&mut self, module: &[u8], name: String) -> Result<()> {
+        wasmparser::validate(module)?;
+        if let Some(ref mut instance) = self.instance {
+            instance.store.load_module(Some(name.clone()), module)?;
+        }
+        self.import_modules
+            .retain(|(registered, _)| *registered != name);
+        self.import_modules.push((name, module.to_vec()));
+        Ok(())
+    }
+
+    pub fn new(
+        preopen_dirs: Vec<(String, String)>,
+        envs: Vec<(String, String)>,
+        wasi: bool,
+    ) -> Result<Self> {
         let is_interrupted = Arc::new(AtomicBool::new(false));
         signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&is_interrupted))?;
         Ok(Self {
@@ -85,12 +477,35 @@ impl MainDebugger {
             opts: DebuggerOpts::default(),
             config: wasminspect_vm::Config {
                 features: WasmFeatures::default(),
+                execution_limit: None,
             },
             breakpoints: Default::default(),
             is_interrupted,
+            executed_instructions: Arc::new(AtomicU64::new(0)),
+            functions_called: Cell::new(0),
+            peak_call_depth: Cell::new(0),
             preopen_dirs,
             envs,
+            wasi,
             selected_frame: None,
+            local_history: RefCell::new(HashMap::new()),
+            profile_counts: RefCell::new(HashMap::new()),
+            instruction_counts: vec![Cell::new(0); InstructionKind::MNEMONICS.len()],
+            watch_hit: RefCell::new(None),
+            memory_watch_hit: RefCell::new(None),
+            current_inst_offset: Cell::new(0),
+            last_trap: RefCell::new(None),
+            step_out: RefCell::new(None),
+            step_out_hit: Cell::new(false),
+            tracer: RefCell::new(None),
+            sampler: RefCell::new(None),
+            history: RefCell::new(VecDeque::new()),
+            recording: RefCell::new(None),
+            mocks: Vec::new(),
+            import_modules: Vec::new(),
+            memory_labels: Vec::new(),
+            line_table: None,
+            subroutine_map: None,
         })
     }
 
@@ -106,6 +521,84 @@ impl MainDebugger {
         }
     }
 
+    /// Returns a counter of instructions executed so far, which can be read from other threads
+    pub fn executed_instructions(&self) -> Arc<AtomicU64> {
+        self.executed_instructions.clone()
+    }
+
+    /// The number of wasm function calls since the last `reset_call_stats`
+    pub fn functions_called(&self) -> u64 {
+        self.functions_called.get()
+    }
+
+    /// The deepest call stack since the last `reset_call_stats`
+    pub fn peak_call_depth(&self) -> usize {
+        self.peak_call_depth.get()
+    }
+
+    pub fn reset_call_stats(&self) {
+        self.functions_called.set(0);
+        self.peak_call_depth.set(0);
+    }
+
+    fn record_call(&self, call_depth: usize) {
+        self.functions_called.set(self.functions_called.get() + 1);
+        self.peak_call_depth
+            .set(self.peak_call_depth.get().max(call_depth));
+    }
+
+    pub fn main_module_index(&self) -> Result<ModuleIndex> {
+        Ok(self.instance()?.main_module_index)
+    }
+
+    /// Function names of the call stack from the outermost frame, followed by the offset of `inst`
+    fn sample_stack(&self, inst: &Instruction, executor: &Executor) -> Vec<String> {
+        let store = match self.instance() {
+            Ok(instance) => &instance.store,
+            Err(_) => return vec![],
+        };
+        let mut stack = executor
+            .stack
+            .peek_frames()
+            .iter()
+            .map(|frame| self.display_symbol(store.func_global(frame.exec_addr).name()))
+            .collect::<Vec<_>>();
+        stack.push(format!("{:#x}", inst.offset));
+        stack
+    }
+
+    /// The name of a function called from the executing frame's module
+    fn callee_name(&self, executor: &Executor, func_index: usize) -> Option<&str> {
+        let module_index = executor.stack.current_frame().ok()?.module_index;
+        let store = &self.instance().ok()?.store;
+        store
+            .module(module_index)
+            .defined()?
+            .function_name(func_index)
+    }
+
+    /// Resolves the module whose globals are accessed, defaulting to the
+    /// selected frame's module
+    fn globals_module(&self, module_index: Option<ModuleIndex>) -> Result<ModuleIndex> {
+        match module_index {
+            Some(module_index) => {
+                let count = self.store()?.module_count();
+                if module_index.0 as usize >= count {
+                    return Err(anyhow!(
+                        "module {} doesn't exist, modules length is {}",
+                        module_index.0,
+                        count
+                    ));
+                }
+                Ok(module_index)
+            }
+            None => Ok(self
+                .current_frame()
+                .ok_or_else(|| anyhow!("function frame not found"))?
+                .module_index),
+        }
+    }
+
     fn executor(&self) -> Result<Rc<RefCell<Executor>>> {
         let instance = self.instance()?;
         if let Some(ref executor) = instance.executor {
@@ -114,6 +607,16 @@ impl MainDebugger {
             Err(anyhow::anyhow!("No execution context"))
         }
     }
+    fn main_memory(&self) -> Result<Rc<RefCell<HostMemory>>> {
+        let instance = self.instance()?;
+        let store = &instance.store;
+        if store.memory_count(instance.main_module_index) == 0 {
+            return Err(anyhow!("no memory"));
+        }
+        let addr = MemoryAddr::new_unsafe(instance.main_module_index, 0);
+        Ok(store.memory(addr))
+    }
+
     fn instance(&self) -> Result<&Instance> {
         if let Some(ref instance) = self.instance {
             Ok(instance)
@@ -153,6 +656,8 @@ impl MainDebugger {
         func_addr: FuncAddr,
         args: Vec<WasmValue>,
     ) -> Result<debugger::RunResult> {
+        self.last_trap.borrow_mut().take();
+        self.history.borrow_mut().clear();
         let instance = self
             .instance
             .as_mut()
@@ -180,11 +685,21 @@ impl MainDebugger {
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
                 let executor = Rc::new(RefCell::new(Executor::new(frame, ret_types.len(), pc)));
                 instance.executor = Some(executor);
+                self.record_call(1);
                 Ok(self.process()?)
             }
         }
     }
 
+    /// Demangles a function name unless `raw_symbols` is set
+    fn display_symbol(&self, name: &str) -> String {
+        if self.opts.raw_symbols {
+            name.to_string()
+        } else {
+            demangle(name)
+        }
+    }
+
     fn selected_frame(&self) -> Result<ProgramCounter> {
         let executor = self.executor()?;
         let executor = executor.borrow();
@@ -203,6 +718,200 @@ impl MainDebugger {
         }
         Ok(executor.pc)
     }
+
+    /// Loads `host_modules`, and WASI if it's enabled and `wasi_args` is given, into `store`
+    fn load_host_modules(
+        &self,
+        store: &mut Store,
+        host_modules: HashMap<String, RawHostModule>,
+        wasi_args: Option<Vec<String>>,
+    ) -> Result<()> {
+        for (name, host_module) in host_modules {
+            store.load_host_module(name, host_module);
+        }
+
+        if let Some(wasi_args) = wasi_args.filter(|_| self.wasi) {
+            fn collect_preopen_dirs(
+                preopen_dirs: &[(String, String)],
+            ) -> anyhow::Result<Vec<(String, cap_std::fs::Dir)>> {
+                preopen_dirs
+                    .iter()
+                    .map(|(guest, host)| {
+                        let dir = unsafe { cap_std::fs::Dir::open_ambient_dir(host) }?;
+                        Ok((guest.clone(), dir))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            }
+
+            let (ctx, wasi_snapshot_preview) = instantiate_wasi(
+                &wasi_args,
+                collect_preopen_dirs(&self.preopen_dirs)?,
+                &self.envs,
+            )?;
+            let (_, wasi_unstable) = instantiate_wasi(
+                &wasi_args,
+                collect_preopen_dirs(&self.preopen_dirs)?,
+                &self.envs,
+            )?;
+            store.add_embed_context(Box::new(ctx));
+            store.load_host_module("wasi_snapshot_preview1".to_string(), wasi_snapshot_preview);
+            store.load_host_module("wasi_unstable".to_string(), wasi_unstable);
+        }
+        Ok(())
+    }
+}
+
+fn read_producers_section(module: &[u8]) -> Result<Option<debugger::ProducersSection>> {
+    let parser = wasmparser::Parser::new(0);
+    for payload in parser.parse_all(module) {
+        let section = match payload? {
+            wasmparser::Payload::CustomSection(section) if section.name() == "producers" => section,
+            _ => continue,
+        };
+        let mut producers = debugger::ProducersSection::default();
+        let reader =
+            wasmparser::ProducersSectionReader::new(section.data(), section.data_offset())?;
+        for field in reader {
+            let field = field?;
+            let values = match field.name {
+                "language" => &mut producers.language,
+                "processed-by" => &mut producers.processed_by,
+                "sdk" => &mut producers.sdk,
+                _ => continue,
+            };
+            for value in field.get_producer_field_values_reader()? {
+                let value = value?;
+                values.push((value.name.to_string(), value.version.to_string()));
+            }
+        }
+        return Ok(Some(producers));
+    }
+    Ok(None)
+}
+
+fn read_data_segments(module: &[u8]) -> Result<Vec<debugger::DataSegment>> {
+    let parser = wasmparser::Parser::new(0);
+    let mut segments = Vec::new();
+    for payload in parser.parse_all(module) {
+        let reader = match payload? {
+            wasmparser::Payload::DataSection(reader) => reader,
+            _ => continue,
+        };
+        for data in reader {
+            let data = data?;
+            let offset_expr = match data.kind {
+                wasmparser::DataKind::Active {
+                    memory_index: 0,
+                    offset_expr,
+                } => offset_expr,
+                _ => continue,
+            };
+            // Offsets given by imported globals can't be determined statically
+            if let wasmparser::Operator::I32Const { value } =
+                offset_expr.get_operators_reader().read()?
+            {
+                segments.push(debugger::DataSegment {
+                    offset: value as u32 as usize,
+                    len: data.data.len(),
+                });
+            }
+        }
+    }
+    Ok(segments)
+}
+
+/// Offsets of instructions in a defined function relative to its first instruction
+fn func_instruction_offsets(module: &[u8], func_index: u32) -> Result<Vec<usize>> {
+    let offsets = func_code_offsets(module, func_index)?;
+    let first = offsets.first().copied().unwrap_or_default();
+    Ok(offsets.into_iter().map(|offset| offset - first).collect())
+}
+
+/// Offsets of instructions in a defined function relative to the code section,
+/// the same as `Instruction::offset`
+fn func_code_offsets(module: &[u8], func_index: u32) -> Result<Vec<usize>> {
+    let parser = wasmparser::Parser::new(0);
+    let mut imported_funcs = 0;
+    let mut defined_index = 0;
+    let mut code_section_offset = 0;
+    for payload in parser.parse_all(module) {
+        match payload? {
+            wasmparser::Payload::CodeSectionStart { range, .. } => {
+                code_section_offset = range.start;
+            }
+            wasmparser::Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let wasmparser::TypeRef::Func(_) = import?.ty {
+                        imported_funcs += 1;
+                    }
+                }
+            }
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let index = imported_funcs + defined_index;
+                defined_index += 1;
+                if index != func_index {
+                    continue;
+                }
+                let mut reader = body.get_operators_reader()?;
+                let mut offsets = Vec::new();
+                while !reader.eof() {
+                    let (_, offset) = reader.read_with_offset()?;
+                    offsets.push(offset - code_section_offset);
+                }
+                return Ok(offsets);
+            }
+            _ => continue,
+        }
+    }
+    Err(anyhow!(
+        "func[{}] is not defined in the main module",
+        func_index
+    ))
+}
+
+/// Looks up the index of a function by its name in the name section
+fn func_index_by_name(module: &[u8], name: &str) -> Result<u32> {
+    let parser = wasmparser::Parser::new(0);
+    for payload in parser.parse_all(module) {
+        let section = match payload? {
+            wasmparser::Payload::CustomSection(section) if section.name() == "name" => section,
+            _ => continue,
+        };
+        let mut reader = wasmparser::NameSectionReader::new(section.data(), section.data_offset())?;
+        while !reader.eof() {
+            if let wasmparser::Name::Function(map) = reader.read()? {
+                for naming in map {
+                    let naming = naming?;
+                    if naming.name == name {
+                        return Ok(naming.index);
+                    }
+                }
+            }
+        }
+    }
+    Err(anyhow!("Function '{}' not found", name))
+}
+
+/// Returns all offsets where `pattern` occurs in `haystack` using Boyer-Moore-Horspool
+pub(crate) fn horspool_search(haystack: &[u8], pattern: &[u8]) -> Vec<usize> {
+    let mut found = Vec::new();
+    let len = pattern.len();
+    if len == 0 || haystack.len() < len {
+        return found;
+    }
+    let mut shifts = [len; 256];
+    for (index, byte) in pattern[..len - 1].iter().enumerate() {
+        shifts[*byte as usize] = len - 1 - index;
+    }
+    let mut offset = 0;
+    while offset + len <= haystack.len() {
+        let window = &haystack[offset..offset + len];
+        if window == pattern {
+            found.push(offset);
+        }
+        offset += shifts[window[len - 1] as usize];
+    }
+    found
 }
 
 impl debugger::Debugger for MainDebugger {
@@ -210,9 +919,22 @@ impl debugger::Debugger for MainDebugger {
         self.opts.clone()
     }
     fn set_opts(&mut self, opts: DebuggerOpts) {
+        if opts.profile && !self.opts.profile {
+            self.profile_counts.borrow_mut().clear();
+        }
+        let mut history = self.history.borrow_mut();
+        while history.len() > opts.history_limit {
+            history.pop_front();
+        }
+        drop(history);
         self.opts = opts
     }
 
+    fn set_execution_limit(&mut self, limit: Option<ExecutionLimit>) -> Result<()> {
+        self.config.execution_limit = limit;
+        Ok(())
+    }
+
     fn select_frame(&mut self, frame_index: Option<usize>) -> Result<()> {
         self.selected_frame = frame_index;
         Ok(())
@@ -226,8 +948,108 @@ impl debugger::Debugger for MainDebugger {
         Ok((insts, pc.inst_index().0 as usize))
     }
 
-    fn set_breakpoint(&mut self, breakpoint: debugger::Breakpoint) {
-        self.breakpoints.insert(breakpoint)
+    fn func_instructions(&self, func: &str) -> Result<&[Instruction]> {
+        let func_index = match func.parse::<usize>() {
+            Ok(index) => index,
+            Err(_) => self
+                .main_module()?
+                .function_index(func)
+                .ok_or_else(|| anyhow!("Function '{}' not found", func))?,
+        };
+        let instance = self.instance()?;
+        let module_index = instance.main_module_index;
+        let func = instance
+            .store
+            .func(FuncAddr::new_unsafe(module_index, func_index))
+            .with_context(|| format!("func[{}] not found", func_index))?;
+        match func.0.defined() {
+            Some(func) if func.module_index() == module_index => Ok(func.instructions()),
+            _ => Err(anyhow!(
+                "func[{}] is not defined in the main module",
+                func_index
+            )),
+        }
+    }
+
+    fn function_name(&self, func_index: u32) -> Option<String> {
+        let module = self.main_module().ok()?;
+        Some(module.function_name(func_index as usize)?.to_string())
+    }
+
+    fn module_bytes(&self) -> Result<&[u8]> {
+        match &self.main_module {
+            Some((module, _)) => Ok(module),
+            None => Err(anyhow!("No module loaded")),
+        }
+    }
+
+    fn set_breakpoint(&mut self, breakpoint: debugger::Breakpoint) -> Result<BreakpointId> {
+        if let debugger::Breakpoint::Instruction {
+            base: debugger::OffsetBase::Function(func),
+            offset,
+            ..
+        } = &breakpoint
+        {
+            let module = match &self.main_module {
+                Some((module, _)) => module,
+                None => return Err(anyhow!("No main module registered")),
+            };
+            let func_index = match func {
+                debugger::FunctionRef::Index(index) => *index,
+                debugger::FunctionRef::Name(name) => func_index_by_name(module, name)?,
+            };
+            if !func_instruction_offsets(module, func_index)?.contains(offset) {
+                return Err(anyhow!(
+                    "Offset {:#x} is not an instruction boundary in {}",
+                    offset,
+                    func
+                ));
+            }
+            let key = (func_index, *offset);
+            let id = self.breakpoints.insert(breakpoint);
+            if let Some(replaced) = self.breakpoints.func_inst_offsets.insert(key, id) {
+                self.breakpoints.remove(replaced);
+            }
+            return Ok(id);
+        }
+        Ok(self.breakpoints.insert(breakpoint))
+    }
+
+    fn set_conditional_breakpoint(
+        &mut self,
+        name: String,
+        predicate: debugger::BreakpointPredicate,
+    ) -> Result<BreakpointId> {
+        Ok(self
+            .breakpoints
+            .insert(debugger::Breakpoint::FunctionConditional { name, predicate }))
+    }
+
+    fn instruction_breakpoints(&self) -> Vec<usize> {
+        self.breakpoints.inst_map.keys().cloned().collect()
+    }
+
+    fn list_breakpoints(&self) -> Vec<(BreakpointId, debugger::Breakpoint)> {
+        self.breakpoints
+            .entries
+            .iter()
+            .map(|(id, breakpoint)| (*id, breakpoint.clone()))
+            .collect()
+    }
+
+    fn delete_breakpoint(&mut self, id: BreakpointId) -> bool {
+        self.breakpoints.remove(id)
+    }
+
+    fn producers(&self) -> Option<debugger::ProducersSection> {
+        let (module, _) = self.main_module.as_ref()?;
+        match read_producers_section(module) {
+            Ok(producers) => producers,
+            Err(err) => {
+                warn!("Failed to read producers section: {}", err);
+                None
+            }
+        }
     }
 
     fn stack_values(&self) -> Vec<WasmValue> {
@@ -249,29 +1071,250 @@ impl debugger::Debugger for MainDebugger {
         Ok(&instance.store)
     }
 
-    fn locals(&self) -> Vec<WasmValue> {
-        if let Ok(ref executor) = self.executor() {
-            let executor = executor.borrow();
-            let frame_index = self.selected_frame.unwrap_or(0);
-            if let Ok(frame) = executor.stack.frame_at(frame_index) {
-                return frame.locals.clone()
+    fn locals_for_frame(&self, depth: usize) -> Vec<WasmValue> {
+        let executor = match self.executor() {
+            Ok(executor) => executor,
+            Err(_) => return Vec::new(),
+        };
+        let executor = executor.borrow();
+        match executor.stack.frame_at(depth) {
+            Ok(frame) => frame.locals.clone(),
+            Err(_) => Vec::new(),
+        }
+    }
+    fn stack_values_for_frame(&self, depth: usize) -> Vec<WasmValue> {
+        let executor = match self.executor() {
+            Ok(executor) => executor,
+            Err(_) => return Vec::new(),
+        };
+        let executor = executor.borrow();
+        match executor.stack.frame_values(depth) {
+            Ok(values) => values.into_iter().copied().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+    fn selected_frame_index(&self) -> usize {
+        self.selected_frame.unwrap_or(0)
+    }
+    fn local_variables(&self) -> Vec<debugger::VariableInfo> {
+        let subroutine_map = match &self.subroutine_map {
+            Some(subroutine_map) => subroutine_map,
+            None => return Vec::new(),
+        };
+        let code_offset = match self.selected_instructions() {
+            Ok((insts, next_index)) => {
+                let current_index = if next_index == 0 { 0 } else { next_index - 1 };
+                insts[current_index].offset
+            }
+            Err(_) => return Vec::new(),
+        };
+        let variables = match subroutine_map.scoped_variables(code_offset) {
+            Ok(variables) => variables,
+            Err(err) => {
+                warn!("Failed to read variables at {:#x}: {}", code_offset, err);
+                return Vec::new();
+            }
+        };
+        let locals = self.locals_for_frame(self.selected_frame_index());
+        variables
+            .into_iter()
+            .map(|variable| {
+                let value = match variable.location {
+                    Some(WasmLoc::Local(index)) => locals.get(index as usize).copied(),
+                    _ => None,
+                };
+                debugger::VariableInfo {
+                    name: variable.name,
+                    type_name: variable.type_name,
+                    value,
+                    is_param: variable.is_param,
+                }
+            })
+            .collect()
+    }
+    fn frame_locals(&self) -> Result<Vec<debugger::LocalInfo>> {
+        let executor = self.executor()?;
+        let executor = executor.borrow();
+        let frame_index = self.selected_frame.unwrap_or(0);
+        let frame = executor
+            .stack
+            .frame_at(frame_index)
+            .map_err(|_| anyhow!("Frame index {} is out of range", frame_index))?;
+        let pc = self.selected_frame()?;
+        let func = self.store()?.func_global(pc.exec_addr());
+        let func = func
+            .defined()
+            .ok_or_else(|| anyhow!("Function frame is not a defined function"))?;
+        let argument_count = func.ty().params().len();
+        Ok(frame
+            .locals
+            .iter()
+            .zip(func.local_types())
+            .enumerate()
+            .map(|(index, (value, ty))| debugger::LocalInfo {
+                index,
+                name: func.local_name(index as u32).cloned(),
+                wasm_type: *ty,
+                value: *value,
+                is_param: index < argument_count,
+                source_location: None,
+            })
+            .collect())
+    }
+    fn set_local(&mut self, index: usize, value: WasmValue) -> Result<()> {
+        let locals = self.frame_locals()?;
+        let local = locals
+            .get(index)
+            .ok_or(debugger::SetLocalError::OutOfRange {
+                index,
+                count: locals.len(),
+            })?;
+        if !value.isa(local.wasm_type) {
+            return Err(debugger::SetLocalError::TypeMismatch {
+                index,
+                expected: local.wasm_type,
+                actual: value.value_type(),
+            }
+            .into());
+        }
+        let executor = self.executor()?;
+        let mut executor = executor.borrow_mut();
+        let frame_index = self.selected_frame.unwrap_or(0);
+        executor
+            .stack
+            .set_frame_local(frame_index, index, value)
+            .map_err(|_| anyhow!("Frame index {} is out of range", frame_index))
+    }
+    fn globals(&self, module_index: Option<ModuleIndex>) -> Result<Vec<(bool, WasmValue)>> {
+        let module_index = self.globals_module(module_index)?;
+        let store = self.store()?;
+        Ok((0..store.global_count(module_index))
+            .map(|index| {
+                let global = store.global(GlobalAddr::new_unsafe(module_index, index));
+                let global = global.borrow();
+                (global.is_mutable(), global.value())
+            })
+            .collect())
+    }
+    fn set_global(
+        &mut self,
+        module_index: Option<ModuleIndex>,
+        index: usize,
+        value: WasmValue,
+    ) -> Result<()> {
+        let module_index = self.globals_module(module_index)?;
+        let store = self.store()?;
+        let count = store.global_count(module_index);
+        if index >= count {
+            return Err(debugger::SetGlobalError::OutOfRange { index, count }.into());
+        }
+        let global = store.global(GlobalAddr::new_unsafe(module_index, index));
+        let mut global = global.borrow_mut();
+        if !global.is_mutable() {
+            return Err(debugger::SetGlobalError::Immutable { index }.into());
+        }
+        let expected = global.ty().content_type;
+        if !value.isa(expected) {
+            return Err(debugger::SetGlobalError::TypeMismatch {
+                index,
+                expected,
+                actual: value.value_type(),
+            }
+            .into());
+        }
+        global.set_value(value);
+        Ok(())
+    }
+    fn local_history(&mut self, func_index: u32, local_index: usize) -> &[debugger::LocalChange] {
+        match self
+            .local_history
+            .get_mut()
+            .get_mut(&(func_index, local_index))
+        {
+            Some(changes) => changes.make_contiguous(),
+            None => &[],
+        }
+    }
+    fn profile_counts(&self) -> HashMap<usize, u64> {
+        self.profile_counts.borrow().clone()
+    }
+    fn instruction_histogram(&self) -> HashMap<String, u64> {
+        InstructionKind::MNEMONICS
+            .iter()
+            .zip(self.instruction_counts.iter())
+            .filter(|(_, count)| count.get() > 0)
+            .map(|(mnemonic, count)| (mnemonic.to_string(), count.get()))
+            .collect()
+    }
+    fn reset_histogram(&mut self) {
+        for count in self.instruction_counts.iter() {
+            count.set(0);
+        }
+    }
+    fn start_trace(&mut self, path: &str) -> Result<()> {
+        let tracer = Tracer::create(path, self.opts.trace_format)?;
+        if let Some(previous) = self.tracer.replace(Some(tracer)) {
+            previous.finish()?;
+        }
+        Ok(())
+    }
+    fn stop_trace(&mut self) -> Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Err(anyhow!("No trace is running")),
+        }
+    }
+    fn start_sampling_profiler(&mut self, interval_instructions: u64) -> Result<()> {
+        if interval_instructions == 0 {
+            return Err(anyhow!("Sampling interval must be at least 1 instruction"));
+        }
+        self.sampler.replace(Some(Sampler {
+            interval: interval_instructions,
+            remaining: interval_instructions,
+            data: debugger::ProfilingData::default(),
+        }));
+        Ok(())
+    }
+    fn stop_sampling_profiler(&mut self) -> Result<debugger::ProfilingData> {
+        match self.sampler.take() {
+            Some(sampler) => Ok(sampler.data),
+            None => Err(anyhow!("No sampling profiler is running")),
+        }
+    }
+    fn instruction_map(&self) -> Result<HashMap<usize, Instruction>> {
+        let instance = self.instance()?;
+        let module_index = instance.main_module_index;
+        let mut insts = HashMap::new();
+        let funcs = (0..).map_while(|index| {
+            instance
+                .store
+                .func(FuncAddr::new_unsafe(module_index, index))
+        });
+        for (func, _) in funcs {
+            match func.defined() {
+                Some(func) if func.module_index() == module_index => {
+                    for inst in func.instructions() {
+                        insts.insert(inst.offset, inst.clone());
+                    }
+                }
+                _ => continue,
             }
         }
-        vec![]
+        Ok(insts)
     }
     fn current_frame(&self) -> Option<debugger::FunctionFrame> {
         let frame = self.selected_frame().ok()?;
-        let func = match self.store() {
-            Ok(store) => store.func_global(frame.exec_addr()),
-            Err(_) => return None,
-        };
+        let store = self.store().ok()?;
+        let func = store.func_global(frame.exec_addr());
 
         Some(debugger::FunctionFrame {
             module_index: frame.module_index(),
+            func_index: store.func_index(frame.exec_addr())? as u32,
             argument_count: func.ty().params().len(),
+            result_types: func.ty().results().to_vec(),
         })
     }
-    fn frame(&self) -> Vec<String> {
+    fn frame(&self) -> Vec<debugger::FrameInfo> {
         let instance = if let Ok(instance) = self.instance() {
             instance
         } else {
@@ -284,25 +1327,283 @@ impl debugger::Debugger for MainDebugger {
         };
         let executor = executor.borrow();
         let frames = executor.stack.peek_frames();
-        return frames
+        // Callers resume from the return address saved in the frame above them
+        let pcs = frames
+            .iter()
+            .skip(1)
+            .map(|frame| frame.ret_pc.map(|pc| (pc, true)))
+            .chain(std::iter::once(Some((executor.pc, false))));
+        frames
             .iter()
-            .map(|frame| instance.store.func_global(frame.exec_addr).name().clone())
-            .collect();
+            .zip(pcs)
+            .map(|(frame, pc)| {
+                let func = instance.store.func_global(frame.exec_addr);
+                let instruction_offset = pc
+                    .and_then(|(pc, is_return)| {
+                        let mut index = pc.inst_index().0 as usize;
+                        if is_return {
+                            index = index.checked_sub(1)?;
+                        }
+                        Some(func.defined()?.instructions().get(index)?.offset)
+                    })
+                    .unwrap_or_default();
+                let source_location = self
+                    .line_table
+                    .as_ref()
+                    .and_then(|sourcemap| sourcemap.lookup_pc(instruction_offset));
+                // Names from DWARF take priority over the name section
+                let demangled_name = self
+                    .subroutine_map
+                    .as_ref()
+                    .filter(|_| func.defined().is_some())
+                    .and_then(|subroutine_map| subroutine_map.function_name(instruction_offset))
+                    .unwrap_or_else(|| self.display_symbol(func.name()));
+                debugger::FrameInfo {
+                    func_name: func.name().clone(),
+                    demangled_name,
+                    func_index: instance.store.func_index(frame.exec_addr),
+                    module_index: frame.module_index,
+                    instruction_offset,
+                    source_location,
+                    is_inlined: false,
+                }
+            })
+            .collect()
+    }
+
+    fn backtrace(&self) -> Vec<debugger::FrameInfo> {
+        let subroutine_map = match &self.subroutine_map {
+            Some(subroutine_map) => subroutine_map,
+            None => return self.frame(),
+        };
+        let mut frames = vec![];
+        for frame in self.frame() {
+            let inlined_frames = subroutine_map.inlined_frames(frame.instruction_offset);
+            if inlined_frames.is_empty() {
+                frames.push(frame);
+                continue;
+            }
+            // Each frame is located at the call site of the subroutine inlined into it,
+            // and the innermost one keeps the location of the executing instruction
+            let mut locations = inlined_frames
+                .iter()
+                .map(|inlined| SourceLocation::from_line_info(inlined.call_site.clone()))
+                .collect::<Vec<_>>();
+            locations.push(frame.source_location.clone());
+            let mut locations = locations.into_iter();
+            let inlined_frames = inlined_frames.into_iter().map(|inlined| {
+                let func_name = inlined.name.unwrap_or_else(|| "<inlined>".to_string());
+                debugger::FrameInfo {
+                    demangled_name: self.display_symbol(&func_name),
+                    func_name,
+                    func_index: frame.func_index,
+                    module_index: frame.module_index,
+                    instruction_offset: frame.instruction_offset,
+                    source_location: None,
+                    is_inlined: true,
+                }
+            });
+            let physical = debugger::FrameInfo {
+                source_location: None,
+                ..frame.clone()
+            };
+            for mut expanded in std::iter::once(physical).chain(inlined_frames) {
+                expanded.source_location = locations.next().flatten();
+                frames.push(expanded);
+            }
+        }
+        frames
+    }
+
+    fn last_trap(&self) -> Option<debugger::TrapInfo> {
+        self.last_trap.borrow().clone()
+    }
+
+    fn memory_at(&self, mem_index: usize) -> Result<Vec<u8>> {
+        let instance = self.instance()?;
+        let store = &instance.store;
+        let count = store.memory_count(instance.main_module_index);
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        if mem_index >= count {
+            return Err(anyhow!(
+                "Memory index {} out of range, the module has {} memories",
+                mem_index,
+                count
+            ));
+        }
+        let addr = MemoryAddr::new_unsafe(instance.main_module_index, mem_index);
+        Ok(store.memory(addr).borrow().raw_data().to_vec())
     }
-    fn memory(&self) -> Result<Vec<u8>> {
+
+    fn memory_checksum(&self, start: usize, len: usize) -> Result<u32> {
+        let memory = self.main_memory()?;
+        let memory = memory.borrow();
+        let data = memory.raw_data();
+        let end = start
+            .checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "range {}..{} out of range for memory of length {}",
+                    start,
+                    start.saturating_add(len),
+                    data.len()
+                )
+            })?;
+        Ok(crc32fast::hash(&data[start..end]))
+    }
+
+    fn dump_memory(&self, path: &Path, offset: usize, len: usize) -> Result<()> {
+        let memory = self.main_memory()?;
+        let memory = memory.borrow();
+        let data = memory.raw_data();
+        let bytes = offset
+            .checked_add(len)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| {
+                anyhow!(
+                    "range {}..{} out of range for memory of length {}",
+                    offset,
+                    offset.saturating_add(len),
+                    data.len()
+                )
+            })?;
+        std::fs::write(path, bytes).map_err(|err| anyhow!("{}: {}", path.display(), err))
+    }
+
+    fn write_memory(&mut self, offset: usize, bytes: Vec<u8>) -> Result<()> {
+        let memory = self.main_memory()?;
+        let result = memory.borrow_mut().store(offset, &bytes);
+        result.map_err(|err| anyhow!("{}", err))
+    }
+
+    fn find_in_memory(&self, pattern: &[u8], align: usize) -> Result<Vec<usize>> {
         let instance = self.instance()?;
         let store = &instance.store;
         if store.memory_count(instance.main_module_index) == 0 {
             return Ok(vec![]);
         }
         let addr = MemoryAddr::new_unsafe(instance.main_module_index, 0);
-        Ok(store.memory(addr).borrow().raw_data().to_vec())
+        let memory = store.memory(addr);
+        let memory = memory.borrow();
+        let align = align.max(1);
+        Ok(horspool_search(memory.raw_data(), pattern)
+            .into_iter()
+            .filter(|offset| offset % align == 0)
+            .collect())
+    }
+
+    fn set_line_table(&mut self, sourcemap: Rc<dyn SourceMap>) {
+        self.line_table = Some(sourcemap);
+    }
+
+    fn set_subroutine_map(&mut self, subroutine: Rc<dyn SubroutineMap>) {
+        self.subroutine_map = Some(subroutine);
+    }
+
+    fn label_memory_region(&mut self, start: usize, len: usize, label: String) {
+        self.memory_labels
+            .retain(|region| region.start != start || region.len != len);
+        self.memory_labels
+            .push(debugger::LabeledRegion { start, len, label });
+        self.memory_labels.sort_by_key(|region| region.start);
+    }
+
+    fn mock_function(&mut self, mock: debugger::FunctionMock) -> Result<()> {
+        // Check the mock against the host modules provided at the next launch
+        let mut store = Store::new();
+        self.load_host_modules(&mut store, HashMap::new(), Some(vec![]))?;
+        apply_mock(&mut store, &mock)?;
+        self.mocks
+            .retain(|m| m.module != mock.module || m.name != mock.name);
+        self.mocks.push(mock);
+        Ok(())
+    }
+
+    fn delete_mock(&mut self, module: &str, name: &str) -> bool {
+        let len = self.mocks.len();
+        self.mocks.retain(|m| m.module != module || m.name != name);
+        self.mocks.len() != len
+    }
+
+    fn clear_mocks(&mut self) {
+        self.mocks.clear();
+    }
+
+    fn load_host_module_from_file(&mut self, module_name: &str, path: &Path) -> Result<()> {
+        let module = std::fs::read(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        self.load_module_named(&module, module_name.to_string())
+    }
+
+    fn memory_labels(&self) -> Vec<debugger::LabeledRegion> {
+        self.memory_labels.clone()
+    }
+
+    fn data_segments(&self) -> Result<Vec<debugger::DataSegment>> {
+        match &self.main_module {
+            Some((module, _)) => read_data_segments(module),
+            None => Err(anyhow!("No main module registered")),
+        }
+    }
+
+    fn global_by_name(&self, name: &str) -> Result<Option<WasmValue>> {
+        let instance = self.instance()?;
+        let module = self.main_module()?;
+        let addr = match module.exported_global(name)? {
+            Some(addr) => addr,
+            None => {
+                let count = instance.store.global_count(instance.main_module_index);
+                let index = (0..count).find(|index| {
+                    module.global_name(*index as u32).map(String::as_str) == Some(name)
+                });
+                match index {
+                    Some(index) => GlobalAddr::new_unsafe(instance.main_module_index, index),
+                    None => return Ok(None),
+                }
+            }
+        };
+        let value = instance.store.global(addr).borrow().value();
+        Ok(Some(value))
     }
 
     fn is_running(&self) -> bool {
         self.executor().is_ok()
     }
 
+    fn step_back(&mut self) -> Result<()> {
+        let entry = match self.history.borrow_mut().pop_back() {
+            Some(entry) => entry,
+            None if self.opts.history_limit == 0 => {
+                return Err(anyhow!(
+                    "History is not recorded, set history.limit to step back"
+                ))
+            }
+            None => return Err(anyhow!("No more history to step back")),
+        };
+        {
+            let store = self.store()?;
+            let executor = self.executor()?;
+            let mut executor = executor.borrow_mut();
+            executor.pc = entry.pc;
+            if let Some(delta) = entry.stack {
+                executor.stack.revert_delta(delta);
+            }
+            for (mem_addr, addr, bytes) in entry.memory.into_iter().rev() {
+                let memory = store.memory(mem_addr);
+                memory.borrow_mut().raw_data_mut()[addr..addr + bytes.len()]
+                    .copy_from_slice(&bytes);
+            }
+            for (addr, value) in entry.globals.into_iter().rev() {
+                store.global(addr).borrow_mut().set_value(value);
+            }
+        }
+        self.last_trap.borrow_mut().take();
+        self.selected_frame = None;
+        Ok(())
+    }
+
     fn step(&self, style: debugger::StepStyle) -> Result<Signal> {
         let store = self.store()?;
         let executor = self.executor()?;
@@ -312,21 +1613,12 @@ impl debugger::Debugger for MainDebugger {
             executor.stack.peek_frames().len()
         }
         match style {
-            InstIn => {
-                return Ok(executor
-                    .borrow_mut()
-                    .execute_step(store, self, &self.config)?)
-            }
+            InstIn => Ok(self.execute_step(&executor, store)?),
             InstOver => {
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal =
-                    executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                let mut last_signal = self.execute_step(&executor, store)?;
                 while initial_frame_depth < frame_depth(&executor.borrow()) {
-                    last_signal = executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                    last_signal = self.execute_step(&executor, store)?;
                     if let Signal::Breakpoint = last_signal {
                         return Ok(last_signal);
                     }
@@ -334,46 +1626,92 @@ impl debugger::Debugger for MainDebugger {
                 Ok(last_signal)
             }
             Out => {
+                let target = {
+                    let executor = executor.borrow();
+                    let insts = match store.func_global(executor.pc.exec_addr()).defined() {
+                        Some(func) => func.instructions(),
+                        None => return Err(anyhow!("Function frame is not defined in wasm")),
+                    };
+                    StepOutTarget::new(insts, frame_depth(&executor))
+                };
+                let initial_frame_depth = target.frame_depth;
+                *self.step_out.borrow_mut() = Some(target);
+                self.step_out_hit.set(false);
+                // Run without checking the frame depth until the temporary breakpoint fires
+                let result = loop {
+                    let signal = self.execute_step(&executor, store);
+                    match signal {
+                        Ok(Signal::Next) => continue,
+                        Ok(Signal::Breakpoint) if self.step_out_hit.replace(false) => {
+                            if frame_depth(&executor.borrow()) < initial_frame_depth {
+                                break Ok(Signal::Next);
+                            }
+                        }
+                        other => break other.map_err(anyhow::Error::from),
+                    }
+                };
+                self.step_out.borrow_mut().take();
+                result
+            }
+            StepLineIn | StepLineOver => {
+                let sourcemap = self
+                    .line_table
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("No line table is loaded"))?;
+                let (inst_style, descends) = match style {
+                    StepLineIn => (InstIn, true),
+                    _ => (InstOver, false),
+                };
+                let statement_line = |executor: &Executor| {
+                    let pc = executor.pc;
+                    let func = store.func_global(pc.exec_addr()).defined()?;
+                    let inst = func.instructions().get(pc.inst_index().0 as usize)?;
+                    if descends {
+                        // Stepping in stops at lines of inlined code too
+                        sourcemap.find_line_info(inst.offset)
+                    } else {
+                        sourcemap.find_statement_line(inst.offset)
+                    }
+                };
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal =
-                    executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
-                while initial_frame_depth <= frame_depth(&executor.borrow()) {
-                    last_signal = executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
-                    if let Signal::Breakpoint = last_signal {
-                        return Ok(last_signal);
+                let initial_line = statement_line(&executor.borrow());
+                loop {
+                    let signal = self.step(inst_style)?;
+                    if !matches!(signal, Signal::Next) {
+                        return Ok(signal);
+                    }
+                    let executor = executor.borrow();
+                    if frame_depth(&executor) < initial_frame_depth {
+                        return Ok(signal);
+                    }
+                    let line = match statement_line(&executor) {
+                        // Instructions without a line, like prologues, stay in the previous one
+                        Some(line) if line.line != Some(0) => line,
+                        _ => continue,
+                    };
+                    let is_same_line = matches!(
+                        &initial_line,
+                        Some(initial) if initial.filepath == line.filepath && initial.line == line.line
+                    );
+                    if !is_same_line {
+                        return Ok(signal);
                     }
                 }
-                Ok(last_signal)
             }
         }
     }
 
     fn process(&mut self) -> Result<RunResult> {
         self.selected_frame = None;
-        let store = self.store()?;
-        let executor = self.executor()?;
-        loop {
-            let result = executor
-                .borrow_mut()
-                .execute_step(store, self, &self.config);
-            match result {
-                Ok(Signal::Next) => continue,
-                Ok(Signal::Breakpoint) => return Ok(RunResult::Breakpoint),
-                Ok(Signal::End) => {
-                    let pc = executor.borrow().pc;
-                    let func = store.func_global(pc.exec_addr());
-                    let results = executor
-                        .borrow_mut()
-                        .pop_result(func.ty().results().to_vec())?;
-                    return Ok(RunResult::Finish(results));
-                }
-                Err(err) => return Err(anyhow!("Function exec failure {}", err)),
-            }
+        self.watch_hit.borrow_mut().take();
+        self.memory_watch_hit.borrow_mut().take();
+        // The time stopped at breakpoints doesn't count
+        self.executor()?.borrow_mut().restart_execution_limit();
+        let result = self.execute_until_signal();
+        if let Some(id) = self.breakpoints.fired_once.take() {
+            self.breakpoints.remove(id);
         }
+        result
     }
 
     fn run(&mut self, name: Option<&str>, args: Vec<WasmValue>) -> Result<debugger::RunResult> {
@@ -397,49 +1735,30 @@ impl debugger::Debugger for MainDebugger {
         host_modules: HashMap<String, RawHostModule>,
         wasi_args: Option<&[String]>,
     ) -> Result<()> {
-        let mut store = Store::new();
-        for (name, host_module) in host_modules {
-            store.load_host_module(name, host_module);
-        }
-
         let (main_module, basename) = if let Some((main_module, basename)) = &self.main_module {
             (main_module, basename.clone())
         } else {
             return Err(anyhow::anyhow!("No main module registered"));
         };
-
-        if let Some(wasi_args) = wasi_args {
+        let wasi_args = wasi_args.map(|wasi_args| {
             let mut wasi_args = wasi_args.to_vec();
             wasi_args.insert(0, basename);
+            wasi_args
+        });
 
-            fn collect_preopen_dirs(
-                preopen_dirs: &[(String, String)],
-            ) -> anyhow::Result<Vec<(String, cap_std::fs::Dir)>> {
-                preopen_dirs
-                    .iter()
-                    .map(|(guest, host)| {
-                        let dir = unsafe { cap_std::fs::Dir::open_ambient_dir(host) }?;
-                        Ok((guest.clone(), dir))
-                    })
-                    .collect::<anyhow::Result<Vec<_>>>()
-            }
-
-            let (ctx, wasi_snapshot_preview) = instantiate_wasi(
-                &wasi_args,
-                collect_preopen_dirs(&self.preopen_dirs)?,
-                &self.envs,
-            )?;
-            let (_, wasi_unstable) = instantiate_wasi(
-                &wasi_args,
-                collect_preopen_dirs(&self.preopen_dirs)?,
-                &self.envs,
-            )?;
-            store.add_embed_context(Box::new(ctx));
-            store.load_host_module("wasi_snapshot_preview1".to_string(), wasi_snapshot_preview);
-            store.load_host_module("wasi_unstable".to_string(), wasi_unstable);
+        let mut store = Store::new();
+        self.load_host_modules(&mut store, host_modules, wasi_args)?;
+        for mock in &self.mocks {
+            apply_mock(&mut store, mock)?;
+        }
+        for (name, module) in &self.import_modules {
+            store
+                .load_module(Some(name.clone()), module)
+                .with_context(|| format!("Failed to load module '{}'", name))?;
         }
 
         let main_module_index = store.load_module(None, main_module)?;
+        self.local_history.borrow_mut().clear();
 
         self.instance = Some(Instance {
             main_module_index,
@@ -450,23 +1769,102 @@ impl debugger::Debugger for MainDebugger {
     }
 }
 
+fn apply_mock(store: &mut Store, mock: &debugger::FunctionMock) -> Result<()> {
+    let host = store
+        .host_module_mut(&mock.module)
+        .ok_or_else(|| anyhow!("Host module '{}' not found", mock.module))?;
+    let ty = host
+        .func_type(&mock.name)
+        .ok_or_else(|| anyhow!("Host function '{}.{}' not found", mock.module, mock.name))?;
+    let results = match (ty.results(), &mock.return_value) {
+        ([], _) => vec![],
+        ([ty], Some(value)) => vec![parse_value(value, *ty)?],
+        ([_], None) => {
+            return Err(anyhow!(
+                "Mock of '{}.{}' requires a return value",
+                mock.module,
+                mock.name
+            ))
+        }
+        _ => return Err(anyhow!("Mocking multi-value functions is not supported")),
+    };
+    host.intercept_func(&mock.name, Box::new(move |_, _| Some(results.clone())))?;
+    Ok(())
+}
+
 impl Interceptor for MainDebugger {
-    fn invoke_func(
-        &self,
-        name: &str,
-        _executor: &Executor,
-        _store: &Store,
-    ) -> Result<Signal, Trap> {
+    fn invoke_func(&self, name: &str, executor: &Executor, _store: &Store) -> Result<Signal, Trap> {
         trace!("Invoke function '{}'", name);
-        if self.breakpoints.should_break_func(name) {
+        self.record_call(executor.stack.frame_depth());
+        let instance = self.instance().ok();
+        if self
+            .breakpoints
+            .should_break_func(name, executor, instance)?
+        {
             Ok(Signal::Breakpoint)
         } else {
             Ok(Signal::Next)
         }
     }
 
-    fn execute_inst(&self, inst: &Instruction) -> Result<Signal, Trap> {
-        if self.breakpoints.should_break_inst(inst) {
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) -> Result<Signal, Trap> {
+        self.executed_instructions.fetch_add(1, Ordering::Relaxed);
+        let count = &self.instruction_counts[inst.kind.ordinal()];
+        count.set(count.get() + 1);
+        self.current_inst_offset.set(inst.offset);
+        if let InstructionKind::GlobalSet { global_index } = inst.kind {
+            if let (Some(entry), Ok(store)) = (self.recording.borrow_mut().as_mut(), self.store()) {
+                let addr =
+                    GlobalAddr::new_unsafe(executor.pc.module_index(), global_index as usize);
+                entry
+                    .globals
+                    .push((addr, store.global(addr).borrow().value()));
+            }
+        }
+        let mut tracer = self.tracer.borrow_mut();
+        if let Some(writer) = tracer.as_mut() {
+            let call_depth = executor.stack.peek_frames().len();
+            let stack_depth = executor.stack.peek_values().len();
+            let callee = match inst.kind {
+                InstructionKind::Call { function_index }
+                | InstructionKind::ReturnCall { function_index } => {
+                    self.callee_name(executor, function_index as usize)
+                }
+                _ => None,
+            };
+            if let Err(err) = writer.record(inst, call_depth, stack_depth, callee) {
+                warn!("Stopped tracing: {}", err);
+                *tracer = None;
+            }
+        }
+        if let Some(sampler) = self.sampler.borrow_mut().as_mut() {
+            sampler.remaining -= 1;
+            if sampler.remaining == 0 {
+                sampler.remaining = sampler.interval;
+                sampler.data.samples.push(self.sample_stack(inst, executor));
+            }
+        }
+        if self.opts.profile {
+            *self
+                .profile_counts
+                .borrow_mut()
+                .entry(inst.offset)
+                .or_insert(0) += 1;
+        }
+        let instance = self.instance().ok();
+        let hit_func_inst = match instance {
+            Some(instance) => self
+                .breakpoints
+                .should_break_func_inst(inst, executor, instance),
+            None => false,
+        };
+        if self.breakpoints.should_break_inst(inst, executor, instance) || hit_func_inst {
+            Ok(Signal::Breakpoint)
+        } else if matches!(
+            self.step_out.borrow().as_ref(),
+            Some(target) if target.should_break(inst, executor)
+        ) {
+            self.step_out_hit.set(true);
             Ok(Signal::Breakpoint)
         } else if self.is_interrupted.swap(false, Ordering::Relaxed) {
             println!("Interrupted by signal");
@@ -476,7 +1874,150 @@ impl Interceptor for MainDebugger {
         }
     }
 
+    fn before_store(&self, addr: usize, bytes: &[u8], memory: &HostMemory) -> Result<Signal, Trap> {
+        let end = addr.saturating_add(bytes.len());
+        if let Some(entry) = self.recording.borrow_mut().as_mut() {
+            if let (Ok(store), Some(old_bytes)) = (self.store(), memory.raw_data().get(addr..end)) {
+                // Find the memory written by the instruction among ones of its module
+                let module_index = entry.pc.module_index();
+                let mem_addr = (0..store.memory_count(module_index))
+                    .map(|index| MemoryAddr::new_unsafe(module_index, index))
+                    .find(|mem_addr| std::ptr::eq(store.memory(*mem_addr).as_ptr(), memory));
+                if let Some(mem_addr) = mem_addr {
+                    entry.memory.push((mem_addr, addr, old_bytes.to_vec()));
+                }
+            }
+        }
+        if self.breakpoints.memory_watches.is_empty() {
+            return Ok(Signal::Next);
+        }
+        // Watchpoints are set on the memory 0 of the main module
+        let is_watched = self
+            .main_memory()
+            .map_or(false, |watched| std::ptr::eq(watched.as_ptr(), memory));
+        if !is_watched {
+            return Ok(Signal::Next);
+        }
+        for (_, offset, len) in &self.breakpoints.memory_watches {
+            // The overlap between the written range and the watched range
+            let start = addr.max(*offset);
+            let stop = end.min(offset.saturating_add(*len));
+            if start >= stop {
+                continue;
+            }
+            let old_bytes = match memory.raw_data().get(start..stop) {
+                Some(bytes) => bytes.to_vec(),
+                // Out of bounds stores trap afterwards
+                None => continue,
+            };
+            *self.memory_watch_hit.borrow_mut() = Some(debugger::MemoryChange {
+                addr: start,
+                old_bytes,
+                new_bytes: bytes[(start - addr)..(stop - addr)].to_vec(),
+                instruction_offset: self.current_inst_offset.get(),
+            });
+            return Ok(Signal::Breakpoint);
+        }
+        Ok(Signal::Next)
+    }
+
     fn after_store(&self, _addr: usize, _bytes: &[u8]) -> Result<Signal, Trap> {
         Ok(Signal::Next)
     }
+
+    fn after_set_local(
+        &self,
+        local_index: usize,
+        old_value: WasmValue,
+        new_value: WasmValue,
+        inst: &Instruction,
+        executor: &Executor,
+    ) -> Result<Signal, Trap> {
+        if !self.opts.track_locals && self.breakpoints.local_watches.is_empty() {
+            return Ok(Signal::Next);
+        }
+        let store = match self.store() {
+            Ok(store) => store,
+            Err(_) => return Ok(Signal::Next),
+        };
+        let func_index = match store.func_index(executor.pc.exec_addr()) {
+            Some(index) => index as u32,
+            None => return Ok(Signal::Next),
+        };
+        let change = debugger::LocalChange {
+            func_index,
+            local_index,
+            old_value,
+            new_value,
+            instruction_offset: inst.offset,
+        };
+        if self.opts.track_locals {
+            let mut history = self.local_history.borrow_mut();
+            let changes = history.entry((func_index, local_index)).or_default();
+            if changes.len() == LOCAL_HISTORY_CAPACITY {
+                changes.pop_front();
+            }
+            changes.push_back(change.clone());
+        }
+        if self.breakpoints.should_break_local(func_index, local_index) {
+            *self.watch_hit.borrow_mut() = Some(change);
+            return Ok(Signal::Breakpoint);
+        }
+        Ok(Signal::Next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debugger::Debugger;
+
+    #[test]
+    fn memory_watch_ignores_other_memories() {
+        // (module $lib (memory 1)
+        //   (func (export "poke") (i32.store (i32.const 0) (i32.const 1))))
+        let lib = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x07, 0x08, 0x01, 0x04, b'p', b'o', b'k', b'e', 0x00, 0x00, // export section
+            0x0a, 0x0b, 0x01, 0x09, 0x00, 0x41, 0x00, 0x41, 0x01, 0x36, 0x02, 0x00,
+            0x0b, // code section
+        ];
+        // (module (import "lib" "poke" (func)) (memory 1)
+        //   (func (export "f") call 0)
+        //   (func (export "g") (i32.store (i32.const 0) (i32.const 1))))
+        let main = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x02, 0x0c, 0x01, 0x03, b'l', b'i', b'b', 0x04, b'p', b'o', b'k', b'e', 0x00,
+            0x00, // import section
+            0x03, 0x03, 0x02, 0x00, 0x00, // function section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x07, 0x09, 0x02, 0x01, b'f', 0x00, 0x01, 0x01, b'g', 0x00,
+            0x02, // export section
+            0x0a, 0x10, 0x02, 0x04, 0x00, 0x10, 0x00, 0x0b, 0x09, 0x00, 0x41, 0x00, 0x41, 0x01,
+            0x36, 0x02, 0x00, 0x0b, // code section
+        ];
+        let mut debugger = MainDebugger::new(vec![], vec![], false).unwrap();
+        debugger.load_module_named(&lib, "lib".to_string()).unwrap();
+        debugger
+            .load_main_module(&main, "main.wasm".to_string())
+            .unwrap();
+        debugger.instantiate(HashMap::new(), None).unwrap();
+        debugger
+            .set_breakpoint(debugger::Breakpoint::MemoryWrite { offset: 0, len: 4 })
+            .unwrap();
+
+        // The store to the memory of `lib` doesn't touch the watched memory
+        assert!(matches!(
+            debugger.run(Some("f"), vec![]).unwrap(),
+            debugger::RunResult::Finish(_)
+        ));
+        assert!(matches!(
+            debugger.run(Some("g"), vec![]).unwrap(),
+            debugger::RunResult::MemoryWatchpoint(_)
+        ));
+    }
 }
@@ -0,0 +1,509 @@
+use crate::commands::format::format_v128;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use wasminspect_vm::InstructionKind;
+use wasmparser::{
+    ConstExpr, Data, DataKind, Element, ElementItem, ElementKind, Export, ExternalKind, FuncType,
+    FunctionBody, Global, GlobalType, Import, MemoryType, OperatorsReader, TableType, TagType,
+    TypeRef, ValType,
+};
+
+/// Sections of a module to print in the text format
+#[derive(Default)]
+struct Module<'a> {
+    types: Vec<FuncType>,
+    imports: Vec<Import<'a>>,
+    /// Type indices of the defined functions
+    funcs: Vec<u32>,
+    bodies: Vec<FunctionBody<'a>>,
+    tables: Vec<TableType>,
+    memories: Vec<MemoryType>,
+    tags: Vec<TagType>,
+    globals: Vec<Global<'a>>,
+    exports: Vec<Export<'a>>,
+    start: Option<u32>,
+    elements: Vec<Element<'a>>,
+    data: Vec<Data<'a>>,
+    func_names: HashMap<u32, String>,
+}
+
+impl<'a> Module<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self> {
+        use wasmparser::Payload;
+        let mut module = Module::default();
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::TypeSection(section) => {
+                    for ty in section {
+                        match ty? {
+                            wasmparser::Type::Func(ty) => module.types.push(ty),
+                        }
+                    }
+                }
+                Payload::ImportSection(section) => {
+                    for import in section {
+                        module.imports.push(import?);
+                    }
+                }
+                Payload::FunctionSection(section) => {
+                    for func in section {
+                        module.funcs.push(func?);
+                    }
+                }
+                Payload::TableSection(section) => {
+                    for table in section {
+                        module.tables.push(table?);
+                    }
+                }
+                Payload::MemorySection(section) => {
+                    for memory in section {
+                        module.memories.push(memory?);
+                    }
+                }
+                Payload::TagSection(section) => {
+                    for tag in section {
+                        module.tags.push(tag?);
+                    }
+                }
+                Payload::GlobalSection(section) => {
+                    for global in section {
+                        module.globals.push(global?);
+                    }
+                }
+                Payload::ExportSection(section) => {
+                    for export in section {
+                        module.exports.push(export?);
+                    }
+                }
+                Payload::StartSection { func, .. } => module.start = Some(func),
+                Payload::ElementSection(section) => {
+                    for element in section {
+                        module.elements.push(element?);
+                    }
+                }
+                Payload::DataSection(section) => {
+                    for data in section {
+                        module.data.push(data?);
+                    }
+                }
+                Payload::CodeSectionEntry(body) => module.bodies.push(body),
+                Payload::CustomSection(section) if section.name() == "name" => {
+                    let mut reader =
+                        wasmparser::NameSectionReader::new(section.data(), section.data_offset())?;
+                    while !reader.eof() {
+                        if let wasmparser::Name::Function(map) = reader.read()? {
+                            for naming in map {
+                                let naming = naming?;
+                                module
+                                    .func_names
+                                    .insert(naming.index, naming.name.to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    fn imported_func_count(&self) -> u32 {
+        self.imports
+            .iter()
+            .filter(|import| matches!(import.ty, TypeRef::Func(_)))
+            .count() as u32
+    }
+
+    /// Resolves a function by its index or its name in the name section
+    fn func_index(&self, func: &str) -> Result<u32> {
+        if let Ok(index) = func.parse::<u32>() {
+            return Ok(index);
+        }
+        self.func_names
+            .iter()
+            .find(|(_, name)| *name == func)
+            .map(|(index, _)| *index)
+            .ok_or_else(|| anyhow!("Function '{}' not found", func))
+    }
+
+    /// The identifier and the index comment of a function, e.g. `$main (;3;)`
+    fn func_label(&self, index: u32) -> String {
+        match self.func_names.get(&index).filter(|name| is_id(name)) {
+            Some(name) => format!("${} (;{};)", name, index),
+            None => format!("(;{};)", index),
+        }
+    }
+
+    fn print(&self, lines: &mut Vec<String>) -> Result<()> {
+        lines.push("(module".to_string());
+        for (index, ty) in self.types.iter().enumerate() {
+            lines.push(format!("  (type (;{};) (func{}))", index, signature(ty)));
+        }
+        let mut counts = HashMap::new();
+        for import in &self.imports {
+            let kind = match import.ty {
+                TypeRef::Func(_) => "func",
+                TypeRef::Table(_) => "table",
+                TypeRef::Memory(_) => "memory",
+                TypeRef::Global(_) => "global",
+                TypeRef::Tag(_) => "tag",
+            };
+            let count = counts.entry(kind).or_insert(0);
+            let desc = match import.ty {
+                TypeRef::Func(ty) => format!("(func {} (type {}))", self.func_label(*count), ty),
+                TypeRef::Table(ty) => format!("(table (;{};) {})", count, table_type(&ty)),
+                TypeRef::Memory(ty) => format!("(memory (;{};) {})", count, memory_type(&ty)),
+                TypeRef::Global(ty) => format!("(global (;{};) {})", count, global_type(&ty)),
+                TypeRef::Tag(ty) => format!("(tag (;{};) (type {}))", count, ty.func_type_idx),
+            };
+            *count += 1;
+            lines.push(format!(
+                "  (import {:?} {:?} {})",
+                import.module, import.name, desc
+            ));
+        }
+        let imported_funcs = self.imported_func_count();
+        for index in 0..self.bodies.len() {
+            self.print_func(imported_funcs + index as u32, "  ", lines)?;
+        }
+        let offset = |kind| counts.get(kind).copied().unwrap_or(0);
+        for (index, ty) in self.tables.iter().enumerate() {
+            let index = offset("table") + index as u32;
+            lines.push(format!("  (table (;{};) {})", index, table_type(ty)));
+        }
+        for (index, ty) in self.memories.iter().enumerate() {
+            let index = offset("memory") + index as u32;
+            lines.push(format!("  (memory (;{};) {})", index, memory_type(ty)));
+        }
+        for (index, ty) in self.tags.iter().enumerate() {
+            let index = offset("tag") + index as u32;
+            lines.push(format!("  (tag (;{};) (type {}))", index, ty.func_type_idx));
+        }
+        for (index, global) in self.globals.iter().enumerate() {
+            let index = offset("global") + index as u32;
+            lines.push(format!(
+                "  (global (;{};) {} {})",
+                index,
+                global_type(&global.ty),
+                const_expr(&global.init_expr)?
+            ));
+        }
+        for export in &self.exports {
+            let kind = match export.kind {
+                ExternalKind::Func => "func",
+                ExternalKind::Table => "table",
+                ExternalKind::Memory => "memory",
+                ExternalKind::Global => "global",
+                ExternalKind::Tag => "tag",
+            };
+            lines.push(format!(
+                "  (export {:?} ({} {}))",
+                export.name, kind, export.index
+            ));
+        }
+        if let Some(start) = self.start {
+            lines.push(format!("  (start {})", start));
+        }
+        for (index, element) in self.elements.iter().enumerate() {
+            lines.push(format!(
+                "  (elem (;{};) {})",
+                index,
+                element_segment(element)?
+            ));
+        }
+        for (index, data) in self.data.iter().enumerate() {
+            let mode = match &data.kind {
+                DataKind::Passive => String::new(),
+                DataKind::Active {
+                    memory_index: 0,
+                    offset_expr,
+                } => format!("{} ", const_expr(offset_expr)?),
+                DataKind::Active {
+                    memory_index,
+                    offset_expr,
+                } => format!("(memory {}) {} ", memory_index, const_expr(offset_expr)?),
+            };
+            lines.push(format!(
+                "  (data (;{};) {}\"{}\")",
+                index,
+                mode,
+                escape(data.data)
+            ));
+        }
+        lines.push(")".to_string());
+        Ok(())
+    }
+
+    fn print_func(&self, index: u32, indent: &str, lines: &mut Vec<String>) -> Result<()> {
+        let imported_funcs = self.imported_func_count();
+        let defined_index = index
+            .checked_sub(imported_funcs)
+            .ok_or_else(|| anyhow!("func[{}] is an imported function", index))?
+            as usize;
+        let (type_index, body) = match (
+            self.funcs.get(defined_index),
+            self.bodies.get(defined_index),
+        ) {
+            (Some(type_index), Some(body)) => (*type_index, body),
+            _ => return Err(anyhow!("func[{}] not found", index)),
+        };
+        let signature = self
+            .types
+            .get(type_index as usize)
+            .map(signature)
+            .unwrap_or_default();
+        lines.push(format!(
+            "{}(func {} (type {}){}",
+            indent,
+            self.func_label(index),
+            type_index,
+            signature
+        ));
+        let mut locals_reader = body.get_locals_reader()?;
+        let mut locals = Vec::new();
+        for _ in 0..locals_reader.get_count() {
+            let (count, ty) = locals_reader.read()?;
+            for _ in 0..count {
+                locals.push(val_type(ty));
+            }
+        }
+        if !locals.is_empty() {
+            lines.push(format!("{}  (local {})", indent, locals.join(" ")));
+        }
+        let mut depth: usize = 1;
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let kind = InstructionKind::try_from(reader.read()?)?;
+            let inst_indent = match kind {
+                InstructionKind::Else
+                | InstructionKind::Catch { .. }
+                | InstructionKind::CatchAll
+                | InstructionKind::End
+                | InstructionKind::Delegate { .. } => depth - 1,
+                _ => depth,
+            };
+            depth = match kind {
+                InstructionKind::Block { .. }
+                | InstructionKind::Loop { .. }
+                | InstructionKind::If { .. }
+                | InstructionKind::Try { .. } => depth + 1,
+                InstructionKind::End | InstructionKind::Delegate { .. } => depth - 1,
+                _ => depth,
+            };
+            // The end of the function body is the closing parenthesis
+            if depth == 0 {
+                break;
+            }
+            let mut line = format!("{}{}{}", indent, "  ".repeat(inst_indent), kind);
+            // The operands stay in the hex form to be valid text format
+            if let InstructionKind::V128Const { value } = &kind {
+                line += &format!(" ;; {}", format_v128(u128::from_le_bytes(*value.bytes())));
+            }
+            lines.push(line);
+        }
+        lines.push(format!("{})", indent));
+        Ok(())
+    }
+}
+
+/// Whether `name` can be printed as an identifier like `$name`
+fn is_id(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+}
+
+fn val_type(ty: ValType) -> &'static str {
+    match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::FuncRef => "funcref",
+        ValType::ExternRef => "externref",
+    }
+}
+
+fn val_types(types: &[ValType]) -> String {
+    types
+        .iter()
+        .map(|ty| val_type(*ty))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn signature(ty: &FuncType) -> String {
+    let mut output = String::new();
+    if !ty.params().is_empty() {
+        output += &format!(" (param {})", val_types(ty.params()));
+    }
+    if !ty.results().is_empty() {
+        output += &format!(" (result {})", val_types(ty.results()));
+    }
+    output
+}
+
+fn limits(initial: u64, maximum: Option<u64>) -> String {
+    match maximum {
+        Some(maximum) => format!("{} {}", initial, maximum),
+        None => initial.to_string(),
+    }
+}
+
+fn table_type(ty: &TableType) -> String {
+    format!(
+        "{} {}",
+        limits(ty.initial as u64, ty.maximum.map(u64::from)),
+        val_type(ty.element_type)
+    )
+}
+
+fn memory_type(ty: &MemoryType) -> String {
+    let mut output = limits(ty.initial, ty.maximum);
+    if ty.memory64 {
+        output = format!("i64 {}", output);
+    }
+    if ty.shared {
+        output += " shared";
+    }
+    output
+}
+
+fn global_type(ty: &GlobalType) -> String {
+    if ty.mutable {
+        format!("(mut {})", val_type(ty.content_type))
+    } else {
+        val_type(ty.content_type).to_string()
+    }
+}
+
+/// Folds each instruction of an init expression, e.g. `(i32.const 0)`
+fn const_expr(expr: &ConstExpr) -> Result<String> {
+    let mut reader: OperatorsReader = expr.get_operators_reader();
+    let mut insts = Vec::new();
+    while !reader.eof() {
+        match InstructionKind::try_from(reader.read()?)? {
+            InstructionKind::End => break,
+            kind => insts.push(format!("({})", kind)),
+        }
+    }
+    Ok(insts.join(" "))
+}
+
+fn element_segment(element: &Element) -> Result<String> {
+    let mut items = Vec::new();
+    let mut func_indices = true;
+    for item in element.items.get_items_reader()? {
+        match item? {
+            ElementItem::Func(index) => items.push(index.to_string()),
+            ElementItem::Expr(expr) => {
+                func_indices = false;
+                items.push(const_expr(&expr)?);
+            }
+        }
+    }
+    let items = if func_indices {
+        format!("func {}", items.join(" "))
+    } else {
+        format!("{} {}", val_type(element.ty), items.join(" "))
+    };
+    Ok(match &element.kind {
+        ElementKind::Passive => items,
+        ElementKind::Declared => format!("declare {}", items),
+        ElementKind::Active {
+            table_index: 0,
+            offset_expr,
+        } => format!("{} {}", const_expr(offset_expr)?, items),
+        ElementKind::Active {
+            table_index,
+            offset_expr,
+        } => format!(
+            "(table {}) {} {}",
+            table_index,
+            const_expr(offset_expr)?,
+            items
+        ),
+    })
+}
+
+/// Escapes bytes as the contents of a string literal
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            b'"' | b'\\' => format!("\\{}", *byte as char),
+            0x20..=0x7e => (*byte as char).to_string(),
+            _ => format!("\\{:02x}", byte),
+        })
+        .collect()
+}
+
+/// Prints the whole module in the text format
+pub fn disassemble_module(bytes: &[u8]) -> Result<Vec<String>> {
+    let module = Module::parse(bytes)?;
+    let mut lines = Vec::new();
+    module.print(&mut lines)?;
+    Ok(lines)
+}
+
+/// Prints a defined function, given by its index or name, in the text format
+pub fn disassemble_func(bytes: &[u8], func: &str) -> Result<Vec<String>> {
+    let module = Module::parse(bytes)?;
+    let mut lines = Vec::new();
+    module.print_func(module.func_index(func)?, "", &mut lines)?;
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_module_as_wat() {
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00,
+            0x00, // import section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x08, 0x0b, // global section
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01, // export section
+            0x0a, 0x12, 0x01, 0x10, 0x01, 0x01, 0x7e, 0x20, 0x00, 0x04, 0x7f, 0x41, 0x01, 0x05,
+            0x20, 0x00, 0x10, 0x00, 0x0b, 0x0b, // code section
+            0x0b, 0x09, 0x01, 0x00, 0x41, 0x10, 0x0b, 0x03, 0x68, 0x69, 0x00, // data section
+            0x00, 0x0b, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x01, 0x04, 0x01, 0x01, 0x01,
+            0x67, // name section
+        ];
+        let expected = vec![
+            "(module",
+            "  (type (;0;) (func (param i32) (result i32)))",
+            "  (import \"env\" \"f\" (func (;0;) (type 0)))",
+            "  (func $g (;1;) (type 0) (param i32) (result i32)",
+            "    (local i64)",
+            "    local.get 0",
+            "    if (result i32)",
+            "      i32.const 1",
+            "    else",
+            "      local.get 0",
+            "      call 0",
+            "    end",
+            "  )",
+            "  (memory (;0;) 1)",
+            "  (global (;0;) (mut i32) (i32.const 8))",
+            "  (export \"g\" (func 1))",
+            "  (data (;0;) (i32.const 16) \"hi\\00\")",
+            ")",
+        ];
+        assert_eq!(disassemble_module(&bytes).unwrap(), expected);
+        let func = expected[3..13]
+            .iter()
+            .map(|line| &line[2..])
+            .collect::<Vec<_>>();
+        assert_eq!(disassemble_func(&bytes, "g").unwrap(), func);
+        assert!(disassemble_func(&bytes, "0").is_err());
+    }
+}
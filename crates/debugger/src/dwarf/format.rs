@@ -1,94 +1,291 @@
-use std::ops::{AddAssign, SubAssign};
-
-use super::utils::*;
-
-use anyhow::{anyhow, Context, Result};
-use gimli::Unit;
-use num_bigint::{BigInt, BigUint, Sign};
-
-pub fn format_object<R: gimli::Reader>(
-    node: gimli::EntriesTreeNode<R>,
-    memory: &[u8],
-    _encoding: gimli::Encoding,
-    dwarf: &gimli::Dwarf<R>,
-    unit: &Unit<R>,
-) -> Result<String> {
-    match node.entry().tag() {
-        gimli::DW_TAG_base_type => {
-            let entry = node.entry();
-            let name = match entry.attr_value(gimli::DW_AT_name)? {
-                Some(attr) => clone_string_attribute(dwarf, unit, attr)?,
-                None => "<no type name>".to_string(),
-            };
-            let byte_size = entry
-                .attr_value(gimli::DW_AT_byte_size)?
-                .and_then(|attr| attr.udata_value())
-                .with_context(|| "Failed to get byte_size".to_string())?;
-            let encoding = entry
-                .attr_value(gimli::DW_AT_encoding)?
-                .and_then(|attr| match attr {
-                    gimli::AttributeValue::Encoding(encoding) => Some(encoding),
-                    _ => None,
-                })
-                .with_context(|| "Failed to get type encoding".to_string())?;
-            let mut bytes = Vec::new();
-            bytes.extend_from_slice(&memory[0..(byte_size as usize)]);
-
-            match encoding {
-                gimli::DW_ATE_signed => {
-                    let v = from_signed_bytes_le(&bytes);
-                    Ok(format!("{}({})", name, v))
-                }
-                gimli::DW_ATE_unsigned => {
-                    let value = BigUint::from_bytes_le(&bytes);
-                    Ok(format!("{}({})", name, value))
+use super::types::{ArrayTypeInfo, BaseTypeInfo, ModifierKind, StructTypeInfo, TypeInfo};
+use super::types::{EnumerationTypeInfo, MemberLocation};
+use std::collections::HashMap;
+
+/// Types nested deeper than this are printed as `...`, which also guards
+/// against cycles in the type graph
+const MAX_DEPTH: usize = 8;
+/// Arrays longer than this are truncated
+const MAX_ELEMENTS: u64 = 64;
+/// Strings pointed by `char*` longer than this are truncated
+const MAX_STRING_LEN: usize = 256;
+
+/// Formats values in the linear memory according to their DWARF types
+pub struct TypePrinter<'a, R: gimli::Reader> {
+    /// Types keyed by their offsets in `.debug_info`
+    types: &'a HashMap<R::Offset, TypeInfo<R>>,
+    memory: &'a [u8],
+    pointer_size: usize,
+}
+
+impl<'a, R: gimli::Reader> TypePrinter<'a, R> {
+    pub fn new(
+        types: &'a HashMap<R::Offset, TypeInfo<R>>,
+        memory: &'a [u8],
+        encoding: gimli::Encoding,
+    ) -> Self {
+        Self {
+            types,
+            memory,
+            pointer_size: encoding.address_size as usize,
+        }
+    }
+
+    /// Formats the value of the type at `ty` stored at `address`
+    pub fn format(&self, ty: R::Offset, address: u64) -> String {
+        self.format_value(ty, address, 0)
+    }
+
+    fn format_value(&self, ty: R::Offset, address: u64, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            return "...".to_string();
+        }
+        let info = match self.types.get(&ty) {
+            Some(info) => info,
+            None => return "<unknown type>".to_string(),
+        };
+        match info {
+            TypeInfo::BaseType(base) => self.format_base(base, address),
+            TypeInfo::TypeDef(typedef) => match typedef.ty {
+                Some(ty) => self.format_value(ty, address, depth + 1),
+                None => "<void>".to_string(),
+            },
+            TypeInfo::ModifiedType(modified) => match modified.kind {
+                ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => {
+                    self.format_pointer(modified.content_ty_offset, address, depth)
                 }
-                _ => unimplemented!(),
+                _ => match modified.content_ty_offset {
+                    Some(ty) => self.format_value(ty, address, depth + 1),
+                    None => "<void>".to_string(),
+                },
+            },
+            TypeInfo::StructType(ty) => self.format_struct(ty, address, depth),
+            TypeInfo::EnumerationType(ty) => self.format_enum(ty, address, depth),
+            TypeInfo::ArrayType(ArrayTypeInfo { ty, dimensions }) => match ty {
+                Some(ty) => self.format_array(*ty, dimensions, address, depth),
+                None => "<unknown type>".to_string(),
+            },
+        }
+    }
+
+    fn format_base(&self, base: &BaseTypeInfo, address: u64) -> String {
+        let size = base.byte_size as usize;
+        if size == 0 {
+            return "()".to_string();
+        }
+        let bytes = match self.read(address, size) {
+            Some(bytes) => bytes,
+            None => return out_of_bounds(address),
+        };
+        if size > 8 {
+            let hex = bytes.iter().rev().map(|b| format!("{:02x}", b));
+            return format!("0x{}", hex.collect::<String>());
+        }
+        let mut buf = [0; 8];
+        buf[..size].copy_from_slice(bytes);
+        let unsigned = u64::from_le_bytes(buf);
+        let shift = 64 - 8 * size as u32;
+        let signed = ((unsigned << shift) as i64) >> shift;
+        match base.encoding {
+            Some(gimli::DW_ATE_boolean) => (unsigned != 0).to_string(),
+            Some(gimli::DW_ATE_float) if size == 4 => f32::from_bits(unsigned as u32).to_string(),
+            Some(gimli::DW_ATE_float) if size == 8 => f64::from_bits(unsigned).to_string(),
+            Some(gimli::DW_ATE_signed_char) => format!("{} {:?}", signed, bytes[0] as char),
+            Some(gimli::DW_ATE_unsigned_char) => format!("{} {:?}", unsigned, bytes[0] as char),
+            Some(gimli::DW_ATE_UTF) => match std::char::from_u32(unsigned as u32) {
+                Some(c) => format!("{:?}", c),
+                None => format!("0x{:x}", unsigned),
+            },
+            Some(gimli::DW_ATE_signed) => signed.to_string(),
+            _ => unsigned.to_string(),
+        }
+    }
+
+    fn format_pointer(&self, pointee: Option<R::Offset>, address: u64, depth: usize) -> String {
+        let target = match self.read_uint(address, self.pointer_size) {
+            Some(target) => target,
+            None => return out_of_bounds(address),
+        };
+        let mut output = format!("0x{:08x}", target);
+        if let Some(pointee) = pointee {
+            if target != 0 && self.is_char(pointee, depth) {
+                let string = match self.read_c_string(target) {
+                    Some(string) => format!("{:?}", string),
+                    None => out_of_bounds(target),
+                };
+                output = format!("{} {}", output, string);
             }
         }
-        gimli::DW_TAG_class_type | gimli::DW_TAG_structure_type => {
-            let entry = node.entry();
-            let type_name = match entry.attr_value(gimli::DW_AT_name)? {
-                Some(attr) => clone_string_attribute(dwarf, unit, attr)?,
-                None => "<no type name>".to_string(),
-            };
-            let mut children = node.children();
-            let mut members = vec![];
-            while let Some(child) = children.next()? {
-                match child.entry().tag() {
-                    gimli::DW_TAG_member => {
-                        let name = match child.entry().attr_value(gimli::DW_AT_name)? {
-                            Some(attr) => clone_string_attribute(dwarf, unit, attr)?,
-                            None => "<no member name>".to_string(),
-                        };
-                        // let ty = match entry.attr_value(gimli::DW_AT_type)? {
-                        //     Some(gimli::AttributeValue::UnitRef(ref offset)) => offset.0,
-                        //     _ => return Err(anyhow!("Failed to get type offset")),
-                        // };
-                        members.push(name);
+        output
+    }
+
+    fn format_struct(&self, ty: &StructTypeInfo<R>, address: u64, depth: usize) -> String {
+        let name = match &ty.name {
+            Some(name) => format!("struct {}", name),
+            None => "struct".to_string(),
+        };
+        if ty.declaration {
+            return format!("{} <incomplete type>", name);
+        }
+        let members = ty
+            .members
+            .iter()
+            .map(|member| {
+                let value = match member.location {
+                    MemberLocation::ConstOffset(offset) => {
+                        self.format_value(member.ty, address + offset, depth + 1)
                     }
-                    _ => continue,
+                    MemberLocation::LocationDescription(_) => "<unsupported location>".to_string(),
+                };
+                let name = member.name.as_deref().unwrap_or("<anonymous>");
+                format!("{} = {}", name, value)
+            })
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            format!("{} {{}}", name)
+        } else {
+            format!("{} {{ {} }}", name, members.join(", "))
+        }
+    }
+
+    fn format_enum(&self, ty: &EnumerationTypeInfo<R>, address: u64, depth: usize) -> String {
+        let size = ty
+            .ty
+            .and_then(|ty| self.byte_size(ty, depth + 1))
+            .unwrap_or(4) as usize;
+        let value = match self.read_uint(address, size) {
+            Some(value) if size > 0 && size <= 8 => {
+                let shift = 64 - 8 * size as u32;
+                ((value << shift) as i64) >> shift
+            }
+            _ => return out_of_bounds(address),
+        };
+        let enumerator = ty.enumerators.iter().find(|e| e.value == Some(value));
+        match enumerator.and_then(|e| e.name.as_ref()) {
+            Some(name) => name.clone(),
+            None => value.to_string(),
+        }
+    }
+
+    fn format_array(
+        &self,
+        ty: R::Offset,
+        dimensions: &[u64],
+        address: u64,
+        depth: usize,
+    ) -> String {
+        let (count, inner) = match dimensions.split_first() {
+            Some((count, inner)) => (*count, inner),
+            None => return self.format_value(ty, address, depth + 1),
+        };
+        if inner.is_empty() && self.is_char(ty, depth) {
+            let len = (count as usize).min(MAX_STRING_LEN);
+            return match self.read(address, len) {
+                Some(bytes) => {
+                    let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+                    format!("{:?}", String::from_utf8_lossy(bytes))
                 }
+                None => out_of_bounds(address),
+            };
+        }
+        let stride = self.byte_size(ty, depth + 1).and_then(|size| {
+            inner
+                .iter()
+                .try_fold(size, |size, dim| size.checked_mul(*dim))
+        });
+        let stride = match stride {
+            Some(stride) => stride,
+            None => return "<unknown type>".to_string(),
+        };
+        let mut elements = (0..count.min(MAX_ELEMENTS))
+            .map(|index| self.format_array(ty, inner, address + index * stride, depth + 1))
+            .collect::<Vec<_>>();
+        if count > MAX_ELEMENTS {
+            elements.push("...".to_string());
+        }
+        format!("[{}]", elements.join(", "))
+    }
+
+    fn byte_size(&self, ty: R::Offset, depth: usize) -> Option<u64> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match self.types.get(&ty)? {
+            TypeInfo::BaseType(base) => Some(base.byte_size),
+            TypeInfo::StructType(ty) => Some(ty.byte_size),
+            TypeInfo::TypeDef(typedef) => self.byte_size(typedef.ty?, depth + 1),
+            TypeInfo::ModifiedType(modified) => match modified.kind {
+                ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => {
+                    Some(self.pointer_size as u64)
+                }
+                _ => self.byte_size(modified.content_ty_offset?, depth + 1),
+            },
+            TypeInfo::EnumerationType(ty) => match ty.ty {
+                Some(ty) => self.byte_size(ty, depth + 1),
+                None => Some(4),
+            },
+            TypeInfo::ArrayType(ArrayTypeInfo { ty, dimensions }) => dimensions
+                .iter()
+                .try_fold(self.byte_size((*ty)?, depth + 1)?, |size, dim| {
+                    size.checked_mul(*dim)
+                }),
+        }
+    }
+
+    /// Whether `ty` is a (possibly qualified) character type
+    fn is_char(&self, ty: R::Offset, depth: usize) -> bool {
+        if depth > MAX_DEPTH {
+            return false;
+        }
+        match self.types.get(&ty) {
+            Some(TypeInfo::BaseType(base)) => {
+                base.byte_size == 1
+                    && matches!(
+                        base.encoding,
+                        Some(gimli::DW_ATE_signed_char) | Some(gimli::DW_ATE_unsigned_char)
+                    )
             }
-            Ok(format!("{} {{\n{}\n}}", type_name, members.join(",\n")))
+            Some(TypeInfo::TypeDef(typedef)) => match typedef.ty {
+                Some(ty) => self.is_char(ty, depth + 1),
+                None => false,
+            },
+            Some(TypeInfo::ModifiedType(modified)) => match modified.kind {
+                ModifierKind::Const | ModifierKind::Volatile | ModifierKind::Atomic => {
+                    match modified.content_ty_offset {
+                        Some(ty) => self.is_char(ty, depth + 1),
+                        None => false,
+                    }
+                }
+                _ => false,
+            },
+            _ => false,
         }
-        _ => Err(anyhow!("unsupported DIE type")),
     }
-}
 
-fn from_signed_bytes_le(bytes: &[u8]) -> BigInt {
-    assert!(!bytes.is_empty());
-    let is_negate = (bytes.last().unwrap() >> 7) == 1;
-    let mut result = Vec::new();
-    for byte in bytes {
-        let flipped = byte ^ !0;
-        result.push(flipped);
+    fn read(&self, address: u64, len: usize) -> Option<&[u8]> {
+        let start = address as usize;
+        self.memory.get(start..start.checked_add(len)?)
     }
-    let mut v = BigInt::from_bytes_le(if is_negate { Sign::Minus } else { Sign::Plus }, &result);
-    if is_negate {
-        v.sub_assign(1);
-    } else {
-        v.add_assign(1);
+
+    fn read_uint(&self, address: u64, size: usize) -> Option<u64> {
+        if size > 8 {
+            return None;
+        }
+        let mut buf = [0; 8];
+        buf[..size].copy_from_slice(self.read(address, size)?);
+        Some(u64::from_le_bytes(buf))
+    }
+
+    fn read_c_string(&self, address: u64) -> Option<String> {
+        let bytes = self.memory.get(address as usize..)?;
+        let limit = bytes.len().min(MAX_STRING_LEN);
+        match bytes[..limit].iter().position(|b| *b == 0) {
+            Some(len) => Some(String::from_utf8_lossy(&bytes[..len]).into_owned()),
+            None => Some(format!("{}...", String::from_utf8_lossy(&bytes[..limit]))),
+        }
     }
-    v
+}
+
+fn out_of_bounds(address: u64) -> String {
+    format!("<out of bounds 0x{:08x}>", address)
 }
@@ -0,0 +1,85 @@
+//! A small evaluator of DWARF location expressions emitted for WebAssembly.
+//!
+//! gimli's evaluator doesn't know `DW_OP_WASM_location`, so this handles the
+//! subset of operations used by LLVM for wasm variables by itself.
+//! See also https://yurydelendik.github.io/webassembly-dwarf/#DWARF-expressions-and-location-descriptions
+use gimli::{Expression, Reader};
+use wasminspect_vm::WasmValue;
+
+/// Where the value of a variable is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolvedLocation {
+    /// An address in the linear memory
+    Memory(u64),
+    /// An index of a wasm local
+    Local(u64),
+}
+
+/// Values of the frame a location expression is evaluated in
+pub struct EvaluationFrame<'a> {
+    /// The value of `DW_AT_frame_base` of the enclosing function
+    pub frame_base: Option<u64>,
+    pub locals: &'a [WasmValue],
+}
+
+const DW_OP_WASM_LOCATION: u8 = 0xed;
+
+/// Evaluates `expr` to a memory address or a wasm local.
+///
+/// Returns `None` if the expression uses an unsupported operation or refers to
+/// a value which is not available in `frame` or `memory`.
+pub fn evaluate_location<R: Reader>(
+    expr: Expression<R>,
+    encoding: gimli::Encoding,
+    frame: &EvaluationFrame,
+    memory: &[u8],
+) -> Option<ResolvedLocation> {
+    let mut bytes = expr.0;
+    let mut stack: Vec<u64> = vec![];
+    while !bytes.is_empty() {
+        let opcode = bytes.read_u8().ok()?;
+        match gimli::DwOp(opcode) {
+            gimli::DW_OP_addr => {
+                stack.push(bytes.read_address(encoding.address_size).ok()?);
+            }
+            gimli::DW_OP_fbreg => {
+                let offset = bytes.read_sleb128().ok()?;
+                stack.push(frame.frame_base?.wrapping_add(offset as u64));
+            }
+            gimli::DW_OP_plus_uconst => {
+                let value = bytes.read_uleb128().ok()?;
+                let top = stack.pop()?;
+                stack.push(top.wrapping_add(value));
+            }
+            gimli::DW_OP_deref => {
+                let address = stack.pop()? as usize;
+                let size = encoding.address_size as usize;
+                let bytes = memory.get(address..address.checked_add(size)?)?;
+                let mut buf = [0; 8];
+                buf[..size].copy_from_slice(bytes);
+                stack.push(u64::from_le_bytes(buf));
+            }
+            gimli::DW_OP_stack_value => return None,
+            _ if opcode == DW_OP_WASM_LOCATION => {
+                let kind = bytes.read_u8().ok()?;
+                // Only locals are readable, globals and operand stack values aren't passed in
+                if kind != 0x00 {
+                    return None;
+                }
+                let index = bytes.read_uleb128().ok()?;
+                if bytes.is_empty() {
+                    return Some(ResolvedLocation::Local(index));
+                }
+                // Followed by other operations, the local holds an address
+                let value = frame.locals.get(index as usize)?;
+                let value = match value.as_i32() {
+                    Some(value) => value as u32 as u64,
+                    None => value.as_i64()? as u64,
+                };
+                stack.push(value);
+            }
+            _ => return None,
+        }
+    }
+    stack.pop().map(ResolvedLocation::Memory)
+}
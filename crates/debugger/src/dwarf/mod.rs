@@ -5,20 +5,25 @@ use gimli::{
     DebugStrOffsets, DebugTypes, DebuggingInformationEntry, EndianSlice, LineRow, LittleEndian,
     LocationLists, RangeLists, Unit, UnitOffset,
 };
-use log::trace;
+use log::{trace, warn};
 use std::collections::{BTreeMap, HashMap};
 
 mod format;
+mod location;
+mod names;
+#[cfg(test)]
+mod test_corpus;
 mod types;
 mod utils;
 
+use location::{evaluate_location, EvaluationFrame, ResolvedLocation};
+use names::DwarfAcceleratedNames;
 use utils::*;
 
 type Reader<'input> = gimli::EndianSlice<'input, LittleEndian>;
 pub type Dwarf<'input> = gimli::Dwarf<Reader<'input>>;
 
-pub fn parse_dwarf(module: &[u8]) -> Result<Dwarf> {
-    const EMPTY_SECTION: &[u8] = &[];
+fn custom_sections(module: &[u8]) -> Result<HashMap<&str, &[u8]>> {
     let parser = wasmparser::Parser::new(0);
     let mut sections = HashMap::new();
     for payload in parser.parse_all(module) {
@@ -29,6 +34,12 @@ pub fn parse_dwarf(module: &[u8]) -> Result<Dwarf> {
             _ => continue,
         }
     }
+    Ok(sections)
+}
+
+pub fn parse_dwarf(module: &[u8]) -> Result<Dwarf<'_>> {
+    const EMPTY_SECTION: &[u8] = &[];
+    let sections = custom_sections(module)?;
     let try_get = |key: &str| sections.get(key).with_context(|| format!("no {}", key));
     let endian = LittleEndian;
     let debug_str = DebugStr::new(try_get(".debug_str")?, endian);
@@ -74,12 +85,15 @@ pub fn parse_dwarf(module: &[u8]) -> Result<Dwarf> {
 pub struct DwarfDebugInfo {
     pub sourcemap: DwarfSourceMap,
     pub subroutine: DwarfSubroutineMap,
+    pub data_objects: Vec<LabeledRegion>,
 }
 pub fn transform_dwarf(buffer: &[u8]) -> Result<DwarfDebugInfo> {
     let dwarf = parse_dwarf(buffer)?;
     let mut headers = dwarf.units();
     let mut sourcemaps = Vec::new();
+    let mut inlined_call_sites = Vec::new();
     let mut subroutines = Vec::new();
+    let mut data_objects = Vec::new();
 
     while let Some(header) = headers.next()? {
         let unit = dwarf.unit(header)?;
@@ -88,23 +102,87 @@ pub fn transform_dwarf(buffer: &[u8]) -> Result<DwarfDebugInfo> {
             Some((_, entry)) => entry,
             None => continue,
         };
-        sourcemaps.push(transform_debug_line(
-            &unit,
-            root,
-            &dwarf,
-            &dwarf.debug_line,
-        )?);
+        let sourcemap = transform_debug_line(&unit, root, &dwarf, &dwarf.debug_line)?;
+        let mut call_sites = transform_inlined_call_sites(&dwarf, &unit, &sourcemap)?;
+        inlined_call_sites.append(&mut call_sites);
+        sourcemaps.push(sourcemap);
         subroutines.append(&mut transform_subprogram(&dwarf, &unit, header.offset())?);
+        data_objects.append(&mut transform_data_objects(&dwarf, &unit)?);
     }
+    let accelerated_names = match custom_sections(buffer)?.get(".debug_names") {
+        Some(section) => {
+            let debug_names = EndianSlice::new(section, LittleEndian);
+            match DwarfAcceleratedNames::parse(debug_names, &dwarf.debug_str) {
+                Ok(names) => Some(names),
+                Err(err) => {
+                    warn!("Failed to parse .debug_names: {}", err);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
     Ok(DwarfDebugInfo {
-        sourcemap: DwarfSourceMap::new(sourcemaps),
-        subroutine: DwarfSubroutineMap {
+        sourcemap: DwarfSourceMap::new(sourcemaps, inlined_call_sites.clone()),
+        subroutine: DwarfSubroutineMap::new(
             subroutines,
-            buffer: buffer.to_vec(),
-        },
+            inlined_call_sites,
+            accelerated_names,
+            buffer.to_vec(),
+        ),
+        data_objects,
     })
 }
 
+/// Collects global variables placed at a fixed address in the linear memory
+fn transform_data_objects<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R>,
+) -> Result<Vec<LabeledRegion>> {
+    let mut tree = unit.entries_tree(None)?;
+    let root = tree.root()?;
+    let mut children = root.children();
+    let mut regions = vec![];
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        if entry.tag() != gimli::DW_TAG_variable {
+            continue;
+        }
+        let expr = match entry.attr_value(gimli::DW_AT_location)? {
+            Some(AttributeValue::Exprloc(expr)) => expr,
+            _ => continue,
+        };
+        let start = match expr.operations(unit.encoding()).next()? {
+            Some(gimli::Operation::Address { address }) => address as usize,
+            _ => continue,
+        };
+        let label = match entry.attr_value(gimli::DW_AT_name)? {
+            Some(attr) => clone_string_attribute(dwarf, unit, attr)?,
+            None => continue,
+        };
+        let len = data_object_size(unit, entry.attr_value(gimli::DW_AT_type)?)?.unwrap_or(1);
+        regions.push(LabeledRegion { start, len, label });
+    }
+    Ok(regions)
+}
+
+fn data_object_size<R: gimli::Reader>(
+    unit: &Unit<R>,
+    mut ty: Option<AttributeValue<R>>,
+) -> Result<Option<usize>> {
+    while let Some(AttributeValue::UnitRef(offset)) = ty {
+        let entry = unit.entry(offset)?;
+        let byte_size = entry
+            .attr_value(gimli::DW_AT_byte_size)?
+            .and_then(|attr| attr.udata_value());
+        if let Some(byte_size) = byte_size {
+            return Ok(Some(byte_size as usize));
+        }
+        ty = entry.attr_value(gimli::DW_AT_type)?;
+    }
+    Ok(None)
+}
+
 #[derive(Clone)]
 pub struct SymbolVariable<R>
 where
@@ -113,6 +191,9 @@ where
     name: Option<String>,
     content: VariableContent<R>,
     ty_offset: Option<R::Offset>,
+    decl_location: Option<sourcemap::LineInfo>,
+    /// Whether the variable is a `DW_TAG_formal_parameter`
+    is_param: bool,
 }
 
 #[derive(Clone)]
@@ -158,11 +239,34 @@ pub enum WasmLoc {
 
 // See also https://yurydelendik.github.io/webassembly-dwarf/#DWARF-expressions-and-location-descriptions
 fn read_wasm_location<R: gimli::Reader>(attr_value: AttributeValue<R>) -> Result<WasmLoc> {
-    let mut bytes_reader = match attr_value {
-        AttributeValue::Exprloc(ref expr) => expr.0.clone(),
-        _ => return Err(anyhow!("unexpected attribute kind: {:?}", attr_value)),
+    match attr_value {
+        AttributeValue::Exprloc(expr) => read_wasm_expression(expr),
+        _ => Err(anyhow!("unexpected attribute kind: {:?}", attr_value)),
+    }
+}
+
+/// Evaluates a location which may be a location list at `code_offset`.
+/// Returns `None` if no entry of the list covers the offset.
+fn evaluate_wasm_location<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R>,
+    attr_value: AttributeValue<R>,
+    code_offset: u64,
+) -> Result<Option<WasmLoc>> {
+    let mut locations = match dwarf.attr_locations(unit, attr_value.clone())? {
+        Some(locations) => locations,
+        None => return read_wasm_location(attr_value).map(Some),
     };
+    while let Some(location) = locations.next()? {
+        if (location.range.begin..location.range.end).contains(&code_offset) {
+            return read_wasm_expression(location.data).map(Some);
+        }
+    }
+    Ok(None)
+}
 
+fn read_wasm_expression<R: gimli::Reader>(expr: gimli::Expression<R>) -> Result<WasmLoc> {
+    let mut bytes_reader = expr.0;
     if bytes_reader.is_empty() {
         return Err(anyhow!("byte sequence should not be empty"));
     }
@@ -301,9 +405,49 @@ fn transform_variable<R: gimli::Reader>(
         name,
         content,
         ty_offset: ty,
+        decl_location: decl_location(dwarf, unit, entry).unwrap_or(None),
+        is_param: entry.tag() == gimli::DW_TAG_formal_parameter,
     })
 }
 
+fn decl_location<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R, R::Offset>,
+    entry: &DebuggingInformationEntry<R>,
+) -> Result<Option<sourcemap::LineInfo>> {
+    let file_index = match entry.attr_value(gimli::DW_AT_decl_file)? {
+        Some(AttributeValue::FileIndex(index)) => index,
+        _ => return Ok(None),
+    };
+    let line = entry
+        .attr_value(gimli::DW_AT_decl_line)?
+        .and_then(|attr| attr.udata_value());
+    let header = match unit.line_program {
+        Some(ref program) => program.header(),
+        None => return Ok(None),
+    };
+    let file = match header.file(file_index) {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let mut path = std::path::PathBuf::new();
+    if let Some(dir) = file.directory(header) {
+        path.push(clone_string_attribute(dwarf, unit, dir)?);
+    }
+    path.push(clone_string_attribute(dwarf, unit, file.path_name())?);
+    if !path.is_absolute() {
+        if let Some(comp_dir) = unit.comp_dir.clone() {
+            let comp_dir = String::from_utf8(comp_dir.to_slice()?.to_vec())?;
+            path = Path::new(&comp_dir).join(path);
+        }
+    }
+    Ok(Some(sourcemap::LineInfo {
+        filepath: path.to_string_lossy().to_string(),
+        line,
+        column: sourcemap::ColumnType::LeftEdge,
+    }))
+}
+
 #[derive(Debug)]
 pub enum FrameBase {
     WasmFrameBase(u64),
@@ -399,6 +543,83 @@ pub fn transform_debug_line<R: gimli::Reader>(
     })
 }
 
+/// The code range of an inlined subroutine and the location it is inlined at
+#[derive(Clone)]
+pub struct InlinedCallSite {
+    pub pc: std::ops::Range<u64>,
+    pub name: Option<String>,
+    pub call_site: sourcemap::LineInfo,
+}
+
+/// Collects code ranges of inlined subroutines with the location of their call sites
+fn transform_inlined_call_sites<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R>,
+    sourcemap: &DwarfUnitSourceMap,
+) -> Result<Vec<InlinedCallSite>> {
+    let mut call_sites = vec![];
+    let mut entries = unit.entries();
+    while let Some((_, entry)) = entries.next_dfs()? {
+        if entry.tag() != gimli::DW_TAG_inlined_subroutine {
+            continue;
+        }
+        let call_file = match entry.attr_value(gimli::DW_AT_call_file)? {
+            Some(AttributeValue::FileIndex(index)) => Some(index),
+            Some(attr) => attr.udata_value(),
+            None => None,
+        };
+        let filepath = match call_file.and_then(|file| sourcemap.file_path(file)) {
+            Some(filepath) => filepath,
+            None => continue,
+        };
+        let line = entry
+            .attr_value(gimli::DW_AT_call_line)?
+            .and_then(|attr| attr.udata_value());
+        let column = match entry
+            .attr_value(gimli::DW_AT_call_column)?
+            .and_then(|attr| attr.udata_value())
+        {
+            Some(column) if column > 0 => sourcemap::ColumnType::Column(column),
+            _ => sourcemap::ColumnType::LeftEdge,
+        };
+        let name = inlined_subroutine_name(dwarf, unit, entry)?;
+        let mut ranges = dwarf.die_ranges(unit, entry)?;
+        while let Some(range) = ranges.next()? {
+            call_sites.push(InlinedCallSite {
+                pc: range.begin..range.end,
+                name: name.clone(),
+                call_site: sourcemap::LineInfo {
+                    filepath: filepath.clone(),
+                    line,
+                    column,
+                },
+            });
+        }
+    }
+    Ok(call_sites)
+}
+
+/// Resolves the name of an inlined subroutine through its abstract origin
+fn inlined_subroutine_name<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R>,
+    entry: &gimli::DebuggingInformationEntry<R>,
+) -> Result<Option<String>> {
+    if let Some(attr) = entry.attr_value(gimli::DW_AT_name)? {
+        return Ok(Some(clone_string_attribute(dwarf, unit, attr)?));
+    }
+    let origin = match entry.attr_value(gimli::DW_AT_abstract_origin)? {
+        Some(AttributeValue::UnitRef(offset)) => unit.entry(offset)?,
+        _ => return Ok(None),
+    };
+    for name_attr in &[gimli::DW_AT_linkage_name, gimli::DW_AT_name] {
+        if let Some(attr) = origin.attr_value(*name_attr)? {
+            return Ok(Some(clone_string_attribute(dwarf, unit, attr)?));
+        }
+    }
+    Ok(None)
+}
+
 pub struct DwarfUnitSourceMap {
     address_sorted_rows: Vec<(u64, LineRow)>,
     paths: Vec<std::path::PathBuf>,
@@ -407,6 +628,11 @@ pub struct DwarfUnitSourceMap {
 
 use crate::commands::sourcemap;
 impl DwarfUnitSourceMap {
+    fn file_path(&self, file_index: u64) -> Option<String> {
+        let index = (file_index as usize).checked_sub(self.sequence_base_index)?;
+        Some(self.paths.get(index)?.to_str()?.to_string())
+    }
+
     fn transform_lineinfo(&self, row: &LineRow) -> sourcemap::LineInfo {
         let filepath = self.paths[row.file_index() as usize - self.sequence_base_index].clone();
         sourcemap::LineInfo {
@@ -423,11 +649,12 @@ impl DwarfUnitSourceMap {
 use std::cell::RefCell;
 pub struct DwarfSourceMap {
     address_sorted_rows: Vec<(u64, sourcemap::LineInfo)>,
+    inlined_call_sites: Vec<InlinedCallSite>,
     directory_map: RefCell<HashMap<String, String>>,
 }
 
 impl DwarfSourceMap {
-    fn new(units: Vec<DwarfUnitSourceMap>) -> Self {
+    fn new(units: Vec<DwarfUnitSourceMap>, inlined_call_sites: Vec<InlinedCallSite>) -> Self {
         let mut rows = BTreeMap::new();
         for unit in &units {
             for (addr, row) in &unit.address_sorted_rows {
@@ -437,9 +664,17 @@ impl DwarfSourceMap {
         }
         Self {
             address_sorted_rows: rows.into_iter().collect(),
+            inlined_call_sites,
             directory_map: RefCell::new(HashMap::new()),
         }
     }
+
+    fn map_directory(&self, mut line_info: sourcemap::LineInfo) -> sourcemap::LineInfo {
+        for (from, to) in self.directory_map.borrow().iter() {
+            line_info.filepath = line_info.filepath.replace(from, to);
+        }
+        line_info
+    }
 }
 
 impl sourcemap::SourceMap for DwarfSourceMap {
@@ -447,7 +682,7 @@ impl sourcemap::SourceMap for DwarfSourceMap {
         self.directory_map.borrow_mut().insert(from, to);
     }
     fn find_line_info(&self, offset: usize) -> Option<sourcemap::LineInfo> {
-        let mut line_info = match self
+        let line_info = match self
             .address_sorted_rows
             .binary_search_by_key(&(offset as u64), |i| i.0)
         {
@@ -460,20 +695,86 @@ impl sourcemap::SourceMap for DwarfSourceMap {
                 }
             }
         };
-        for (from, to) in self.directory_map.borrow().iter() {
-            line_info.filepath = line_info.filepath.replace(from, to);
+        Some(self.map_directory(line_info))
+    }
+    fn find_statement_line(&self, offset: usize) -> Option<sourcemap::LineInfo> {
+        // The outermost inlined subroutine covers the widest range
+        let outermost = self
+            .inlined_call_sites
+            .iter()
+            .filter(|inlined| inlined.pc.contains(&(offset as u64)))
+            .max_by_key(|inlined| inlined.pc.end - inlined.pc.start);
+        match outermost {
+            Some(inlined) => Some(self.map_directory(inlined.call_site.clone())),
+            None => self.find_line_info(offset),
         }
-        Some(line_info)
+    }
+    fn find_line_address(&self, file: &str, line: u64) -> Option<usize> {
+        self.address_sorted_rows
+            .iter()
+            .find(|(_, line_info)| {
+                let line_info = self.map_directory(line_info.clone());
+                line_info.line == Some(line)
+                    && std::path::Path::new(&line_info.filepath).ends_with(file)
+            })
+            .map(|(addr, _)| *addr as usize)
     }
 }
 
+use crate::commands::debugger::LabeledRegion;
 use crate::commands::subroutine;
 
 pub struct DwarfSubroutineMap {
     pub subroutines: Vec<Subroutine<usize>>,
+    /// Indices of `subroutines` keyed by their DIE offsets
+    entry_index: HashMap<UnitOffset, Vec<usize>>,
+    inlined_call_sites: Vec<InlinedCallSite>,
+    accelerated_names: Option<DwarfAcceleratedNames>,
     buffer: Vec<u8>,
 }
 
+impl DwarfSubroutineMap {
+    fn new(
+        subroutines: Vec<Subroutine<usize>>,
+        inlined_call_sites: Vec<InlinedCallSite>,
+        accelerated_names: Option<DwarfAcceleratedNames>,
+        buffer: Vec<u8>,
+    ) -> Self {
+        let mut entry_index = HashMap::<_, Vec<_>>::new();
+        for (index, subroutine) in subroutines.iter().enumerate() {
+            entry_index
+                .entry(subroutine.entry_offset)
+                .or_default()
+                .push(index);
+        }
+        Self {
+            subroutines,
+            entry_index,
+            inlined_call_sites,
+            accelerated_names,
+            buffer,
+        }
+    }
+
+    /// Finds a subroutine by name, using `.debug_names` if present
+    fn find_subroutine_by_name(&self, name: &str) -> Option<&Subroutine<usize>> {
+        let is_named = |s: &&Subroutine<usize>| s.name.as_deref() == Some(name);
+        match &self.accelerated_names {
+            Some(names) => {
+                let offset = names.lookup_function(name)?;
+                let candidates = self.entry_index.get(&offset)?;
+                // DIE offsets are relative to each unit, so they can collide between units
+                candidates
+                    .iter()
+                    .map(|index| &self.subroutines[*index])
+                    .find(is_named)
+                    .or_else(|| candidates.first().map(|index| &self.subroutines[*index]))
+            }
+            None => self.subroutines.iter().find(is_named),
+        }
+    }
+}
+
 fn header_from_offset<R: gimli::Reader>(
     dwarf: &gimli::Dwarf<R>,
     offset: DebugInfoOffset<R::Offset>,
@@ -531,6 +832,57 @@ fn unit_type_name<R: gimli::Reader>(
 }
 
 impl subroutine::SubroutineMap for DwarfSubroutineMap {
+    fn inlined_frames(&self, code_offset: usize) -> Vec<subroutine::InlinedFrame> {
+        let offset = code_offset as u64;
+        let mut call_sites = self
+            .inlined_call_sites
+            .iter()
+            .filter(|inlined| inlined.pc.contains(&offset))
+            .collect::<Vec<_>>();
+        // Enclosing subroutines cover wider ranges than the ones inlined into them
+        call_sites.sort_by_key(|inlined| std::cmp::Reverse(inlined.pc.end - inlined.pc.start));
+        call_sites
+            .into_iter()
+            .map(|inlined| subroutine::InlinedFrame {
+                name: inlined.name.clone(),
+                call_site: inlined.call_site.clone(),
+            })
+            .collect()
+    }
+
+    fn function_name(&self, code_offset: usize) -> Option<String> {
+        let offset = code_offset as u64;
+        self.subroutines
+            .iter()
+            .find(|s| s.pc.contains(&offset))?
+            .name
+            .clone()
+    }
+
+    fn lookup_func_by_name(&self, name: &str) -> Option<std::ops::Range<u64>> {
+        self.find_subroutine_by_name(name).map(|s| s.pc.clone())
+    }
+
+    fn return_type_name(&self, name: &str) -> Result<Option<String>> {
+        let subroutine = match self.find_subroutine_by_name(name) {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let dwarf = parse_dwarf(&self.buffer)?;
+        let header = match header_from_offset(&dwarf, subroutine.unit_offset)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let unit = dwarf.unit(header)?;
+        let entry = unit.entry(subroutine.entry_offset)?;
+        match entry.attr_value(gimli::DW_AT_type)? {
+            Some(AttributeValue::UnitRef(offset)) => {
+                unit_type_name(&dwarf, &unit, Some(offset.0)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<subroutine::Variable>> {
         let offset = &(code_offset as u64);
         let subroutine = match self.subroutines.iter().find(|s| s.pc.contains(offset)) {
@@ -566,6 +918,88 @@ impl subroutine::SubroutineMap for DwarfSubroutineMap {
             .collect())
     }
 
+    fn scoped_variables(&self, code_offset: usize) -> Result<Vec<subroutine::ScopedVariable>> {
+        let offset = code_offset as u64;
+        // The function and lexical blocks enclosing the offset, from the outermost one
+        let mut scopes = self
+            .subroutines
+            .iter()
+            .filter(|s| s.pc.contains(&offset))
+            .collect::<Vec<_>>();
+        scopes.sort_by_key(|s| std::cmp::Reverse(s.pc.end - s.pc.start));
+        let dwarf = parse_dwarf(&self.buffer)?;
+        let mut scoped_variables = vec![];
+        for scope in scopes {
+            let header = match header_from_offset(&dwarf, scope.unit_offset)? {
+                Some(header) => header,
+                None => continue,
+            };
+            let unit = dwarf.unit(header)?;
+            for var in subroutine_variables(&dwarf, &unit, scope)? {
+                let name = match var.name {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let location = match var.content {
+                    VariableContent::Location(location) => {
+                        evaluate_wasm_location(&dwarf, &unit, location, offset).unwrap_or(None)
+                    }
+                    _ => None,
+                };
+                // Variables of inner blocks shadow the ones of outer scopes
+                scoped_variables.retain(|v: &subroutine::ScopedVariable| v.name != name);
+                scoped_variables.push(subroutine::ScopedVariable {
+                    name,
+                    type_name: unit_type_name(&dwarf, &unit, var.ty_offset)
+                        .unwrap_or_else(|_| "<unknown>".to_string()),
+                    location,
+                    is_param: var.is_param,
+                });
+            }
+        }
+        Ok(scoped_variables)
+    }
+
+    fn local_variables(
+        &self,
+        code_offset: usize,
+    ) -> Result<HashMap<usize, subroutine::LocalVariable>> {
+        let offset = &(code_offset as u64);
+        let subroutine = match self.subroutines.iter().find(|s| s.pc.contains(offset)) {
+            Some(s) => s,
+            None => return Err(anyhow!("failed to determine subroutine")),
+        };
+        let dwarf = parse_dwarf(&self.buffer)?;
+        let header = match header_from_offset(&dwarf, subroutine.unit_offset)? {
+            Some(header) => header,
+            None => {
+                return Ok(HashMap::new());
+            }
+        };
+
+        let unit = dwarf.unit(header)?;
+        let variables = subroutine_variables(&dwarf, &unit, subroutine)?;
+
+        let mut locals = HashMap::new();
+        for var in variables {
+            let name = match var.name {
+                Some(name) => name,
+                None => continue,
+            };
+            if let VariableContent::Location(location) = var.content {
+                let location = evaluate_wasm_location(&dwarf, &unit, location, *offset);
+                if let Ok(Some(WasmLoc::Local(index))) = location {
+                    let local = subroutine::LocalVariable {
+                        name,
+                        source_location: var.decl_location,
+                    };
+                    locals.insert(index as usize, local);
+                }
+            }
+        }
+        Ok(locals)
+    }
+
     fn get_frame_base(&self, code_offset: usize) -> Result<Option<WasmLoc>> {
         let offset = &(code_offset as u64);
         let subroutine = match self.subroutines.iter().find(|s| s.pc.contains(offset)) {
@@ -609,11 +1043,9 @@ impl subroutine::SubroutineMap for DwarfSubroutineMap {
                 return Err(anyhow!("'{}' is not valid variable name", name));
             }
         };
-        let piece = match var.content {
+        let expr = match var.content {
             VariableContent::Location(location) => match location {
-                AttributeValue::Exprloc(expr) => {
-                    evaluate_variable_location(subroutine.encoding, frame_base, expr)?
-                }
+                AttributeValue::Exprloc(expr) => expr,
                 AttributeValue::LocationListsRef(_listsref) => unimplemented!("listsref"),
                 _ => panic!(),
             },
@@ -623,36 +1055,325 @@ impl subroutine::SubroutineMap for DwarfSubroutineMap {
             }
         };
 
-        let piece = match piece.get(0) {
-            Some(p) => p,
-            None => {
-                println!("failed to get piece of variable");
-                return Ok(());
+        let address = match frame_base {
+            FrameBase::WasmFrameBase(base) => {
+                let frame = EvaluationFrame {
+                    frame_base: Some(base),
+                    locals: &[],
+                };
+                match evaluate_location(expr, subroutine.encoding, &frame, memory) {
+                    Some(ResolvedLocation::Memory(address)) => address,
+                    _ => return Err(anyhow!("'{}' is not located in the memory", name)),
+                }
+            }
+            FrameBase::Rbp(_) => {
+                let piece = evaluate_variable_location(subroutine.encoding, frame_base, expr)?;
+                match piece.first().map(|p| &p.location) {
+                    Some(gimli::Location::Address { address }) => *address,
+                    Some(_) => unimplemented!(),
+                    None => {
+                        println!("failed to get piece of variable");
+                        return Ok(());
+                    }
+                }
             }
         };
 
         if let Some(offset) = var.ty_offset {
-            use format::format_object;
-            match piece.location {
-                gimli::Location::Address { address } => {
-                    let mut tree = unit.entries_tree(Some(UnitOffset(offset)))?;
-                    let root = tree.root()?;
-                    println!(
-                        "{}",
-                        format_object(
-                            root,
-                            &memory[(address as usize)..],
-                            subroutine.encoding,
-                            &dwarf,
-                            &unit
-                        )?
-                    );
-                }
-                _ => unimplemented!(),
-            }
+            let mut types = HashMap::new();
+            types::get_types(&dwarf, &unit, &header, &mut types)?;
+            let printer = format::TypePrinter::new(&types, memory, subroutine.encoding);
+            println!("{}", printer.format(offset, address));
         } else {
             println!("no explicit type");
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::test_corpus::{DwarfCorpusBuilder, DwarfType};
+    use super::*;
+    use crate::commands::subroutine::SubroutineMap;
+
+    fn corpus() -> Vec<u8> {
+        DwarfCorpusBuilder::new()
+            .add_compile_unit("main.rs")
+            .add_function("foo", 0, 100)
+            .add_local("x", DwarfType::I32, 0x1000)
+            .add_local("y", DwarfType::I64, 0x1008)
+            .add_local("z", DwarfType::U32, 0x1004)
+            .add_compile_unit("lib.rs")
+            .add_function("bar", 100, 200)
+            .add_local("w", DwarfType::U64, 8)
+            .build()
+    }
+
+    #[test]
+    fn subroutines_from_corpus() {
+        let debug_info = transform_dwarf(&corpus()).unwrap();
+        let subroutine = debug_info.subroutine;
+        assert_eq!(subroutine.lookup_func_by_name("foo"), Some(0..100));
+        assert_eq!(subroutine.lookup_func_by_name("bar"), Some(100..200));
+        assert_eq!(subroutine.lookup_func_by_name("baz"), None);
+
+        let variables = subroutine.variable_name_list(50).unwrap();
+        let variables = variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.type_name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(variables, vec![("x", "i32"), ("y", "i64"), ("z", "u32")]);
+    }
+
+    #[test]
+    fn statement_line_of_inlined_code() {
+        use crate::commands::sourcemap::SourceMap;
+        let buffer = DwarfCorpusBuilder::new()
+            .add_compile_unit("main.rs")
+            .add_function("foo", 0, 100)
+            .add_inlined_call("bar", 10, 40, 7)
+            .add_inlined_call("baz", 15, 20, 3)
+            .build();
+        let debug_info = transform_dwarf(&buffer).unwrap();
+        let sourcemap = debug_info.sourcemap;
+        assert_eq!(sourcemap.find_line_info(15).unwrap().line, Some(1));
+        assert_eq!(sourcemap.find_statement_line(15).unwrap().line, Some(7));
+        assert_eq!(sourcemap.find_statement_line(50).unwrap().line, Some(1));
+        assert_eq!(sourcemap.find_line_address("main.rs", 1), Some(0));
+        assert_eq!(sourcemap.find_line_address("main.rs", 2), None);
+
+        use crate::commands::subroutine::SubroutineMap;
+        let frames = debug_info.subroutine.inlined_frames(15);
+        let names = frames.iter().map(|f| f.name.as_deref()).collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("bar"), Some("baz")]);
+        assert_eq!(frames[1].call_site.line, Some(3));
+        assert!(debug_info.subroutine.inlined_frames(50).is_empty());
+    }
+
+    #[test]
+    fn local_names_from_location_list() {
+        use crate::commands::subroutine::SubroutineMap;
+        let buffer = DwarfCorpusBuilder::new()
+            .add_compile_unit("main.rs")
+            .add_function("foo", 0, 100)
+            .add_wasm_local("x", DwarfType::I32, &[(0..50, 2), (50..100, 0)])
+            .add_wasm_local("y", DwarfType::I32, &[(20..40, 1)])
+            .build();
+        let subroutine = transform_dwarf(&buffer).unwrap().subroutine;
+        let names = |offset| subroutine.local_names(offset);
+        assert_eq!(names(10), vec![None, None, Some("x".to_string())]);
+        assert_eq!(
+            names(30),
+            vec![None, Some("y".to_string()), Some("x".to_string())]
+        );
+        assert_eq!(names(60), vec![Some("x".to_string())]);
+
+        let variables = subroutine.scoped_variables(45).unwrap();
+        let locations = variables
+            .iter()
+            .map(|v| match v.location {
+                Some(WasmLoc::Local(index)) => (v.name.as_str(), Some(index)),
+                _ => (v.name.as_str(), None),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(locations, vec![("x", Some(2)), ("y", None)]);
+        assert_eq!(variables[0].type_name, "i32");
+    }
+
+    #[test]
+    fn variables_in_lexical_blocks() {
+        let buffer = DwarfCorpusBuilder::new()
+            .add_compile_unit("main.c")
+            .add_function("foo", 0, 100)
+            .add_wasm_param("n", DwarfType::I32, &[(0..100, 0)])
+            .add_wasm_local("x", DwarfType::I32, &[(0..100, 1)])
+            .add_lexical_block(40, 60)
+            .add_block_local("x", DwarfType::I64, &[(40..60, 2)])
+            .add_block_local("y", DwarfType::I32, &[(40..60, 3)])
+            .build();
+        let subroutine = transform_dwarf(&buffer).unwrap().subroutine;
+        let variables = |offset| {
+            subroutine
+                .scoped_variables(offset)
+                .unwrap()
+                .into_iter()
+                .map(|v| match v.location {
+                    Some(WasmLoc::Local(index)) => (v.name, v.is_param, index),
+                    _ => panic!("{} should live in a wasm local", v.name),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            variables(10),
+            vec![("n".to_string(), true, 0), ("x".to_string(), false, 1)]
+        );
+        // The block's `x` shadows the function's one
+        assert_eq!(
+            variables(50),
+            vec![
+                ("n".to_string(), true, 0),
+                ("x".to_string(), false, 2),
+                ("y".to_string(), false, 3)
+            ]
+        );
+        assert_eq!(variables(70), variables(10));
+    }
+
+    #[test]
+    fn evaluate_memory_locations() {
+        use wasminspect_vm::{NumVal, WasmValue};
+        let encoding = gimli::Encoding {
+            address_size: 4,
+            format: gimli::Format::Dwarf32,
+            version: 4,
+        };
+        let locals = [WasmValue::Num(NumVal::I32(0x200))];
+        let frame = EvaluationFrame {
+            frame_base: Some(0x1000),
+            locals: &locals,
+        };
+        let mut memory = vec![0; 0x400];
+        memory[0x210..0x214].copy_from_slice(&0x300u32.to_le_bytes());
+        let evaluate = |bytes: &[u8]| {
+            let expr = Expression(EndianSlice::new(bytes, LittleEndian));
+            evaluate_location(expr, encoding, &frame, &memory)
+        };
+        // DW_OP_addr 0x400
+        assert_eq!(
+            evaluate(&[0x03, 0x00, 0x04, 0x00, 0x00]),
+            Some(ResolvedLocation::Memory(0x400))
+        );
+        // DW_OP_fbreg -8
+        assert_eq!(
+            evaluate(&[0x91, 0x78]),
+            Some(ResolvedLocation::Memory(0xff8))
+        );
+        // DW_OP_fbreg 16, DW_OP_plus_uconst 4
+        assert_eq!(
+            evaluate(&[0x91, 0x10, 0x23, 0x04]),
+            Some(ResolvedLocation::Memory(0x1014))
+        );
+        // DW_OP_WASM_location local 3
+        assert_eq!(
+            evaluate(&[0xed, 0x00, 0x03]),
+            Some(ResolvedLocation::Local(3))
+        );
+        // DW_OP_WASM_location local 0, DW_OP_plus_uconst 0x10, DW_OP_deref
+        assert_eq!(
+            evaluate(&[0xed, 0x00, 0x00, 0x23, 0x10, 0x06]),
+            Some(ResolvedLocation::Memory(0x300))
+        );
+        // DW_OP_WASM_location global 0
+        assert_eq!(evaluate(&[0xed, 0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn format_typed_values() {
+        use types::*;
+        let base = |name: &str, byte_size, encoding| {
+            TypeInfo::BaseType(BaseTypeInfo {
+                name: name.to_string(),
+                byte_size,
+                encoding: Some(encoding),
+            })
+        };
+        let pointer = |ty| {
+            TypeInfo::ModifiedType(ModifiedTypeInfo {
+                content_ty_offset: Some(ty),
+                kind: ModifierKind::Pointer,
+            })
+        };
+        let member = |name: &str, ty, offset| Member {
+            name: Some(name.to_string()),
+            ty,
+            location: MemberLocation::ConstOffset(offset),
+        };
+        let mut types = HashMap::<usize, TypeInfo<Reader>>::new();
+        types.insert(1, base("int", 4, gimli::DW_ATE_signed));
+        types.insert(2, base("char", 1, gimli::DW_ATE_signed_char));
+        types.insert(3, pointer(2));
+        types.insert(
+            4,
+            TypeInfo::StructType(StructTypeInfo {
+                name: Some("Point".to_string()),
+                members: vec![member("x", 1, 0), member("y", 1, 4)],
+                byte_size: 8,
+                declaration: false,
+            }),
+        );
+        types.insert(
+            5,
+            TypeInfo::ArrayType(ArrayTypeInfo {
+                ty: Some(4),
+                dimensions: vec![2],
+            }),
+        );
+        // A typedef referring to itself
+        types.insert(
+            6,
+            TypeInfo::TypeDef(TypeDef {
+                name: Some("loop".to_string()),
+                ty: Some(6),
+            }),
+        );
+
+        let mut memory = vec![0; 0x40];
+        memory[0x00..0x04].copy_from_slice(&1i32.to_le_bytes());
+        memory[0x04..0x08].copy_from_slice(&(-2i32).to_le_bytes());
+        memory[0x08..0x0c].copy_from_slice(&3i32.to_le_bytes());
+        memory[0x0c..0x10].copy_from_slice(&4i32.to_le_bytes());
+        memory[0x10..0x14].copy_from_slice(&0x20u32.to_le_bytes());
+        memory[0x20..0x26].copy_from_slice(b"hello\0");
+        let encoding = gimli::Encoding {
+            address_size: 4,
+            format: gimli::Format::Dwarf32,
+            version: 4,
+        };
+        let printer = format::TypePrinter::new(&types, &memory, encoding);
+        assert_eq!(printer.format(4, 0), "struct Point { x = 1, y = -2 }");
+        assert_eq!(
+            printer.format(5, 0),
+            "[struct Point { x = 1, y = -2 }, struct Point { x = 3, y = 4 }]"
+        );
+        assert_eq!(printer.format(3, 0x10), "0x00000020 \"hello\"");
+        assert_eq!(printer.format(2, 0x20), "104 'h'");
+        assert_eq!(printer.format(6, 0), "...");
+        assert_eq!(printer.format(1, 0x3e), "<out of bounds 0x0000003e>");
+    }
+
+    #[test]
+    fn evaluate_and_format_local() {
+        let buffer = corpus();
+        let subroutine_map = transform_dwarf(&buffer).unwrap().subroutine;
+        let subroutine = subroutine_map.find_subroutine_by_name("foo").unwrap();
+        assert!(matches!(subroutine.frame_base, Some(WasmLoc::Local(0))));
+
+        let dwarf = parse_dwarf(&buffer).unwrap();
+        let header = header_from_offset(&dwarf, subroutine.unit_offset)
+            .unwrap()
+            .unwrap();
+        let unit = dwarf.unit(header).unwrap();
+        let variables = subroutine_variables(&dwarf, &unit, subroutine).unwrap();
+        let x = &variables[0];
+        let expr = match x.content {
+            VariableContent::Location(AttributeValue::Exprloc(expr)) => expr,
+            _ => panic!("x should have a location expression"),
+        };
+        let pieces =
+            evaluate_variable_location(subroutine.encoding, FrameBase::WasmFrameBase(0x10), expr)
+                .unwrap();
+        let address = match pieces[0].location {
+            gimli::Location::Address { address } => address,
+            ref location => panic!("unexpected location {:?}", location),
+        };
+        assert_eq!(address, 0x1010);
+
+        let mut memory = vec![0; 0x1020];
+        memory[0x1010..0x1014].copy_from_slice(&(-42i32).to_le_bytes());
+        let mut types = HashMap::new();
+        types::get_types(&dwarf, &unit, &header, &mut types).unwrap();
+        let printer = format::TypePrinter::new(&types, &memory, subroutine.encoding);
+        assert_eq!(printer.format(x.ty_offset.unwrap(), address), "-42");
+    }
+}
@@ -0,0 +1,209 @@
+use super::Reader;
+use anyhow::{anyhow, Result};
+use gimli::{DebugStr, DebugStrOffset, Format, LittleEndian, UnitOffset};
+use std::collections::HashMap;
+
+struct IndexAttribute {
+    index: gimli::DwIdx,
+    form: gimli::DwForm,
+}
+
+struct Abbreviation {
+    tag: gimli::DwTag,
+    attributes: Vec<IndexAttribute>,
+}
+
+/// A name index, which is a unit of the `.debug_names` section
+struct NameIndex {
+    format: Format,
+    buckets: Vec<u32>,
+    hashes: Vec<u32>,
+    names: Vec<String>,
+    entry_offsets: Vec<usize>,
+    abbreviations: HashMap<u64, Abbreviation>,
+    entry_pool: Vec<u8>,
+}
+
+impl NameIndex {
+    fn parse(mut input: Reader, format: Format, debug_str: &DebugStr<Reader>) -> Result<Self> {
+        use gimli::Reader as _;
+        let version = input.read_u16()?;
+        if version != 5 {
+            return Err(anyhow!("unsupported .debug_names version {}", version));
+        }
+        // padding
+        input.skip(2)?;
+        let comp_unit_count = input.read_u32()? as usize;
+        let local_type_unit_count = input.read_u32()? as usize;
+        let foreign_type_unit_count = input.read_u32()? as usize;
+        let bucket_count = input.read_u32()? as usize;
+        let name_count = input.read_u32()? as usize;
+        let abbrev_table_size = input.read_u32()? as usize;
+        let augmentation_string_size = input.read_u32()? as usize;
+        input.skip(augmentation_string_size)?;
+
+        let unit_offset_size = format.word_size() as usize;
+        input.skip((comp_unit_count + local_type_unit_count) * unit_offset_size)?;
+        // foreign type units are referred by 8-byte signatures
+        input.skip(foreign_type_unit_count * 8)?;
+        let buckets = (0..bucket_count)
+            .map(|_| input.read_u32())
+            .collect::<gimli::Result<Vec<_>>>()?;
+        let hash_count = if bucket_count > 0 { name_count } else { 0 };
+        let hashes = (0..hash_count)
+            .map(|_| input.read_u32())
+            .collect::<gimli::Result<Vec<_>>>()?;
+        let names = (0..name_count)
+            .map(|_| {
+                let offset = input.read_offset(format)?;
+                let name = debug_str.get_str(DebugStrOffset(offset))?;
+                Ok(name.to_string_lossy().into_owned())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let entry_offsets = (0..name_count)
+            .map(|_| input.read_offset(format))
+            .collect::<gimli::Result<Vec<_>>>()?;
+        let abbreviations = parse_abbreviations(input.split(abbrev_table_size)?)?;
+
+        Ok(Self {
+            format,
+            buckets,
+            hashes,
+            names,
+            entry_offsets,
+            abbreviations,
+            entry_pool: input.slice().to_vec(),
+        })
+    }
+
+    fn lookup(&self, name: &str, hash: u32, tag: gimli::DwTag) -> Option<UnitOffset> {
+        if self.buckets.is_empty() {
+            // The hash table is optional, so names can only be scanned without it
+            let index = self.names.iter().position(|n| n == name)?;
+            return self.find_entry(index, tag);
+        }
+        let bucket_count = self.buckets.len() as u32;
+        let bucket = hash % bucket_count;
+        let first = self.buckets[bucket as usize] as usize;
+        // Bucket entries are 1-based indices and 0 means an empty bucket
+        if first == 0 {
+            return None;
+        }
+        for index in (first - 1)..self.hashes.len() {
+            let name_hash = self.hashes[index];
+            if name_hash % bucket_count != bucket {
+                break;
+            }
+            if name_hash == hash && self.names[index] == name {
+                return self.find_entry(index, tag);
+            }
+        }
+        None
+    }
+
+    fn find_entry(&self, index: usize, tag: gimli::DwTag) -> Option<UnitOffset> {
+        use gimli::Reader as _;
+        let mut input = Reader::new(&self.entry_pool, LittleEndian);
+        input.skip(*self.entry_offsets.get(index)?).ok()?;
+        loop {
+            let code = input.read_uleb128().ok()?;
+            if code == 0 {
+                return None;
+            }
+            let abbrev = self.abbreviations.get(&code)?;
+            let mut die_offset = None;
+            for attr in &abbrev.attributes {
+                let value = read_index_value(&mut input, attr.form, self.format).ok()?;
+                if attr.index == gimli::DW_IDX_die_offset {
+                    die_offset = Some(value as usize);
+                }
+            }
+            if abbrev.tag != tag {
+                continue;
+            }
+            if let Some(die_offset) = die_offset {
+                return Some(UnitOffset(die_offset));
+            }
+        }
+    }
+}
+
+fn parse_abbreviations(mut input: Reader) -> Result<HashMap<u64, Abbreviation>> {
+    use gimli::Reader as _;
+    let mut abbreviations = HashMap::new();
+    loop {
+        let code = input.read_uleb128()?;
+        if code == 0 {
+            break;
+        }
+        let tag = gimli::DwTag(input.read_uleb128_u16()?);
+        let mut attributes = vec![];
+        loop {
+            let index = input.read_uleb128_u16()?;
+            let form = input.read_uleb128_u16()?;
+            if index == 0 && form == 0 {
+                break;
+            }
+            attributes.push(IndexAttribute {
+                index: gimli::DwIdx(index),
+                form: gimli::DwForm(form),
+            });
+        }
+        abbreviations.insert(code, Abbreviation { tag, attributes });
+    }
+    Ok(abbreviations)
+}
+
+fn read_index_value(input: &mut Reader, form: gimli::DwForm, format: Format) -> Result<u64> {
+    use gimli::Reader as _;
+    let value = match form {
+        gimli::DW_FORM_flag_present => 1,
+        gimli::DW_FORM_flag | gimli::DW_FORM_data1 | gimli::DW_FORM_ref1 => input.read_u8()? as u64,
+        gimli::DW_FORM_data2 | gimli::DW_FORM_ref2 => input.read_u16()? as u64,
+        gimli::DW_FORM_data4 | gimli::DW_FORM_ref4 => input.read_u32()? as u64,
+        gimli::DW_FORM_data8 | gimli::DW_FORM_ref8 | gimli::DW_FORM_ref_sig8 => input.read_u64()?,
+        gimli::DW_FORM_udata | gimli::DW_FORM_ref_udata => input.read_uleb128()?,
+        gimli::DW_FORM_sdata => input.read_sleb128()? as u64,
+        gimli::DW_FORM_sec_offset => input.read_offset(format)? as u64,
+        _ => return Err(anyhow!("unsupported form {} in .debug_names", form)),
+    };
+    Ok(value)
+}
+
+/// The hash function of `.debug_names`, which is the DJB hash of the case-folded name
+fn case_folding_djb_hash(name: &str) -> u32 {
+    let mut buffer = [0; 4];
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .fold(5381u32, |hash, c| {
+            c.encode_utf8(&mut buffer).bytes().fold(hash, |hash, byte| {
+                hash.wrapping_mul(33).wrapping_add(byte as u32)
+            })
+        })
+}
+
+/// Function name lookup backed by the DWARF 5 `.debug_names` section
+pub struct DwarfAcceleratedNames {
+    indices: Vec<NameIndex>,
+}
+
+impl DwarfAcceleratedNames {
+    pub fn parse(debug_names: Reader, debug_str: &DebugStr<Reader>) -> Result<Self> {
+        use gimli::Reader as _;
+        let mut input = debug_names;
+        let mut indices = vec![];
+        while !input.is_empty() {
+            let (length, format) = input.read_initial_length()?;
+            indices.push(NameIndex::parse(input.split(length)?, format, debug_str)?);
+        }
+        Ok(Self { indices })
+    }
+
+    /// Returns the offset of the function DIE in its compilation unit
+    pub fn lookup_function(&self, name: &str) -> Option<UnitOffset> {
+        let hash = case_folding_djb_hash(name);
+        self.indices
+            .iter()
+            .find_map(|index| index.lookup(name, hash, gimli::DW_TAG_subprogram))
+    }
+}
@@ -0,0 +1,448 @@
+//! Generates small wasm modules with DWARF sections to test the DWARF subsystem
+//! without externally compiled binaries.
+use gimli::write::{
+    Address, AttributeValue, Dwarf, EndianVec, Expression, LineProgram, LineString, Location,
+    LocationList, Sections, Unit, UnitEntryId,
+};
+use gimli::{Encoding, Format, LineEncoding, LittleEndian};
+use std::collections::HashMap;
+
+/// Base types which can be given to locals
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DwarfType {
+    I32,
+    U32,
+    I64,
+    U64,
+}
+
+impl DwarfType {
+    fn name(self) -> &'static str {
+        match self {
+            Self::I32 => "i32",
+            Self::U32 => "u32",
+            Self::I64 => "i64",
+            Self::U64 => "u64",
+        }
+    }
+
+    fn byte_size(self) -> u8 {
+        match self {
+            Self::I32 | Self::U32 => 4,
+            Self::I64 | Self::U64 => 8,
+        }
+    }
+
+    fn encoding(self) -> gimli::DwAte {
+        match self {
+            Self::I32 | Self::I64 => gimli::DW_ATE_signed,
+            Self::U32 | Self::U64 => gimli::DW_ATE_unsigned,
+        }
+    }
+}
+
+enum CorpusLocation {
+    /// `DW_OP_fbreg` offset from the frame base
+    FrameOffset(i64),
+    /// Wasm local indices valid in each code range, written as a location list
+    WasmLocals(Vec<(std::ops::Range<u64>, u64)>),
+}
+
+struct CorpusLocal {
+    name: String,
+    ty: DwarfType,
+    location: CorpusLocation,
+    is_param: bool,
+}
+
+struct CorpusBlock {
+    low_pc: u64,
+    high_pc: u64,
+    locals: Vec<CorpusLocal>,
+}
+
+struct CorpusInlinedCall {
+    callee: String,
+    low_pc: u64,
+    high_pc: u64,
+    call_line: u64,
+}
+
+struct CorpusFunction {
+    name: String,
+    low_pc: u64,
+    high_pc: u64,
+    locals: Vec<CorpusLocal>,
+    blocks: Vec<CorpusBlock>,
+    inlined_calls: Vec<CorpusInlinedCall>,
+}
+
+struct CorpusUnit {
+    name: String,
+    functions: Vec<CorpusFunction>,
+}
+
+/// Builds a wasm module which only has DWARF custom sections.
+///
+/// Functions use the wasm local 0 as their frame base, and locals are placed
+/// at `DW_OP_fbreg` offsets from it.
+#[derive(Default)]
+pub struct DwarfCorpusBuilder {
+    units: Vec<CorpusUnit>,
+}
+
+impl DwarfCorpusBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_compile_unit(mut self, name: &str) -> Self {
+        self.units.push(CorpusUnit {
+            name: name.to_string(),
+            functions: vec![],
+        });
+        self
+    }
+
+    /// Adds a function covering `low_pc..high_pc` to the last compile unit
+    pub fn add_function(mut self, name: &str, low_pc: u64, high_pc: u64) -> Self {
+        let unit = self.units.last_mut().expect("no compile unit is added");
+        unit.functions.push(CorpusFunction {
+            name: name.to_string(),
+            low_pc,
+            high_pc,
+            locals: vec![],
+            blocks: vec![],
+            inlined_calls: vec![],
+        });
+        self
+    }
+
+    /// Adds a local variable to the last function
+    pub fn add_local(mut self, name: &str, ty: DwarfType, frame_offset: i64) -> Self {
+        let function = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .expect("no function is added");
+        function.locals.push(CorpusLocal {
+            name: name.to_string(),
+            ty,
+            location: CorpusLocation::FrameOffset(frame_offset),
+            is_param: false,
+        });
+        self
+    }
+
+    /// Adds a local variable living in the wasm local `ranges[i].1` during `ranges[i].0`
+    pub fn add_wasm_local(
+        mut self,
+        name: &str,
+        ty: DwarfType,
+        ranges: &[(std::ops::Range<u64>, u64)],
+    ) -> Self {
+        let function = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .expect("no function is added");
+        function.locals.push(CorpusLocal {
+            name: name.to_string(),
+            ty,
+            location: CorpusLocation::WasmLocals(ranges.to_vec()),
+            is_param: false,
+        });
+        self
+    }
+
+    /// Same as `add_wasm_local`, but adds a parameter
+    pub fn add_wasm_param(
+        mut self,
+        name: &str,
+        ty: DwarfType,
+        ranges: &[(std::ops::Range<u64>, u64)],
+    ) -> Self {
+        let function = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .expect("no function is added");
+        function.locals.push(CorpusLocal {
+            name: name.to_string(),
+            ty,
+            location: CorpusLocation::WasmLocals(ranges.to_vec()),
+            is_param: true,
+        });
+        self
+    }
+
+    /// Adds a lexical block covering `low_pc..high_pc` to the last function
+    pub fn add_lexical_block(mut self, low_pc: u64, high_pc: u64) -> Self {
+        let function = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .expect("no function is added");
+        function.blocks.push(CorpusBlock {
+            low_pc,
+            high_pc,
+            locals: vec![],
+        });
+        self
+    }
+
+    /// Same as `add_wasm_local`, but adds the variable to the last lexical block
+    pub fn add_block_local(
+        mut self,
+        name: &str,
+        ty: DwarfType,
+        ranges: &[(std::ops::Range<u64>, u64)],
+    ) -> Self {
+        let block = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .and_then(|function| function.blocks.last_mut())
+            .expect("no lexical block is added");
+        block.locals.push(CorpusLocal {
+            name: name.to_string(),
+            ty,
+            location: CorpusLocation::WasmLocals(ranges.to_vec()),
+            is_param: false,
+        });
+        self
+    }
+
+    /// Adds code of `callee` inlined at `call_line` of the source file to the last function
+    pub fn add_inlined_call(
+        mut self,
+        callee: &str,
+        low_pc: u64,
+        high_pc: u64,
+        call_line: u64,
+    ) -> Self {
+        let function = self
+            .units
+            .last_mut()
+            .and_then(|unit| unit.functions.last_mut())
+            .expect("no function is added");
+        function.inlined_calls.push(CorpusInlinedCall {
+            callee: callee.to_string(),
+            low_pc,
+            high_pc,
+            call_line,
+        });
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let encoding = Encoding {
+            address_size: 4,
+            format: Format::Dwarf32,
+            version: 4,
+        };
+        let mut dwarf = Dwarf::new();
+        for corpus_unit in &self.units {
+            let line_program = LineProgram::new(
+                encoding,
+                LineEncoding::default(),
+                LineString::String(b"/src".to_vec()),
+                LineString::String(corpus_unit.name.as_bytes().to_vec()),
+                None,
+            );
+            let unit_id = dwarf.units.add(Unit::new(encoding, line_program));
+            let unit = dwarf.units.get_mut(unit_id);
+            build_unit(unit, corpus_unit, &mut dwarf.strings);
+        }
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).expect("failed to write DWARF");
+
+        let mut module = b"\0asm".to_vec();
+        module.extend_from_slice(&1u32.to_le_bytes());
+        sections
+            .for_each(|id, data| {
+                write_custom_section(&mut module, id.name(), data.slice());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        module
+    }
+}
+
+fn build_unit(unit: &mut Unit, corpus_unit: &CorpusUnit, strings: &mut gimli::write::StringTable) {
+    let file = unit.line_program.add_file(
+        LineString::String(corpus_unit.name.as_bytes().to_vec()),
+        unit.line_program.default_directory(),
+        None,
+    );
+    for (index, function) in corpus_unit.functions.iter().enumerate() {
+        let program = &mut unit.line_program;
+        program.begin_sequence(Some(Address::Constant(function.low_pc)));
+        program.row().file = file;
+        program.row().line = index as u64 + 1;
+        program.generate_row();
+        program.end_sequence(function.high_pc - function.low_pc);
+    }
+
+    let root = unit.root();
+    let name = strings.add(corpus_unit.name.as_str());
+    let entry = unit.get_mut(root);
+    entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    entry.set(
+        gimli::DW_AT_language,
+        AttributeValue::Language(gimli::DW_LANG_Rust),
+    );
+
+    let mut base_types = HashMap::<DwarfType, UnitEntryId>::new();
+    for function in &corpus_unit.functions {
+        let subprogram = unit.add(root, gimli::DW_TAG_subprogram);
+        let name = strings.add(function.name.as_str());
+        let entry = unit.get_mut(subprogram);
+        entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.low_pc)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Udata(function.high_pc - function.low_pc),
+        );
+        // DW_OP_WASM_location local 0
+        entry.set(
+            gimli::DW_AT_frame_base,
+            AttributeValue::Exprloc(Expression::raw(vec![0xed, 0x00, 0x00])),
+        );
+
+        for local in &function.locals {
+            add_variable(unit, subprogram, local, &mut base_types, strings);
+        }
+
+        for block in &function.blocks {
+            let lexical_block = unit.add(subprogram, gimli::DW_TAG_lexical_block);
+            let entry = unit.get_mut(lexical_block);
+            entry.set(
+                gimli::DW_AT_low_pc,
+                AttributeValue::Address(Address::Constant(block.low_pc)),
+            );
+            entry.set(
+                gimli::DW_AT_high_pc,
+                AttributeValue::Udata(block.high_pc - block.low_pc),
+            );
+            for local in &block.locals {
+                add_variable(unit, lexical_block, local, &mut base_types, strings);
+            }
+        }
+
+        for call in &function.inlined_calls {
+            let origin = unit.add(root, gimli::DW_TAG_subprogram);
+            let name = strings.add(call.callee.as_str());
+            let entry = unit.get_mut(origin);
+            entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+            entry.set(
+                gimli::DW_AT_inline,
+                AttributeValue::Inline(gimli::DW_INL_inlined),
+            );
+
+            let inlined = unit.add(subprogram, gimli::DW_TAG_inlined_subroutine);
+            let entry = unit.get_mut(inlined);
+            entry.set(
+                gimli::DW_AT_abstract_origin,
+                AttributeValue::UnitRef(origin),
+            );
+            entry.set(
+                gimli::DW_AT_low_pc,
+                AttributeValue::Address(Address::Constant(call.low_pc)),
+            );
+            entry.set(
+                gimli::DW_AT_high_pc,
+                AttributeValue::Udata(call.high_pc - call.low_pc),
+            );
+            entry.set(
+                gimli::DW_AT_call_file,
+                AttributeValue::FileIndex(Some(file)),
+            );
+            entry.set(
+                gimli::DW_AT_call_line,
+                AttributeValue::Udata(call.call_line),
+            );
+        }
+    }
+}
+
+fn add_variable(
+    unit: &mut Unit,
+    parent: UnitEntryId,
+    local: &CorpusLocal,
+    base_types: &mut HashMap<DwarfType, UnitEntryId>,
+    strings: &mut gimli::write::StringTable,
+) {
+    let root = unit.root();
+    let ty = *base_types
+        .entry(local.ty)
+        .or_insert_with(|| add_base_type(unit, root, local.ty, strings));
+    let tag = if local.is_param {
+        gimli::DW_TAG_formal_parameter
+    } else {
+        gimli::DW_TAG_variable
+    };
+    let variable = unit.add(parent, tag);
+    let name = strings.add(local.name.as_str());
+    let location = match &local.location {
+        CorpusLocation::FrameOffset(frame_offset) => {
+            let mut location = Expression::new();
+            location.op_fbreg(*frame_offset);
+            AttributeValue::Exprloc(location)
+        }
+        CorpusLocation::WasmLocals(ranges) => {
+            let locations = ranges
+                .iter()
+                .map(|(range, local_index)| Location::StartEnd {
+                    begin: Address::Constant(range.start),
+                    end: Address::Constant(range.end),
+                    // DW_OP_WASM_location local, encoded as a single byte ULEB128
+                    data: Expression::raw(vec![0xed, 0x00, *local_index as u8]),
+                })
+                .collect();
+            AttributeValue::LocationListRef(unit.locations.add(LocationList(locations)))
+        }
+    };
+    let entry = unit.get_mut(variable);
+    entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(ty));
+    entry.set(gimli::DW_AT_location, location);
+}
+
+fn add_base_type(
+    unit: &mut Unit,
+    root: UnitEntryId,
+    ty: DwarfType,
+    strings: &mut gimli::write::StringTable,
+) -> UnitEntryId {
+    let id = unit.add(root, gimli::DW_TAG_base_type);
+    let name = strings.add(ty.name());
+    let entry = unit.get_mut(id);
+    entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    entry.set(
+        gimli::DW_AT_byte_size,
+        AttributeValue::Data1(ty.byte_size()),
+    );
+    entry.set(
+        gimli::DW_AT_encoding,
+        AttributeValue::Encoding(ty.encoding()),
+    );
+    id
+}
+
+fn write_uleb128(out: &mut Vec<u8>, value: usize) {
+    gimli::leb128::write::unsigned(out, value as u64).unwrap();
+}
+
+fn write_custom_section(module: &mut Vec<u8>, name: &str, data: &[u8]) {
+    let mut payload = vec![];
+    write_uleb128(&mut payload, name.len());
+    payload.extend_from_slice(name.as_bytes());
+    payload.extend_from_slice(data);
+    module.push(0);
+    write_uleb128(module, payload.len());
+    module.extend(payload);
+}
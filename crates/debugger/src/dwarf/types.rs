@@ -11,6 +11,7 @@ use super::utils::*;
 pub struct BaseTypeInfo {
     pub name: String,
     pub byte_size: u64,
+    pub encoding: Option<gimli::DwAte>,
 }
 
 #[derive(Debug)]
@@ -88,8 +89,6 @@ pub enum TypeInfo<R: gimli::Reader> {
     ArrayType(ArrayTypeInfo<R>),
 }
 
-}
-
 pub fn get_types<R: gimli::Reader>(
     dwarf: &gimli::Dwarf<R>,
     unit: &gimli::Unit<R, R::Offset>,
@@ -196,7 +195,15 @@ fn parse_base_type<R: gimli::Reader>(
         .attr_value(gimli::DW_AT_byte_size)?
         .and_then(|attr| attr.udata_value())
         .unwrap_or(0);
-    Ok(BaseTypeInfo { name, byte_size })
+    let encoding = match node.entry().attr_value(gimli::DW_AT_encoding)? {
+        Some(gimli::AttributeValue::Encoding(encoding)) => Some(encoding),
+        _ => None,
+    };
+    Ok(BaseTypeInfo {
+        name,
+        byte_size,
+        encoding,
+    })
 }
 
 fn parse_modified_type<R: gimli::Reader>(
@@ -372,7 +379,11 @@ fn parse_array_type<R: gimli::Reader>(
 fn parse_array_count<R: gimli::Reader>(node: &gimli::EntriesTreeNode<R>) -> Result<u64> {
     let count = match node.entry().attr_value(gimli::DW_AT_count)? {
         Some(v) => v.udata_value().unwrap_or(0),
-        _ => 0,
+        // C compilers describe the bound instead of the count
+        _ => match node.entry().attr_value(gimli::DW_AT_upper_bound)? {
+            Some(v) => v.udata_value().map(|bound| bound + 1).unwrap_or(0),
+            _ => 0,
+        },
     };
     Ok(count)
 }
@@ -1,30 +1,51 @@
 mod commands;
 mod debugger;
+mod disassembler;
 mod dwarf;
 mod process;
+#[cfg(feature = "remote-api")]
+mod remote;
+pub mod rpc;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
 
 use std::{cell::RefCell, rc::Rc};
 
 pub use commands::command::CommandContext;
 pub use commands::command::CommandResult;
-pub use commands::debugger::{Debugger, RunResult};
+pub use commands::debugger::{
+    Breakpoint, BreakpointId, Debugger, FrameInfo, FunctionRef, OffsetBase, RunResult,
+    SetGlobalError, SetLocalError, StepStyle, TrapInfo, VariableInfo,
+};
+pub use commands::sourcemap::SourceLocation;
 pub use debugger::MainDebugger;
 pub use linefeed;
 pub use process::Interactive;
 pub use process::Process;
+#[cfg(feature = "remote-api")]
+pub use remote::{BreakpointHit, RemoteDebugger};
 
 use anyhow::{anyhow, Result};
 use commands::command;
 use log::warn;
 
-pub fn try_load_dwarf(
+pub fn try_load_dwarf<D: Debugger>(
     buffer: &[u8],
     context: &mut commands::command::CommandContext,
+    debugger: &mut D,
 ) -> Result<()> {
     use dwarf::transform_dwarf;
     let debug_info = transform_dwarf(buffer)?;
-    context.sourcemap = Box::new(debug_info.sourcemap);
-    context.subroutine = Box::new(debug_info.subroutine);
+    let sourcemap = Rc::new(debug_info.sourcemap);
+    debugger.set_line_table(sourcemap.clone());
+    context.sourcemap = Box::new(sourcemap);
+    let subroutine = Rc::new(debug_info.subroutine);
+    debugger.set_subroutine_map(subroutine.clone());
+    context.subroutine = Box::new(subroutine);
+    for region in debug_info.data_objects {
+        debugger.label_memory_region(region.start, region.len, region.label);
+    }
     Ok(())
 }
 
@@ -43,85 +64,165 @@ pub struct ModuleInput {
     pub basename: String,
 }
 
-pub fn start_debugger(
-    module_input: Option<ModuleInput>,
-    preopen_dirs: Vec<(String, String)>,
-    envs: Vec<(String, String)>,
-) -> Result<(
-    process::Process<debugger::MainDebugger>,
-    command::CommandContext,
-)> {
-    let mut debugger = debugger::MainDebugger::new(preopen_dirs, envs)?;
-    let mut context = commands::command::CommandContext {
-        sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
-        subroutine: Box::new(commands::subroutine::EmptySubroutineMap::new()),
-        printer: Box::new(ConsolePrinter {}),
+/// The config file loaded from the current directory at startup
+static CONFIG_FILE: &str = ".wasminspect.toml";
+
+fn load_startup_opts() -> Result<commands::debugger::DebuggerOpts> {
+    use commands::debugger::DebuggerOptsLayer;
+    let path = std::path::Path::new(CONFIG_FILE);
+    let base = if path.exists() {
+        DebuggerOptsLayer::from_file(path)?
+    } else {
+        DebuggerOptsLayer::default()
     };
+    Ok(DebuggerOptsLayer::from_env()?.or(base).into_opts())
+}
 
-    if let Some(ref module_input) = module_input {
-        debugger.load_main_module(&module_input.bytes, module_input.basename.clone())?;
-        match try_load_dwarf(&module_input.bytes, &mut context) {
-            Ok(_) => (),
-            Err(err) => {
-                warn!("Failed to load dwarf info: {}", err);
-            }
-        }
-    }
-    let process = process::Process::new(
+fn new_process<D: Debugger>(debugger: D) -> Result<process::Process<D>> {
+    process::Process::new(
         debugger,
         vec![
             Box::new(commands::thread::ThreadCommand::new()),
+            Box::new(commands::finish::FinishCommand::new()),
+            Box::new(commands::run_to::RunToCommand::new()),
             Box::new(commands::list::ListCommand::new()),
             Box::new(commands::memory::MemoryCommand::new()),
+            Box::new(commands::examine::ExamineCommand::new()),
+            Box::new(commands::mock::MockCommand::new()),
+            Box::new(commands::module::ModuleCommand::new()),
             Box::new(commands::stack::StackCommand::new()),
+            Box::new(commands::print::PrintCommand::new()),
             Box::new(commands::breakpoint::BreakpointCommand::new()),
+            Box::new(commands::watch::WatchCommand::new()),
             Box::new(commands::disassemble::DisassembleCommand::new()),
+            Box::new(commands::disassemble::DisassembleModuleCommand::new()),
             Box::new(commands::expression::ExpressionCommand::new()),
             Box::new(commands::global::GlobalCommand::new()),
+            Box::new(commands::load::LoadCommand::new()),
             Box::new(commands::local::LocalCommand::new()),
             Box::new(commands::frame::FrameCommand::new()),
+            Box::new(commands::info::InfoCommand::new()),
             Box::new(commands::settings::SettingsCommand::new()),
             Box::new(commands::process::ProcessCommand::new()),
+            Box::new(commands::trace::TraceCommand::new()),
+            Box::new(commands::profiler::ProfilerCommand::new()),
+            Box::new(commands::stats::StatsCommand::new()),
         ],
         vec![
             Box::new(commands::run::RunCommand::new()),
             Box::new(commands::backtrace::BacktraceCommand::new()),
+            Box::new(commands::breakpoint::BreakCommand::new()),
+            Box::new(commands::local::SetCommand::new()),
+            Box::new(commands::local::LocalsCommand::new()),
+            Box::new(commands::frame::UpCommand::new()),
+            Box::new(commands::frame::DownCommand::new()),
+            Box::new(commands::memory::MemCommand::new()),
         ],
-    )?;
-    Ok((process, context))
+    )
 }
 
-pub fn run_loop(
+pub fn start_debugger(
     module_input: Option<ModuleInput>,
-    init_source: Option<String>,
     preopen_dirs: Vec<(String, String)>,
     envs: Vec<(String, String)>,
-) -> Result<()> {
-    let (mut process, context) = start_debugger(module_input, preopen_dirs, envs)?;
+    wasi: bool,
+) -> Result<(
+    process::Process<debugger::MainDebugger>,
+    command::CommandContext,
+)> {
+    let mut debugger = debugger::MainDebugger::new(preopen_dirs, envs, wasi)?;
+    match load_startup_opts() {
+        Ok(opts) => debugger.set_opts(opts),
+        Err(err) => warn!("Failed to load debugger options: {}", err),
+    }
+    let mut context = commands::command::CommandContext {
+        sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
+        subroutine: Box::new(commands::subroutine::EmptySubroutineMap::new()),
+        printer: Box::new(ConsolePrinter {}),
+        watches: Default::default(),
+    };
 
-    {
-        let is_default = init_source.is_none();
-        let lines = match {
-            let init_source = init_source.unwrap_or_else(|| "~/.wasminspect_init".to_string());
-            use std::fs::File;
-            use std::io::{BufRead, BufReader};
-            File::open(init_source).map(|file| BufReader::new(file).lines())
-        } {
-            Ok(lines) => lines.map(|l| l.unwrap()).collect::<Vec<String>>(),
+    if let Some(ref module_input) = module_input {
+        debugger.load_main_module(&module_input.bytes, module_input.basename.clone())?;
+        match try_load_dwarf(&module_input.bytes, &mut context, &mut debugger) {
+            Ok(_) => (),
             Err(err) => {
-                if is_default {
-                    vec![]
-                } else {
-                    return Err(anyhow!("{}", err));
-                }
+                warn!("Failed to load dwarf info: {}", err);
             }
-        };
-        for line in lines {
-            process.dispatch_command(&line, &context)?;
         }
     }
+    let process = new_process(debugger)?;
+    Ok((process, context))
+}
+
+fn run_init_source(
+    process: &mut process::Process<debugger::MainDebugger>,
+    context: &command::CommandContext,
+    init_source: Option<String>,
+) -> Result<()> {
+    let is_default = init_source.is_none();
+    let lines = match {
+        let init_source = init_source.unwrap_or_else(|| "~/.wasminspect_init".to_string());
+        use std::fs::File;
+        use std::io::{BufRead, BufReader};
+        File::open(init_source).map(|file| BufReader::new(file).lines())
+    } {
+        Ok(lines) => lines.map(|l| l.unwrap()).collect::<Vec<String>>(),
+        Err(err) => {
+            if is_default {
+                vec![]
+            } else {
+                return Err(anyhow!("{}", err));
+            }
+        }
+    };
+    for line in lines {
+        process.dispatch_command(&line, context)?;
+    }
+    Ok(())
+}
+
+pub fn run_loop(
+    module_input: Option<ModuleInput>,
+    init_source: Option<String>,
+    preopen_dirs: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+    wasi: bool,
+) -> Result<()> {
+    let (mut process, context) = start_debugger(module_input, preopen_dirs, envs, wasi)?;
+    run_init_source(&mut process, &context, init_source)?;
     let mut interactive = Interactive::new_with_loading_history()?;
     let process = Rc::new(RefCell::new(process));
     while let CommandResult::ProcessFinish(_) = interactive.run_loop(&context, process.clone())? {}
     Ok(())
 }
+
+/// Runs the interactive debugger against a module executed by a remote debugger server
+#[cfg(feature = "remote-api")]
+pub fn attach(addr: &str) -> Result<()> {
+    let debugger = RemoteDebugger::connect(addr)?;
+    println!("Attached to {}", addr);
+    let context = commands::command::CommandContext {
+        sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
+        subroutine: Box::new(commands::subroutine::EmptySubroutineMap::new()),
+        printer: Box::new(ConsolePrinter {}),
+        watches: Default::default(),
+    };
+    let process = Rc::new(RefCell::new(new_process(debugger)?));
+    let mut interactive = Interactive::new_with_loading_history()?;
+    while let CommandResult::ProcessFinish(_) = interactive.run_loop(&context, process.clone())? {}
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+pub fn run_tui(
+    module_input: Option<ModuleInput>,
+    init_source: Option<String>,
+    preopen_dirs: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+    wasi: bool,
+) -> Result<()> {
+    let (mut process, mut context) = start_debugger(module_input, preopen_dirs, envs, wasi)?;
+    run_init_source(&mut process, &context, init_source)?;
+    tui::run_tui(&mut process, &mut context)
+}
@@ -45,7 +45,7 @@ impl<D: Debugger> Process<D> {
             match cmd.run(&mut self.debugger, context, args) {
                 Ok(result) => Ok(result),
                 Err(err) => {
-                    eprintln!("{}", err);
+                    context.printer.eprintln(&err.to_string());
                     Ok(None)
                 }
             }
@@ -53,15 +53,17 @@ impl<D: Debugger> Process<D> {
             let line = alias.run(args)?;
             self.dispatch_command(&line, context)
         } else if cmd_name == "help" {
-            println!("Available commands:");
+            context.printer.println("Available commands:");
             for command in self.commands.values() {
-                println!("  {} -- {}", command.name(), command.description());
+                let output = format!("  {} -- {}", command.name(), command.description());
+                context.printer.println(&output);
             }
             Ok(None)
         } else if cfg!(feature = "remote-api") && cmd_name == "start-server" {
             Ok(Some(CommandResult::Exit))
         } else {
-            eprintln!("'{}' is not a valid command.", cmd_name);
+            let output = format!("'{}' is not a valid command.", cmd_name);
+            context.printer.eprintln(&output);
             Ok(None)
         }
     }
@@ -142,7 +144,8 @@ impl Interactive {
 fn extract_command_name(s: &str) -> &str {
     let s = s.trim();
 
-    match s.find(|ch: char| ch.is_whitespace()) {
+    // `/` separates options in gdb style commands like `x/16xb`
+    match s.find(|ch: char| ch.is_whitespace() || ch == '/') {
         Some(pos) => &s[..pos],
         None => s,
     }
@@ -0,0 +1,590 @@
+use crate::commands::debugger::{
+    self, Breakpoint, BreakpointId, BreakpointPredicate, DataSegment, Debugger, DebuggerOpts,
+    FrameInfo, FunctionFrame, FunctionMock, LabeledRegion, LocalChange, LocalInfo, OffsetBase,
+    ProducersSection, ProfilingData, RawHostModule, RunResult, TrapInfo, VariableInfo,
+};
+use crate::commands::sourcemap::SourceMap;
+use crate::commands::subroutine::SubroutineMap;
+use crate::debugger::horspool_search;
+use crate::rpc::{self, TextRequest, TextResponse};
+use anyhow::{anyhow, Result};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc;
+use tungstenite::{client::AutoStream, Message, WebSocket};
+use wasminspect_vm::{ExecutionLimit, Instruction, ModuleIndex, Signal, Store, WasmValue};
+
+/// The name of the exported memory inspected through the connection
+static MEMORY_NAME: &str = "memory";
+
+/// A breakpoint set over RPC was hit, pushed by the server without a request
+#[derive(Debug)]
+pub struct BreakpointHit {
+    pub id: u32,
+    pub func_name: String,
+    pub offset: usize,
+    pub locals: Vec<WasmValue>,
+}
+
+/// A debugger which forwards every operation to a running debugger server
+pub struct RemoteDebugger {
+    socket: RefCell<WebSocket<AutoStream>>,
+    opts: DebuggerOpts,
+    is_running: Cell<bool>,
+    memory_labels: Vec<LabeledRegion>,
+    events_tx: mpsc::Sender<BreakpointHit>,
+    events: mpsc::Receiver<BreakpointHit>,
+    /// Predicates of conditional breakpoints, evaluated on the locals sent with
+    /// their `BreakpointHit`s because they can't be sent to the server
+    predicates: HashMap<u32, BreakpointPredicate>,
+    /// Set when the last breakpoint hit didn't satisfy its predicate
+    skip_hit: Cell<bool>,
+}
+
+impl RemoteDebugger {
+    /// Connects to a debugger server listening on `addr` (e.g. `127.0.0.1:4000`)
+    pub fn connect(addr: &str) -> Result<Self> {
+        let url = if addr.contains("://") {
+            addr.to_string()
+        } else {
+            format!("ws://{}/debugger", addr)
+        };
+        let (socket, _) = tungstenite::connect(url.as_str())
+            .map_err(|err| anyhow!("failed to connect to {}: {}", url, err))?;
+        let (events_tx, events) = mpsc::channel();
+        Ok(Self {
+            socket: RefCell::new(socket),
+            opts: DebuggerOpts::default(),
+            is_running: Cell::new(false),
+            memory_labels: Vec::new(),
+            events_tx,
+            events,
+            predicates: HashMap::new(),
+            skip_hit: Cell::new(false),
+        })
+    }
+
+    /// Breakpoint hits pushed by the server while handling requests, which
+    /// arrive before the response halting the process
+    pub fn events(&self) -> &mpsc::Receiver<BreakpointHit> {
+        &self.events
+    }
+
+    fn request(&self, request: TextRequest) -> Result<TextResponse> {
+        self.skip_hit.set(false);
+        let mut socket = self.socket.borrow_mut();
+        socket.write_message(Message::Text(serde_json::to_string(&request)?))?;
+        loop {
+            let text = match socket.read_message()? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Err(anyhow!("connection closed by the server")),
+                _ => continue,
+            };
+            let response = serde_json::from_str(&text)
+                .map_err(|_| anyhow!("unexpected response: {}", text))?;
+            match response {
+                TextResponse::Progress {
+                    instructions_executed,
+                    ..
+                } => {
+                    log::debug!("{} instructions executed", instructions_executed);
+                }
+                TextResponse::BreakpointHit {
+                    id,
+                    func_name,
+                    offset,
+                    locals,
+                } => {
+                    let event = BreakpointHit {
+                        id,
+                        func_name,
+                        offset,
+                        locals: locals.into_iter().map(WasmValue::from).collect(),
+                    };
+                    if let Some(predicate) = self.predicates.get(&id) {
+                        if !predicate(&event.locals) {
+                            self.skip_hit.set(true);
+                            continue;
+                        }
+                    }
+                    // The receiver lives as long as the sender in `self`
+                    let _ = self.events_tx.send(event);
+                }
+                TextResponse::Stdout { data } => print!("{}", data),
+                TextResponse::Stderr { data } => eprint!("{}", data),
+                TextResponse::Error { message } => return Err(anyhow!("{}", message)),
+                TextResponse::CallHost { module, field, .. } => {
+                    return Err(anyhow!(
+                        "host function '{}.{}' is not available in a remote session",
+                        module,
+                        field
+                    ))
+                }
+                response => return Ok(response),
+            }
+        }
+    }
+
+    fn run_result(&self, response: TextResponse) -> Result<RunResult> {
+        if self.skip_hit.replace(false) {
+            if let TextResponse::Stopped { .. } = response {
+                // Halted at a conditional breakpoint whose predicate doesn't hold
+                let response = self.request(TextRequest::Continue)?;
+                return self.run_result(response);
+            }
+        }
+        match response {
+            TextResponse::CallResult { values, .. } => {
+                self.is_running.set(false);
+                Ok(RunResult::Finish(
+                    values
+                        .into_iter()
+                        .map(|named| WasmValue::from(named.value))
+                        .collect(),
+                ))
+            }
+            TextResponse::Stopped { .. } => {
+                self.is_running.set(true);
+                Ok(RunResult::Breakpoint)
+            }
+            _ => Err(anyhow!("unexpected response to an execution request")),
+        }
+    }
+
+    fn set_function_breakpoint(
+        &mut self,
+        name: String,
+        hit_count: Option<u32>,
+    ) -> Result<BreakpointId> {
+        match self.request(TextRequest::SetBreakpoint { name, hit_count })? {
+            TextResponse::SetBreakpointResult { id } => Ok(BreakpointId(id)),
+            _ => Err(anyhow!("unexpected response to a breakpoint request")),
+        }
+    }
+
+    /// Rebuilds a breakpoint from its location listed by the server, which is
+    /// a function name or the offset of a line breakpoint
+    fn breakpoint_at(&self, info: rpc::BreakpointInfo) -> Breakpoint {
+        let offset = info
+            .location
+            .strip_prefix("0x")
+            .and_then(|hex| usize::from_str_radix(hex, 16).ok());
+        match (offset, self.predicates.get(&info.id)) {
+            (Some(offset), _) => Breakpoint::Instruction {
+                base: OffsetBase::Absolute,
+                offset,
+                condition: None,
+            },
+            (None, Some(predicate)) => Breakpoint::FunctionConditional {
+                name: info.location,
+                predicate: predicate.clone(),
+            },
+            (None, None) => Breakpoint::Function {
+                name: info.location,
+                condition: None,
+                hit_count: None,
+            },
+        }
+    }
+
+    fn memory_size(&self) -> Result<usize> {
+        match self.request(TextRequest::MemorySize {
+            name: MEMORY_NAME.to_string(),
+        })? {
+            TextResponse::MemorySizeResult { size } => Ok(size),
+            _ => Err(anyhow!("unexpected response to a memory size request")),
+        }
+    }
+}
+
+fn unsupported<T>(feature: &str) -> Result<T> {
+    Err(anyhow!("{} is not supported in a remote session", feature))
+}
+
+impl Debugger for RemoteDebugger {
+    fn get_opts(&self) -> DebuggerOpts {
+        self.opts.clone()
+    }
+
+    fn set_opts(&mut self, opts: DebuggerOpts) {
+        self.opts = opts
+    }
+
+    fn set_execution_limit(&mut self, _limit: Option<ExecutionLimit>) -> Result<()> {
+        unsupported("Execution limits")
+    }
+
+    fn instantiate(
+        &mut self,
+        _host_modules: HashMap<String, RawHostModule>,
+        _wasi_args: Option<&[String]>,
+    ) -> Result<()> {
+        // The module has been instantiated by the server
+        Ok(())
+    }
+
+    fn run(&mut self, name: Option<&str>, args: Vec<WasmValue>) -> Result<RunResult> {
+        let name = match name {
+            Some(name) => name,
+            None => return Err(anyhow!("function name is required in a remote session")),
+        };
+        let args = args
+            .into_iter()
+            .map(rpc::WasmValue::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        self.is_running.set(true);
+        let response = self.request(TextRequest::CallExported {
+            name: name.to_string(),
+            args,
+            progress_interval_instructions: rpc::DEFAULT_PROGRESS_INTERVAL,
+            include_stats: false,
+        })?;
+        self.run_result(response)
+    }
+
+    fn is_running(&self) -> bool {
+        self.is_running.get()
+    }
+
+    fn frame(&self) -> Vec<FrameInfo> {
+        vec![]
+    }
+
+    fn backtrace(&self) -> Vec<FrameInfo> {
+        vec![]
+    }
+
+    fn last_trap(&self) -> Option<TrapInfo> {
+        None
+    }
+
+    fn current_frame(&self) -> Option<FunctionFrame> {
+        None
+    }
+
+    fn frame_locals(&self) -> Result<Vec<LocalInfo>> {
+        unsupported("Inspecting locals")
+    }
+
+    fn local_variables(&self) -> Vec<VariableInfo> {
+        vec![]
+    }
+
+    fn set_local(&mut self, _index: usize, _value: WasmValue) -> Result<()> {
+        unsupported("Modifying locals")
+    }
+
+    fn globals(&self, module_index: Option<ModuleIndex>) -> Result<Vec<(bool, WasmValue)>> {
+        // Without frames, the main module of the server stands for the selected frame's module
+        match self.request(TextRequest::ReadGlobals {
+            module_index: module_index.map(|index| index.0),
+        })? {
+            TextResponse::ReadGlobalsResult { values, mutable } => Ok(values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let is_mutable = mutable.get(index).cloned().unwrap_or(false);
+                    (is_mutable, WasmValue::from(value))
+                })
+                .collect()),
+            _ => Err(anyhow!("unexpected response to a globals request")),
+        }
+    }
+
+    fn set_global(
+        &mut self,
+        module_index: Option<ModuleIndex>,
+        index: usize,
+        value: WasmValue,
+    ) -> Result<()> {
+        let module_index = match module_index {
+            Some(module_index) => module_index,
+            None => return Err(anyhow!("module index is required in a remote session")),
+        };
+        match self.request(TextRequest::WriteGlobal {
+            module_index: module_index.0,
+            global_index: index as u32,
+            value: rpc::WasmValue::try_from(value)?,
+        })? {
+            TextResponse::WriteGlobalResult => Ok(()),
+            _ => Err(anyhow!("unexpected response to a global request")),
+        }
+    }
+
+    fn local_history(&mut self, _func_index: u32, _local_index: usize) -> &[LocalChange] {
+        &[]
+    }
+
+    fn profile_counts(&self) -> HashMap<usize, u64> {
+        HashMap::new()
+    }
+
+    fn instruction_histogram(&self) -> HashMap<String, u64> {
+        HashMap::new()
+    }
+
+    fn reset_histogram(&mut self) {}
+
+    fn start_trace(&mut self, _path: &str) -> Result<()> {
+        unsupported("Tracing")
+    }
+
+    fn stop_trace(&mut self) -> Result<()> {
+        unsupported("Tracing")
+    }
+
+    fn start_sampling_profiler(&mut self, _interval_instructions: u64) -> Result<()> {
+        unsupported("Sampling profiler")
+    }
+
+    fn stop_sampling_profiler(&mut self) -> Result<ProfilingData> {
+        unsupported("Sampling profiler")
+    }
+
+    fn instruction_map(&self) -> Result<HashMap<usize, Instruction>> {
+        unsupported("Reading instructions")
+    }
+
+    fn memory_at(&self, mem_index: usize) -> Result<Vec<u8>> {
+        if mem_index != 0 {
+            return unsupported("Reading memories other than the memory 0");
+        }
+        let length = self.memory_size()?;
+        match self.request(TextRequest::LoadMemory {
+            name: MEMORY_NAME.to_string(),
+            offset: 0,
+            length,
+        })? {
+            TextResponse::LoadMemoryResult { bytes } => Ok(bytes),
+            _ => Err(anyhow!("unexpected response to a memory request")),
+        }
+    }
+
+    fn memory_checksum(&self, start: usize, len: usize) -> Result<u32> {
+        match self.request(TextRequest::MemoryChecksum {
+            name: MEMORY_NAME.to_string(),
+            offset: start,
+            length: len,
+        })? {
+            TextResponse::MemoryChecksumResult { checksum } => Ok(checksum),
+            _ => Err(anyhow!("unexpected response to a checksum request")),
+        }
+    }
+
+    fn dump_memory(&self, path: &Path, offset: usize, len: usize) -> Result<()> {
+        let bytes = match self.request(TextRequest::LoadMemory {
+            name: MEMORY_NAME.to_string(),
+            offset,
+            length: len,
+        })? {
+            TextResponse::LoadMemoryResult { bytes } => bytes,
+            _ => return Err(anyhow!("unexpected response to a memory request")),
+        };
+        std::fs::write(path, bytes).map_err(|err| anyhow!("{}: {}", path.display(), err))
+    }
+
+    fn write_memory(&mut self, offset: usize, bytes: Vec<u8>) -> Result<()> {
+        match self.request(TextRequest::StoreMemory {
+            name: MEMORY_NAME.to_string(),
+            offset,
+            bytes,
+        })? {
+            TextResponse::StoreMemoryResult => Ok(()),
+            _ => Err(anyhow!("unexpected response to a memory request")),
+        }
+    }
+
+    fn find_in_memory(&self, pattern: &[u8], align: usize) -> Result<Vec<usize>> {
+        let align = align.max(1);
+        Ok(horspool_search(&self.memory()?, pattern)
+            .into_iter()
+            .filter(|offset| offset % align == 0)
+            .collect())
+    }
+
+    fn set_line_table(&mut self, _sourcemap: Rc<dyn SourceMap>) {}
+
+    fn set_subroutine_map(&mut self, _subroutine: Rc<dyn SubroutineMap>) {}
+
+    fn label_memory_region(&mut self, start: usize, len: usize, label: String) {
+        self.memory_labels
+            .retain(|region| region.start != start || region.len != len);
+        self.memory_labels.push(LabeledRegion { start, len, label });
+        self.memory_labels.sort_by_key(|region| region.start);
+    }
+
+    fn mock_function(&mut self, _mock: FunctionMock) -> Result<()> {
+        unsupported("Mocking functions")
+    }
+
+    fn delete_mock(&mut self, _module: &str, _name: &str) -> bool {
+        false
+    }
+
+    fn clear_mocks(&mut self) {}
+
+    fn load_host_module_from_file(&mut self, _module_name: &str, _path: &Path) -> Result<()> {
+        unsupported("Loading modules")
+    }
+
+    fn memory_labels(&self) -> Vec<LabeledRegion> {
+        self.memory_labels.clone()
+    }
+
+    fn data_segments(&self) -> Result<Vec<DataSegment>> {
+        unsupported("Reading data segments")
+    }
+
+    fn global_by_name(&self, name: &str) -> Result<Option<WasmValue>> {
+        match self.request(TextRequest::ReadGlobalByName {
+            name: name.to_string(),
+        })? {
+            TextResponse::ReadGlobalByNameResult { value } => Ok(value.map(WasmValue::from)),
+            _ => Err(anyhow!("unexpected response to a global request")),
+        }
+    }
+
+    fn store(&self) -> Result<&Store> {
+        unsupported("Accessing the store")
+    }
+
+    fn set_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<BreakpointId> {
+        match breakpoint {
+            Breakpoint::Function {
+                name,
+                condition: None,
+                hit_count,
+            } => self.set_function_breakpoint(name, hit_count),
+            Breakpoint::FunctionConditional { name, predicate } => {
+                self.set_conditional_breakpoint(name, predicate)
+            }
+            Breakpoint::Function { .. } => unsupported("Breakpoint conditions"),
+            Breakpoint::FunctionOnce { .. } => unsupported("One-shot breakpoints"),
+            Breakpoint::Instruction { .. } => unsupported("Instruction breakpoints"),
+            Breakpoint::WatchLocal { .. } | Breakpoint::MemoryWrite { .. } => {
+                unsupported("Watchpoints")
+            }
+        }
+    }
+
+    fn set_conditional_breakpoint(
+        &mut self,
+        name: String,
+        predicate: BreakpointPredicate,
+    ) -> Result<BreakpointId> {
+        let id = self.set_function_breakpoint(name, None)?;
+        self.predicates.insert(id.0, predicate);
+        Ok(id)
+    }
+
+    fn instruction_breakpoints(&self) -> Vec<usize> {
+        self.list_breakpoints()
+            .into_iter()
+            .filter_map(|(_, breakpoint)| match breakpoint {
+                Breakpoint::Instruction { offset, .. } => Some(offset),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn list_breakpoints(&self) -> Vec<(BreakpointId, Breakpoint)> {
+        let breakpoints = match self.request(TextRequest::ListBreakpoints) {
+            Ok(TextResponse::ListBreakpointsResult { breakpoints }) => breakpoints,
+            Ok(_) => {
+                log::warn!("unexpected response to a breakpoints request");
+                return vec![];
+            }
+            Err(err) => {
+                log::warn!("failed to list breakpoints: {}", err);
+                return vec![];
+            }
+        };
+        breakpoints
+            .into_iter()
+            .map(|info| (BreakpointId(info.id), self.breakpoint_at(info)))
+            .collect()
+    }
+
+    fn delete_breakpoint(&mut self, id: BreakpointId) -> bool {
+        match self.request(TextRequest::RemoveBreakpoint { id: id.0 }) {
+            Ok(TextResponse::RemoveBreakpointResult) => {
+                self.predicates.remove(&id.0);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn producers(&self) -> Option<ProducersSection> {
+        None
+    }
+
+    fn stack_values(&self) -> Vec<WasmValue> {
+        vec![]
+    }
+
+    fn locals_for_frame(&self, _depth: usize) -> Vec<WasmValue> {
+        vec![]
+    }
+
+    fn stack_values_for_frame(&self, _depth: usize) -> Vec<WasmValue> {
+        vec![]
+    }
+
+    fn selected_frame_index(&self) -> usize {
+        0
+    }
+
+    fn selected_instructions(&self) -> Result<(&[Instruction], usize)> {
+        unsupported("Disassembling")
+    }
+
+    fn func_instructions(&self, _func: &str) -> Result<&[Instruction]> {
+        unsupported("Disassembling")
+    }
+
+    fn function_name(&self, _func_index: u32) -> Option<String> {
+        None
+    }
+
+    fn module_bytes(&self) -> Result<&[u8]> {
+        unsupported("Disassembling")
+    }
+
+    fn step(&self, style: debugger::StepStyle) -> Result<Signal> {
+        let style = match style {
+            debugger::StepStyle::InstIn => rpc::StepStyle::In,
+            debugger::StepStyle::InstOver => rpc::StepStyle::Over,
+            debugger::StepStyle::Out => rpc::StepStyle::Out,
+            debugger::StepStyle::StepLineIn | debugger::StepStyle::StepLineOver => {
+                return unsupported("Line stepping")
+            }
+        };
+        match self.request(TextRequest::Step { style })? {
+            TextResponse::Stopped {
+                inst_offset: Some(_),
+            } => Ok(Signal::Next),
+            TextResponse::Stopped { inst_offset: None } => {
+                self.is_running.set(false);
+                Ok(Signal::End)
+            }
+            _ => Err(anyhow!("unexpected response to a step request")),
+        }
+    }
+
+    fn step_back(&mut self) -> Result<()> {
+        unsupported("Stepping back")
+    }
+
+    fn process(&mut self) -> Result<RunResult> {
+        let response = self.request(TextRequest::Continue)?;
+        self.run_result(response)
+    }
+
+    fn select_frame(&mut self, _frame_index: Option<usize>) -> Result<()> {
+        unsupported("Selecting frames")
+    }
+}
@@ -0,0 +1,549 @@
+//! JSON messages of the debugger server protocol, shared by the server and `RemoteDebugger`
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use wasminspect_vm::NumVal;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WasmValue {
+    I32 {
+        value: i32,
+    },
+    I64 {
+        value: i64,
+    },
+    F32 {
+        value: f32,
+    },
+    F64 {
+        value: f64,
+    },
+    V128 {
+        #[serde(with = "v128_base64")]
+        value: [u8; 16],
+    },
+}
+
+/// Serializes the little-endian bytes of a `v128` value as a base64 string,
+/// since JSON numbers can't hold 128 bits
+mod v128_base64 {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::convert::TryInto;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 16], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 16], D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let bytes = base64::decode(text).map_err(D::Error::custom)?;
+        bytes.try_into().map_err(|bytes: Vec<u8>| {
+            D::Error::custom(format!(
+                "v128 should be 16 bytes, but {} bytes",
+                bytes.len()
+            ))
+        })
+    }
+}
+
+impl From<WasmValue> for wasminspect_vm::WasmValue {
+    fn from(value: WasmValue) -> Self {
+        match value {
+            WasmValue::I32 { value } => value.into(),
+            WasmValue::I64 { value } => value.into(),
+            WasmValue::F32 { value } => value.into(),
+            WasmValue::F64 { value } => value.into(),
+            WasmValue::V128 { value } => Self::V128(u128::from_le_bytes(value)),
+        }
+    }
+}
+
+impl TryFrom<wasminspect_vm::WasmValue> for WasmValue {
+    type Error = anyhow::Error;
+
+    fn try_from(value: wasminspect_vm::WasmValue) -> Result<Self> {
+        match value {
+            wasminspect_vm::WasmValue::Num(NumVal::I32(value)) => Ok(Self::I32 { value }),
+            wasminspect_vm::WasmValue::Num(NumVal::I64(value)) => Ok(Self::I64 { value }),
+            wasminspect_vm::WasmValue::Num(NumVal::F32(value)) => Ok(Self::F32 {
+                value: value.to_float(),
+            }),
+            wasminspect_vm::WasmValue::Num(NumVal::F64(value)) => Ok(Self::F64 {
+                value: value.to_float(),
+            }),
+            wasminspect_vm::WasmValue::V128(value) => Ok(Self::V128 {
+                value: value.to_le_bytes(),
+            }),
+            wasminspect_vm::WasmValue::Ref(_) => {
+                Err(anyhow!("reference values are not supported over RPC"))
+            }
+        }
+    }
+}
+
+/// A numeric type to decode memory into
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValueType {
+    pub fn size(&self) -> usize {
+        match self {
+            ValueType::I32 | ValueType::F32 => 4,
+            ValueType::I64 | ValueType::F64 => 8,
+        }
+    }
+
+    /// Decodes little-endian values of this type from `bytes`, ignoring a trailing partial value
+    pub fn decode(&self, bytes: &[u8]) -> Vec<WasmValue> {
+        bytes
+            .chunks_exact(self.size())
+            .map(|chunk| {
+                let mut buf = [0; 8];
+                buf[..chunk.len()].copy_from_slice(chunk);
+                let raw = u64::from_le_bytes(buf);
+                match self {
+                    ValueType::I32 => WasmValue::I32 { value: raw as i32 },
+                    ValueType::I64 => WasmValue::I64 { value: raw as i64 },
+                    ValueType::F32 => WasmValue::F32 {
+                        value: f32::from_bits(raw as u32),
+                    },
+                    ValueType::F64 => WasmValue::F64 {
+                        value: f64::from_bits(raw),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// A result value named after the DWARF return type, or `result_N`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct NamedValue {
+    pub name: Option<String>,
+    pub value: WasmValue,
+}
+
+pub type JSNumber = f64;
+
+/// Performance data of a call, returned when requested by `CallExported`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    #[serde(rename = "instructionsExecuted")]
+    pub instructions_executed: u64,
+    #[serde(rename = "functionsCalled")]
+    pub functions_called: u64,
+    #[serde(rename = "peakCallDepth")]
+    pub peak_call_depth: usize,
+    #[serde(rename = "wallTimeMs")]
+    pub wall_time_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WasmImport {
+    Func { name: String },
+    Global { name: String },
+    Mem { name: String },
+    Table { name: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WasmExport {
+    Memory {
+        name: String,
+        #[serde(rename = "memorySize")]
+        memory_size: usize,
+    },
+    Function {
+        name: String,
+    },
+    Global {
+        name: String,
+        mutable: bool,
+        /// The value type, e.g. `i32`
+        #[serde(rename = "valueType")]
+        ty: String,
+    },
+    Table {
+        name: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum StepStyle {
+    In,
+    Over,
+    Out,
+}
+
+/// Instructions between `Progress` messages of `CallExported` unless the request specifies
+pub const DEFAULT_PROGRESS_INTERVAL: u64 = 10000;
+
+fn default_progress_interval() -> u64 {
+    DEFAULT_PROGRESS_INTERVAL
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TextRequest {
+    /// Negotiates the protocol, which must be the first request of a session if sent
+    Handshake {
+        #[serde(rename = "clientVersion")]
+        client_version: String,
+        /// Features the client can use, e.g. `simd`, `multi-memory` and `breakpoints`
+        #[serde(default, rename = "supportedFeatures")]
+        supported_features: Vec<String>,
+    },
+    Version,
+    InitMemory,
+    /// Sends `Progress` with the `requestId` of the request while running
+    CallExported {
+        name: String,
+        args: Vec<WasmValue>,
+        #[serde(
+            default = "default_progress_interval",
+            rename = "progressIntervalInstructions"
+        )]
+        progress_interval_instructions: u64,
+        #[serde(default, rename = "includeStats")]
+        include_stats: bool,
+    },
+    CallResult {
+        values: Vec<JSNumber>,
+    },
+    LoadMemory {
+        name: String,
+        offset: usize,
+        length: usize,
+    },
+    /// Same as `LoadMemory`, but decodes `count` values of `ty`
+    LoadMemoryTyped {
+        name: String,
+        offset: usize,
+        count: usize,
+        ty: ValueType,
+    },
+    StoreMemory {
+        name: String,
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    MemoryChecksum {
+        name: String,
+        offset: usize,
+        length: usize,
+    },
+    MemorySize {
+        name: String,
+    },
+    Continue,
+    Step {
+        style: StepStyle,
+    },
+    /// Reads the globals of the main module unless `moduleIndex` is given
+    ReadGlobals {
+        #[serde(
+            default,
+            rename = "moduleIndex",
+            skip_serializing_if = "Option::is_none"
+        )]
+        module_index: Option<u32>,
+    },
+    ReadGlobal {
+        #[serde(rename = "moduleIndex")]
+        module_index: u32,
+        #[serde(rename = "globalIndex")]
+        global_index: u32,
+    },
+    /// Looks up an exported global of the main module, then one named in the name section
+    ReadGlobalByName {
+        name: String,
+    },
+    /// Fails if the global is immutable or `value` has another type
+    WriteGlobal {
+        #[serde(rename = "moduleIndex")]
+        module_index: u32,
+        #[serde(rename = "globalIndex")]
+        global_index: u32,
+        value: WasmValue,
+    },
+    SetBreakpoint {
+        #[serde(alias = "function", alias = "funcName")]
+        name: String,
+        /// Halts only on the Nth call of the function
+        #[serde(default, rename = "hitCount")]
+        hit_count: Option<u32>,
+    },
+    /// Halts at the first instruction of the line in a file whose path ends with `file`
+    SetBreakpointAtLine {
+        file: String,
+        line: u64,
+    },
+    #[serde(alias = "DeleteBreakpoint")]
+    RemoveBreakpoint {
+        id: u32,
+    },
+    ListBreakpoints,
+}
+
+/// A JSON text message, `TextRequest` with an optional `requestId`
+#[derive(Debug, Deserialize)]
+pub struct TextRequestMessage {
+    #[serde(default, rename = "requestId")]
+    pub request_id: Option<u64>,
+    #[serde(flatten)]
+    pub request: TextRequest,
+}
+
+/// Exports of a module loaded by `Init`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModuleExports {
+    pub name: String,
+    pub exports: Vec<WasmExport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BreakpointInfo {
+    pub id: u32,
+    pub location: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TextResponse {
+    HandshakeResult {
+        #[serde(rename = "serverVersion")]
+        server_version: String,
+        /// Features supported by both of the client and the server
+        #[serde(rename = "acceptedFeatures")]
+        accepted_features: Vec<String>,
+    },
+    Version {
+        value: String,
+    },
+    Init {
+        /// Exports of the main module
+        exports: Vec<WasmExport>,
+        modules: Vec<ModuleExports>,
+    },
+    CallResult {
+        values: Vec<NamedValue>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stats: Option<ExecutionStats>,
+    },
+    CallHost {
+        module: String,
+        field: String,
+        args: Vec<WasmValue>,
+    },
+    LoadMemoryResult {
+        bytes: Vec<u8>,
+    },
+    LoadMemoryTypedResult {
+        values: Vec<WasmValue>,
+    },
+    StoreMemoryResult,
+    Progress {
+        #[serde(rename = "requestId")]
+        request_id: u64,
+        #[serde(rename = "instructionsExecuted")]
+        instructions_executed: u64,
+        #[serde(rename = "elapsedMs")]
+        elapsed_ms: u64,
+    },
+    MemoryChecksumResult {
+        checksum: u32,
+    },
+    MemorySizeResult {
+        size: usize,
+    },
+    Stopped {
+        #[serde(rename = "instOffset")]
+        inst_offset: Option<usize>,
+    },
+    ReadGlobalsResult {
+        values: Vec<WasmValue>,
+        /// Mutability of each of `values`
+        #[serde(default)]
+        mutable: Vec<bool>,
+    },
+    ReadGlobalResult {
+        value: WasmValue,
+        mutable: bool,
+    },
+    /// `value` is `None` if there's no global named so
+    ReadGlobalByNameResult {
+        value: Option<WasmValue>,
+    },
+    WriteGlobalResult,
+    SetBreakpointResult {
+        id: u32,
+    },
+    RemoveBreakpointResult,
+    ListBreakpointsResult {
+        breakpoints: Vec<BreakpointInfo>,
+    },
+    /// `CallExported` halted at a breakpoint or a watchpoint
+    Breakpoint {
+        /// `None` if the halt is not caused by a breakpoint set over RPC
+        id: Option<u32>,
+        location: String,
+        #[serde(rename = "instOffset")]
+        inst_offset: Option<usize>,
+    },
+    /// Pushed without a request when the process halts at a breakpoint set over RPC,
+    /// before the response of the request which resumed the process
+    BreakpointHit {
+        id: u32,
+        #[serde(rename = "funcName")]
+        func_name: String,
+        offset: usize,
+        locals: Vec<WasmValue>,
+    },
+    /// Output of the guest, sent line by line and when the process halts
+    Stdout {
+        data: String,
+    },
+    Stderr {
+        data: String,
+    },
+    Error {
+        message: String,
+    },
+}
+/// A JSON text message, `TextResponse` with `requestId` if it responds to a request with an id
+#[derive(Debug, Serialize)]
+pub struct TextResponseMessage<'a> {
+    #[serde(rename = "requestId", skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    #[serde(flatten)]
+    pub response: &'a TextResponse,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_exported_with_i64_beyond_f64_precision() {
+        let value = (1i64 << 53) + 1;
+        let json = format!(
+            r#"{{"type":"CallExported","name":"f","args":[{{"type":"I64","value":{}}}]}}"#,
+            value
+        );
+        match serde_json::from_str::<TextRequest>(&json).unwrap() {
+            TextRequest::CallExported { args, .. } => {
+                assert_eq!(args, vec![WasmValue::I64 { value }]);
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+    }
+
+    #[test]
+    fn call_with_i64_beyond_f64_precision() {
+        use crate::commands::debugger::{Debugger, RunResult};
+        use crate::debugger::MainDebugger;
+        use std::collections::HashMap;
+        // (func (export "f") (param i64) (result i64) local.get 0)
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7e, 0x01, 0x7e, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export section
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x20, 0x00, 0x0b, // code section
+        ];
+        let mut debugger = MainDebugger::new(vec![], vec![], false).unwrap();
+        debugger
+            .load_main_module(&bytes, "test.wasm".to_string())
+            .unwrap();
+        debugger.instantiate(HashMap::new(), None).unwrap();
+
+        let value = (1i64 << 53) + 1;
+        let json = format!(
+            r#"{{"type":"CallExported","name":"f","args":[{{"type":"I64","value":{}}}]}}"#,
+            value
+        );
+        let args = match serde_json::from_str::<TextRequest>(&json).unwrap() {
+            TextRequest::CallExported { args, .. } => args,
+            other => panic!("unexpected request {:?}", other),
+        };
+        let args = args
+            .into_iter()
+            .map(wasminspect_vm::WasmValue::from)
+            .collect();
+        let results = match debugger.run(Some("f"), args).unwrap() {
+            RunResult::Finish(results) => results,
+            _ => panic!("f didn't finish"),
+        };
+        let results = results
+            .into_iter()
+            .map(WasmValue::try_from)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(results, vec![WasmValue::I64 { value }]);
+        let json = serde_json::to_string(&results[0]).unwrap();
+        assert_eq!(json, format!(r#"{{"type":"I64","value":{}}}"#, value));
+    }
+
+    #[test]
+    fn echo_request_id() {
+        let json = r#"{"type":"CallExported","requestId":7,"name":"f","args":[]}"#;
+        let message = serde_json::from_str::<TextRequestMessage>(json).unwrap();
+        assert_eq!(message.request_id, Some(7));
+        assert!(matches!(message.request, TextRequest::CallExported { .. }));
+        let message = serde_json::from_str::<TextRequestMessage>(r#"{"type":"Version"}"#).unwrap();
+        assert_eq!(message.request_id, None);
+
+        let response = TextResponse::MemorySizeResult { size: 1 };
+        let json = |request_id| {
+            serde_json::to_string(&TextResponseMessage {
+                request_id,
+                response: &response,
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            json(Some(7)),
+            r#"{"requestId":7,"type":"MemorySizeResult","size":1}"#
+        );
+        assert_eq!(json(None), r#"{"type":"MemorySizeResult","size":1}"#);
+    }
+
+    #[test]
+    fn breakpoint_request_aliases() {
+        let request = |json| serde_json::from_str::<TextRequest>(json).unwrap();
+        assert!(matches!(
+            request(r#"{"type":"SetBreakpoint","funcName":"main"}"#),
+            TextRequest::SetBreakpoint { name, hit_count: None } if name == "main"
+        ));
+        assert!(matches!(
+            request(r#"{"type":"DeleteBreakpoint","id":3}"#),
+            TextRequest::RemoveBreakpoint { id: 3 }
+        ));
+    }
+
+    #[test]
+    fn read_globals_module_index() {
+        let request = |json| serde_json::from_str::<TextRequest>(json).unwrap();
+        assert!(matches!(
+            request(r#"{"type":"ReadGlobals"}"#),
+            TextRequest::ReadGlobals { module_index: None }
+        ));
+        assert!(matches!(
+            request(r#"{"type":"ReadGlobals","moduleIndex":2}"#),
+            TextRequest::ReadGlobals {
+                module_index: Some(2)
+            }
+        ));
+        let json = serde_json::to_string(&TextRequest::ReadGlobals { module_index: None }).unwrap();
+        assert_eq!(json, r#"{"type":"ReadGlobals"}"#);
+    }
+}
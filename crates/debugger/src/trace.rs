@@ -0,0 +1,233 @@
+use crate::commands::debugger::TraceFormat;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use wasminspect_vm::{Instruction, InstructionKind};
+
+/// The size of a record in binary traces
+const RECORD_SIZE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InstructionCategory {
+    Control = 0,
+    Parametric = 1,
+    Variable = 2,
+    Table = 3,
+    Memory = 4,
+    Reference = 5,
+    Numeric = 6,
+}
+
+impl InstructionCategory {
+    pub fn of(kind: &InstructionKind) -> Self {
+        use InstructionKind::*;
+        match kind {
+            Unreachable
+            | Nop
+            | Block { .. }
+            | Loop { .. }
+            | If { .. }
+            | Else
+            | End
+            | Br { .. }
+            | BrIf { .. }
+            | BrTable { .. }
+            | Return
+            | Call { .. }
+            | CallIndirect { .. } => Self::Control,
+            Drop | Select | TypedSelect { .. } => Self::Parametric,
+            LocalGet { .. }
+            | LocalSet { .. }
+            | LocalTee { .. }
+            | GlobalGet { .. }
+            | GlobalSet { .. } => Self::Variable,
+            TableGet { .. }
+            | TableSet { .. }
+            | TableSize { .. }
+            | TableGrow { .. }
+            | TableFill { .. }
+            | TableCopy { .. }
+            | TableInit { .. }
+            | ElemDrop { .. } => Self::Table,
+            I32Load { .. }
+            | I64Load { .. }
+            | F32Load { .. }
+            | F64Load { .. }
+            | I32Load8S { .. }
+            | I32Load8U { .. }
+            | I32Load16S { .. }
+            | I32Load16U { .. }
+            | I64Load8S { .. }
+            | I64Load8U { .. }
+            | I64Load16S { .. }
+            | I64Load16U { .. }
+            | I64Load32S { .. }
+            | I64Load32U { .. }
+            | I32Store { .. }
+            | I64Store { .. }
+            | F32Store { .. }
+            | F64Store { .. }
+            | I32Store8 { .. }
+            | I32Store16 { .. }
+            | I64Store8 { .. }
+            | I64Store16 { .. }
+            | I64Store32 { .. }
+            | MemorySize { .. }
+            | MemoryGrow { .. }
+            | MemoryInit { .. }
+            | DataDrop { .. }
+            | MemoryCopy { .. }
+            | MemoryFill { .. } => Self::Memory,
+            RefNull { .. } | RefIsNull | RefFunc { .. } => Self::Reference,
+            _ => Self::Numeric,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        use InstructionCategory::*;
+        [
+            Control, Parametric, Variable, Table, Memory, Reference, Numeric,
+        ]
+        .get(value as usize)
+        .copied()
+    }
+}
+
+/// A fixed-size entry of binary traces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub offset: u32,
+    pub category: InstructionCategory,
+    pub call_depth: u8,
+}
+
+impl TraceRecord {
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[4] = self.category as u8;
+        bytes[5] = self.call_depth;
+        // bytes[6..8] are reserved
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Result<Self> {
+        let offset = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let category = InstructionCategory::from_u8(bytes[4])
+            .ok_or_else(|| anyhow!("invalid instruction category {}", bytes[4]))?;
+        Ok(Self {
+            offset,
+            category,
+            call_depth: bytes[5],
+        })
+    }
+}
+
+/// Formats a line of text traces, where `callee` is the name of the function called by `inst`
+fn text_line(
+    offset: usize,
+    call_depth: usize,
+    inst: Option<&InstructionKind>,
+    callee: Option<&str>,
+) -> String {
+    let line = match inst {
+        Some(kind) => format!("{: <3} 0x{:>08x}: {:?}", call_depth, offset, kind),
+        None => format!("{: <3} 0x{:>08x}: <unknown>", call_depth, offset),
+    };
+    match callee {
+        Some(callee) => format!("{} <{}>", line, callee),
+        None => line,
+    }
+}
+
+/// Writes executed instructions to a trace file
+pub struct Tracer {
+    writer: BufWriter<File>,
+    format: TraceFormat,
+}
+
+impl Tracer {
+    pub fn create(path: &str, format: TraceFormat) -> Result<Self> {
+        let file = File::create(path).map_err(|err| anyhow!("{}: {}", path, err))?;
+        let mut writer = BufWriter::new(file);
+        if format == TraceFormat::Csv {
+            writeln!(writer, "offset,instruction,call_depth,stack_depth")?;
+        }
+        Ok(Self { writer, format })
+    }
+
+    /// Records an instruction about to be executed with the number of frames
+    /// and values on the stack
+    pub fn record(
+        &mut self,
+        inst: &Instruction,
+        call_depth: usize,
+        stack_depth: usize,
+        callee: Option<&str>,
+    ) -> std::io::Result<()> {
+        match self.format {
+            TraceFormat::Text => {
+                writeln!(
+                    self.writer,
+                    "{}",
+                    text_line(inst.offset, call_depth, Some(&inst.kind), callee)
+                )
+            }
+            TraceFormat::Binary => {
+                let record = TraceRecord {
+                    offset: inst.offset as u32,
+                    category: InstructionCategory::of(&inst.kind),
+                    call_depth: call_depth.min(u8::MAX as usize) as u8,
+                };
+                self.writer.write_all(&record.encode())
+            }
+            TraceFormat::Csv => writeln!(
+                self.writer,
+                "0x{:x},{},{},{}",
+                inst.offset,
+                inst.kind.mnemonic(),
+                call_depth,
+                stack_depth
+            ),
+        }
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Decodes a binary trace into the text format, returning the number of records
+pub fn convert_binary_trace(
+    binary_path: &str,
+    text_path: &str,
+    insts: &HashMap<usize, Instruction>,
+) -> Result<usize> {
+    let input = File::open(binary_path).map_err(|err| anyhow!("{}: {}", binary_path, err))?;
+    let output = File::create(text_path).map_err(|err| anyhow!("{}: {}", text_path, err))?;
+    let mut reader = BufReader::new(input);
+    let mut writer = BufWriter::new(output);
+    let mut buffer = [0; RECORD_SIZE];
+    let mut count = 0;
+    loop {
+        match reader.read_exact(&mut buffer) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let record = TraceRecord::decode(&buffer)?;
+        let offset = record.offset as usize;
+        let kind = insts.get(&offset).map(|inst| &inst.kind);
+        writeln!(
+            writer,
+            "{}",
+            text_line(offset, record.call_depth as usize, kind, None)
+        )?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
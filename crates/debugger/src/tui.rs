@@ -0,0 +1,370 @@
+use crate::commands::command::{CommandContext, CommandResult};
+use crate::commands::debugger::{Debugger, OutputPrinter};
+use crate::commands::format::{format_value, type_name};
+use crate::commands::list::next_line_info;
+use crate::process::Process;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Terminal;
+use std::{cell::RefCell, io, rc::Rc};
+
+/// Collects command outputs to show them in the output pane
+pub struct BufferPrinter {
+    lines: Rc<RefCell<Vec<String>>>,
+}
+
+impl BufferPrinter {
+    pub fn new(lines: Rc<RefCell<Vec<String>>>) -> Self {
+        Self { lines }
+    }
+
+    fn push(&self, output: &str) {
+        let output = strip_escape_sequences(output);
+        self.lines
+            .borrow_mut()
+            .extend(output.lines().map(String::from));
+    }
+}
+
+impl OutputPrinter for BufferPrinter {
+    fn println(&self, output: &str) {
+        self.push(output);
+    }
+    fn eprintln(&self, output: &str) {
+        self.push(output);
+    }
+}
+
+fn strip_escape_sequences(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Source,
+    Locals,
+    Output,
+    Disassembly,
+}
+
+impl Pane {
+    fn next(self) -> Self {
+        match self {
+            Pane::Source => Pane::Locals,
+            Pane::Locals => Pane::Output,
+            Pane::Output => Pane::Disassembly,
+            Pane::Disassembly => Pane::Source,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+struct App {
+    focus: Pane,
+    input: String,
+    last_line: Option<String>,
+    /// Scroll offsets relative to the auto-followed position of each pane
+    scrolls: [i32; 4],
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+fn pane_block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title)
+}
+
+/// Returns the first visible line to keep `anchor` around the middle of the pane
+fn scroll_top(anchor: usize, height: u16, offset: i32) -> u16 {
+    let top = anchor as i32 - (height as i32) / 2 + offset;
+    top.max(0) as u16
+}
+
+fn current_style() -> Style {
+    Style::default()
+        .fg(Color::Black)
+        .bg(Color::Cyan)
+        .add_modifier(Modifier::BOLD)
+}
+
+fn source_lines<D: Debugger>(
+    debugger: &D,
+    context: &CommandContext,
+) -> (Vec<Line<'static>>, usize) {
+    let line_info = match next_line_info(debugger, context.sourcemap.as_ref()) {
+        Ok(line_info) => line_info,
+        Err(err) => return (vec![Line::from(err.to_string())], 0),
+    };
+    let source = match std::fs::read_to_string(&line_info.filepath) {
+        Ok(source) => source,
+        Err(err) => {
+            let output = format!("{}: {}", line_info.filepath, err);
+            return (vec![Line::from(output)], 0);
+        }
+    };
+    let breakpoint_lines = debugger
+        .instruction_breakpoints()
+        .into_iter()
+        .filter_map(|offset| context.sourcemap.find_line_info(offset))
+        .filter(|info| info.filepath == line_info.filepath)
+        .filter_map(|info| info.line)
+        .collect::<Vec<_>>();
+    let current = line_info.line.unwrap_or(0) as usize;
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            // line_info.line begin with 1
+            let index = index + 1;
+            let marker = if breakpoint_lines.contains(&(index as u64)) {
+                "*"
+            } else {
+                " "
+            };
+            let arrow = if index == current { "->" } else { "  " };
+            let text = format!("{}{} {: <4} {}", marker, arrow, index, line);
+            if index == current {
+                Line::from(Span::styled(text, current_style()))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    (lines, current.saturating_sub(1))
+}
+
+fn locals_lines<D: Debugger>(debugger: &D) -> Vec<Line<'static>> {
+    let format = debugger.get_opts().value_format;
+    debugger
+        .frame_locals()
+        .unwrap_or_default()
+        .iter()
+        .map(|local| {
+            Line::from(format!(
+                "{: <3} {}: {} = {}",
+                local.index,
+                local.name.as_deref().unwrap_or("<unnamed>"),
+                type_name(local.wasm_type),
+                format_value(&local.value, format)
+            ))
+        })
+        .collect()
+}
+
+fn disassembly_lines<D: Debugger>(debugger: &D) -> (Vec<Line<'static>>, usize) {
+    let (insts, inst_index) = match debugger.selected_instructions() {
+        Ok(selected) => selected,
+        Err(err) => return (vec![Line::from(err.to_string())], 0),
+    };
+    let lines = insts
+        .iter()
+        .enumerate()
+        .map(|(index, inst)| {
+            let prefix = if index == inst_index { "->" } else { "  " };
+            let text = format!("{} 0x{:>08x}: {:?}", prefix, inst.offset, inst.kind);
+            if index == inst_index {
+                Line::from(Span::styled(text, current_style()))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    (lines, inst_index)
+}
+
+impl App {
+    fn submit<D: Debugger>(
+        &mut self,
+        process: &mut Process<D>,
+        context: &CommandContext,
+    ) -> Result<Option<CommandResult>> {
+        let input = std::mem::take(&mut self.input);
+        let line = if input.trim().is_empty() {
+            match self.last_line.clone() {
+                Some(line) => line,
+                None => return Ok(None),
+            }
+        } else {
+            self.last_line = Some(input.clone());
+            input
+        };
+        context.printer.println(&format!("(wasminspect) {}", line));
+        self.scrolls = [0; 4];
+        let result = process.dispatch_command(&line, context);
+        match result {
+            Ok(Some(CommandResult::ProcessFinish(values))) => {
+                let output = format!("Process finished: {:?}", values);
+                context.printer.println(&output);
+                Ok(None)
+            }
+            Ok(result) => Ok(result),
+            Err(err) => {
+                context.printer.eprintln(&err.to_string());
+                Ok(None)
+            }
+        }
+    }
+
+    fn scroll(&mut self, delta: i32) {
+        self.scrolls[self.focus.index()] += delta;
+    }
+}
+
+fn event_loop<D: Debugger>(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    process: &mut Process<D>,
+    context: &CommandContext,
+) -> Result<()> {
+    loop {
+        terminal.draw(|f| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+                .split(f.size());
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    [
+                        Constraint::Percentage(25),
+                        Constraint::Percentage(50),
+                        Constraint::Percentage(25),
+                    ]
+                    .as_ref(),
+                )
+                .split(rows[1]);
+            let console = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                .split(columns[1]);
+
+            let debugger = &process.debugger;
+            let (lines, anchor) = source_lines(debugger, context);
+            let top = scroll_top(anchor, rows[0].height, app.scrolls[Pane::Source.index()]);
+            let source = Paragraph::new(lines)
+                .block(pane_block("Source", app.focus == Pane::Source))
+                .scroll((top, 0));
+            f.render_widget(source, rows[0]);
+
+            let top = app.scrolls[Pane::Locals.index()].max(0) as u16;
+            let locals = Paragraph::new(locals_lines(debugger))
+                .block(pane_block("Locals", app.focus == Pane::Locals))
+                .scroll((top, 0));
+            f.render_widget(locals, columns[0]);
+
+            let output_lines = app
+                .output
+                .borrow()
+                .iter()
+                .map(|line| Line::from(line.clone()))
+                .collect::<Vec<_>>();
+            let height = console[0].height.saturating_sub(2) as i32;
+            let top = output_lines.len() as i32 - height + app.scrolls[Pane::Output.index()];
+            let output = Paragraph::new(output_lines)
+                .block(pane_block("Output", app.focus == Pane::Output))
+                .scroll((top.max(0) as u16, 0));
+            f.render_widget(output, console[0]);
+
+            let input = Paragraph::new(format!("(wasminspect) {}", app.input))
+                .block(pane_block("Command", false));
+            f.render_widget(input, console[1]);
+            let cursor_x =
+                console[1].x + 1 + "(wasminspect) ".len() as u16 + app.input.len() as u16;
+            f.set_cursor(cursor_x, console[1].y + 1);
+
+            let (lines, anchor) = disassembly_lines(debugger);
+            let top = scroll_top(
+                anchor,
+                columns[2].height,
+                app.scrolls[Pane::Disassembly.index()],
+            );
+            let disassembly = Paragraph::new(lines)
+                .block(pane_block("Disassembly", app.focus == Pane::Disassembly))
+                .scroll((top, 0));
+            f.render_widget(disassembly, columns[2]);
+        })?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                return Ok(());
+            }
+            KeyCode::Tab => app.focus = app.focus.next(),
+            KeyCode::Enter => {
+                if let Some(CommandResult::Exit) = app.submit(process, context)? {
+                    return Ok(());
+                }
+            }
+            KeyCode::Char(c) => app.input.push(c),
+            KeyCode::Backspace => {
+                app.input.pop();
+            }
+            KeyCode::Up => app.scroll(-1),
+            KeyCode::Down => app.scroll(1),
+            KeyCode::PageUp => app.scroll(-10),
+            KeyCode::PageDown => app.scroll(10),
+            _ => {}
+        }
+    }
+}
+
+/// Runs the split-pane terminal interface until the user exits
+pub fn run_tui<D: Debugger>(process: &mut Process<D>, context: &mut CommandContext) -> Result<()> {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    context.printer = Box::new(BufferPrinter::new(output.clone()));
+    let mut app = App {
+        focus: Pane::Source,
+        input: String::new(),
+        last_line: None,
+        scrolls: [0; 4],
+        output,
+    };
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = event_loop(&mut terminal, &mut app, process, context);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
@@ -0,0 +1,59 @@
+//! Measures branching in a loop-heavy function.
+//!
+//! Run with `cargo bench -p wasminspect-vm --bench branch`.
+
+mod common;
+
+use wasminspect_vm::WasmValue;
+
+/// Numbers of `local.set` sequences in the `else` arm, which is skipped by
+/// the branch at `else` on every iteration. The time shouldn't grow with them
+/// since the matching `end` is looked up instead of scanned for
+const ELSE_SIZES: [usize; 3] = [1, 64, 1024];
+const ITERATIONS: i32 = 100_000;
+
+/// (func (export "count") (param i32) (result i32) (local i32)
+///   (block
+///     (loop
+///       (br_if 1 (i32.eqz (local.get 0)))
+///       (if (local.get 0)
+///         (then (local.set 1 (i32.add (local.get 1) (i32.const 1))))
+///         (else (local.set 1 (i32.add (local.get 1) (i32.const 1))) ...))
+///       (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+///       (br 0)))
+///   (local.get 1))
+fn loop_module(else_size: usize) -> Vec<u8> {
+    let increment = [0x20, 0x01, 0x41, 0x01, 0x6a, 0x21, 0x01];
+    let mut body = vec![0x01, 0x01, 0x7f, 0x02, 0x40, 0x03, 0x40];
+    body.extend_from_slice(&[0x20, 0x00, 0x45, 0x0d, 0x01]);
+    body.extend_from_slice(&[0x20, 0x00, 0x04, 0x40]);
+    body.extend_from_slice(&increment);
+    body.push(0x05);
+    body.extend_from_slice(&increment.repeat(else_size));
+    body.push(0x0b);
+    body.extend_from_slice(&[0x20, 0x00, 0x41, 0x01, 0x6b, 0x21, 0x00, 0x0c, 0x00]);
+    body.extend_from_slice(&[0x0b, 0x0b, 0x20, 0x01, 0x0b]);
+
+    let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    common::section(0x01, &[0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f], &mut bytes);
+    common::section(0x03, &[0x01, 0x00], &mut bytes);
+    let mut export = vec![0x01, 0x05];
+    export.extend_from_slice(b"count");
+    export.extend_from_slice(&[0x00, 0x00]);
+    common::section(0x07, &export, &mut bytes);
+    common::code_section(&[body], &mut bytes);
+    bytes
+}
+
+fn main() {
+    for else_size in ELSE_SIZES {
+        let (results, elapsed) = common::time_run(loop_module(else_size), "count", ITERATIONS);
+        assert_eq!(results, vec![WasmValue::I32(ITERATIONS)]);
+        println!(
+            "{} iterations over an else arm of {} instructions: {:?}",
+            ITERATIONS,
+            else_size * 4,
+            elapsed
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Measures `call_indirect` calling the same table entry in a loop, against
+//! alternating between two entries so that every call misses the callee cache.
+//!
+//! Run with `cargo bench -p wasminspect-vm --bench call_indirect`.
+
+mod common;
+
+use wasminspect_vm::WasmValue;
+
+const ITERATIONS: i32 = 100_000;
+
+/// (table 2 funcref)
+/// (elem (i32.const 0) $one $one)
+/// (func $one (result i32) (i32.const 1))
+/// (func (export "count") (param i32) (result i32) (local i32)
+///   (block
+///     (loop
+///       (br_if 1 (i32.eqz (local.get 0)))
+///       (local.set 1 (i32.add (call_indirect (result i32) <index>) (local.get 1)))
+///       (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+///       (br 0)))
+///   (local.get 1))
+///
+/// where `<index>` is `(i32.const 0)`, or `(i32.and (local.get 0) (i32.const 1))`
+/// if `alternate` is set.
+fn dispatch_module(alternate: bool) -> Vec<u8> {
+    let one = vec![0x00, 0x41, 0x01, 0x0b];
+    let mut count = vec![0x01, 0x01, 0x7f, 0x02, 0x40, 0x03, 0x40];
+    count.extend_from_slice(&[0x20, 0x00, 0x45, 0x0d, 0x01]);
+    if alternate {
+        count.extend_from_slice(&[0x20, 0x00, 0x41, 0x01, 0x71]);
+    } else {
+        count.extend_from_slice(&[0x41, 0x00]);
+    }
+    count.extend_from_slice(&[0x11, 0x00, 0x00, 0x20, 0x01, 0x6a, 0x21, 0x01]);
+    count.extend_from_slice(&[0x20, 0x00, 0x41, 0x01, 0x6b, 0x21, 0x00, 0x0c, 0x00]);
+    count.extend_from_slice(&[0x0b, 0x0b, 0x20, 0x01, 0x0b]);
+
+    let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    let types = [0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f];
+    common::section(0x01, &types, &mut bytes);
+    common::section(0x03, &[0x02, 0x00, 0x01], &mut bytes);
+    common::section(0x04, &[0x01, 0x70, 0x00, 0x02], &mut bytes);
+    let mut export = vec![0x01, 0x05];
+    export.extend_from_slice(b"count");
+    export.extend_from_slice(&[0x00, 0x01]);
+    common::section(0x07, &export, &mut bytes);
+    common::section(
+        0x09,
+        &[0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, 0x00, 0x00],
+        &mut bytes,
+    );
+    common::code_section(&[one, count], &mut bytes);
+    bytes
+}
+
+fn main() {
+    for &(alternate, label) in &[(false, "the same entry"), (true, "alternating entries")] {
+        let (results, elapsed) = common::time_run(dispatch_module(alternate), "count", ITERATIONS);
+        assert_eq!(results, vec![WasmValue::I32(ITERATIONS)]);
+        println!("{} iterations over {}: {:?}", ITERATIONS, label, elapsed);
+    }
+}
@@ -0,0 +1,62 @@
+//! Helpers to assemble modules and time their execution in benchmarks.
+
+use std::time::{Duration, Instant};
+use wasminspect_vm::{Config, WasmInstance, WasmValue};
+
+fn leb128(mut value: usize, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+pub fn section(id: u8, contents: &[u8], bytes: &mut Vec<u8>) {
+    bytes.push(id);
+    leb128(contents.len(), bytes);
+    bytes.extend_from_slice(contents);
+}
+
+/// Appends the code section of function `bodies`, each of which starts with its locals
+pub fn code_section(bodies: &[Vec<u8>], bytes: &mut Vec<u8>) {
+    let mut code = Vec::new();
+    leb128(bodies.len(), &mut code);
+    for body in bodies {
+        leb128(body.len(), &mut code);
+        code.extend_from_slice(body);
+    }
+    section(0x0a, &code, bytes);
+}
+
+/// Number of timed calls, of which the fastest is reported to reduce the noise
+pub const SAMPLES: usize = 10;
+
+/// Calls the exported `func_name` of `bytes` with `iterations` `SAMPLES` times,
+/// and returns its results with the fastest elapsed time
+pub fn time_run(
+    mut bytes: Vec<u8>,
+    func_name: &str,
+    iterations: i32,
+) -> (Vec<WasmValue>, Duration) {
+    let mut instance = WasmInstance::new();
+    let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+    let mut results = Vec::new();
+    let mut fastest = Duration::MAX;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        results = instance
+            .run(
+                module_index,
+                Some(func_name.to_string()),
+                vec![WasmValue::I32(iterations)],
+                &Config::default(),
+            )
+            .unwrap();
+        fastest = fastest.min(start.elapsed());
+    }
+    (results, fastest)
+}
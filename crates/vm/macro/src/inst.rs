@@ -26,6 +26,9 @@ pub fn define_instr_kind(ast: proc_macro2::TokenStream) -> Result<proc_macro2::T
     // Accept ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*)
 
     let mut tokens = proc_macro2::TokenStream::new();
+    let mut ordinal_arms = Vec::new();
+    let mut mnemonics = Vec::new();
+    let mut immediates_arms = Vec::new();
     let mut iter = ast.into_iter();
 
     loop {
@@ -57,8 +60,23 @@ pub fn define_instr_kind(ast: proc_macro2::TokenStream) -> Result<proc_macro2::T
             iter.next().expect("unexpected end of input").to_string(),
             ">"
         );
-        iter.next().expect("unexpected end of input");
+        let visit = iter.next().expect("unexpected end of input");
 
+        let mnemonic = wat_mnemonic(&visit.to_string());
+        let fields = payload.as_ref().map(payload_fields).unwrap_or_default();
+        let ordinal = mnemonics.len();
+        ordinal_arms.push(quote! {
+            InstructionKind::#op { .. } => #ordinal
+        });
+        mnemonics.push(mnemonic);
+        // `*_byte` fields are reserved bytes in the binary format
+        let immediates = fields
+            .iter()
+            .filter(|field| !field.to_string().ends_with("_byte"))
+            .collect::<Vec<_>>();
+        immediates_arms.push(quote! {
+            InstructionKind::#op { #(#immediates,)* .. } => vec![#(#immediates as &dyn Immediate),*]
+        });
         tokens.extend(build_instr_kind_case(op, payload));
     }
 
@@ -67,9 +85,77 @@ pub fn define_instr_kind(ast: proc_macro2::TokenStream) -> Result<proc_macro2::T
         pub enum InstructionKind {
             #tokens
         }
+
+        impl InstructionKind {
+            /// Mnemonics of all kinds indexed by `ordinal`
+            pub const MNEMONICS: &'static [&'static str] = &[#(#mnemonics),*];
+
+            /// The index of the kind in the declaration order, which is less than `MNEMONICS.len()`
+            pub fn ordinal(&self) -> usize {
+                match self {
+                    #(#ordinal_arms),*
+                }
+            }
+
+            /// The name of the instruction in the text format
+            pub fn mnemonic(&self) -> &'static str {
+                Self::MNEMONICS[self.ordinal()]
+            }
+
+            /// Immediate operands in the order of the text format
+            pub fn immediates(&self) -> Vec<&dyn Immediate> {
+                match self {
+                    #(#immediates_arms),*
+                }
+            }
+        }
     })
 }
 
+/// Names of the fields in a payload like `{ memarg: $crate::MemArg, lane: u8 }`
+fn payload_fields(payload: &proc_macro2::TokenStream) -> Vec<proc_macro2::Ident> {
+    let mut fields = Vec::new();
+    let mut expects_field = true;
+    for token in payload.clone() {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) if expects_field => {
+                fields.push(ident);
+                expects_field = false;
+            }
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                expects_field = true;
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Converts a visitor name like `visit_i32_atomic_rmw8_add_u` to the text
+/// format mnemonic `i32.atomic.rmw8.add_u`
+fn wat_mnemonic(visit: &str) -> String {
+    const NAMESPACES: &[&str] = &[
+        "i32", "i64", "f32", "f64", "v128", "i8x16", "i16x8", "i32x4", "i64x2", "f32x4", "f64x2",
+        "local", "global", "memory", "table", "ref", "data", "elem", "atomic",
+    ];
+    let name = visit.trim_start_matches("visit_");
+    if name == "typed_select" {
+        return "select".to_string();
+    }
+    let (namespace, rest) = match name.split_once('_') {
+        Some((namespace, rest)) if NAMESPACES.contains(&namespace) => (namespace, rest),
+        _ => return name.to_string(),
+    };
+    let rest = match rest.strip_prefix("atomic_") {
+        Some(atomic) => match atomic.split_once('_') {
+            Some((rmw, op)) if rmw.starts_with("rmw") => format!("atomic.{}.{}", rmw, op),
+            _ => format!("atomic.{}", atomic),
+        },
+        None => rest.to_string(),
+    };
+    format!("{}.{}", namespace, rest)
+}
+
 fn build_instr_kind_case(
     op: proc_macro2::Ident,
     payload: Option<proc_macro2::TokenStream>,
@@ -25,3 +25,7 @@ pub type ElemAddr = LinkableAddress<Rc<RefCell<ElementInstance>>>;
 
 use crate::data::DataInstance;
 pub type DataAddr = LinkableAddress<Rc<RefCell<DataInstance>>>;
+
+use crate::tag::TagInstance;
+pub type TagAddr = LinkableAddress<TagInstance>;
+pub type ResolvedTagAddr = GlobalAddress<TagInstance>;
@@ -1,6 +1,16 @@
+use std::time::Duration;
 use wasmparser::WasmFeatures;
 
 #[derive(Default)]
 pub struct Config {
     pub features: WasmFeatures,
+    /// Aborts the execution with `Trap::ExecutionLimitExceeded` when exceeded
+    pub execution_limit: Option<ExecutionLimit>,
+}
+
+/// A budget of an execution counted from `Executor::new` or `Executor::restart_execution_limit`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionLimit {
+    WallClock(Duration),
+    InstructionCount(u64),
 }
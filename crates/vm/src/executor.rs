@@ -1,5 +1,5 @@
-use crate::address::{DataAddr, ElemAddr, FuncAddr, GlobalAddr, MemoryAddr, TableAddr};
-use crate::config::Config;
+use crate::address::{DataAddr, ElemAddr, FuncAddr, GlobalAddr, MemoryAddr, TableAddr, TagAddr};
+use crate::config::{Config, ExecutionLimit};
 use crate::func::*;
 use crate::inst::{Instruction, InstructionKind};
 use crate::interceptor::Interceptor;
@@ -7,20 +7,41 @@ use crate::memory::MemoryInstance;
 use crate::module::*;
 use crate::stack::{CallFrame, Label, ProgramCounter, Stack, StackValue};
 use crate::store::*;
+use crate::tag::Exception;
 use crate::value::{Copysign, Nearest, RefType, RefVal, TruncSat, TruncTo};
 use crate::value::{
     ExtendInto, FromLittleEndian, IntoLittleEndian, NativeValue, Value, F32, F64, I32, I64, U32,
     U64,
 };
-use crate::{data, elem, memory, stack, table, value};
-use wasmparser::{BlockType, FuncType, ValType};
+use crate::{data, elem, memory, simd, stack, table, value};
+use wasmparser::{BlockType, FuncType, MemArg, ValType};
 
 use std::convert::TryInto;
+use std::rc::Rc;
+use std::time::Instant;
 use std::{ops::*, usize};
 
 #[derive(Debug)]
 pub enum Trap {
-    Unreachable,
+    UnreachableInstruction {
+        /// The offset of the `unreachable` instruction
+        offset: usize,
+    },
+    OutOfBoundsMemoryAccess {
+        address: usize,
+        /// The size of the accessed memory in bytes
+        size: usize,
+    },
+    TableOutOfBounds {
+        index: usize,
+        table_size: usize,
+    },
+    DivisionByZero,
+    IntegerOverflow,
+    StackOverflow {
+        /// The number of frames on the call stack
+        depth: usize,
+    },
     Memory(memory::Error),
     Stack(stack::Error),
     Table(table::Error),
@@ -55,6 +76,76 @@ pub enum Trap {
         base: u32,
         offset: u64,
     },
+    HostFunctionTimeout {
+        name: String,
+        duration: std::time::Duration,
+    },
+    UndefinedTag(usize),
+    /// No function is exported with the name
+    UndefinedExport(String),
+    UncaughtException {
+        values: Vec<Value>,
+    },
+    InvalidRethrow {
+        relative_depth: u32,
+    },
+    TailCallTypeMismatch {
+        callee_name: String,
+        expected: Vec<ValType>,
+        actual: Vec<ValType>,
+    },
+    ExecutionLimitExceeded(ExecutionLimit),
+    /// A trap raised by the instruction at `location`
+    Located {
+        trap: Box<Trap>,
+        location: TrapLocation,
+    },
+}
+
+/// Where a trap was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapLocation {
+    /// The offset of the trapping instruction in the code section
+    pub offset: usize,
+    /// The source file and line resolved from debug info, if any
+    pub source: Option<(String, u64)>,
+}
+
+impl std::fmt::Display for TrapLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Some((file, line)) => write!(f, "{}:{}", file, line),
+            None => write!(f, "offset 0x{:x}", self.offset),
+        }
+    }
+}
+
+impl Trap {
+    /// Returns the trap without its location
+    pub fn kind(&self) -> &Trap {
+        match self {
+            Self::Located { trap, .. } => trap.kind(),
+            other => other,
+        }
+    }
+
+    pub fn location(&self) -> Option<&TrapLocation> {
+        match self {
+            Self::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Attaches `location` to the trap, replacing the existing one
+    pub fn with_location(self, location: TrapLocation) -> Self {
+        match self {
+            Self::Located { trap, .. } => Self::Located { trap, location },
+            trap => Self::Located {
+                trap: Box::new(trap),
+                location,
+            },
+        }
+    }
 }
 
 impl std::error::Error for Trap {}
@@ -79,13 +170,57 @@ impl std::fmt::Display for Trap {
  >> but actual implementation has      {:?}",
                 callee_name, expected, actual
             ),
+            Self::UncaughtException { values } => {
+                write!(f, "uncaught exception with values {:?}", values)
+            }
+            Self::TailCallTypeMismatch {
+                callee_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tail call type mismatch for '{}': caller returns {:?} but callee returns {:?}",
+                callee_name, expected, actual
+            ),
             Self::UndefinedFunc(addr) => write!(f, "uninitialized element {:?}", addr),
-            Self::Unreachable => write!(f, "unreachable"),
+            Self::UnreachableInstruction { .. } => write!(f, "unreachable executed"),
+            Self::OutOfBoundsMemoryAccess { address, size } => write!(
+                f,
+                "out of bounds memory access at {} but the size of memory is {}",
+                address, size
+            ),
+            Self::TableOutOfBounds { index, table_size } => write!(
+                f,
+                "undefined element: out of bounds table access at {} but the size of table is {}",
+                index, table_size
+            ),
+            Self::DivisionByZero => write!(f, "integer divide by zero"),
+            Self::IntegerOverflow => write!(f, "integer overflow"),
+            Self::StackOverflow { depth } => {
+                write!(f, "call stack exhausted at the depth of {} frames", depth)
+            }
             Self::MemoryAddrOverflow { base, offset } => write!(
                 f,
                 "out of bounds memory access: memory address overflow (base: {}, offset: {})",
                 base, offset
             ),
+            Self::ExecutionLimitExceeded(ExecutionLimit::WallClock(duration)) => {
+                write!(f, "execution did not complete within {:?}", duration)
+            }
+            Self::ExecutionLimitExceeded(ExecutionLimit::InstructionCount(count)) => {
+                write!(
+                    f,
+                    "execution did not complete within {} instructions",
+                    count
+                )
+            }
+            Self::HostFunctionTimeout { name, duration } => write!(
+                f,
+                "host function '{}' did not complete within {:?}",
+                name, duration
+            ),
+            Self::UndefinedExport(name) => write!(f, "function '{}' is not exported", name),
+            Self::Located { trap, location } => write!(f, "{} at {}", trap, location),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -93,7 +228,16 @@ impl std::fmt::Display for Trap {
 
 impl From<table::Error> for Trap {
     fn from(e: table::Error) -> Self {
-        Trap::Table(e)
+        match e {
+            table::Error::AccessOutOfBounds {
+                try_to_access: Some(index),
+                size,
+            } => Trap::TableOutOfBounds {
+                index,
+                table_size: size,
+            },
+            e => Trap::Table(e),
+        }
     }
 }
 
@@ -105,7 +249,26 @@ impl From<elem::Error> for Trap {
 
 impl From<memory::Error> for Trap {
     fn from(e: memory::Error) -> Self {
-        Trap::Memory(e)
+        match e {
+            memory::Error::AccessOutOfBounds {
+                try_to_access: Some(address),
+                memory_size,
+            } => Trap::OutOfBoundsMemoryAccess {
+                address,
+                size: memory_size,
+            },
+            e => Trap::Memory(e),
+        }
+    }
+}
+
+impl From<value::Error> for Trap {
+    fn from(e: value::Error) -> Self {
+        match e {
+            value::Error::ZeroDivision => Trap::DivisionByZero,
+            value::Error::IntegerOverflow => Trap::IntegerOverflow,
+            e => Trap::Value(e),
+        }
     }
 }
 
@@ -115,6 +278,13 @@ impl From<data::Error> for Trap {
     }
 }
 
+/// A handler of a `try` block. `catch_all` doesn't bind the exception values
+enum Handler {
+    Catch { inst_index: usize, bind: bool },
+    Delegate { relative_depth: usize },
+    None,
+}
+
 pub enum Signal {
     Next,
     Breakpoint,
@@ -140,9 +310,29 @@ impl std::fmt::Display for ReturnValError {
     }
 }
 
+/// The wall clock is read once every `CLOCK_CHECK_MASK + 1` instructions
+/// when `ExecutionLimit::WallClock` is set
+const CLOCK_CHECK_MASK: u64 = (1 << 13) - 1;
+
 pub struct Executor {
     pub pc: ProgramCounter,
     pub stack: Stack,
+    executed_instructions: u64,
+    started_at: Instant,
+    indirect_call_cache: Option<IndirectCallCache>,
+}
+
+/// The callee of the last `call_indirect` which passed the type check, reused
+/// while the same table entry is called with the same type
+#[derive(Clone, Copy)]
+struct IndirectCallCache {
+    module_index: ModuleIndex,
+    table_index: u32,
+    type_index: u32,
+    elem_index: usize,
+    /// `TableInstance::version` when the callee was resolved
+    table_version: u64,
+    func_addr: FuncAddr,
 }
 
 impl Executor {
@@ -152,7 +342,39 @@ impl Executor {
         stack.push_label(Label::Return {
             arity: initial_arity,
         });
-        Self { pc, stack }
+        Self {
+            pc,
+            stack,
+            executed_instructions: 0,
+            started_at: Instant::now(),
+            indirect_call_cache: None,
+        }
+    }
+
+    /// Starts counting `Config::execution_limit` again, e.g. when resuming from a breakpoint
+    pub fn restart_execution_limit(&mut self) {
+        self.executed_instructions = 0;
+        self.started_at = Instant::now();
+    }
+
+    fn check_execution_limit(&mut self, config: &Config) -> ExecResult<()> {
+        let limit = match config.execution_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        self.executed_instructions += 1;
+        let exceeded = match limit {
+            ExecutionLimit::InstructionCount(count) => self.executed_instructions > count,
+            ExecutionLimit::WallClock(duration) => {
+                self.executed_instructions & CLOCK_CHECK_MASK == 0
+                    && self.started_at.elapsed() > duration
+            }
+        };
+        if exceeded {
+            Err(Trap::ExecutionLimitExceeded(limit))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn pop_result(&mut self, return_ty: Vec<ValType>) -> ReturnValResult {
@@ -167,9 +389,12 @@ impl Executor {
         Ok(results.into_iter().rev().collect())
     }
 
+    fn current_func<'a>(&self, store: &'a Store) -> &'a DefinedFunctionInstance {
+        store.func_global(self.pc.exec_addr()).defined().unwrap()
+    }
+
     fn current_func_insts<'a>(&self, store: &'a Store) -> ExecResult<&'a [Instruction]> {
-        let func = store.func_global(self.pc.exec_addr());
-        Ok(func.defined().unwrap().instructions())
+        Ok(self.current_func(store).instructions())
     }
 
     pub fn execute_step<I: Interceptor>(
@@ -178,6 +403,7 @@ impl Executor {
         interceptor: &I,
         config: &Config,
     ) -> ExecResult<Signal> {
+        self.check_execution_limit(config)?;
         let func = store.func_global(self.pc.exec_addr()).defined().unwrap();
         let module_index = func.module_index();
         let inst = match func.inst(self.pc.inst_index()) {
@@ -185,8 +411,15 @@ impl Executor {
             None => return Err(Trap::NoMoreInstruction),
         };
 
-        let signal = interceptor.execute_inst(inst)?;
-        let result = self.execute_inst(inst, module_index, store, interceptor, config)?;
+        let signal = interceptor.execute_inst(inst, self)?;
+        let result = self
+            .execute_inst(inst, module_index, store, interceptor, config)
+            .map_err(|trap| {
+                trap.with_location(TrapLocation {
+                    offset: inst.offset,
+                    source: None,
+                })
+            })?;
         Ok(match (signal, result) {
             (_, Signal::End) => Signal::End,
             (signal, Signal::Next) => signal,
@@ -204,13 +437,19 @@ impl Executor {
     ) -> ExecResult<Signal> {
         self.pc.inc_inst_index();
         let result: Signal = match &inst.kind {
-            InstructionKind::Unreachable => return Err(Trap::Unreachable),
+            InstructionKind::Unreachable => {
+                return Err(Trap::UnreachableInstruction {
+                    offset: inst.offset,
+                })
+            }
             InstructionKind::Nop => Signal::Next,
             InstructionKind::Block { blockty } => {
+                let start = InstIndex(self.pc.inst_index().0 - 1);
                 let (params_size, results_size) = self.get_type_arity(blockty, store)?;
                 let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
                 self.stack.push_label(Label::Block {
                     arity: results_size,
+                    start,
                 });
                 self.stack.push_values(params.into_iter().rev());
                 Signal::Next
@@ -225,34 +464,21 @@ impl Executor {
                 Signal::Next
             }
             InstructionKind::If { blockty } => {
+                let start = InstIndex(self.pc.inst_index().0 - 1);
                 let val: i32 = self.pop_as()?;
                 let (params_size, results_size) = self.get_type_arity(blockty, store)?;
                 let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
                 self.stack.push_label(Label::If {
                     arity: results_size,
+                    start,
                 });
                 self.stack.push_values(params.into_iter().rev());
                 if val == 0 {
-                    let mut depth = 1;
-                    loop {
-                        let index = self.pc.inst_index().0 as usize;
-                        match self.current_func_insts(store)?[index].kind {
-                            InstructionKind::End => depth -= 1,
-                            InstructionKind::Block { .. } => depth += 1,
-                            InstructionKind::If { .. } => depth += 1,
-                            InstructionKind::Loop { .. } => depth += 1,
-                            InstructionKind::Else => {
-                                if depth == 1 {
-                                    self.pc.inc_inst_index();
-                                    break;
-                                }
-                            }
-                            _ => (),
-                        }
-                        if depth == 0 {
-                            break;
-                        }
-                        self.pc.inc_inst_index();
+                    // Continue with the `else` arm, or the `end` to pop the label
+                    let block_end = self.current_func(store).block_end(start);
+                    match block_end.else_index {
+                        Some(else_index) => self.pc.jump(InstIndex(else_index.0 + 1)),
+                        None => self.pc.jump(block_end.end_index),
                     }
                 }
                 Signal::Next
@@ -277,16 +503,53 @@ impl Executor {
                         Signal::End
                     }
                 } else {
-                    // When the end of a block is reached without a jump
-                    let results = self.stack.pop_while(|v| matches!(v, StackValue::Value(_)));
-                    self.stack.pop_label().map_err(Trap::Stack)?;
-                    let results = results
-                        .into_iter()
-                        .rev()
-                        .map(|v| v.into_value().map_err(Trap::Stack))
-                        .collect::<ExecResult<Vec<_>>>()?;
-                    self.stack.push_values(results);
-                    Signal::Next
+                    self.end_block()?
+                }
+            }
+            InstructionKind::Try { blockty } => {
+                let start = InstIndex(self.pc.inst_index().0 - 1);
+                let (params_size, results_size) = self.get_type_arity(blockty, store)?;
+                let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
+                self.stack.push_label(Label::Try {
+                    arity: results_size,
+                    start,
+                });
+                self.stack.push_values(params.into_iter().rev());
+                Signal::Next
+            }
+            // Reached at the end of a `try` body or a handler without an exception
+            InstructionKind::Catch { .. } | InstructionKind::CatchAll => self.branch(0, store)?,
+            InstructionKind::Delegate { .. } => self.end_block()?,
+            InstructionKind::Throw { tag_index } => {
+                let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+                let addr = TagAddr::new_unsafe(frame.module_index(), *tag_index as usize);
+                let (tag, tag_addr) = store.tag(addr).ok_or(Trap::UndefinedTag(addr.1))?;
+                let mut values = self
+                    .stack
+                    .pop_values(tag.ty().params().len())
+                    .map_err(Trap::Stack)?;
+                values.reverse();
+                let exception = Exception {
+                    tag: tag_addr,
+                    values,
+                };
+                self.throw(Rc::new(exception), store)?
+            }
+            InstructionKind::Rethrow { relative_depth } => {
+                let label = self
+                    .stack
+                    .frame_label(*relative_depth as usize)
+                    .map_err(Trap::Stack)?;
+                match label {
+                    Label::Catch { caught, .. } => {
+                        let exception = caught.clone();
+                        self.throw(exception, store)?
+                    }
+                    _ => {
+                        return Err(Trap::InvalidRethrow {
+                            relative_depth: *relative_depth,
+                        })
+                    }
                 }
             }
             InstructionKind::Br { relative_depth } => self.branch(*relative_depth, store)?,
@@ -319,35 +582,20 @@ impl Executor {
                 table_index,
                 ..
             } => {
+                let addr = self.indirect_callee(*type_index, *table_index, store)?;
+                self.invoke(addr, store, interceptor)?
+            }
+            InstructionKind::ReturnCall { function_index } => {
                 let frame = self.stack.current_frame().map_err(Trap::Stack)?;
-                let addr = TableAddr::new_unsafe(frame.module_index(), *table_index as usize);
-                let module = store.module(frame.module_index()).defined().unwrap();
-                let ty = module.get_type(*type_index as usize);
-                let buf_index: i32 = self.pop_as()?;
-                let table = store.table(addr);
-                let buf_index = buf_index as usize;
-                let func_ref = table.borrow().get_at(buf_index).map_err(Trap::Table)?;
-
-                let func_addr = match func_ref {
-                    RefVal::NullRef(_) => Err(Trap::UndefinedFunc(buf_index)),
-                    RefVal::FuncRef(addr) => Ok(addr),
-                    other => Err(Trap::ElementTypeMismatch {
-                        expected: RefType::FuncRef,
-                        actual: other,
-                    }),
-                }?;
-                let (func, _) = store
-                    .func(func_addr)
-                    .ok_or(Trap::UndefinedFunc(func_addr.1))?;
-                if func.ty() == ty {
-                    self.invoke(func_addr, store, interceptor)?
-                } else {
-                    return Err(Trap::IndirectCallTypeMismatch {
-                        callee_name: func.name().clone(),
-                        expected: ty.clone(),
-                        actual: func.ty().clone(),
-                    })
-                }
+                let addr = FuncAddr::new_unsafe(frame.module_index(), *function_index as usize);
+                self.tail_invoke(addr, store, interceptor)?
+            }
+            InstructionKind::ReturnCallIndirect {
+                type_index,
+                table_index,
+            } => {
+                let addr = self.indirect_callee(*type_index, *table_index, store)?;
+                self.tail_invoke(addr, store, interceptor)?
             }
             InstructionKind::Drop => {
                 self.stack.pop_value().map_err(Trap::Stack)?;
@@ -373,12 +621,14 @@ impl Executor {
                 self.stack.push_value(value);
                 Signal::Next
             }
-            InstructionKind::LocalSet { local_index } => self.set_local(*local_index as usize)?,
+            InstructionKind::LocalSet { local_index } => {
+                self.set_local(*local_index as usize, inst, interceptor)?
+            }
             InstructionKind::LocalTee { local_index } => {
                 let val = self.stack.pop_value().map_err(Trap::Stack)?;
                 self.stack.push_value(val);
                 self.stack.push_value(val);
-                self.set_local(*local_index as usize)?
+                self.set_local(*local_index as usize, inst, interceptor)?
             }
             InstructionKind::GlobalGet { global_index } => {
                 let addr = GlobalAddr::new_unsafe(module_index, *global_index as usize);
@@ -498,80 +748,80 @@ impl Executor {
                 Signal::Next
             }
 
-            InstructionKind::I32Load { memarg } => self.load::<i32>(memarg.offset, store, config)?,
-            InstructionKind::I64Load { memarg } => self.load::<i64>(memarg.offset, store, config)?,
-            InstructionKind::F32Load { memarg } => self.load::<F32>(memarg.offset, store, config)?,
-            InstructionKind::F64Load { memarg } => self.load::<F64>(memarg.offset, store, config)?,
+            InstructionKind::I32Load { memarg } => self.load::<i32>(memarg, store, config)?,
+            InstructionKind::I64Load { memarg } => self.load::<i64>(memarg, store, config)?,
+            InstructionKind::F32Load { memarg } => self.load::<F32>(memarg, store, config)?,
+            InstructionKind::F64Load { memarg } => self.load::<F64>(memarg, store, config)?,
 
             InstructionKind::I32Load8S { memarg } => {
-                self.load_extend::<i8, i32>(memarg.offset, store, config)?
+                self.load_extend::<i8, i32>(memarg, store, config)?
             }
             InstructionKind::I32Load8U { memarg } => {
-                self.load_extend::<u8, i32>(memarg.offset, store, config)?
+                self.load_extend::<u8, i32>(memarg, store, config)?
             }
             InstructionKind::I32Load16S { memarg } => {
-                self.load_extend::<i16, i32>(memarg.offset, store, config)?
+                self.load_extend::<i16, i32>(memarg, store, config)?
             }
             InstructionKind::I32Load16U { memarg } => {
-                self.load_extend::<u16, i32>(memarg.offset, store, config)?
+                self.load_extend::<u16, i32>(memarg, store, config)?
             }
 
             InstructionKind::I64Load8S { memarg } => {
-                self.load_extend::<i8, i64>(memarg.offset, store, config)?
+                self.load_extend::<i8, i64>(memarg, store, config)?
             }
             InstructionKind::I64Load8U { memarg } => {
-                self.load_extend::<u8, i64>(memarg.offset, store, config)?
+                self.load_extend::<u8, i64>(memarg, store, config)?
             }
             InstructionKind::I64Load16S { memarg } => {
-                self.load_extend::<i16, i64>(memarg.offset, store, config)?
+                self.load_extend::<i16, i64>(memarg, store, config)?
             }
             InstructionKind::I64Load16U { memarg } => {
-                self.load_extend::<u16, i64>(memarg.offset, store, config)?
+                self.load_extend::<u16, i64>(memarg, store, config)?
             }
             InstructionKind::I64Load32S { memarg } => {
-                self.load_extend::<i32, i64>(memarg.offset, store, config)?
+                self.load_extend::<i32, i64>(memarg, store, config)?
             }
             InstructionKind::I64Load32U { memarg } => {
-                self.load_extend::<u32, i64>(memarg.offset, store, config)?
+                self.load_extend::<u32, i64>(memarg, store, config)?
             }
 
             InstructionKind::I32Store { memarg } => {
-                self.store::<i32, _>(memarg.offset, store, interceptor, config)?
+                self.store::<i32, _>(memarg, store, interceptor, config)?
             }
             InstructionKind::I64Store { memarg } => {
-                self.store::<i64, _>(memarg.offset, store, interceptor, config)?
+                self.store::<i64, _>(memarg, store, interceptor, config)?
             }
             InstructionKind::F32Store { memarg } => {
-                self.store::<F32, _>(memarg.offset, store, interceptor, config)?
+                self.store::<F32, _>(memarg, store, interceptor, config)?
             }
             InstructionKind::F64Store { memarg } => {
-                self.store::<F64, _>(memarg.offset, store, interceptor, config)?
+                self.store::<F64, _>(memarg, store, interceptor, config)?
             }
 
             InstructionKind::I32Store8 { memarg } => {
-                self.store_with_width::<i32, _>(memarg.offset, 1, store, interceptor, config)?
+                self.store_with_width::<i32, _>(memarg, 1, store, interceptor, config)?
             }
             InstructionKind::I32Store16 { memarg } => {
-                self.store_with_width::<i32, _>(memarg.offset, 2, store, interceptor, config)?
+                self.store_with_width::<i32, _>(memarg, 2, store, interceptor, config)?
             }
             InstructionKind::I64Store8 { memarg } => {
-                self.store_with_width::<i64, _>(memarg.offset, 1, store, interceptor, config)?
+                self.store_with_width::<i64, _>(memarg, 1, store, interceptor, config)?
             }
             InstructionKind::I64Store16 { memarg } => {
-                self.store_with_width::<i64, _>(memarg.offset, 2, store, interceptor, config)?
+                self.store_with_width::<i64, _>(memarg, 2, store, interceptor, config)?
             }
             InstructionKind::I64Store32 { memarg } => {
-                self.store_with_width::<i64, _>(memarg.offset, 4, store, interceptor, config)?
+                self.store_with_width::<i64, _>(memarg, 4, store, interceptor, config)?
             }
 
-            InstructionKind::MemorySize { .. } => {
-                self.stack
-                    .push_value(Value::I32(self.memory(store)?.borrow().page_count() as i32));
+            InstructionKind::MemorySize { mem, .. } => {
+                let size = self.memory(*mem, store)?.borrow().page_count();
+                self.stack.push_value(Value::I32(size as i32));
                 Signal::Next
             }
-            InstructionKind::MemoryGrow { .. } => {
+            InstructionKind::MemoryGrow { mem, .. } => {
                 let grow_page: i32 = self.pop_as()?;
-                let mem = self.memory(store)?;
+                let mem = self.memory(*mem, store)?;
                 let size = mem.borrow().page_count();
                 match mem.borrow_mut().grow(grow_page as usize) {
                     Ok(_) => {
@@ -602,9 +852,10 @@ impl Executor {
                     .collect::<ExecResult<Vec<_>>>()?;
 
                 dst_mem.borrow().validate_region(dst_base, n)?;
+                let signal = interceptor.before_store(dst_base, &values, &dst_mem.borrow())?;
                 dst_mem.borrow_mut().store(dst_base, &values)?;
 
-                Signal::Next
+                signal
             }
             InstructionKind::MemoryFill { mem } => {
                 let addr = MemoryAddr::new_unsafe(module_index, *mem as usize);
@@ -616,10 +867,11 @@ impl Executor {
 
                 mem.borrow().validate_region(offset, n)?;
 
-                mem.borrow_mut()
-                    .store(offset, &std::iter::repeat(val).take(n).collect::<Vec<_>>())?;
+                let values = vec![val; n];
+                let signal = interceptor.before_store(offset, &values, &mem.borrow())?;
+                mem.borrow_mut().store(offset, &values)?;
 
-                Signal::Next
+                signal
             }
             InstructionKind::MemoryInit { data_index, mem } => {
                 let mem_addr = MemoryAddr::new_unsafe(module_index, *mem as usize);
@@ -633,9 +885,11 @@ impl Executor {
                 mem.borrow().validate_region(dst_base, n)?;
                 data.borrow().validate_region(src_base, n)?;
 
-                mem.borrow_mut()
-                    .store(dst_base, &data.borrow().raw()[src_base..(src_base + n)])?;
-                Signal::Next
+                let data = data.borrow();
+                let values = &data.raw()[src_base..(src_base + n)];
+                let signal = interceptor.before_store(dst_base, values, &mem.borrow())?;
+                mem.borrow_mut().store(dst_base, values)?;
+                signal
             }
             InstructionKind::DataDrop { data_index } => {
                 let data_addr = DataAddr::new_unsafe(module_index, *data_index as usize);
@@ -836,6 +1090,42 @@ impl Executor {
             InstructionKind::I64TruncSatF32U => self.unop::<F32, _, _>(TruncSat::<u64>::trunc_sat)?,
             InstructionKind::I64TruncSatF64S => self.unop::<F64, _, _>(TruncSat::<i64>::trunc_sat)?,
             InstructionKind::I64TruncSatF64U => self.unop::<F64, _, _>(TruncSat::<u64>::trunc_sat)?,
+
+            InstructionKind::I8x16RelaxedSwizzle => self.binop(simd::i8x16_relaxed_swizzle)?,
+            InstructionKind::I32x4RelaxedTruncSatF32x4S => {
+                self.unop(simd::i32x4_relaxed_trunc_f32x4::<i32>)?
+            }
+            InstructionKind::I32x4RelaxedTruncSatF32x4U => {
+                self.unop(simd::i32x4_relaxed_trunc_f32x4::<u32>)?
+            }
+            InstructionKind::I32x4RelaxedTruncSatF64x2SZero => {
+                self.unop(simd::i32x4_relaxed_trunc_f64x2_zero::<i32>)?
+            }
+            InstructionKind::I32x4RelaxedTruncSatF64x2UZero => {
+                self.unop(simd::i32x4_relaxed_trunc_f64x2_zero::<u32>)?
+            }
+            InstructionKind::F32x4RelaxedFma => self.ternop(simd::f32x4_relaxed_fma)?,
+            InstructionKind::F32x4RelaxedFnma => self.ternop(simd::f32x4_relaxed_fnma)?,
+            InstructionKind::F64x2RelaxedFma => self.ternop(simd::f64x2_relaxed_fma)?,
+            InstructionKind::F64x2RelaxedFnma => self.ternop(simd::f64x2_relaxed_fnma)?,
+            InstructionKind::I8x16RelaxedLaneselect
+            | InstructionKind::I16x8RelaxedLaneselect
+            | InstructionKind::I32x4RelaxedLaneselect
+            | InstructionKind::I64x2RelaxedLaneselect => self.ternop(simd::relaxed_laneselect)?,
+            InstructionKind::F32x4RelaxedMin => self.binop(simd::f32x4_relaxed_min)?,
+            InstructionKind::F32x4RelaxedMax => self.binop(simd::f32x4_relaxed_max)?,
+            InstructionKind::F64x2RelaxedMin => self.binop(simd::f64x2_relaxed_min)?,
+            InstructionKind::F64x2RelaxedMax => self.binop(simd::f64x2_relaxed_max)?,
+            InstructionKind::I16x8RelaxedQ15mulrS => self.binop(simd::i16x8_relaxed_q15mulr_s)?,
+            InstructionKind::I16x8DotI8x16I7x16S => {
+                self.binop(simd::i16x8_relaxed_dot_i8x16_i7x16_s)?
+            }
+            InstructionKind::I32x4DotI8x16I7x16AddS => {
+                self.ternop(simd::i32x4_relaxed_dot_i8x16_i7x16_add_s)?
+            }
+            InstructionKind::F32x4RelaxedDotBf16x8AddF32x4 => {
+                self.ternop(simd::f32x4_relaxed_dot_bf16x8_add_f32x4)?
+            }
             other => unimplemented!("{:?}", other),
         };
         if self.stack.is_over_top_level() {
@@ -867,7 +1157,7 @@ impl Executor {
 
     fn branch(&mut self, depth: u32, store: &Store) -> ExecResult<Signal> {
         let depth = depth as usize;
-        let label = *self.stack.frame_label(depth).map_err(Trap::Stack)?;
+        let label = self.stack.frame_label(depth).map_err(Trap::Stack)?.clone();
 
         let arity = label.arity();
 
@@ -891,25 +1181,124 @@ impl Executor {
             Label::Return { .. } => {
                 return self.do_return(store);
             }
-            Label::If { .. } | Label::Block { .. } => {
-                let mut depth = depth + 1;
-                loop {
-                    let index = self.pc.inst_index().0 as usize;
-                    match self.current_func_insts(store)?[index].kind {
-                        InstructionKind::End => depth -= 1,
-                        InstructionKind::Block { .. } => depth += 1,
-                        InstructionKind::If { .. } => depth += 1,
-                        InstructionKind::Loop { .. } => depth += 1,
-                        _ => (),
+            Label::If { start, .. }
+            | Label::Block { start, .. }
+            | Label::Try { start, .. }
+            | Label::Catch { start, .. } => {
+                let end_index = self.current_func(store).block_end(start).end_index;
+                self.pc.jump(InstIndex(end_index.0 + 1));
+            }
+        }
+        Ok(Signal::Next)
+    }
+
+    /// Pops the innermost label at the end of a block, keeping its results
+    fn end_block(&mut self) -> ExecResult<Signal> {
+        let results = self.stack.pop_while(|v| matches!(v, StackValue::Value(_)));
+        self.stack.pop_label().map_err(Trap::Stack)?;
+        let results = results
+            .into_iter()
+            .rev()
+            .map(|v| v.into_value().map_err(Trap::Stack))
+            .collect::<ExecResult<Vec<_>>>()?;
+        self.stack.push_values(results);
+        Ok(Signal::Next)
+    }
+
+    /// Unwinds the stack until a `catch` or `catch_all` of an enclosing `try`
+    /// handles the exception, and transfers control to the handler
+    fn throw(&mut self, exception: Rc<Exception>, store: &Store) -> ExecResult<Signal> {
+        loop {
+            self.stack.pop_while(|v| matches!(v, StackValue::Value(_)));
+            if self.stack.frame_label(0).is_err() {
+                // No handler in this frame, so unwind to the caller
+                let frame = self.stack.pop_frame().map_err(Trap::Stack)?;
+                match frame.ret_pc {
+                    Some(ret_pc) if !self.stack.is_over_top_level() => self.pc = ret_pc,
+                    _ => {
+                        return Err(Trap::UncaughtException {
+                            values: exception.values.clone(),
+                        })
+                    }
+                }
+                continue;
+            }
+            let (arity, start) = match self.stack.pop_label().map_err(Trap::Stack)? {
+                Label::Try { arity, start } => (arity, start),
+                _ => continue,
+            };
+            match self.find_handler(start, &exception, store)? {
+                Handler::Catch { inst_index, bind } => {
+                    self.stack.push_label(Label::Catch {
+                        arity,
+                        start,
+                        caught: exception.clone(),
+                    });
+                    if bind {
+                        self.stack.push_values(exception.values.iter().cloned());
                     }
-                    self.pc.inc_inst_index();
-                    if depth == 0 {
-                        break;
+                    self.pc = ProgramCounter::new(
+                        self.pc.module_index(),
+                        self.pc.exec_addr(),
+                        InstIndex(inst_index as u32 + 1),
+                    );
+                    return Ok(Signal::Next);
+                }
+                Handler::Delegate { relative_depth } => {
+                    // Continue with the handlers of the target label
+                    for _ in 0..relative_depth {
+                        self.stack.pop_while(|v| matches!(v, StackValue::Value(_)));
+                        self.stack.pop_label().map_err(Trap::Stack)?;
                     }
                 }
+                Handler::None => (),
             }
         }
-        Ok(Signal::Next)
+    }
+
+    /// Finds the handler of the `try` block at `start` for the exception
+    fn find_handler(
+        &self,
+        start: InstIndex,
+        exception: &Exception,
+        store: &Store,
+    ) -> ExecResult<Handler> {
+        let insts = self.current_func_insts(store)?;
+        let mut depth = 0;
+        for (inst_index, inst) in insts.iter().enumerate().skip(start.0 as usize + 1) {
+            match inst.kind {
+                InstructionKind::Block { .. }
+                | InstructionKind::If { .. }
+                | InstructionKind::Loop { .. }
+                | InstructionKind::Try { .. } => depth += 1,
+                InstructionKind::End if depth == 0 => break,
+                InstructionKind::End => depth -= 1,
+                InstructionKind::Delegate { relative_depth } if depth == 0 => {
+                    return Ok(Handler::Delegate {
+                        relative_depth: relative_depth as usize,
+                    })
+                }
+                InstructionKind::Delegate { .. } => depth -= 1,
+                InstructionKind::Catch { tag_index } if depth == 0 => {
+                    let addr = TagAddr::new_unsafe(self.pc.module_index(), tag_index as usize);
+                    let (_, tag_addr) = store.tag(addr).ok_or(Trap::UndefinedTag(addr.1))?;
+                    if tag_addr == exception.tag {
+                        return Ok(Handler::Catch {
+                            inst_index,
+                            bind: true,
+                        });
+                    }
+                }
+                InstructionKind::CatchAll if depth == 0 => {
+                    return Ok(Handler::Catch {
+                        inst_index,
+                        bind: false,
+                    })
+                }
+                _ => (),
+            }
+        }
+        Ok(Handler::None)
     }
 
     fn testop<T: NativeValue, F: Fn(T) -> bool>(&mut self, f: F) -> ExecResult<Signal> {
@@ -927,7 +1316,18 @@ impl Executor {
         let rhs = self.pop_as()?;
         let lhs = self.pop_as()?;
         self.stack
-            .push_value(f(lhs, rhs).map(|v| v.into()).map_err(Trap::Value)?);
+            .push_value(f(lhs, rhs).map(|v| v.into()).map_err(Trap::from)?);
+        Ok(Signal::Next)
+    }
+
+    fn ternop<T: NativeValue, To: Into<Value>, F: Fn(T, T, T) -> To>(
+        &mut self,
+        f: F,
+    ) -> ExecResult<Signal> {
+        let c = self.pop_as()?;
+        let b = self.pop_as()?;
+        let a = self.pop_as()?;
+        self.stack.push_value(f(a, b, c).into());
         Ok(Signal::Next)
     }
 
@@ -947,7 +1347,7 @@ impl Executor {
     ) -> ExecResult<Signal> {
         let v: From = self.pop_as()?;
         self.stack
-            .push_value(f(v).map(|v| v.into()).map_err(Trap::Value)?);
+            .push_value(f(v).map(|v| v.into()).map_err(Trap::from)?);
         Ok(Signal::Next)
     }
 
@@ -960,14 +1360,74 @@ impl Executor {
         Ok(Signal::Next)
     }
 
-    fn invoke<I: Interceptor>(
+    fn push_frame(&mut self, frame: CallFrame) -> ExecResult<()> {
+        match self.stack.set_frame(frame) {
+            Err(stack::Error::Overflow) => Err(Trap::StackOverflow {
+                depth: self.stack.frame_depth(),
+            }),
+            result => result.map_err(Trap::Stack),
+        }
+    }
+
+    /// Resolves the callee of `call_indirect` and `return_call_indirect` from the table
+    fn indirect_callee(
         &mut self,
-        addr: FuncAddr,
+        type_index: u32,
+        table_index: u32,
         store: &Store,
-        interceptor: &I,
-    ) -> ExecResult<Signal> {
-        let (func, exec_addr) = store.func(addr).ok_or(Trap::UndefinedFunc(addr.1))?;
+    ) -> ExecResult<FuncAddr> {
+        let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+        let module_index = frame.module_index();
+        let addr = TableAddr::new_unsafe(module_index, table_index as usize);
+        let buf_index: i32 = self.pop_as()?;
+        let table = store.table(addr);
+        let buf_index = buf_index as usize;
+        let table_version = table.borrow().version();
+        if let Some(cache) = self.indirect_call_cache {
+            if cache.module_index == module_index
+                && cache.table_index == table_index
+                && cache.type_index == type_index
+                && cache.elem_index == buf_index
+                && cache.table_version == table_version
+            {
+                return Ok(cache.func_addr);
+            }
+        }
+        let module = store.module(module_index).defined().unwrap();
+        let ty = module.get_type(type_index as usize);
+        let func_ref = table.borrow().get_at(buf_index).map_err(Trap::from)?;
+
+        let func_addr = match func_ref {
+            RefVal::NullRef(_) => Err(Trap::UndefinedFunc(buf_index)),
+            RefVal::FuncRef(addr) => Ok(addr),
+            other => Err(Trap::ElementTypeMismatch {
+                expected: RefType::FuncRef,
+                actual: other,
+            }),
+        }?;
+        let (func, _) = store
+            .func(func_addr)
+            .ok_or(Trap::UndefinedFunc(func_addr.1))?;
+        if func.ty() == ty {
+            self.indirect_call_cache = Some(IndirectCallCache {
+                module_index,
+                table_index,
+                type_index,
+                elem_index: buf_index,
+                table_version,
+                func_addr,
+            });
+            Ok(func_addr)
+        } else {
+            Err(Trap::IndirectCallTypeMismatch {
+                callee_name: func.name().clone(),
+                expected: ty.clone(),
+                actual: func.ty().clone(),
+            })
+        }
+    }
 
+    fn pop_args(&mut self, func: &FunctionInstance) -> ExecResult<Vec<Value>> {
         let mut args = Vec::new();
         let mut found_mismatch = false;
         for _ in func.ty().params().iter() {
@@ -985,29 +1445,107 @@ impl Executor {
             });
         }
         args.reverse();
+        Ok(args)
+    }
+
+    fn call_native(
+        &mut self,
+        func: &NativeFunctionInstance,
+        args: &[Value],
+        addr: FuncAddr,
+        store: &Store,
+    ) -> ExecResult<()> {
+        let mut result = Vec::new();
+        func.code()
+            .call(args, &mut result, store, addr.module_index())
+            .map_err(|trap| match trap {
+                // Bodies wrapped by `HostFuncBody::with_timeout` don't know their name
+                Trap::HostFunctionTimeout { name, duration } if name.is_empty() => {
+                    Trap::HostFunctionTimeout {
+                        name: func.field_name().clone(),
+                        duration,
+                    }
+                }
+                trap => trap,
+            })?;
+        assert_eq!(result.len(), func.ty().results().len());
+        for v in result {
+            self.stack.push_value(v);
+        }
+        Ok(())
+    }
+
+    fn invoke<I: Interceptor>(
+        &mut self,
+        addr: FuncAddr,
+        store: &Store,
+        interceptor: &I,
+    ) -> ExecResult<Signal> {
+        let (func, exec_addr) = store.func(addr).ok_or(Trap::UndefinedFunc(addr.1))?;
+        let args = self.pop_args(func)?;
 
         let arity = func.ty().results().len();
         match func {
             FunctionInstance::Defined(func) => {
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
                 let frame = CallFrame::new_from_func(exec_addr, func, args, Some(self.pc));
-                self.stack.set_frame(frame).map_err(Trap::Stack)?;
+                self.push_frame(frame)?;
+                self.stack.push_label(Label::Return { arity });
+                self.pc = pc;
+                interceptor.invoke_func(func.name(), self, store)
+            }
+            FunctionInstance::Native(func) => {
+                self.call_native(func, &args, addr, store)?;
+                Ok(Signal::Next)
+            }
+        }
+    }
+
+    /// Invokes the function in place of the current frame, so that tail calls
+    /// don't grow the call stack
+    fn tail_invoke<I: Interceptor>(
+        &mut self,
+        addr: FuncAddr,
+        store: &Store,
+        interceptor: &I,
+    ) -> ExecResult<Signal> {
+        let (func, exec_addr) = store.func(addr).ok_or(Trap::UndefinedFunc(addr.1))?;
+        let caller = store.func_global(self.pc.exec_addr());
+        if func.ty().results() != caller.ty().results() {
+            return Err(Trap::TailCallTypeMismatch {
+                callee_name: func.name().to_string(),
+                expected: caller.ty().results().to_vec(),
+                actual: func.ty().results().to_vec(),
+            });
+        }
+        let args = self.pop_args(func)?;
+
+        let ret_pc = self.stack.current_frame().map_err(Trap::Stack)?.ret_pc;
+        self.stack
+            .pop_while(|v| !matches!(v, StackValue::Activation(_)));
+        self.stack.pop_frame().map_err(Trap::Stack)?;
+
+        let arity = func.ty().results().len();
+        match func {
+            FunctionInstance::Defined(func) => {
+                let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
+                let frame = CallFrame::new_from_func(exec_addr, func, args, ret_pc);
+                self.push_frame(frame)?;
                 self.stack.push_label(Label::Return { arity });
                 self.pc = pc;
                 interceptor.invoke_func(func.name(), self, store)
             }
             FunctionInstance::Native(func) => {
-                let mut result = Vec::new();
-                func.code()
-                    .call(&args, &mut result, store, addr.module_index())?;
-                assert_eq!(result.len(), arity);
-                for v in result {
-                    self.stack.push_value(v);
+                // Host functions have no frame to reuse, so return their results to the caller
+                self.call_native(func, &args, addr, store)?;
+                if let Some(ret_pc) = ret_pc {
+                    self.pc = ret_pc;
                 }
                 Ok(Signal::Next)
             }
         }
     }
+
     fn do_return(&mut self, store: &Store) -> ExecResult<Signal> {
         let ret_pc = self.stack.current_frame().map_err(Trap::Stack)?.ret_pc;
         let func = store.func_global(self.pc.exec_addr());
@@ -1038,16 +1576,26 @@ impl Executor {
         })
     }
 
-    fn set_local(&mut self, index: usize) -> ExecResult<Signal> {
+    fn set_local<I: Interceptor>(
+        &mut self,
+        index: usize,
+        inst: &Instruction,
+        interceptor: &I,
+    ) -> ExecResult<Signal> {
         let value = self.stack.pop_value().map_err(Trap::Stack)?;
+        let old_value = self.stack.current_frame().map_err(Trap::Stack)?.local(index);
         self.stack.set_local(index, value).map_err(Trap::Stack)?;
 
-        Ok(Signal::Next)
+        interceptor.after_set_local(index, old_value, value, inst, self)
     }
 
-    fn memory(&self, store: &Store) -> ExecResult<std::rc::Rc<std::cell::RefCell<MemoryInstance>>> {
+    fn memory(
+        &self,
+        index: u32,
+        store: &Store,
+    ) -> ExecResult<std::rc::Rc<std::cell::RefCell<MemoryInstance>>> {
         let frame = self.stack.current_frame().map_err(Trap::Stack)?;
-        let mem_addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
+        let mem_addr = MemoryAddr::new_unsafe(frame.module_index(), index as usize);
         Ok(store.memory(mem_addr))
     }
 
@@ -1070,7 +1618,7 @@ impl Executor {
 
     fn store<T: NativeValue + IntoLittleEndian, I: Interceptor>(
         &mut self,
-        offset: u64,
+        memarg: &MemArg,
         store: &Store,
         interceptor: &I,
         config: &Config,
@@ -1078,18 +1626,14 @@ impl Executor {
         let val: T = self.pop_as()?;
         let base_addr: i32 = self.pop_as()?;
         let base_addr: u32 = u32::from_le_bytes(base_addr.to_le_bytes());
-        let addr = Self::mem_addr(base_addr, offset, config.features.memory64)? as usize;
+        let addr = Self::mem_addr(base_addr, memarg.offset, config.features.memory64)? as usize;
         let buf = val.into_le_bytes();
-        self.memory(store)?
-            .borrow_mut()
-            .store(addr, &buf)
-            .map_err(Trap::Memory)?;
-        interceptor.after_store(addr, &buf)
+        self.commit_store(memarg.memory, addr, &buf, store, interceptor)
     }
 
     fn store_with_width<T: NativeValue + IntoLittleEndian, I: Interceptor>(
         &mut self,
-        offset: u64,
+        memarg: &MemArg,
         width: usize,
         store: &Store,
         interceptor: &I,
@@ -1098,48 +1642,61 @@ impl Executor {
         let val: T = self.pop_as()?;
         let base_addr: i32 = self.pop_as()?;
         let base_addr: u32 = u32::from_le_bytes(base_addr.to_le_bytes());
-        let addr = Self::mem_addr(base_addr, offset, config.features.memory64)? as usize;
+        let addr = Self::mem_addr(base_addr, memarg.offset, config.features.memory64)? as usize;
         let buf = val.into_le_bytes();
         let buf: Vec<u8> = buf.into_iter().take(width).collect();
-        self.memory(store)?
-            .borrow_mut()
-            .store(addr, &buf)
-            .map_err(Trap::Memory)?;
-        interceptor.after_store(addr, &buf)
+        self.commit_store(memarg.memory, addr, &buf, store, interceptor)
+    }
+
+    fn commit_store<I: Interceptor>(
+        &mut self,
+        mem: u32,
+        addr: usize,
+        buf: &[u8],
+        store: &Store,
+        interceptor: &I,
+    ) -> ExecResult<Signal> {
+        let memory = self.memory(mem, store)?;
+        let before = interceptor.before_store(addr, buf, &memory.borrow())?;
+        memory.borrow_mut().store(addr, buf).map_err(Trap::from)?;
+        match interceptor.after_store(addr, buf)? {
+            Signal::Next => Ok(before),
+            signal => Ok(signal),
+        }
     }
 
-    fn load<T>(&mut self, offset: u64, store: &Store, config: &Config) -> ExecResult<Signal>
+    fn load<T>(&mut self, memarg: &MemArg, store: &Store, config: &Config) -> ExecResult<Signal>
     where
         T: NativeValue + FromLittleEndian,
         T: Into<Value>,
     {
         let base_addr: i32 = self.pop_as()?;
         let base_addr: u32 = u32::from_le_bytes(base_addr.to_le_bytes());
-        let addr = Self::mem_addr(base_addr, offset, config.features.memory64)? as usize;
+        let addr = Self::mem_addr(base_addr, memarg.offset, config.features.memory64)? as usize;
         let result: T = self
-            .memory(store)?
+            .memory(memarg.memory, store)?
             .borrow_mut()
             .load_as(addr)
-            .map_err(Trap::Memory)?;
+            .map_err(Trap::from)?;
         self.stack.push_value(result.into());
         Ok(Signal::Next)
     }
 
     fn load_extend<T: FromLittleEndian + ExtendInto<U>, U: Into<Value>>(
         &mut self,
-        offset: u64,
+        memarg: &MemArg,
         store: &Store,
         config: &Config,
     ) -> ExecResult<Signal> {
         let base_addr: i32 = self.pop_as()?;
         let base_addr: u32 = u32::from_le_bytes(base_addr.to_le_bytes());
-        let addr = Self::mem_addr(base_addr, offset, config.features.memory64)? as usize;
+        let addr = Self::mem_addr(base_addr, memarg.offset, config.features.memory64)? as usize;
 
         let result: T = self
-            .memory(store)?
+            .memory(memarg.memory, store)?
             .borrow_mut()
             .load_as(addr)
-            .map_err(Trap::Memory)?;
+            .map_err(Trap::from)?;
         let result = result.extend_into();
         self.stack.push_value(result.into());
         Ok(Signal::Next)
@@ -1199,3 +1756,505 @@ impl std::fmt::Display for WasmError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Executor, Signal};
+    use crate::{
+        CallFrame, Config, DefinedModuleError, ExecutionLimit, HostFuncBody, InstIndex, MemoryAddr,
+        NopInterceptor, ProgramCounter, StoreError, Trap, TrapLocation, WasmError, WasmInstance,
+        WasmValue,
+    };
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use wasmparser::ValType;
+
+    #[test]
+    fn tail_recursion_does_not_grow_call_stack() {
+        // (func (export "countdown") (param i32) (result i32)
+        //   (if (result i32) (i32.eqz (local.get 0))
+        //     (then (i32.const 7))
+        //     (else (return_call 0 (i32.sub (local.get 0) (i32.const 1))))))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x0d, 0x01, 0x09, b'c', b'o', b'u', b'n', b't', b'd', b'o', b'w', b'n', 0x00,
+            0x00, // export section
+            0x0a, 0x14, 0x01, 0x12, 0x00, 0x20, 0x00, 0x45, 0x04, 0x7f, 0x41, 0x07, 0x05, 0x20,
+            0x00, 0x41, 0x01, 0x6b, 0x12, 0x00, 0x0b, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let results = instance
+            .run(
+                module_index,
+                Some("countdown".to_string()),
+                vec![WasmValue::I32(1_000_000)],
+                &Config::default(),
+            )
+            .unwrap();
+        assert_eq!(results, vec![WasmValue::I32(7)]);
+    }
+
+    #[test]
+    fn multi_memory() {
+        // (memory 1) (memory 1)
+        // (func (export "f") (result i32)
+        //   (i32.store 1 (i32.const 0) (i32.const 42))
+        //   (i32.add
+        //     (i32.add
+        //       (i32.load 0 (i32.const 0))
+        //       (i32.mul (i32.load 1 (i32.const 0)) (i32.const 1000)))
+        //     (memory.grow 1 (i32.const 1))))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x05, 0x05, 0x02, 0x00, 0x01, 0x00, 0x01, // memory section
+            0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00, // export section
+            0x0a, 0x21, 0x01, 0x1f, 0x00, 0x41, 0x00, 0x41, 0x2a, 0x36, 0x42, 0x01, 0x00, 0x41,
+            0x00, 0x28, 0x02, 0x00, 0x41, 0x00, 0x28, 0x42, 0x01, 0x00, 0x41, 0xe8, 0x07, 0x6c,
+            0x6a, 0x41, 0x01, 0x40, 0x01, 0x6a, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let results = instance
+            .run(
+                module_index,
+                Some("f".to_string()),
+                vec![],
+                &Config::default(),
+            )
+            .unwrap();
+        // memory 0 is left untouched and memory 1 grows from its own size
+        assert_eq!(results, vec![WasmValue::I32(42_001)]);
+    }
+
+    #[test]
+    fn structured_traps() {
+        // (func (export "div") (param i32 i32) (result i32)
+        //   (i32.div_s (local.get 0) (local.get 1)))
+        // (func (export "trap") unreachable)
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x0a, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x00,
+            0x00, // type section
+            0x03, 0x03, 0x02, 0x00, 0x01, // function section
+            0x07, 0x0e, 0x02, 0x03, b'd', b'i', b'v', 0x00, 0x00, 0x04, b't', b'r', b'a', b'p',
+            0x00, 0x01, // export section
+            0x0a, 0x0d, 0x02, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6d, 0x0b, 0x03, 0x00, 0x00,
+            0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let mut run = |name: &str, args| {
+            instance.run(
+                module_index,
+                Some(name.to_string()),
+                args,
+                &Config::default(),
+            )
+        };
+        assert!(matches!(
+            run("div", vec![WasmValue::I32(1), WasmValue::I32(0)]),
+            Err(WasmError::ExecutionError(trap)) if matches!(trap.kind(), Trap::DivisionByZero)
+        ));
+        assert!(matches!(
+            run("div", vec![WasmValue::I32(i32::MIN), WasmValue::I32(-1)]),
+            Err(WasmError::ExecutionError(trap)) if matches!(trap.kind(), Trap::IntegerOverflow)
+        ));
+        assert!(matches!(
+            run("trap", vec![]),
+            Err(WasmError::ExecutionError(trap))
+                if matches!(trap.kind(), Trap::UnreachableInstruction { .. })
+        ));
+    }
+
+    #[test]
+    fn trap_locations() {
+        // (memory 1)
+        // (func (export "div") (param i32 i32) (result i32)
+        //   (i32.div_u (local.get 0) (local.get 1)))
+        // (func (export "load") (param i32 i32) (result i32)
+        //   (i32.load (local.get 0)))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x03, 0x02, 0x00, 0x00, // function section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x07, 0x0e, 0x02, 0x03, b'd', b'i', b'v', 0x00, 0x00, 0x04, b'l', b'o', b'a', b'd',
+            0x00, 0x01, // export section
+            0x0a, 0x11, 0x02, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6e, 0x0b, 0x07, 0x00, 0x20,
+            0x00, 0x28, 0x02, 0x00, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let mut run = |name: &str, address| match instance.run(
+            module_index,
+            Some(name.to_string()),
+            vec![WasmValue::I32(address), WasmValue::I32(0)],
+            &Config::default(),
+        ) {
+            Err(WasmError::ExecutionError(trap)) => trap,
+            other => panic!("expected a trap, got {:?}", other.map(|_| ())),
+        };
+        let division = run("div", 1);
+        assert!(matches!(division.kind(), Trap::DivisionByZero));
+        assert_eq!(division.location().unwrap().offset, 0x7);
+        assert_eq!(division.to_string(), "integer divide by zero at offset 0x7");
+        let access = run("load", 65536);
+        assert!(matches!(
+            access.kind(),
+            Trap::OutOfBoundsMemoryAccess { .. }
+        ));
+        assert_eq!(access.location().unwrap().offset, 0xd);
+        assert!(access.to_string().ends_with(" at offset 0xd"));
+
+        let division = division.with_location(TrapLocation {
+            offset: 0x7,
+            source: Some(("math.c".to_string(), 88)),
+        });
+        assert_eq!(division.to_string(), "integer divide by zero at math.c:88");
+    }
+
+    #[test]
+    fn revert_stack_deltas() {
+        // (func (export "f") (param i32) (result i32)
+        //   (local.set 0 (i32.add (local.get 0) (i32.const 1)))
+        //   (local.get 0))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00, // export section
+            0x0a, 0x0d, 0x01, 0x0b, 0x00, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x21, 0x00, 0x20, 0x00,
+            0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let store = &instance.store;
+        let func_addr = store
+            .module(module_index)
+            .defined()
+            .unwrap()
+            .exported_func("f")
+            .unwrap()
+            .unwrap();
+        let (func, exec_addr) = store.func(func_addr).unwrap();
+        let frame = CallFrame::new_from_func(
+            exec_addr,
+            func.defined().unwrap(),
+            vec![WasmValue::I32(41)],
+            None,
+        );
+        let pc = ProgramCounter::new(module_index, exec_addr, InstIndex::zero());
+        let mut executor = Executor::new(frame, 1, pc);
+        let snapshot = |executor: &Executor| {
+            let locals = executor
+                .stack
+                .peek_frames()
+                .iter()
+                .map(|frame| frame.locals.clone())
+                .collect::<Vec<_>>();
+            format!(
+                "{:?} {:?} {:?}",
+                executor.pc.inst_index(),
+                executor.stack,
+                locals
+            )
+        };
+
+        let mut history = vec![];
+        loop {
+            let before = (snapshot(&executor), executor.pc);
+            executor.stack.record_delta();
+            let signal = executor
+                .execute_step(store, &NopInterceptor::new(), &Config::default())
+                .unwrap();
+            history.push((before, executor.stack.take_delta().unwrap()));
+            if let Signal::End = signal {
+                break;
+            }
+        }
+        assert_eq!(
+            executor.pop_result(vec![ValType::I32]).unwrap(),
+            vec![WasmValue::I32(42)]
+        );
+        for ((before, pc), delta) in history.into_iter().rev() {
+            executor.stack.revert_delta(delta);
+            executor.pc = pc;
+            assert_eq!(snapshot(&executor), before);
+        }
+    }
+
+    #[test]
+    fn execution_limit() {
+        // (func (export "spin") (loop (br 0)))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x08, 0x01, 0x04, b's', b'p', b'i', b'n', 0x00, 0x00, // export section
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let limit = ExecutionLimit::InstructionCount(1000);
+        let config = Config {
+            execution_limit: Some(limit),
+            ..Config::default()
+        };
+        let result = instance.run(module_index, Some("spin".to_string()), vec![], &config);
+        assert!(matches!(
+            result,
+            Err(WasmError::ExecutionError(Trap::ExecutionLimitExceeded(l))) if l == limit
+        ));
+    }
+
+    #[test]
+    fn linked_modules() {
+        // (module $a (func (export "double") (param i32) (result i32)
+        //   (i32.add (local.get 0) (local.get 0))))
+        let a = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x0a, 0x01, 0x06, b'd', b'o', b'u', b'b', b'l', b'e', 0x00,
+            0x00, // export section
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x00, 0x6a, 0x0b, // code section
+        ];
+        // (module $b (import "a" "double" (func $double (param i32) (result i32)))
+        //   (func (export "quad") (param i32) (result i32)
+        //     (call $double (call $double (local.get 0)))))
+        let b = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x02, 0x0c, 0x01, 0x01, b'a', 0x06, b'd', b'o', b'u', b'b', b'l', b'e', 0x00,
+            0x00, // import section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x08, 0x01, 0x04, b'q', b'u', b'a', b'd', 0x00, 0x01, // export section
+            0x0a, 0x0a, 0x01, 0x08, 0x00, 0x20, 0x00, 0x10, 0x00, 0x10, 0x00,
+            0x0b, // code section
+        ];
+        // (module $c (import "a" "double" (func (param i64) (result i64))))
+        let c = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7e, 0x01, 0x7e, // type section
+            0x02, 0x0c, 0x01, 0x01, b'a', 0x06, b'd', b'o', b'u', b'b', b'l', b'e', 0x00,
+            0x00, // import section
+        ];
+        let mut instance = WasmInstance::new();
+        // `b` is given first, but loaded after `a`
+        let indices = instance
+            .load_linked_modules(&[("b".to_string(), &b), ("a".to_string(), &a)])
+            .unwrap();
+        let result = instance
+            .run(
+                indices[0],
+                Some("quad".to_string()),
+                vec![WasmValue::I32(3)],
+                &Config::default(),
+            )
+            .unwrap();
+        assert_eq!(result[0].as_i32(), Some(12));
+
+        let err = instance
+            .load_linked_modules(&[("c".to_string(), &c)])
+            .unwrap_err()
+            .downcast::<StoreError>()
+            .unwrap();
+        assert!(matches!(
+            err,
+            StoreError::InvalidImport(DefinedModuleError::TypeMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn register_host_funcs() {
+        // (module (import "env" "f" (func (result i32)))
+        //   (func (export "g") (result i32) (call 0)))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x02, 0x09, 0x01, 0x03, b'e', b'n', b'v', 0x01, b'f', 0x00,
+            0x00, // import section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x05, 0x01, 0x01, b'g', 0x00, 0x01, // export section
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        instance.load_host_module("env".to_string(), HashMap::new());
+        let run = |instance: &mut WasmInstance, module_index| {
+            instance
+                .run(
+                    module_index,
+                    Some("g".to_string()),
+                    vec![],
+                    &Config::default(),
+                )
+                .unwrap()
+        };
+
+        // Unregistered before link
+        let env = instance.store.host_module_mut("env").unwrap();
+        env.register_func("f".to_string(), HostFuncBody::wrap(|| 0));
+        assert!(env.unregister_func("f").is_some());
+        let err = instance
+            .load_module_from_module(None, &mut bytes.clone())
+            .unwrap_err()
+            .downcast::<StoreError>()
+            .unwrap();
+        assert!(matches!(err, StoreError::UndefinedFunction { .. }));
+
+        // Registered then linked
+        let env = instance.store.host_module_mut("env").unwrap();
+        env.register_func("f".to_string(), HostFuncBody::wrap(|| 1));
+        let first = instance
+            .load_module_from_module(None, &mut bytes.clone())
+            .unwrap();
+        assert_eq!(run(&mut instance, first), vec![WasmValue::I32(1)]);
+
+        // Re-registered after link, which only affects modules linked later
+        let env = instance.store.host_module_mut("env").unwrap();
+        assert!(env.unregister_func("f").is_none());
+        env.register_func("f".to_string(), HostFuncBody::wrap(|| 2));
+        let second = instance
+            .load_module_from_module(None, &mut bytes.clone())
+            .unwrap();
+        assert_eq!(run(&mut instance, first), vec![WasmValue::I32(1)]);
+        assert_eq!(run(&mut instance, second), vec![WasmValue::I32(2)]);
+    }
+
+    #[test]
+    fn memory_grow_hook() {
+        // (memory 1 2)
+        // (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x02, // memory section
+            0x07, 0x08, 0x01, 0x04, b'g', b'r', b'o', b'w', 0x00, 0x00, // export section
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let grown = Rc::new(RefCell::new(Vec::new()));
+        instance
+            .store
+            .memory(MemoryAddr::new_unsafe(module_index, 0))
+            .borrow_mut()
+            .set_on_grow(Box::new({
+                let grown = grown.clone();
+                move |old, new| grown.borrow_mut().push((old, new))
+            }));
+        let mut grow = |delta| {
+            instance
+                .run(
+                    module_index,
+                    Some("grow".to_string()),
+                    vec![WasmValue::I32(delta)],
+                    &Config::default(),
+                )
+                .unwrap()
+        };
+        assert_eq!(grow(1), vec![WasmValue::I32(1)]);
+        assert_eq!(*grown.borrow(), vec![(1, 2)]);
+        // Growing past the maximum fails without calling the hook
+        assert_eq!(grow(1), vec![WasmValue::I32(-1)]);
+        assert_eq!(*grown.borrow(), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn exception_handling() {
+        // (tag $e0 (param i32)) (tag $e1 (param i32))
+        // (func (export "nested") (result i32)
+        //   (try (result i32)
+        //     (do (try (result i32)
+        //       (do (throw $e0 (i32.const 42)))
+        //       (catch $e1)))
+        //     (catch $e0 (i32.add (i32.const 1)))))
+        // (func (export "rethrow") (result i32)
+        //   (try (result i32)
+        //     (do (try (result i32)
+        //       (do (throw $e0 (i32.const 7)))
+        //       (catch_all (rethrow 0))))
+        //     (catch $e0)))
+        // (func (export "delegate") (result i32)
+        //   (try (result i32)
+        //     (do (try (result i32)
+        //       (do (throw $e1 (i32.const 5)))
+        //       (delegate 0)))
+        //     (catch $e1)))
+        // (func (export "uncaught") (result i32)
+        //   (throw $e0 (i32.const 3)))
+        // (func (export "caller") (result i32)
+        //   (try (result i32)
+        //     (do (call $thrower (i32.const 9)) (i32.const 0))
+        //     (catch $e0)))
+        // (func $thrower (param i32)
+        //   (throw $e0 (local.get 0)))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x07, 0x06, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00, // function section
+            0x0d, 0x05, 0x02, 0x00, 0x00, 0x00, 0x00, // tag section
+            0x07, 0x33, 0x05, 0x06, 0x6e, 0x65, 0x73, 0x74, 0x65, 0x64, 0x00, 0x00, 0x07, 0x72,
+            0x65, 0x74, 0x68, 0x72, 0x6f, 0x77, 0x00, 0x01, 0x08, 0x64, 0x65, 0x6c, 0x65, 0x67,
+            0x61, 0x74, 0x65, 0x00, 0x02, 0x08, 0x75, 0x6e, 0x63, 0x61, 0x75, 0x67, 0x68, 0x74,
+            0x00, 0x03, 0x06, 0x63, 0x61, 0x6c, 0x6c, 0x65, 0x72, 0x00,
+            0x04, // export section
+            0x0a, 0x53, 0x06, 0x13, 0x00, 0x06, 0x7f, 0x06, 0x7f, 0x41, 0x2a, 0x08, 0x00, 0x07,
+            0x01, 0x0b, 0x07, 0x00, 0x41, 0x01, 0x6a, 0x0b, 0x0b, 0x11, 0x00, 0x06, 0x7f, 0x06,
+            0x7f, 0x41, 0x07, 0x08, 0x00, 0x19, 0x09, 0x00, 0x0b, 0x07, 0x00, 0x0b, 0x0b, 0x0f,
+            0x00, 0x06, 0x7f, 0x06, 0x7f, 0x41, 0x05, 0x08, 0x01, 0x18, 0x00, 0x07, 0x01, 0x0b,
+            0x0b, 0x06, 0x00, 0x41, 0x03, 0x08, 0x00, 0x0b, 0x0d, 0x00, 0x06, 0x7f, 0x41, 0x09,
+            0x10, 0x05, 0x41, 0x00, 0x07, 0x00, 0x0b, 0x0b, 0x06, 0x00, 0x20, 0x00, 0x08, 0x00,
+            0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let config = Config::default();
+        let mut run =
+            |name: &str| instance.run(module_index, Some(name.to_string()), vec![], &config);
+        assert_eq!(run("nested").unwrap(), vec![WasmValue::I32(43)]);
+        assert_eq!(run("rethrow").unwrap(), vec![WasmValue::I32(7)]);
+        assert_eq!(run("delegate").unwrap(), vec![WasmValue::I32(5)]);
+        assert_eq!(run("caller").unwrap(), vec![WasmValue::I32(9)]);
+        assert!(matches!(
+            run("uncaught"),
+            Err(WasmError::ExecutionError(trap))
+                if matches!(trap.kind(), Trap::UncaughtException { .. })
+        ));
+    }
+
+    #[test]
+    fn call_indirect_after_table_set() {
+        // (table 1 funcref)
+        // (elem (i32.const 0) $one $two)
+        // (func $one (result i32) (i32.const 1))
+        // (func $two (result i32) (i32.const 2))
+        // (func (export "f") (result i32)
+        //   (i32.mul (call_indirect (result i32) (i32.const 0)) (i32.const 10))
+        //   (table.set (i32.const 0) (ref.func $two))
+        //   (i32.add (call_indirect (result i32) (i32.const 0))))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x04, 0x03, 0x00, 0x00, 0x00, // function section
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x02, // table section
+            0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x02, // export section
+            0x09, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, 0x00, 0x01, // element section
+            0x0a, 0x22, 0x03, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x04, 0x00, 0x41, 0x02, 0x0b, 0x16,
+            0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x41, 0x0a, 0x6c, 0x41, 0x00, 0xd2, 0x01, 0x26,
+            0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x6a, 0x0b, // code section
+        ];
+        let mut instance = WasmInstance::new();
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let config = Config::default();
+        let results = instance.run(module_index, Some("f".to_string()), vec![], &config);
+        assert_eq!(results.unwrap(), vec![WasmValue::I32(12)]);
+    }
+}
@@ -37,7 +37,8 @@ impl ExportInstance {
                     ExternalValue::Table(addr)
                 }
                 ExternalKind::Tag => {
-                    panic!("event is not supported yet")
+                    let addr = TagAddr::new_unsafe(module_index, entry.index as usize);
+                    ExternalValue::Tag(addr)
                 }
             },
         }
@@ -50,6 +51,7 @@ pub enum ExternalValue {
     Global(GlobalAddr),
     Memory(MemoryAddr),
     Table(TableAddr),
+    Tag(TagAddr),
 }
 
 impl ExternalValue {
@@ -59,6 +61,7 @@ impl ExternalValue {
             Self::Global(_) => "global",
             Self::Memory(_) => "memory",
             Self::Table(_) => "table",
+            Self::Tag(_) => "tag",
         }
     }
 }
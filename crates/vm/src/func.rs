@@ -6,6 +6,7 @@ use crate::inst::*;
 use crate::module::*;
 use crate::value::Value;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::iter;
 use wasmparser::ValType;
 use wasmparser::{FuncType, FunctionBody};
@@ -19,8 +20,16 @@ impl InstIndex {
     }
 }
 
+/// The matching `else` and `end` of a structured control instruction
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BlockEnd {
+    pub else_index: Option<InstIndex>,
+    /// The index of `end`, or `delegate` for a `try` block
+    pub end_index: InstIndex,
+}
+
 pub enum FunctionInstance {
-    Defined(DefinedFunctionInstance),
+    Defined(Box<DefinedFunctionInstance>),
     Native(NativeFunctionInstance),
 }
 
@@ -52,7 +61,11 @@ pub struct DefinedFunctionInstance {
     ty: FuncType,
     module_index: ModuleIndex,
     instructions: Vec<Instruction>,
+    /// Keyed by the index of `block`, `loop`, `if` and `try` instructions
+    block_ends: HashMap<u32, BlockEnd>,
     default_locals: Vec<Value>,
+    local_types: Vec<ValType>,
+    local_names: HashMap<u32, String>,
 }
 
 impl DefinedFunctionInstance {
@@ -62,6 +75,7 @@ impl DefinedFunctionInstance {
         module_index: ModuleIndex,
         body: FunctionBody,
         base_offset: usize,
+        local_names: HashMap<u32, String>,
     ) -> Result<Self> {
         let mut locals = Vec::new();
         let reader = body.get_locals_reader()?;
@@ -76,13 +90,14 @@ impl DefinedFunctionInstance {
             let inst = transform_inst(&mut reader, base_offset)?;
             instructions.push(inst);
         }
+        let block_ends = compute_block_ends(&instructions);
 
         // Compute default local values here instead of frame initialization
         // to avoid re-computation
         let mut local_tys = ty.params().to_vec();
         local_tys.append(&mut locals.to_vec());
         let mut default_locals = Vec::new();
-        for ty in local_tys {
+        for ty in local_tys.iter().copied() {
             let v = match ty {
                 ValType::I32 => Value::I32(0),
                 ValType::I64 => Value::I64(0),
@@ -100,7 +115,10 @@ impl DefinedFunctionInstance {
             ty,
             module_index,
             instructions,
+            block_ends,
             default_locals,
+            local_types: local_tys,
+            local_names,
         })
     }
 
@@ -124,9 +142,58 @@ impl DefinedFunctionInstance {
         self.instructions.get(index.0 as usize)
     }
 
+    /// Returns the matching `else` and `end` of the structured instruction at `start`
+    pub(crate) fn block_end(&self, start: InstIndex) -> BlockEnd {
+        self.block_ends[&start.0]
+    }
+
     pub(crate) fn default_locals(&self) -> &[Value] {
         &self.default_locals
     }
+
+    /// Returns the types of all locals including parameters
+    pub fn local_types(&self) -> &[ValType] {
+        &self.local_types
+    }
+
+    /// Returns the name of the local given by the name section
+    pub fn local_name(&self, index: u32) -> Option<&String> {
+        self.local_names.get(&index)
+    }
+}
+
+/// Matches structured control instructions with their `else` and `end` once
+/// when decoding, so that branches don't scan the body for them
+fn compute_block_ends(instructions: &[Instruction]) -> HashMap<u32, BlockEnd> {
+    let mut block_ends = HashMap::new();
+    // Pairs of the start index and the `else` index of open blocks
+    let mut open_blocks: Vec<(u32, Option<InstIndex>)> = Vec::new();
+    for (index, inst) in instructions.iter().enumerate() {
+        let index = index as u32;
+        match inst.kind {
+            InstructionKind::Block { .. }
+            | InstructionKind::Loop { .. }
+            | InstructionKind::If { .. }
+            | InstructionKind::Try { .. } => open_blocks.push((index, None)),
+            InstructionKind::Else => {
+                if let Some((_, else_index)) = open_blocks.last_mut() {
+                    *else_index = Some(InstIndex(index));
+                }
+            }
+            InstructionKind::End | InstructionKind::Delegate { .. } => {
+                // The last `end` of the function body has no open block
+                if let Some((start, else_index)) = open_blocks.pop() {
+                    let end = BlockEnd {
+                        else_index,
+                        end_index: InstIndex(index),
+                    };
+                    block_ends.insert(start, end);
+                }
+            }
+            _ => (),
+        }
+    }
+    block_ends
 }
 
 pub struct NativeFunctionInstance {
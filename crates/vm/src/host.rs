@@ -1,29 +1,44 @@
-use crate::address::MemoryAddr;
+use crate::address::{MemoryAddr, TableAddr};
 use crate::executor::Trap;
 use crate::global::GlobalInstance;
 use crate::memory::MemoryInstance;
 use crate::module::ModuleIndex;
 use crate::store::Store;
 use crate::table::TableInstance;
-use crate::value::Value;
+use crate::value::{NativeValue, Value};
 use std::cell::RefCell;
+#[cfg(feature = "async-host")]
+use std::future::Future;
+#[cfg(feature = "async-host")]
+use std::pin::Pin;
 use std::rc::Rc;
-use wasmparser::FuncType;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use wasmparser::{FuncType, ValType};
 
 type Ref<T> = Rc<RefCell<T>>;
 
 pub struct HostContext<'a> {
-    pub mem: &'a mut [u8],
+    /// Linear memories of the calling module in the order of memory indices
+    pub mems: Vec<&'a mut [u8]>,
+    /// The table 0 of the calling module, e.g. to resolve `funcref` arguments
+    pub table: Option<&'a mut TableInstance>,
 }
 
 pub enum HostValue {
     Func(HostFuncBody),
+    #[cfg(feature = "async-host")]
+    AsyncFunc(AsyncHostFuncBody),
     Global(Rc<RefCell<GlobalInstance>>),
     Mem(Ref<MemoryInstance>),
     Table(Ref<TableInstance>),
 }
 
 type HostCode = dyn Fn(&[Value], &mut Vec<Value>, &mut HostContext, &Store) -> Result<(), Trap>;
+type TimeoutCode = dyn Fn(Vec<Value>) -> Result<Vec<Value>, Trap> + Send + Sync;
+
+/// Runs before a host function with its name and arguments, returning results to skip the body
+pub type HostFuncInterceptor = Box<dyn Fn(&str, &[Value]) -> Option<Vec<Value>>>;
 
 pub struct HostFuncBody {
     ty: FuncType,
@@ -42,6 +57,93 @@ impl HostFuncBody {
         }
     }
 
+    /// Makes a body of a closure taking and returning Rust values, e.g. `|a: i32, b: i32| a + b`,
+    /// whose function type is derived from the closure signature
+    pub fn wrap<Params, Results>(f: impl IntoHostFunc<Params, Results>) -> Self {
+        f.into_host_func()
+    }
+
+    /// Makes a body which fails with `Trap::HostFunctionTimeout` when `code` takes longer than
+    /// `duration`.
+    ///
+    /// `code` runs on a worker thread, so unlike other bodies it takes the arguments by value
+    /// and has no access to the memories or the store. The worker is reused across calls.
+    /// When a call times out, the worker is abandoned and keeps running until `code` returns,
+    /// and the next call spawns a new one.
+    pub fn new_with_timeout<F>(ty: FuncType, name: String, duration: Duration, code: F) -> Self
+    where
+        F: Fn(Vec<Value>) -> Result<Vec<Value>, Trap>,
+        F: Send + Sync + 'static,
+    {
+        let code: Arc<TimeoutCode> = Arc::new(code);
+        let worker = RefCell::new(None);
+        Self::new(ty, move |args, results, _, _| {
+            let mut worker = worker.borrow_mut();
+            let current = worker.get_or_insert_with(|| TimeoutWorker::spawn(code.clone()));
+            let received = match current.args.send(args.to_vec()) {
+                Ok(()) => current.results.recv_timeout(duration),
+                Err(_) => Err(mpsc::RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(values) => {
+                    *results = values?;
+                    Ok(())
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    *worker = None;
+                    Err(Trap::HostFunctionTimeout {
+                        name: name.clone(),
+                        duration,
+                    })
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    *worker = None;
+                    Err(host_argument_error(format!(
+                        "host function '{}' panicked",
+                        name
+                    )))
+                }
+            }
+        })
+    }
+
+    /// Wraps the body to fail with `Trap::HostFunctionTimeout` when it takes longer than
+    /// `duration`.
+    ///
+    /// Bodies and the store are not thread-safe, so the body keeps running on the calling
+    /// thread and the timeout is reported when it returns. Use `new_with_timeout` to give up
+    /// waiting on code which may never return. The name of the trap is left empty, and the
+    /// executor fills it with the imported name.
+    pub fn with_timeout(self, duration: Duration) -> HostFuncBody {
+        let code = self.code;
+        Self::new(self.ty, move |args, results, ctx, store| {
+            let started_at = Instant::now();
+            let result = code(args, results, ctx, store);
+            if started_at.elapsed() > duration {
+                return Err(Trap::HostFunctionTimeout {
+                    name: String::new(),
+                    duration,
+                });
+            }
+            result
+        })
+    }
+
+    /// Wraps the body to return the results given by `interceptor` instead when it has any
+    pub fn with_interceptor(self, name: String, interceptor: HostFuncInterceptor) -> HostFuncBody {
+        let code = self.code;
+        Self::new(
+            self.ty,
+            move |args, results, ctx, store| match interceptor(&name, args) {
+                Some(values) => {
+                    *results = values;
+                    Ok(())
+                }
+                None => code(args, results, ctx, store),
+            },
+        )
+    }
+
     pub fn call(
         &self,
         param: &[Value],
@@ -49,20 +151,339 @@ impl HostFuncBody {
         store: &Store,
         module_index: ModuleIndex,
     ) -> Result<(), Trap> {
-        if store.memory_count(module_index) > 0 {
-            let mem_addr = MemoryAddr::new_unsafe(module_index, 0);
-            let mem = store.memory(mem_addr);
-            let mem = &mut mem.borrow_mut();
-            let raw_mem = mem.raw_data_mut();
-            let mut ctx = HostContext { mem: raw_mem };
-            (self.code)(param, results, &mut ctx, store)
+        let mems = (0..store.memory_count(module_index))
+            .map(|index| store.memory(MemoryAddr::new_unsafe(module_index, index)))
+            .collect::<Vec<_>>();
+        let table = if store.table_count(module_index) > 0 {
+            Some(store.table(TableAddr::new_unsafe(module_index, 0)))
         } else {
-            let mut ctx = HostContext { mem: &mut [] };
-            (self.code)(param, results, &mut ctx, store)
+            None
+        };
+        let mut mems = mems.iter().map(|mem| mem.borrow_mut()).collect::<Vec<_>>();
+        let mut table = table.as_ref().map(|table| table.borrow_mut());
+        let mut ctx = HostContext {
+            mems: mems.iter_mut().map(|mem| mem.raw_data_mut()).collect(),
+            table: table.as_deref_mut(),
+        };
+        (self.code)(param, results, &mut ctx, store)
+    }
+
+    /// Calls the body with a context already borrowed by another host function
+    pub fn call_with_context(
+        &self,
+        param: &[Value],
+        results: &mut Vec<Value>,
+        ctx: &mut HostContext,
+        store: &Store,
+    ) -> Result<(), Trap> {
+        (self.code)(param, results, ctx, store)
+    }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+}
+
+/// Thread running the code of a body made by `HostFuncBody::new_with_timeout`
+struct TimeoutWorker {
+    args: mpsc::Sender<Vec<Value>>,
+    results: mpsc::Receiver<Result<Vec<Value>, Trap>>,
+}
+
+impl TimeoutWorker {
+    fn spawn(code: Arc<TimeoutCode>) -> Self {
+        let (args_tx, args_rx) = mpsc::channel::<Vec<Value>>();
+        let (results_tx, results_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for args in args_rx {
+                // The caller has given up waiting when the receiver is gone
+                if results_tx.send(code(args)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            args: args_tx,
+            results: results_rx,
+        }
+    }
+}
+
+/// Results of host functions made by `HostFuncBody::wrap`: `()`, a `NativeValue` or a tuple of them
+pub trait HostResults {
+    fn types() -> Vec<ValType>;
+    fn into_values(self) -> Vec<Value>;
+}
+
+impl<T: NativeValue + Into<Value>> HostResults for T {
+    fn types() -> Vec<ValType> {
+        vec![T::value_type()]
+    }
+    fn into_values(self) -> Vec<Value> {
+        vec![self.into()]
+    }
+}
+
+macro_rules! impl_host_results {
+    ($($result:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($result: NativeValue + Into<Value>),*> HostResults for ($($result,)*) {
+            fn types() -> Vec<ValType> {
+                vec![$($result::value_type()),*]
+            }
+            fn into_values(self) -> Vec<Value> {
+                let ($($result,)*) = self;
+                vec![$($result.into()),*]
+            }
+        }
+    };
+}
+
+impl_host_results!();
+impl_host_results!(R1, R2);
+impl_host_results!(R1, R2, R3);
+impl_host_results!(R1, R2, R3, R4);
+
+/// Closures which `HostFuncBody::wrap` accepts, taking up to 8 `NativeValue` parameters
+pub trait IntoHostFunc<Params, Results> {
+    fn into_host_func(self) -> HostFuncBody;
+}
+
+fn host_argument_error(message: String) -> Trap {
+    Trap::HostFunctionError(message.into())
+}
+
+macro_rules! impl_into_host_func {
+    ($($param:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<F, $($param,)* R> IntoHostFunc<($($param,)*), R> for F
+        where
+            F: Fn($($param),*) -> R + 'static,
+            $($param: NativeValue,)*
+            R: HostResults,
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_host_func(self) -> HostFuncBody {
+                let params = vec![$($param::value_type()),*];
+                let ty = FuncType::new(params.clone(), R::types());
+                HostFuncBody::new(ty, move |args, results, _ctx, _store| {
+                    if args.len() != params.len() {
+                        return Err(host_argument_error(format!(
+                            "host function takes {} arguments, but {} are given",
+                            params.len(),
+                            args.len()
+                        )));
+                    }
+                    let mut args = args.iter().copied().enumerate();
+                    $(
+                        let (index, value) = args.next().unwrap();
+                        let $param = $param::from_value(value).ok_or_else(|| {
+                            host_argument_error(format!(
+                                "argument {} of host function should be {:?}, but {:?} is given",
+                                index,
+                                $param::value_type(),
+                                value.value_type()
+                            ))
+                        })?;
+                    )*
+                    *results = (self)($($param),*).into_values();
+                    Ok(())
+                })
+            }
+        }
+    };
+}
+
+impl_into_host_func!();
+impl_into_host_func!(A1);
+impl_into_host_func!(A1, A2);
+impl_into_host_func!(A1, A2, A3);
+impl_into_host_func!(A1, A2, A3, A4);
+impl_into_host_func!(A1, A2, A3, A4, A5);
+impl_into_host_func!(A1, A2, A3, A4, A5, A6);
+impl_into_host_func!(A1, A2, A3, A4, A5, A6, A7);
+impl_into_host_func!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+#[cfg(feature = "async-host")]
+type AsyncHostCode = dyn Fn(Vec<Value>) -> Pin<Box<dyn Future<Output = Result<Vec<Value>, Trap>>>>;
+
+/// A host function whose body returns a future, e.g. to do async I/O without blocking in it
+#[cfg(feature = "async-host")]
+pub struct AsyncHostFuncBody {
+    ty: FuncType,
+    code: Box<AsyncHostCode>,
+    handle: Option<tokio::runtime::Handle>,
+}
+
+#[cfg(feature = "async-host")]
+impl AsyncHostFuncBody {
+    /// The futures are driven by the runtime entered when this is called, if any
+    pub fn new_async<F, Fut>(ty: FuncType, f: F) -> Self
+    where
+        F: Fn(Vec<Value>) -> Fut,
+        F: 'static,
+        Fut: Future<Output = Result<Vec<Value>, Trap>> + 'static,
+    {
+        Self {
+            ty,
+            code: Box::new(move |args| Box::pin(f(args))),
+            handle: tokio::runtime::Handle::try_current().ok(),
         }
     }
 
+    /// Drives the futures by the runtime of `handle` instead
+    pub fn with_handle(mut self, handle: tokio::runtime::Handle) -> Self {
+        self.handle = Some(handle);
+        self
+    }
+
+    /// Adapts the body to a synchronous one, which the executor can call, by blocking on
+    /// each future. Without a runtime handle, a fresh current-thread runtime is created on
+    /// the first call.
+    ///
+    /// Blocking panics on a thread driving the runtime, so the VM has to run on another thread.
+    pub fn to_sync(self) -> HostFuncBody {
+        let code = self.code;
+        let handle = self.handle;
+        let runtime = RefCell::new(None);
+        HostFuncBody::new(self.ty, move |args, results, _ctx, _store| {
+            let future = code(args.to_vec());
+            *results = match &handle {
+                Some(handle) => handle.block_on(future)?,
+                None => {
+                    let mut runtime = runtime.borrow_mut();
+                    if runtime.is_none() {
+                        let fresh = tokio::runtime::Builder::new_current_thread()
+                            .enable_all()
+                            .build()
+                            .map_err(|err| Trap::HostFunctionError(Box::new(err)))?;
+                        *runtime = Some(fresh);
+                    }
+                    let runtime: &tokio::runtime::Runtime = runtime.as_ref().unwrap();
+                    runtime.block_on(future)?
+                }
+            };
+            Ok(())
+        })
+    }
+
     pub fn ty(&self) -> &FuncType {
         &self.ty
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn call(body: &HostFuncBody, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let mut results = Vec::new();
+        body.call(args, &mut results, &Store::new(), ModuleIndex(0))?;
+        Ok(results)
+    }
+
+    #[test]
+    fn wrap_closures() {
+        let add = HostFuncBody::wrap(|a: i32, b: i32| a + b);
+        assert_eq!(
+            add.ty(),
+            &FuncType::new([ValType::I32, ValType::I32], [ValType::I32])
+        );
+        assert_eq!(
+            call(&add, &[Value::I32(1), Value::I32(2)]).unwrap(),
+            vec![Value::I32(3)]
+        );
+        match call(&add, &[Value::I32(1)]) {
+            Err(Trap::HostFunctionError(err)) => assert_eq!(
+                err.to_string(),
+                "host function takes 2 arguments, but 1 are given"
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let logged = Rc::new(Cell::new(0));
+        let log = HostFuncBody::wrap({
+            let logged = logged.clone();
+            move |value: i64| logged.set(value)
+        });
+        assert_eq!(log.ty(), &FuncType::new([ValType::I64], []));
+        assert_eq!(call(&log, &[Value::I64(7)]).unwrap(), vec![]);
+        assert_eq!(logged.get(), 7);
+
+        let split = HostFuncBody::wrap(|value: f64| (value.trunc() as i32, value.fract()));
+        assert_eq!(
+            split.ty(),
+            &FuncType::new([ValType::F64], [ValType::I32, ValType::F64])
+        );
+        assert_eq!(
+            call(&split, &[Value::from(2.5f64)]).unwrap(),
+            vec![Value::I32(2), Value::from(0.5f64)]
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let ty = FuncType::new([ValType::I32], [ValType::I32]);
+        let body = HostFuncBody::new_with_timeout(
+            ty,
+            "sleep".to_string(),
+            Duration::from_millis(50),
+            |args| {
+                let millis = args[0].as_i32().unwrap();
+                std::thread::sleep(Duration::from_millis(millis as u64));
+                Ok(vec![Value::I32(millis)])
+            },
+        );
+        assert_eq!(call(&body, &[Value::I32(0)]).unwrap(), vec![Value::I32(0)]);
+        match call(&body, &[Value::I32(10_000)]) {
+            Err(Trap::HostFunctionTimeout { name, duration }) => {
+                assert_eq!(name, "sleep");
+                assert_eq!(duration, Duration::from_millis(50));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // The abandoned worker does not block later calls
+        assert_eq!(call(&body, &[Value::I32(1)]).unwrap(), vec![Value::I32(1)]);
+    }
+
+    #[test]
+    fn with_timeout() {
+        let ty = FuncType::new([ValType::I32], [ValType::I32]);
+        let body = HostFuncBody::new(ty, |args, results, _, _| {
+            let millis = args[0].as_i32().unwrap();
+            std::thread::sleep(Duration::from_millis(millis as u64));
+            results.push(Value::I32(millis));
+            Ok(())
+        })
+        .with_timeout(Duration::from_millis(50));
+        assert_eq!(call(&body, &[Value::I32(0)]).unwrap(), vec![Value::I32(0)]);
+        assert!(matches!(
+            call(&body, &[Value::I32(100)]),
+            Err(Trap::HostFunctionTimeout { duration, .. }) if duration == Duration::from_millis(50)
+        ));
+    }
+
+    #[cfg(feature = "async-host")]
+    #[test]
+    fn async_host_func_to_sync() {
+        let ty = FuncType::new([ValType::I32], [ValType::I32]);
+        let body = AsyncHostFuncBody::new_async(ty, |args| async move {
+            tokio::task::yield_now().await;
+            match args[0].as_i32() {
+                Some(v) => Ok(vec![Value::I32(v + 1)]),
+                None => Err(Trap::UnreachableInstruction { offset: 0 }),
+            }
+        });
+        let body = body.to_sync();
+        let mut results = Vec::new();
+        body.call(
+            &[Value::I32(41)],
+            &mut results,
+            &Store::new(),
+            ModuleIndex(0),
+        )
+        .unwrap();
+        assert_eq!(results, vec![Value::I32(42)]);
+    }
+}
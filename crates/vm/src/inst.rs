@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::fmt;
 use wasminspect_vm_macro::{define_instr_kind, TryFromWasmParserOperator};
 use wasmparser::*;
 #[derive(Debug, Clone)]
@@ -38,8 +39,143 @@ impl WasmInstPayloadFrom<BrTable<'_>> for BrTableData {
     }
 }
 
+/// An immediate operand of an instruction, printed in the text format
+pub trait Immediate {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl Immediate for u8 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Immediate for u32 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Immediate for i32 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Immediate for i64 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Immediate for Ieee32 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", f32::from_bits(self.bits()))
+    }
+}
+
+impl Immediate for Ieee64 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", f64::from_bits(self.bits()))
+    }
+}
+
+impl Immediate for V128 {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "i32x4")?;
+        for lane in self.bytes().chunks(4) {
+            let lane = u32::from_le_bytes([lane[0], lane[1], lane[2], lane[3]]);
+            write!(f, " 0x{:08x}", lane)?;
+        }
+        Ok(())
+    }
+}
+
+impl Immediate for [u8; 16] {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lanes = self.iter().map(|lane| lane.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", lanes.join(" "))
+    }
+}
+
+impl Immediate for ValType {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+            ValType::V128 => "v128",
+            ValType::FuncRef => "funcref",
+            ValType::ExternRef => "externref",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Immediate for BlockType {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockType::Empty => Ok(()),
+            BlockType::Type(ty) => {
+                write!(f, "(result ")?;
+                ty.fmt_immediate(f)?;
+                write!(f, ")")
+            }
+            BlockType::FuncType(index) => write!(f, "(type {})", index),
+        }
+    }
+}
+
+impl Immediate for MemArg {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields = vec![];
+        if self.memory != 0 {
+            fields.push(self.memory.to_string());
+        }
+        if self.offset != 0 {
+            fields.push(format!("offset={}", self.offset));
+        }
+        // Natural alignments are omitted as in the text format
+        if self.align != self.max_align {
+            fields.push(format!("align={}", 1u64 << self.align));
+        }
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+impl Immediate for BrTableData {
+    fn fmt_immediate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for target in &self.table {
+            write!(f, "{} ", target)?;
+        }
+        write!(f, "{}", self.default)
+    }
+}
+
 for_each_operator!(define_instr_kind);
 
+impl fmt::Display for InstructionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for immediate in self.immediates() {
+            let immediate = ImmediateDisplay(immediate).to_string();
+            if !immediate.is_empty() {
+                write!(f, " {}", immediate)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ImmediateDisplay<'a>(&'a dyn Immediate);
+
+impl fmt::Display for ImmediateDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_immediate(f)
+    }
+}
+
 pub fn transform_inst(
     reader: &mut OperatorsReader,
     base_offset: usize,
@@ -51,3 +187,59 @@ pub fn transform_inst(
         offset: offset - base_offset,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_instructions() {
+        let memarg = MemArg {
+            align: 0,
+            max_align: 2,
+            offset: 4,
+            memory: 0,
+        };
+        let cases = vec![
+            (InstructionKind::Nop, "nop"),
+            (InstructionKind::LocalGet { local_index: 1 }, "local.get 1"),
+            (
+                InstructionKind::I32Load { memarg },
+                "i32.load offset=4 align=1",
+            ),
+            (
+                InstructionKind::BrTable {
+                    targets: BrTableData {
+                        table: vec![0, 1],
+                        default: 2,
+                    },
+                },
+                "br_table 0 1 2",
+            ),
+            (
+                InstructionKind::I32x4ExtractLane { lane: 3 },
+                "i32x4.extract_lane 3",
+            ),
+            (
+                InstructionKind::I32AtomicRmw8AddU { memarg },
+                "i32.atomic.rmw8.add_u offset=4 align=1",
+            ),
+            (InstructionKind::I32TruncSatF32S, "i32.trunc_sat_f32_s"),
+            (
+                InstructionKind::Block {
+                    blockty: BlockType::Type(ValType::I32),
+                },
+                "block (result i32)",
+            ),
+            (
+                InstructionKind::Block {
+                    blockty: BlockType::Empty,
+                },
+                "block",
+            ),
+        ];
+        for (inst, expected) in cases {
+            assert_eq!(inst.to_string(), expected);
+        }
+    }
+}
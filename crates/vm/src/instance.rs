@@ -35,6 +35,11 @@ impl WasmInstance {
         self.store.load_module(name, reader)
     }
 
+    /// Loads modules which import each other's exports by their names
+    pub fn load_linked_modules(&mut self, modules: &[(String, &[u8])]) -> Result<Vec<ModuleIndex>> {
+        self.store.load_linked_modules(modules)
+    }
+
     pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
         self.store.load_host_module(name, module)
     }
@@ -1,11 +1,37 @@
 use crate::executor::{ExecResult, Signal};
 use crate::inst::Instruction;
+use crate::memory::MemoryInstance;
+use crate::value::Value;
 use crate::{Executor, Store};
 
 pub trait Interceptor {
     fn invoke_func(&self, name: &str, executor: &Executor, store: &Store) -> ExecResult<Signal>;
-    fn execute_inst(&self, inst: &Instruction) -> ExecResult<Signal>;
+    /// Called before `inst` is executed by `executor`. Does nothing by default
+    fn execute_inst(&self, _inst: &Instruction, _executor: &Executor) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
+    /// Called before `bytes` are written at `addr`, so `memory` still has the old contents.
+    /// Does nothing by default
+    fn before_store(
+        &self,
+        _addr: usize,
+        _bytes: &[u8],
+        _memory: &MemoryInstance,
+    ) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
     fn after_store(&self, addr: usize, bytes: &[u8]) -> ExecResult<Signal>;
+    /// Called after `local.set` or `local.tee` changed a local. Does nothing by default
+    fn after_set_local(
+        &self,
+        _local_index: usize,
+        _old_value: Value,
+        _new_value: Value,
+        _inst: &Instruction,
+        _executor: &Executor,
+    ) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
 }
 
 #[derive(Default)]
@@ -19,9 +45,6 @@ impl Interceptor for NopInterceptor {
     fn invoke_func(&self, _name: &str, _executor: &Executor, _store: &Store) -> ExecResult<Signal> {
         Ok(Signal::Next)
     }
-    fn execute_inst(&self, _inst: &Instruction) -> ExecResult<Signal> {
-        Ok(Signal::Next)
-    }
 
     fn after_store(&self, _addr: usize, _bytes: &[u8]) -> ExecResult<Signal> {
         Ok(Signal::Next)
@@ -0,0 +1,133 @@
+use crate::address::MemoryAddr;
+use crate::config::Config;
+use crate::executor::{ReturnValError, Trap, WasmError};
+use crate::host::HostValue;
+use crate::instance::WasmInstance;
+use crate::memory::MemoryInstance;
+use crate::module::ModuleIndex;
+use crate::stack;
+use crate::value::Value;
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An instantiated module for embedding the interpreter, which hides `Store`
+/// and `ModuleIndex` behind the exports of the module
+///
+/// ```
+/// use std::collections::HashMap;
+/// use wasminspect_vm::{Interpreter, Trap, WasmValue};
+///
+/// // (func (export "add") (param i32 i32) (result i32)
+/// //   (i32.add (local.get 0) (local.get 1)))
+/// let bytes = [
+///     0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x02, 0x7f,
+///     0x7f, 0x01, 0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x07, 0x01, 0x03, b'a', b'd', b'd',
+///     0x00, 0x00, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+/// ];
+/// let mut interpreter = Interpreter::instantiate(&bytes, HashMap::new()).unwrap();
+/// let results = interpreter
+///     .invoke("add", &[WasmValue::I32(1), WasmValue::I32(2)])
+///     .unwrap();
+/// assert_eq!(results, vec![WasmValue::I32(3)]);
+/// assert!(matches!(
+///     interpreter.invoke("sub", &[]),
+///     Err(Trap::UndefinedExport(_))
+/// ));
+/// ```
+pub struct Interpreter {
+    instance: WasmInstance,
+    module_index: ModuleIndex,
+    config: Config,
+}
+
+impl Interpreter {
+    /// Instantiates the module of `bytes`, whose imports are resolved from
+    /// `imports` keyed by their module names and field names
+    pub fn instantiate(
+        bytes: &[u8],
+        imports: HashMap<String, HashMap<String, HostValue>>,
+    ) -> Result<Self> {
+        let mut instance = WasmInstance::new();
+        for (name, module) in imports {
+            instance.load_host_module(name, module);
+        }
+        let module_index = instance.load_module_from_module(None, &mut bytes.to_vec())?;
+        Ok(Self {
+            instance,
+            module_index,
+            config: Config::default(),
+        })
+    }
+
+    /// Sets the configuration used by `invoke`, e.g. its execution limit
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Calls the exported function `name` with `args`
+    pub fn invoke(&mut self, name: &str, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        self.instance
+            .run(
+                self.module_index,
+                Some(name.to_string()),
+                args.to_vec(),
+                &self.config,
+            )
+            .map_err(into_trap)
+    }
+
+    /// Returns the value of the exported global `name`
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.instance.get_global(self.module_index, name)
+    }
+
+    /// Reads `length` bytes at `offset` of the default memory
+    pub fn read_memory(&self, offset: usize, length: usize) -> Result<Vec<u8>> {
+        let memory = self.default_memory()?;
+        let memory = memory.borrow();
+        memory
+            .validate_region(offset, length)
+            .map_err(|err| anyhow!("{}", err))?;
+        Ok(memory.raw_data()[offset..offset + length].to_vec())
+    }
+
+    /// Writes `bytes` at `offset` of the default memory
+    pub fn write_memory(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let memory = self.default_memory()?;
+        let mut memory = memory.borrow_mut();
+        memory
+            .store(offset, bytes)
+            .map_err(|err| anyhow!("{}", err))
+    }
+
+    fn default_memory(&self) -> Result<Rc<RefCell<MemoryInstance>>> {
+        if self.instance.store.memory_count(self.module_index) == 0 {
+            return Err(anyhow!("the module has no memory"));
+        }
+        Ok(self
+            .instance
+            .store
+            .memory(MemoryAddr::new_unsafe(self.module_index, 0)))
+    }
+}
+
+/// Reports the errors which are not raised by the executed code as traps too
+fn into_trap(err: WasmError) -> Trap {
+    match err {
+        WasmError::ExecutionError(trap) => trap,
+        WasmError::EntryFunctionNotFound(name) => Trap::UndefinedExport(name),
+        WasmError::ReturnValueError(ReturnValError::TypeMismatchReturnValue(value, ty)) => {
+            Trap::UnexpectedStackValueType {
+                expected: ty,
+                actual: value.value_type(),
+            }
+        }
+        WasmError::ReturnValueError(ReturnValError::Stack(err)) => Trap::Stack(err),
+        WasmError::ReturnValueError(ReturnValError::NoValue(_)) => {
+            Trap::Stack(stack::Error::PopEmptyStack)
+        }
+        err @ WasmError::HostExecutionError => Trap::HostFunctionError(err.to_string().into()),
+    }
+}
@@ -10,28 +10,41 @@ mod host;
 mod inst;
 mod instance;
 mod interceptor;
+mod interpreter;
 mod linker;
 mod memory;
 mod module;
+mod name;
+mod simd;
 mod stack;
 mod store;
 mod table;
+mod tag;
 mod value;
 
 pub use self::address::*;
-pub use self::config::Config;
-pub use self::executor::{Executor, Signal, Trap, WasmError};
+pub use self::config::{Config, ExecutionLimit};
+pub use self::executor::{Executor, Signal, Trap, TrapLocation, WasmError};
 pub use self::func::{FunctionInstance, InstIndex};
 pub use self::global::GlobalInstance;
-pub use self::host::{HostContext, HostFuncBody, HostValue};
-pub use self::inst::{Instruction, InstructionKind};
+#[cfg(feature = "async-host")]
+pub use self::host::AsyncHostFuncBody;
+pub use self::host::{
+    HostContext, HostFuncBody, HostFuncInterceptor, HostResults, HostValue, IntoHostFunc,
+};
+pub use self::inst::{BrTableData, Immediate, Instruction, InstructionKind};
 pub use self::instance::WasmInstance;
 pub use self::interceptor::{Interceptor, NopInterceptor};
+pub use self::interpreter::Interpreter;
 pub use self::memory::MemoryInstance as HostMemory;
-pub use self::module::{DefinedModuleInstance, ModuleIndex};
-pub use self::stack::{CallFrame, ProgramCounter};
-pub use self::store::Store;
+pub use self::module::{
+    DefinedModuleError, DefinedModuleInstance, HostModuleInstance, ModuleIndex,
+};
+pub use self::name::NameMap;
+pub use self::stack::{CallFrame, ProgramCounter, StackDelta};
+pub use self::store::{Store, StoreError};
 pub use self::table::TableInstance as HostTable;
+pub use self::tag::{Exception, TagInstance};
 pub use self::value::Value as WasmValue;
 pub use self::value::*;
 
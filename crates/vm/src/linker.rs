@@ -186,6 +186,17 @@ impl<Item> LinkableCollection<Item> {
         )
     }
 
+    pub(crate) fn index_in_module(
+        &self,
+        module_index: ModuleIndex,
+        address: GlobalAddress<Item>,
+    ) -> Option<usize> {
+        self.item_addrs_by_module
+            .get(&module_index)?
+            .iter()
+            .position(|index| *index == address.0)
+    }
+
     pub(crate) fn is_empty(&self, module_index: ModuleIndex) -> bool {
         self.item_addrs_by_module
             .get(&module_index)
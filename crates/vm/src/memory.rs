@@ -5,6 +5,7 @@ pub struct MemoryInstance {
     data: Vec<u8>,
     pub max: Option<usize>,
     pub initial: usize,
+    on_grow: Option<Box<dyn Fn(u32, u32)>>,
 }
 
 #[derive(Debug)]
@@ -45,9 +46,15 @@ impl MemoryInstance {
                 .collect(),
             initial,
             max: maximum,
+            on_grow: None,
         }
     }
 
+    /// Sets a hook called with `(old_pages, new_pages)` after the memory successfully grows
+    pub fn set_on_grow(&mut self, hook: Box<dyn Fn(u32, u32)>) {
+        self.on_grow = Some(hook);
+    }
+
     pub fn validate_region(&self, offset: usize, size: usize) -> Result<()> {
         if let Some(max_addr) = offset.checked_add(size) {
             if max_addr > self.data_len() {
@@ -97,9 +104,13 @@ impl MemoryInstance {
                 return Err(Error::GrowOverMaximumSize(max));
             }
         }
+        let old_len = self.page_count();
         let zero_len = n * WASM_PAGE_SIZE;
         self.data.resize(self.data.len() + zero_len, 0);
         self.initial = len;
+        if let Some(hook) = &self.on_grow {
+            hook(old_len as u32, len as u32);
+        }
         Ok(())
     }
     pub fn raw_data_mut(&mut self) -> &mut [u8] {
@@ -1,5 +1,7 @@
 use crate::address::*;
 use crate::export::{ExportInstance, ExternalValue};
+use crate::host::{HostFuncBody, HostFuncInterceptor};
+use crate::name::NameMap;
 
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -25,11 +27,12 @@ pub struct DefinedModuleInstance {
     types: Vec<wasmparser::FuncType>,
     pub exports: Vec<ExportInstance>,
     start_func: Option<FuncAddr>,
+    names: NameMap,
 }
 
 #[derive(Debug)]
 pub enum DefinedModuleError {
-    TypeMismatch(&'static str, String),
+    TypeMismatch(String, String),
 }
 
 impl std::fmt::Display for DefinedModuleError {
@@ -54,6 +57,7 @@ impl DefinedModuleInstance {
         types: Vec<wasmparser::FuncType>,
         exports: Vec<wasmparser::Export>,
         start_func: Option<FuncAddr>,
+        names: NameMap,
     ) -> Self {
         Self {
             types,
@@ -62,6 +66,7 @@ impl DefinedModuleInstance {
                 .map(|e| ExportInstance::new_from_entry(*e, module_index))
                 .collect(),
             start_func,
+            names,
         }
     }
 
@@ -75,7 +80,7 @@ impl DefinedModuleInstance {
             Some(e) => match e.value() {
                 ExternalValue::Global(addr) => Ok(Some(*addr)),
                 _ => Err(DefinedModuleError::TypeMismatch(
-                    "global",
+                    "global".to_string(),
                     e.value().type_name().to_string(),
                 )),
             },
@@ -89,7 +94,7 @@ impl DefinedModuleInstance {
             Some(e) => match e.value() {
                 ExternalValue::Func(addr) => Ok(Some(*addr)),
                 _ => Err(DefinedModuleError::TypeMismatch(
-                    "function",
+                    "function".to_string(),
                     e.value().type_name().to_string(),
                 )),
             },
@@ -103,7 +108,7 @@ impl DefinedModuleInstance {
             Some(e) => match e.value() {
                 ExternalValue::Table(addr) => Ok(Some(*addr)),
                 _ => Err(DefinedModuleError::TypeMismatch(
-                    "table",
+                    "table".to_string(),
                     e.value().type_name().to_string(),
                 )),
             },
@@ -117,7 +122,21 @@ impl DefinedModuleInstance {
             Some(e) => match e.value() {
                 ExternalValue::Memory(addr) => Ok(Some(*addr)),
                 _ => Err(DefinedModuleError::TypeMismatch(
-                    "memory",
+                    "memory".to_string(),
+                    e.value().type_name().to_string(),
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn exported_tag(&self, name: &str) -> DefinedModuleResult<Option<TagAddr>> {
+        let export = self.exported_by_name(name);
+        match export {
+            Some(e) => match e.value() {
+                ExternalValue::Tag(addr) => Ok(Some(*addr)),
+                _ => Err(DefinedModuleError::TypeMismatch(
+                    "tag".to_string(),
                     e.value().type_name().to_string(),
                 )),
             },
@@ -132,15 +151,39 @@ impl DefinedModuleInstance {
     pub fn get_type(&self, index: usize) -> &wasmparser::FuncType {
         &self.types[index]
     }
+
+    /// Names given by the name section
+    pub fn names(&self) -> &NameMap {
+        &self.names
+    }
+
+    /// Returns the name of the global given by the name section
+    pub fn global_name(&self, index: u32) -> Option<&String> {
+        self.names.global_name(index)
+    }
+
+    /// Returns the name of the function given by the name section
+    pub fn function_name(&self, index: usize) -> Option<&str> {
+        self.names.function_name(index as u32)
+    }
+
+    /// Returns the index of the function named `name` in the name section
+    pub fn function_index(&self, name: &str) -> Option<usize> {
+        self.names.function_index(name).map(|index| index as usize)
+    }
 }
 
 pub struct HostModuleInstance {
     values: HashMap<String, HostExport>,
+    /// Functions registered after construction, which are allocated in the store on link
+    pending_funcs: HashMap<String, HostFuncBody>,
 }
 
 #[derive(Debug)]
 pub enum HostModuleError {
     TypeMismatch(&'static str, String),
+    UnknownFunction(String),
+    AlreadyLinked(String),
 }
 
 impl std::fmt::Display for HostModuleError {
@@ -151,10 +194,16 @@ impl std::fmt::Display for HostModuleError {
                 "incompatible import type, expected {} but actual {}",
                 expected, actual
             ),
+            Self::UnknownFunction(name) => write!(f, "unknown host function '{}'", name),
+            Self::AlreadyLinked(name) => {
+                write!(f, "host function '{}' has already been linked", name)
+            }
         }
     }
 }
 
+impl std::error::Error for HostModuleError {}
+
 type HostModuleResult<T> = std::result::Result<T, HostModuleError>;
 
 pub enum HostExport {
@@ -176,7 +225,63 @@ impl HostExport {
 }
 impl HostModuleInstance {
     pub fn new(values: HashMap<String, HostExport>) -> Self {
-        Self { values }
+        Self {
+            values,
+            pending_funcs: HashMap::new(),
+        }
+    }
+
+    /// Registers a function, replacing any existing export with the same name.
+    ///
+    /// The function is allocated when a module imports from this host module next time,
+    /// so modules which already linked the previous function keep its address and call it.
+    pub fn register_func(&mut self, name: String, func: HostFuncBody) {
+        self.values.remove(&name);
+        self.pending_funcs.insert(name, func);
+    }
+
+    /// Removes a function from the exports.
+    ///
+    /// Returns the body if it has not been linked to any module yet. Modules which
+    /// already imported the function keep calling it.
+    pub fn unregister_func(&mut self, name: &str) -> Option<HostFuncBody> {
+        if let Some(HostExport::Func(_)) = self.values.get(name) {
+            self.values.remove(name);
+        }
+        self.pending_funcs.remove(name)
+    }
+
+    /// Returns the type of a function which has not been linked yet
+    pub fn func_type(&self, name: &str) -> Option<&wasmparser::FuncType> {
+        self.pending_funcs.get(name).map(|func| func.ty())
+    }
+
+    /// Wraps the function so that `interceptor` can replace its results.
+    ///
+    /// Only functions which have not been linked to any module can be intercepted.
+    pub fn intercept_func(
+        &mut self,
+        name: &str,
+        interceptor: HostFuncInterceptor,
+    ) -> HostModuleResult<()> {
+        let func = match self.pending_funcs.remove(name) {
+            Some(func) => func,
+            None if self.values.contains_key(name) => {
+                return Err(HostModuleError::AlreadyLinked(name.to_string()))
+            }
+            None => return Err(HostModuleError::UnknownFunction(name.to_string())),
+        };
+        let func = func.with_interceptor(name.to_string(), interceptor);
+        self.pending_funcs.insert(name.to_string(), func);
+        Ok(())
+    }
+
+    pub(crate) fn take_pending_funcs(&mut self) -> Vec<(String, HostFuncBody)> {
+        self.pending_funcs.drain().collect()
+    }
+
+    pub(crate) fn insert_func(&mut self, name: String, addr: ExecutableFuncAddr) {
+        self.values.insert(name, HostExport::Func(addr));
     }
 }
 
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmparser::NameSectionReader;
+
+/// Names of functions, locals and globals given by the `name` custom section
+#[derive(Clone, Debug, Default)]
+pub struct NameMap {
+    func_names: HashMap<u32, String>,
+    local_names: HashMap<u32, HashMap<u32, String>>,
+    global_names: HashMap<u32, String>,
+}
+
+impl NameMap {
+    /// Reads the subsections known to the debugger, stopping at the first malformed one
+    pub(crate) fn read(mut reader: NameSectionReader) -> Result<Self> {
+        let mut names = Self::default();
+        while !reader.eof() {
+            let name = match reader.read() {
+                Ok(name) => name,
+                Err(_) => return Ok(names),
+            };
+            match name {
+                wasmparser::Name::Module { .. } => continue,
+                wasmparser::Name::Function(map) => {
+                    for naming in map {
+                        let naming = naming?;
+                        names
+                            .func_names
+                            .insert(naming.index, String::from(naming.name));
+                    }
+                }
+                wasmparser::Name::Local(map) => {
+                    for indirect_naming in map {
+                        let indirect_naming = indirect_naming?;
+                        let mut local_names = HashMap::new();
+                        for naming in indirect_naming.names {
+                            let naming = naming?;
+                            local_names.insert(naming.index, String::from(naming.name));
+                        }
+                        names.local_names.insert(indirect_naming.index, local_names);
+                    }
+                }
+                wasmparser::Name::Global(map) => {
+                    for naming in map {
+                        let naming = naming?;
+                        names
+                            .global_names
+                            .insert(naming.index, String::from(naming.name));
+                    }
+                }
+                wasmparser::Name::Label(_)
+                | wasmparser::Name::Type(_)
+                | wasmparser::Name::Table(_)
+                | wasmparser::Name::Memory(_)
+                | wasmparser::Name::Element(_)
+                | wasmparser::Name::Data(_)
+                | wasmparser::Name::Unknown { .. } => continue,
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn function_name(&self, func_index: u32) -> Option<&str> {
+        self.func_names.get(&func_index).map(String::as_str)
+    }
+
+    /// Returns the smallest index of the functions named `name`
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        self.func_names
+            .iter()
+            .filter(|(_, func_name)| *func_name == name)
+            .map(|(index, _)| *index)
+            .min()
+    }
+
+    pub fn local_name(&self, func_index: u32, local_index: u32) -> Option<&str> {
+        self.local_names
+            .get(&func_index)?
+            .get(&local_index)
+            .map(String::as_str)
+    }
+
+    /// Names of the locals of a function keyed by local indices
+    pub fn local_names(&self, func_index: u32) -> HashMap<u32, String> {
+        self.local_names
+            .get(&func_index)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn global_name(&self, global_index: u32) -> Option<&String> {
+        self.global_names.get(&global_index)
+    }
+}
@@ -0,0 +1,293 @@
+//! Lane-wise operations on 128-bit vectors.
+//!
+//! The relaxed-simd proposal lets each relaxed instruction pick one of several
+//! results depending on the host hardware. This interpreter always picks the
+//! deterministic result described by the proposal, so the same module
+//! produces the same values on every host.
+//! Spec: https://github.com/WebAssembly/relaxed-simd/blob/main/proposals/relaxed-simd/Overview.md
+
+use crate::value::{TruncSat, F32, F64, IEEE754};
+
+fn i8_lanes(v: u128) -> [i8; 16] {
+    let mut lanes = [0; 16];
+    for (lane, byte) in lanes.iter_mut().zip(v.to_le_bytes().iter()) {
+        *lane = *byte as i8;
+    }
+    lanes
+}
+
+fn i16_lanes(v: u128) -> [i16; 8] {
+    let mut lanes = [0; 8];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = (v >> (i * 16)) as i16;
+    }
+    lanes
+}
+
+fn f32_lanes(v: u128) -> [F32; 4] {
+    let mut lanes = [F32::from_bits(0); 4];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = F32::from_bits((v >> (i * 32)) as u32);
+    }
+    lanes
+}
+
+fn f64_lanes(v: u128) -> [F64; 2] {
+    [F64::from_bits(v as u64), F64::from_bits((v >> 64) as u64)]
+}
+
+fn from_i8_lanes(lanes: [i8; 16]) -> u128 {
+    let mut bytes = [0; 16];
+    for (byte, lane) in bytes.iter_mut().zip(lanes.iter()) {
+        *byte = *lane as u8;
+    }
+    u128::from_le_bytes(bytes)
+}
+
+fn from_i16_lanes(lanes: [i16; 8]) -> u128 {
+    lanes
+        .iter()
+        .enumerate()
+        .fold(0, |v, (i, lane)| v | (u128::from(*lane as u16) << (i * 16)))
+}
+
+fn from_i32_lanes(lanes: [i32; 4]) -> u128 {
+    lanes
+        .iter()
+        .enumerate()
+        .fold(0, |v, (i, lane)| v | (u128::from(*lane as u32) << (i * 32)))
+}
+
+fn from_f32_lanes(lanes: [F32; 4]) -> u128 {
+    from_i32_lanes([
+        lanes[0].to_bits() as i32,
+        lanes[1].to_bits() as i32,
+        lanes[2].to_bits() as i32,
+        lanes[3].to_bits() as i32,
+    ])
+}
+
+fn from_f64_lanes(lanes: [F64; 2]) -> u128 {
+    u128::from(lanes[0].to_bits()) | (u128::from(lanes[1].to_bits()) << 64)
+}
+
+fn build_f32x4(f: impl Fn(usize) -> F32) -> u128 {
+    from_f32_lanes([f(0), f(1), f(2), f(3)])
+}
+
+fn zip_f32x4(a: u128, b: u128, f: impl Fn(F32, F32) -> F32) -> u128 {
+    let (a, b) = (f32_lanes(a), f32_lanes(b));
+    build_f32x4(|i| f(a[i], b[i]))
+}
+
+fn zip_f64x2(a: u128, b: u128, f: impl Fn(F64, F64) -> F64) -> u128 {
+    let (a, b) = (f64_lanes(a), f64_lanes(b));
+    from_f64_lanes([f(a[0], b[0]), f(a[1], b[1])])
+}
+
+/// `i8x16.relaxed_swizzle`: out of range indices select 0 as `i8x16.swizzle`
+pub(crate) fn i8x16_relaxed_swizzle(a: u128, s: u128) -> u128 {
+    let (a, s) = (a.to_le_bytes(), s.to_le_bytes());
+    let mut lanes = [0; 16];
+    for (lane, index) in lanes.iter_mut().zip(s.iter()) {
+        *lane = a.get(*index as usize).map_or(0, |v| *v as i8);
+    }
+    from_i8_lanes(lanes)
+}
+
+/// `i32x4.relaxed_trunc_f32x4_{s,u}`: saturates as `i32x4.trunc_sat_f32x4_{s,u}`
+pub(crate) fn i32x4_relaxed_trunc_f32x4<T: Into<i64>>(a: u128) -> u128
+where
+    F32: TruncSat<T>,
+{
+    let a = f32_lanes(a);
+    from_i32_lanes([
+        TruncSat::<T>::trunc_sat(a[0]).into() as i32,
+        TruncSat::<T>::trunc_sat(a[1]).into() as i32,
+        TruncSat::<T>::trunc_sat(a[2]).into() as i32,
+        TruncSat::<T>::trunc_sat(a[3]).into() as i32,
+    ])
+}
+
+/// `i32x4.relaxed_trunc_f64x2_{s,u}_zero`: saturates as
+/// `i32x4.trunc_sat_f64x2_{s,u}_zero` and fills the upper lanes with 0
+pub(crate) fn i32x4_relaxed_trunc_f64x2_zero<T: Into<i64>>(a: u128) -> u128
+where
+    F64: TruncSat<T>,
+{
+    let a = f64_lanes(a);
+    from_i32_lanes([
+        TruncSat::<T>::trunc_sat(a[0]).into() as i32,
+        TruncSat::<T>::trunc_sat(a[1]).into() as i32,
+        0,
+        0,
+    ])
+}
+
+/// `f32x4.relaxed_fma` (`relaxed_madd` in the latest proposal): a fused
+/// multiply-add with a single rounding
+pub(crate) fn f32x4_relaxed_fma(a: u128, b: u128, c: u128) -> u128 {
+    let (a, b, c) = (f32_lanes(a), f32_lanes(b), f32_lanes(c));
+    build_f32x4(|i| F32::from_native(a[i].to_float().mul_add(b[i].to_float(), c[i].to_float())))
+}
+
+/// `f32x4.relaxed_fnma` (`relaxed_nmadd` in the latest proposal): a fused
+/// multiply-add of the negated product
+pub(crate) fn f32x4_relaxed_fnma(a: u128, b: u128, c: u128) -> u128 {
+    f32x4_relaxed_fma(a ^ 0x80000000_80000000_80000000_80000000, b, c)
+}
+
+/// `f64x2.relaxed_fma` (`relaxed_madd` in the latest proposal): a fused
+/// multiply-add with a single rounding
+pub(crate) fn f64x2_relaxed_fma(a: u128, b: u128, c: u128) -> u128 {
+    let (a, b, c) = (f64_lanes(a), f64_lanes(b), f64_lanes(c));
+    let fma =
+        |i: usize| F64::from_native(a[i].to_float().mul_add(b[i].to_float(), c[i].to_float()));
+    from_f64_lanes([fma(0), fma(1)])
+}
+
+/// `f64x2.relaxed_fnma` (`relaxed_nmadd` in the latest proposal): a fused
+/// multiply-add of the negated product
+pub(crate) fn f64x2_relaxed_fnma(a: u128, b: u128, c: u128) -> u128 {
+    f64x2_relaxed_fma(a ^ 0x80000000_00000000_80000000_00000000, b, c)
+}
+
+/// `*.relaxed_laneselect`: selects every bit by the mask as `v128.bitselect`,
+/// regardless of the lane width
+pub(crate) fn relaxed_laneselect(a: u128, b: u128, m: u128) -> u128 {
+    (a & m) | (b & !m)
+}
+
+/// `f32x4.relaxed_min`: propagates NaN and orders `-0.0` below `+0.0` as `f32x4.min`
+pub(crate) fn f32x4_relaxed_min(a: u128, b: u128) -> u128 {
+    zip_f32x4(a, b, F32::min)
+}
+
+/// `f32x4.relaxed_max`: propagates NaN and orders `-0.0` below `+0.0` as `f32x4.max`
+pub(crate) fn f32x4_relaxed_max(a: u128, b: u128) -> u128 {
+    zip_f32x4(a, b, F32::max)
+}
+
+/// `f64x2.relaxed_min`: propagates NaN and orders `-0.0` below `+0.0` as `f64x2.min`
+pub(crate) fn f64x2_relaxed_min(a: u128, b: u128) -> u128 {
+    zip_f64x2(a, b, F64::min)
+}
+
+/// `f64x2.relaxed_max`: propagates NaN and orders `-0.0` below `+0.0` as `f64x2.max`
+pub(crate) fn f64x2_relaxed_max(a: u128, b: u128) -> u128 {
+    zip_f64x2(a, b, F64::max)
+}
+
+/// `i16x8.relaxed_q15mulr_s`: saturates the overflowing `-0x8000 * -0x8000`
+/// to `0x7fff` as `i16x8.q15mulr_sat_s`
+pub(crate) fn i16x8_relaxed_q15mulr_s(a: u128, b: u128) -> u128 {
+    let (a, b) = (i16_lanes(a), i16_lanes(b));
+    let mut lanes = [0; 8];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        let product = (i32::from(a[i]) * i32::from(b[i]) + 0x4000) >> 15;
+        *lane = product.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16;
+    }
+    from_i16_lanes(lanes)
+}
+
+/// Sums of adjacent signed 8-bit products. Lanes of `b` are signed even when
+/// they are out of the 7-bit range.
+fn dot_i8x16(a: u128, b: u128) -> [i32; 8] {
+    let (a, b) = (i8_lanes(a), i8_lanes(b));
+    let mut sums = [0; 8];
+    for (i, sum) in sums.iter_mut().enumerate() {
+        let product = |j: usize| i32::from(a[j]) * i32::from(b[j]);
+        *sum = product(2 * i) + product(2 * i + 1);
+    }
+    sums
+}
+
+/// `i16x8.relaxed_dot_i8x16_i7x16_s`: lanes of the second operand are signed
+/// and the sums wrap to 16 bits
+pub(crate) fn i16x8_relaxed_dot_i8x16_i7x16_s(a: u128, b: u128) -> u128 {
+    let sums = dot_i8x16(a, b);
+    let mut lanes = [0; 8];
+    for (lane, sum) in lanes.iter_mut().zip(sums.iter()) {
+        *lane = *sum as i16;
+    }
+    from_i16_lanes(lanes)
+}
+
+/// `i32x4.relaxed_dot_i8x16_i7x16_add_s`: lanes of the second operand are
+/// signed and the sums are added to `c` with wrapping
+pub(crate) fn i32x4_relaxed_dot_i8x16_i7x16_add_s(a: u128, b: u128, c: u128) -> u128 {
+    let sums = dot_i8x16(a, b);
+    let mut lanes = [0; 4];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        let addend = (c >> (i * 32)) as i32;
+        *lane = sums[2 * i]
+            .wrapping_add(sums[2 * i + 1])
+            .wrapping_add(addend);
+    }
+    from_i32_lanes(lanes)
+}
+
+/// `f32x4.relaxed_dot_bf16x8_add_f32x4`: bfloat16 lanes are widened to f32
+/// exactly and accumulated into `c` by two fused multiply-adds, even lane first
+pub(crate) fn f32x4_relaxed_dot_bf16x8_add_f32x4(a: u128, b: u128, c: u128) -> u128 {
+    let bf16 = |v: u128, j: usize| f32::from_bits(u32::from((v >> (j * 16)) as u16) << 16);
+    let c = f32_lanes(c);
+    build_f32x4(|i| {
+        let (even, odd) = (2 * i, 2 * i + 1);
+        let sum = bf16(a, even).mul_add(bf16(b, even), c[i].to_float());
+        F32::from_native(bf16(a, odd).mul_add(bf16(b, odd), sum))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32x4(lanes: [f32; 4]) -> u128 {
+        from_f32_lanes([
+            F32::from_native(lanes[0]),
+            F32::from_native(lanes[1]),
+            F32::from_native(lanes[2]),
+            F32::from_native(lanes[3]),
+        ])
+    }
+
+    #[test]
+    fn relaxed_ops_are_deterministic() {
+        let identity = u128::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        let indices = u128::from_le_bytes([15, 16, 0x80, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(i8x16_relaxed_swizzle(identity, indices), 0x01_00_00_0f);
+
+        let trunc = i32x4_relaxed_trunc_f32x4::<i32>(f32x4([f32::NAN, 1e10, -1e10, -1.5]));
+        assert_eq!(trunc, from_i32_lanes([0, i32::MAX, i32::MIN, -1]));
+        let trunc = i32x4_relaxed_trunc_f32x4::<u32>(f32x4([-1.0, 1e10, 2.5, 0.0]));
+        assert_eq!(trunc, from_i32_lanes([0, -1, 2, 0]));
+
+        // 1 + 2^-24 is not representable, so a separate multiply would round it away
+        let x = f32x4([1.0 + f32::EPSILON; 4]);
+        let fma = f32x4_relaxed_fma(x, x, f32x4([-1.0; 4]));
+        let expected = (1.0 + f32::EPSILON).mul_add(1.0 + f32::EPSILON, -1.0);
+        assert_eq!(fma, f32x4([expected; 4]));
+        let fnma = f32x4_relaxed_fnma(f32x4([2.0; 4]), f32x4([3.0; 4]), f32x4([1.0; 4]));
+        assert_eq!(fnma, f32x4([-5.0; 4]));
+
+        let min = f32x4_relaxed_min(f32x4([0.0, 1.0, 0.0, 0.0]), f32x4([-0.0, -1.0, 0.0, 0.0]));
+        assert_eq!(min, f32x4([-0.0, -1.0, 0.0, 0.0]));
+
+        let q15 =
+            i16x8_relaxed_q15mulr_s(from_i16_lanes([i16::MIN; 8]), from_i16_lanes([i16::MIN; 8]));
+        assert_eq!(q15, from_i16_lanes([i16::MAX; 8]));
+
+        let dot =
+            i16x8_relaxed_dot_i8x16_i7x16_s(from_i8_lanes([-128; 16]), from_i8_lanes([-128; 16]));
+        assert_eq!(dot, from_i16_lanes([i16::MIN; 8]));
+        let dot = i32x4_relaxed_dot_i8x16_i7x16_add_s(
+            from_i8_lanes([2; 16]),
+            from_i8_lanes([-3; 16]),
+            from_i32_lanes([1, 2, 3, 4]),
+        );
+        assert_eq!(dot, from_i32_lanes([-23, -22, -21, -20]));
+
+        assert_eq!(relaxed_laneselect(0xff00, 0x00ff, 0x0ff0), 0x0f0f);
+    }
+}
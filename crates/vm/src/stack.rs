@@ -1,7 +1,9 @@
 use crate::address::*;
 use crate::func::{DefinedFunctionInstance, InstIndex};
 use crate::module::ModuleIndex;
+use crate::tag::Exception;
 use crate::value::Value;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum StackValueType {
@@ -35,12 +37,34 @@ impl std::fmt::Display for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Clone, Copy, Debug)]
+/// `Try` is the body of a `try` block starting at `start`, and `Catch` is its
+/// handler, which keeps the caught exception for `rethrow`
+#[derive(Clone, Debug)]
 pub enum Label {
-    If { arity: usize },
-    Block { arity: usize },
-    Loop { arity: usize, label: LoopLabel },
-    Return { arity: usize },
+    If {
+        arity: usize,
+        start: InstIndex,
+    },
+    Block {
+        arity: usize,
+        start: InstIndex,
+    },
+    Loop {
+        arity: usize,
+        label: LoopLabel,
+    },
+    Return {
+        arity: usize,
+    },
+    Try {
+        arity: usize,
+        start: InstIndex,
+    },
+    Catch {
+        arity: usize,
+        start: InstIndex,
+        caught: Rc<Exception>,
+    },
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -58,10 +82,12 @@ impl Label {
 
     pub fn arity(&self) -> usize {
         match self {
-            Label::If { arity } => *arity,
-            Label::Block { arity } => *arity,
+            Label::If { arity, .. } => *arity,
+            Label::Block { arity, .. } => *arity,
             Label::Loop { arity, .. } => *arity,
             Label::Return { arity } => *arity,
+            Label::Try { arity, .. } => *arity,
+            Label::Catch { arity, .. } => *arity,
         }
     }
 }
@@ -105,6 +131,10 @@ impl ProgramCounter {
     pub fn loop_jump(&mut self, loop_label: &LoopLabel) {
         self.inst_index = loop_label.inst_index;
     }
+
+    pub fn jump(&mut self, inst_index: InstIndex) {
+        self.inst_index = inst_index;
+    }
 }
 
 #[derive(Clone)]
@@ -243,6 +273,19 @@ impl std::fmt::Display for StackValue {
 pub struct Stack {
     stack: Vec<StackValue>,
     frame_index: Vec<usize>,
+    delta: Option<StackDelta>,
+}
+
+/// Entries removed from and locals overwritten in the stack since `Stack::record_delta`,
+/// which are enough to restore the stack at that time
+#[derive(Clone, Default)]
+pub struct StackDelta {
+    /// The stack has never been shorter than this while recording
+    base_len: usize,
+    /// Entries popped from below `base_len`, from the top one
+    popped: Vec<StackValue>,
+    /// `(position, local index, old value)` of locals of frames below `base_len` in writing order
+    locals: Vec<(usize, usize, Value)>,
 }
 
 // Debugger
@@ -268,6 +311,49 @@ impl Stack {
         frames.nth(index).ok_or(Error::NotEnoughFrames)
     }
 
+    /// Overwrites a local of the frame at `frame_index` from the innermost one
+    pub fn set_frame_local(
+        &mut self,
+        frame_index: usize,
+        index: usize,
+        value: Value,
+    ) -> Result<()> {
+        let mut frames = self.stack.iter_mut().rev().filter_map(|v| match v {
+            StackValue::Activation(frame) => Some(frame),
+            _ => None,
+        });
+        let frame = frames.nth(frame_index).ok_or(Error::NotEnoughFrames)?;
+        frame.set_local(index, value);
+        Ok(())
+    }
+
+    /// Values pushed by the frame at `index` from the innermost one
+    pub fn frame_values(&self, index: usize) -> Result<Vec<&Value>> {
+        let activations: Vec<usize> = self
+            .stack
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| matches!(v, StackValue::Activation(_)))
+            .map(|(position, _)| position)
+            .collect();
+        let nth = activations
+            .len()
+            .checked_sub(index + 1)
+            .ok_or(Error::NotEnoughFrames)?;
+        let start = activations[nth] + 1;
+        let end = activations
+            .get(nth + 1)
+            .copied()
+            .unwrap_or(self.stack.len());
+        Ok(self.stack[start..end]
+            .iter()
+            .filter_map(|v| match v {
+                StackValue::Value(v) => Some(v),
+                _ => None,
+            })
+            .collect())
+    }
+
     pub fn peek_values(&self) -> Vec<&Value> {
         self.stack
             .iter()
@@ -283,11 +369,16 @@ impl Stack {
     pub fn pop_while<F: Fn(&StackValue) -> bool>(&mut self, f: F) -> Vec<StackValue> {
         let mut result = vec![];
         while f(self.latest()) {
-            result.push(self.stack.pop().unwrap());
+            result.push(self.pop_entry().unwrap());
         }
         result
     }
 
+    /// The number of call frames on the stack
+    pub fn frame_depth(&self) -> usize {
+        self.frame_index.len()
+    }
+
     pub fn current_frame_index(&self) -> Result<usize> {
         self.frame_index.last().cloned().ok_or(Error::NoCallFrame)
     }
@@ -337,7 +428,7 @@ impl Stack {
     }
 
     pub fn pop_value(&mut self) -> Result<Value> {
-        match self.stack.pop() {
+        match self.pop_entry() {
             Some(val) => val.into_value(),
             None => Err(Error::PopEmptyStack),
         }
@@ -348,7 +439,7 @@ impl Stack {
     }
 
     pub fn pop_label(&mut self) -> Result<Label> {
-        match self.stack.pop() {
+        match self.pop_entry() {
             Some(val) => val.into_label(),
             None => Err(Error::PopEmptyStack),
         }
@@ -368,7 +459,7 @@ impl Stack {
     }
 
     pub fn pop_frame(&mut self) -> Result<CallFrame> {
-        match self.stack.pop() {
+        match self.pop_entry() {
             Some(val) => {
                 self.frame_index.pop();
                 val.into_activation()
@@ -385,12 +476,62 @@ impl Stack {
         let size = self.current_frame_index()?;
         if let Some(stack) = self.stack.get_mut(size) {
             let frame = stack.as_activation_mut()?;
+            if let Some(delta) = &mut self.delta {
+                if size < delta.base_len {
+                    delta.locals.push((size, index, frame.local(index)));
+                }
+            }
             frame.set_local(index, value);
             Ok(())
         } else {
             Err(Error::NoCallFrame)
         }
     }
+
+    fn pop_entry(&mut self) -> Option<StackValue> {
+        let entry = self.stack.pop()?;
+        if let Some(delta) = &mut self.delta {
+            if self.stack.len() < delta.base_len {
+                delta.base_len = self.stack.len();
+                delta.popped.push(entry.clone());
+            }
+        }
+        Some(entry)
+    }
+}
+
+// History
+impl Stack {
+    /// Starts recording changes to the stack, discarding the previous recording
+    pub fn record_delta(&mut self) {
+        self.delta = Some(StackDelta {
+            base_len: self.stack.len(),
+            ..StackDelta::default()
+        });
+    }
+
+    /// Stops recording and returns the changes since `record_delta`
+    pub fn take_delta(&mut self) -> Option<StackDelta> {
+        self.delta.take()
+    }
+
+    /// Restores the stack at the time `delta` started to be recorded
+    pub fn revert_delta(&mut self, delta: StackDelta) {
+        self.stack.truncate(delta.base_len);
+        self.stack.extend(delta.popped.into_iter().rev());
+        for (position, index, value) in delta.locals.into_iter().rev() {
+            if let Some(StackValue::Activation(frame)) = self.stack.get_mut(position) {
+                frame.set_local(index, value);
+            }
+        }
+        self.frame_index = self
+            .stack
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| matches!(entry, StackValue::Activation(_)))
+            .map(|(position, _)| position)
+            .collect();
+    }
 }
 
 impl std::fmt::Debug for Stack {
@@ -10,7 +10,9 @@ use crate::memory::{self, MemoryInstance};
 use crate::module::{
     self, DefinedModuleInstance, HostExport, HostModuleInstance, ModuleIndex, ModuleInstance,
 };
+use crate::name::NameMap;
 use crate::table::{self, TableInstance};
+use crate::tag::TagInstance;
 use crate::value::{NumVal, RefType, RefVal, Value};
 use anyhow::Result;
 use std::cell::RefCell;
@@ -18,7 +20,7 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use wasmparser::{
     Data, DataKind, Element, ElementItem, ElementKind, FuncType, FunctionBody, Global, GlobalType,
-    Import, MemoryType, NameSectionReader, TableType, ValType,
+    Import, MemoryType, NameSectionReader, TableType, TagType, ValType,
 };
 
 #[derive(Default)]
@@ -29,6 +31,7 @@ pub struct Store {
     globals: LinkableCollection<Rc<RefCell<GlobalInstance>>>,
     elems: LinkableCollection<Rc<RefCell<ElementInstance>>>,
     data: LinkableCollection<Rc<RefCell<DataInstance>>>,
+    tags: LinkableCollection<TagInstance>,
     modules: Vec<ModuleInstance>,
     module_index_by_name: HashMap<String, ModuleIndex>,
 
@@ -48,6 +51,12 @@ impl Store {
         self.funcs.get(addr)
     }
 
+    /// Returns the index of a defined function in its defining module
+    pub fn func_index(&self, addr: ExecutableFuncAddr) -> Option<usize> {
+        let module_index = self.func_global(addr).defined()?.module_index();
+        self.funcs.index_in_module(module_index, addr)
+    }
+
     pub fn global(&self, addr: GlobalAddr) -> Rc<RefCell<GlobalInstance>> {
         self.globals.get(addr).unwrap().0.clone()
     }
@@ -74,6 +83,14 @@ impl Store {
         self.mems.items(addr).map(|c| c.len()).unwrap_or(0)
     }
 
+    pub fn table_count(&self, addr: ModuleIndex) -> usize {
+        self.tables.items(addr).map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn global_count(&self, addr: ModuleIndex) -> usize {
+        self.globals.items(addr).map(|c| c.len()).unwrap_or(0)
+    }
+
     pub fn elem(&self, addr: ElemAddr) -> Rc<RefCell<ElementInstance>> {
         self.elems.get(addr).unwrap().0.clone()
     }
@@ -82,35 +99,69 @@ impl Store {
         self.data.get(addr).unwrap().0.clone()
     }
 
+    pub fn tag(&self, addr: TagAddr) -> Option<(&TagInstance, ResolvedTagAddr)> {
+        self.tags.get(addr)
+    }
+
+    pub fn module_count(&self) -> usize {
+        self.modules.len()
+    }
+
     pub fn module(&self, module_index: ModuleIndex) -> &ModuleInstance {
         &self.modules[module_index.0 as usize]
     }
 
-    pub(crate) fn module_by_name(&self, name: String) -> &ModuleInstance {
-        if let Some(index) = self.module_index_by_name.get(&name) {
-            self.module(*index)
-        } else {
-            panic!("Module {} was not loaded", name);
+    pub(crate) fn module_by_name(&self, name: String) -> Result<&ModuleInstance, StoreError> {
+        match self.module_index_by_name.get(&name) {
+            Some(index) => Ok(self.module(*index)),
+            None => Err(StoreError::UnknownModule { name }),
         }
     }
 
     pub fn register_name(&mut self, name: String, module_index: ModuleIndex) {
         self.module_index_by_name.insert(name, module_index);
     }
+
+    pub fn host_module_mut(&mut self, name: &str) -> Option<&mut HostModuleInstance> {
+        let index = self.module_index_by_name.get(name)?;
+        match &mut self.modules[index.0 as usize] {
+            ModuleInstance::Host(host) => Some(host),
+            ModuleInstance::Defined(_) => None,
+        }
+    }
+
+    /// Allocates functions registered to the host module after its construction
+    fn link_pending_host_funcs(&mut self, name: &str) {
+        let index = match self.module_index_by_name.get(name) {
+            Some(index) => index.0 as usize,
+            None => return,
+        };
+        let pending = match &mut self.modules[index] {
+            ModuleInstance::Host(host) => host.take_pending_funcs(),
+            ModuleInstance::Defined(_) => return,
+        };
+        for (field, f) in pending {
+            let instance =
+                NativeFunctionInstance::new(f.ty().clone(), name.to_string(), field.clone(), f);
+            let addr = self.funcs.push_global(FunctionInstance::Native(instance));
+            if let ModuleInstance::Host(host) = &mut self.modules[index] {
+                host.insert_func(field, addr);
+            }
+        }
+    }
 }
 
 impl Store {
     pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
         let module_index = ModuleIndex(self.modules.len() as u32);
         let mut values = HashMap::new();
+        let mut funcs = Vec::new();
         for (field, entry) in module {
             match entry {
-                HostValue::Func(f) => {
-                    let instance =
-                        NativeFunctionInstance::new(f.ty().clone(), name.clone(), field.clone(), f);
-                    let addr = self.funcs.push_global(FunctionInstance::Native(instance));
-                    values.insert(field, HostExport::Func(addr));
-                }
+                // Functions are allocated on link so that they can be intercepted until then
+                HostValue::Func(f) => funcs.push((field, f)),
+                #[cfg(feature = "async-host")]
+                HostValue::AsyncFunc(f) => funcs.push((field, f.to_sync())),
                 HostValue::Global(g) => {
                     let addr = self.globals.push_global(g);
                     values.insert(field, HostExport::Global(addr));
@@ -125,7 +176,10 @@ impl Store {
                 }
             }
         }
-        let instance = HostModuleInstance::new(values);
+        let mut instance = HostModuleInstance::new(values);
+        for (field, f) in funcs {
+            instance.register_func(field, f);
+        }
         self.modules.push(ModuleInstance::Host(instance));
         self.module_index_by_name.insert(name, module_index);
     }
@@ -149,11 +203,14 @@ pub enum StoreError {
     InvalidDataSegments(memory::Error),
     InvalidHostImport(module::HostModuleError),
     InvalidImport(module::DefinedModuleError),
+    UnknownModule { name: String },
+    CyclicImports { modules: Vec<String> },
     UnknownType { type_index: usize },
     UndefinedFunction { module: String, name: String },
     UndefinedMemory { module: String, name: String },
     UndefinedTable { module: String, name: String },
     UndefinedGlobal { module: String, name: String },
+    UndefinedTag { module: String, name: String },
     IncompatibleImportFuncType(String, FuncType, FuncType),
     IncompatibleImportGlobalType(ValType, ValType),
     IncompatibleImportGlobalMutability,
@@ -172,6 +229,12 @@ impl std::fmt::Display for StoreError {
             Self::InvalidDataSegments(err) => write!(f, "data segment does not fit: {}", err),
             Self::InvalidHostImport(err) => write!(f, "invalid host import: {}", err),
             Self::InvalidImport(err) => write!(f, "invalid import: {}", err),
+            Self::UnknownModule { name } => {
+                write!(f, "unknown import: module \"{}\" is not loaded", name)
+            }
+            Self::CyclicImports { modules } => {
+                write!(f, "cyclic imports between modules {}", modules.join(", "))
+            }
             Self::UnknownType { type_index } => {
                 write!(f, "Unknown type index used: {:?}", type_index)
             }
@@ -195,6 +258,11 @@ impl std::fmt::Display for StoreError {
                 "unknown import: Undefined global \"{}\" in \"{}\"",
                 name, module
             ),
+            Self::UndefinedTag { module, name } => write!(
+                f,
+                "unknown import: Undefined tag \"{}\" in \"{}\"",
+                name, module
+            ),
             Self::IncompatibleImportFuncType(name, expected, actual) => write!(
                 f,
                 "incompatible import type, \"{}\" expected {:?} but got {:?}",
@@ -219,33 +287,47 @@ impl std::fmt::Display for StoreError {
     }
 }
 
-fn read_name_section(mut reader: wasmparser::NameSectionReader) -> Result<HashMap<u32, String>> {
-    let mut func_names = HashMap::new();
-    while !reader.eof() {
-        let name = match reader.read() {
-            Ok(name) => name,
-            Err(_) => return Ok(func_names),
-        };
-        match name {
-            wasmparser::Name::Module { .. } => continue,
-            wasmparser::Name::Function(map) => {
-                for naming in map {
-                    let naming = naming?;
-                    func_names.insert(naming.index, String::from(naming.name));
-                }
+fn imported_module_names(bytes: &[u8]) -> Result<Vec<String>> {
+    let mut names = vec![];
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        if let wasmparser::Payload::ImportSection(section) = payload? {
+            for import in section {
+                names.push(import?.module.to_string());
             }
-            wasmparser::Name::Local(_)
-            | wasmparser::Name::Label(_)
-            | wasmparser::Name::Type(_)
-            | wasmparser::Name::Table(_)
-            | wasmparser::Name::Memory(_)
-            | wasmparser::Name::Global(_)
-            | wasmparser::Name::Element(_)
-            | wasmparser::Name::Data(_)
-            | wasmparser::Name::Unknown { .. } => continue,
         }
     }
-    Ok(func_names)
+    Ok(names)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LinkState {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+/// Appends `index` to `order` after its dependencies, or returns the modules forming a cycle
+fn link_order(
+    index: usize,
+    deps: &[Vec<usize>],
+    states: &mut [LinkState],
+    order: &mut Vec<usize>,
+) -> std::result::Result<(), Vec<usize>> {
+    match states[index] {
+        LinkState::Visited => return Ok(()),
+        LinkState::Visiting => return Err(vec![index]),
+        LinkState::Unvisited => {}
+    }
+    states[index] = LinkState::Visiting;
+    for dep in &deps[index] {
+        link_order(*dep, deps, states, order).map_err(|mut cycle| {
+            cycle.push(index);
+            cycle
+        })?;
+    }
+    states[index] = LinkState::Visited;
+    order.push(index);
+    Ok(())
 }
 
 impl Store {
@@ -265,7 +347,8 @@ impl Store {
         let mut tables = Vec::new();
         let mut globals = Vec::new();
         let mut mems = Vec::new();
-        let mut func_names = HashMap::new();
+        let mut tags = Vec::new();
+        let mut names = NameMap::default();
 
         let mut start_func = None;
 
@@ -340,6 +423,12 @@ impl Store {
                         globals.push(entry?);
                     }
                 }
+                Payload::TagSection(section) => {
+                    tags.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        tags.push(entry?);
+                    }
+                }
                 Payload::StartSection { func, .. } => {
                     start_func = Some(FuncAddr::new_unsafe(module_index, func as usize));
                 }
@@ -347,7 +436,7 @@ impl Store {
                     if section.name() == "name" {
                         let section =
                             NameSectionReader::new(section.data(), section.data_offset())?;
-                        func_names = read_name_section(section)?;
+                        names = NameMap::read(section)?;
                     }
                 }
                 Payload::ModuleSection { .. } => {
@@ -366,6 +455,7 @@ impl Store {
             types.clone(),
             exports,
             start_func,
+            names.clone(),
         );
         self.modules.push(ModuleInstance::Defined(instance));
 
@@ -374,16 +464,10 @@ impl Store {
         }
 
         self.load_imports(imports, module_index, &types)?;
+        self.load_tags(tags, module_index, &types)?;
         self.load_globals(globals, module_index)?;
         if let Some(base_offset) = code_section_base_offset {
-            self.load_functions(
-                module_index,
-                func_sigs,
-                bodies,
-                func_names,
-                &types,
-                base_offset,
-            )?;
+            self.load_functions(module_index, func_sigs, bodies, &names, &types, base_offset)?;
         }
         self.load_tables_and_elems(tables, module_index, elem_segs)?;
         self.load_mems(mems, module_index, data_segs)?;
@@ -400,6 +484,38 @@ impl Store {
         }
     }
 
+    /// Loads modules importing each other's exports, registering each by its name.
+    /// A module is loaded after the modules it imports from, and the returned
+    /// indices are in the given order
+    pub fn load_linked_modules(&mut self, modules: &[(String, &[u8])]) -> Result<Vec<ModuleIndex>> {
+        let mut deps = Vec::with_capacity(modules.len());
+        for (_, bytes) in modules {
+            let imported = imported_module_names(bytes)?;
+            let indices = modules
+                .iter()
+                .enumerate()
+                .filter(|(_, (name, _))| imported.contains(name))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            deps.push(indices);
+        }
+        let mut states = vec![LinkState::Unvisited; modules.len()];
+        let mut order = Vec::with_capacity(modules.len());
+        for index in 0..modules.len() {
+            link_order(index, &deps, &mut states, &mut order).map_err(|cycle| {
+                StoreError::CyclicImports {
+                    modules: cycle.iter().map(|i| modules[*i].0.clone()).collect(),
+                }
+            })?;
+        }
+        let mut module_indices = vec![ModuleIndex(0); modules.len()];
+        for index in order {
+            let (name, bytes) = &modules[index];
+            module_indices[index] = self.load_module(Some(name.clone()), bytes)?;
+        }
+        Ok(module_indices)
+    }
+
     fn load_imports(
         &mut self,
         imports: Vec<Import>,
@@ -421,7 +537,9 @@ impl Store {
                 Global(global_ty) => {
                     self.load_import_global(module_index, import, global_ty)?;
                 }
-                Tag(_) => panic!("event type is not supported yet"),
+                Tag(tag_ty) => {
+                    self.load_import_tag(module_index, import, tag_ty, types)?;
+                }
             }
         }
         Ok(())
@@ -439,11 +557,13 @@ impl Store {
             .ok_or(StoreError::UnknownType { type_index })?
             .clone();
         let name = import.name.to_string();
-        let module = self.module_by_name(import.module.to_string());
+        self.link_pending_host_funcs(import.module);
+        let module = self.module_by_name(import.module.to_string())?;
         let err = || StoreError::UndefinedFunction {
             module: import.module.to_string(),
             name: import.name.to_string(),
         };
+        let is_defined = matches!(module, ModuleInstance::Defined(_));
         let exec_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let func_addr = defined
@@ -460,12 +580,16 @@ impl Store {
         let actual_func_ty = self.funcs.get_global(exec_addr).ty();
         // Validation
         if actual_func_ty != &func_ty {
-            return Err(StoreError::IncompatibleImportFuncType(
-                name,
-                func_ty,
-                actual_func_ty.clone(),
-            )
-            .into());
+            let actual_func_ty = actual_func_ty.clone();
+            let err = if is_defined {
+                StoreError::InvalidImport(module::DefinedModuleError::TypeMismatch(
+                    format!("{:?}", func_ty),
+                    format!("{:?}", actual_func_ty),
+                ))
+            } else {
+                StoreError::IncompatibleImportFuncType(name, func_ty, actual_func_ty)
+            };
+            return Err(err.into());
         }
         self.funcs.link(exec_addr, module_index);
         Ok(())
@@ -482,7 +606,7 @@ impl Store {
             name: import.name.to_string(),
         };
         let name = import.name.to_string();
-        let module = self.module_by_name(import.module.to_string());
+        let module = self.module_by_name(import.module.to_string())?;
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -541,7 +665,7 @@ impl Store {
         table_ty: TableType,
     ) -> Result<()> {
         let name = import.name.to_string();
-        let module = self.module_by_name(import.module.to_string());
+        let module = self.module_by_name(import.module.to_string())?;
         let err = || StoreError::UndefinedTable {
             module: import.module.to_string(),
             name: import.name.to_string(),
@@ -590,7 +714,7 @@ impl Store {
         global_ty: GlobalType,
     ) -> Result<()> {
         let name = import.name.to_string();
-        let module = self.module_by_name(import.module.to_string());
+        let module = self.module_by_name(import.module.to_string())?;
         let err = || StoreError::UndefinedGlobal {
             module: import.module.to_string(),
             name: import.name.to_string(),
@@ -628,12 +752,54 @@ impl Store {
         Ok(())
     }
 
+    fn load_import_tag(
+        &mut self,
+        module_index: ModuleIndex,
+        import: Import,
+        tag_ty: TagType,
+        types: &[FuncType],
+    ) -> Result<()> {
+        let type_index = tag_ty.func_type_idx as usize;
+        let tag_ty = types
+            .get(type_index)
+            .ok_or(StoreError::UnknownType { type_index })?;
+        let name = import.name.to_string();
+        let module = self.module_by_name(import.module.to_string())?;
+        let err = || StoreError::UndefinedTag {
+            module: import.module.to_string(),
+            name: import.name.to_string(),
+        };
+        // Host modules don't provide tags
+        let resolved_addr = match module {
+            ModuleInstance::Defined(defined) => {
+                let addr = defined
+                    .exported_tag(&name)
+                    .map_err(StoreError::InvalidImport)?
+                    .ok_or_else(err)?;
+                self.tags.resolve(addr).ok_or_else(err)?
+            }
+            ModuleInstance::Host(_) => return Err(err().into()),
+        };
+        // Validation
+        let actual_tag_ty = self.tags.get_global(resolved_addr).ty();
+        if actual_tag_ty != tag_ty {
+            return Err(StoreError::IncompatibleImportFuncType(
+                name,
+                tag_ty.clone(),
+                actual_tag_ty.clone(),
+            )
+            .into());
+        }
+        self.tags.link(resolved_addr, module_index);
+        Ok(())
+    }
+
     fn load_functions(
         &mut self,
         module_index: ModuleIndex,
         func_sigs: Vec<u32>,
         bodies: Vec<FunctionBody>,
-        names: HashMap<u32, String>,
+        names: &NameMap,
         types: &[FuncType],
         base_offset: usize,
     ) -> Result<Vec<FuncAddr>> {
@@ -648,13 +814,22 @@ impl Store {
                     type_index: func_sig,
                 })?
                 .clone();
-            let name = names.get(&index).cloned().unwrap_or(format!(
-                "<module #{} defined func #{}>",
-                module_index.0, index
-            ));
-            let defined =
-                DefinedFunctionInstance::new(name, func_type, module_index, body, base_offset)?;
-            let instance = FunctionInstance::Defined(defined);
+            let name = names
+                .function_name(index)
+                .map(String::from)
+                .unwrap_or(format!(
+                    "<module #{} defined func #{}>",
+                    module_index.0, index
+                ));
+            let defined = DefinedFunctionInstance::new(
+                name,
+                func_type,
+                module_index,
+                body,
+                base_offset,
+                names.local_names(index),
+            )?;
+            let instance = FunctionInstance::Defined(Box::new(defined));
             let func_addr = self.funcs.push(module_index, instance);
             func_addrs.push(func_addr);
             index += 1;
@@ -672,6 +847,22 @@ impl Store {
         Ok(())
     }
 
+    fn load_tags(
+        &mut self,
+        tags: Vec<TagType>,
+        module_index: ModuleIndex,
+        types: &[FuncType],
+    ) -> Result<()> {
+        for entry in tags {
+            let type_index = entry.func_type_idx as usize;
+            let ty = types
+                .get(type_index)
+                .ok_or(StoreError::UnknownType { type_index })?;
+            self.tags.push(module_index, TagInstance::new(ty.clone()));
+        }
+        Ok(())
+    }
+
     fn load_tables_and_elems(
         &mut self,
         tables: Vec<TableType>,
@@ -717,11 +908,11 @@ impl Store {
                     ))),
                     ElementItem::Expr(init_expr) => {
                         match eval_const_expr(&init_expr, self, module_index)? {
-                            Value::Num(n) => unreachable!(
-                                "unexpected num value returned by init_expr in segment: {:?}",
-                                n
-                            ),
                             Value::Ref(r) => Ok(r),
+                            v => unreachable!(
+                                "unexpected non-reference value returned by init_expr in segment: {:?}",
+                                v
+                            ),
                         }
                     }
                 })
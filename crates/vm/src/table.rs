@@ -40,6 +40,8 @@ type Result<T> = std::result::Result<T, Error>;
 /// https://webassembly.github.io/spec/core/exec/runtime.html#table-instances
 pub struct TableInstance {
     buffer: Vec<RefVal>,
+    /// Incremented on every change of `buffer`
+    version: u64,
     pub max: Option<usize>,
     pub initial: usize,
     pub ty: RefType,
@@ -51,6 +53,7 @@ impl TableInstance {
             buffer: std::iter::repeat(RefVal::NullRef(ty))
                 .take(initial)
                 .collect(),
+            version: 0,
             initial,
             max: maximum,
             ty,
@@ -79,6 +82,7 @@ impl TableInstance {
         for (index, func_addr) in data.into_iter().enumerate() {
             self.buffer[offset + index] = func_addr;
         }
+        self.version += 1;
         Ok(())
     }
 
@@ -86,6 +90,11 @@ impl TableInstance {
         self.buffer.len()
     }
 
+    /// Returns a number which changes whenever an element is set or the table grows
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     pub fn get_at(&self, index: usize) -> Result<RefVal> {
         self.buffer
             .get(index)
@@ -103,6 +112,7 @@ impl TableInstance {
             size: buffer_len,
         })?;
         *entry = val;
+        self.version += 1;
         Ok(())
     }
 
@@ -124,6 +134,7 @@ impl TableInstance {
         }
         let mut extra = std::iter::repeat(val).take(n).collect();
         self.buffer.append(&mut extra);
+        self.version += 1;
         Ok(())
     }
 }
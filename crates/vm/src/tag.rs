@@ -0,0 +1,26 @@
+use crate::address::ResolvedTagAddr;
+use crate::value::Value;
+use wasmparser::FuncType;
+
+/// A tag of the exception handling proposal, which types the values of an exception
+#[derive(PartialEq)]
+pub struct TagInstance {
+    ty: FuncType,
+}
+
+impl TagInstance {
+    pub fn new(ty: FuncType) -> Self {
+        Self { ty }
+    }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+}
+
+/// An exception thrown by `throw`, identified by its resolved tag
+#[derive(Debug)]
+pub struct Exception {
+    pub tag: ResolvedTagAddr,
+    pub values: Vec<Value>,
+}
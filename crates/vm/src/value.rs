@@ -5,6 +5,8 @@ pub enum Error {
     ZeroDivision,
     InvalidConversionToInt,
     IntegerOverflow,
+    InsufficientBytes { expected: usize, actual: usize },
+    UnsupportedType(wasmparser::ValType),
 }
 
 impl std::fmt::Display for Error {
@@ -13,6 +15,12 @@ impl std::fmt::Display for Error {
             Self::ZeroDivision => write!(f, "integer divide by zero"),
             Self::InvalidConversionToInt => write!(f, "invalid conversion to integer"),
             Self::IntegerOverflow => write!(f, "integer overflow"),
+            Self::InsufficientBytes { expected, actual } => write!(
+                f,
+                "expected {} bytes but only {} bytes are available",
+                expected, actual
+            ),
+            Self::UnsupportedType(ty) => write!(f, "{:?} has no byte representation", ty),
         }
     }
 }
@@ -25,6 +33,8 @@ pub enum Value {
     Num(NumVal),
     /// Reference value
     Ref(RefVal),
+    /// 128-bit vector value, whose lane 0 is in the least significant bits
+    V128(u128),
 }
 
 /// Runtime representation of a basic number value
@@ -108,6 +118,51 @@ impl Value {
         Value::Num(NumVal::F64(F64(v)))
     }
 
+    /// Compares two values as WebAssembly does: integers are signed, any NaN is unordered
+    /// and `+0.0` equals `-0.0`. Values of different types are unordered.
+    fn wasm_cmp(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+        match (a, b) {
+            (Value::Num(NumVal::I32(a)), Value::Num(NumVal::I32(b))) => Some(a.cmp(b)),
+            (Value::Num(NumVal::I64(a)), Value::Num(NumVal::I64(b))) => Some(a.cmp(b)),
+            (Value::Num(NumVal::F32(a)), Value::Num(NumVal::F32(b))) => {
+                a.to_float().partial_cmp(&b.to_float())
+            }
+            (Value::Num(NumVal::F64(a)), Value::Num(NumVal::F64(b))) => {
+                a.to_float().partial_cmp(&b.to_float())
+            }
+            (Value::Ref(a), Value::Ref(b)) if a == b => Some(std::cmp::Ordering::Equal),
+            (Value::V128(a), Value::V128(b)) if a == b => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    /// Spec-conformant equality, unlike `==` NaN is not equal to itself and `+0.0 == -0.0`
+    pub fn wasm_eq(a: &Value, b: &Value) -> bool {
+        Self::wasm_cmp(a, b) == Some(std::cmp::Ordering::Equal)
+    }
+
+    pub fn wasm_lt(a: &Value, b: &Value) -> bool {
+        Self::wasm_cmp(a, b) == Some(std::cmp::Ordering::Less)
+    }
+
+    pub fn wasm_gt(a: &Value, b: &Value) -> bool {
+        Self::wasm_cmp(a, b) == Some(std::cmp::Ordering::Greater)
+    }
+
+    pub fn wasm_le(a: &Value, b: &Value) -> bool {
+        matches!(
+            Self::wasm_cmp(a, b),
+            Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal)
+        )
+    }
+
+    pub fn wasm_ge(a: &Value, b: &Value) -> bool {
+        matches!(
+            Self::wasm_cmp(a, b),
+            Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)
+        )
+    }
+
     pub fn null_ref(ty: wasmparser::ValType) -> Option<Value> {
         let r = match ty {
             wasmparser::ValType::FuncRef => RefVal::NullRef(RefType::FuncRef),
@@ -119,7 +174,7 @@ impl Value {
 
     pub fn isa(&self, ty: wasmparser::ValType) -> bool {
         match self {
-            Value::Num(_) => self.value_type() == ty,
+            Value::Num(_) | Value::V128(_) => self.value_type() == ty,
             Value::Ref(r) => matches!(
                 (r, ty),
                 (RefVal::ExternRef(_), wasmparser::ValType::ExternRef)
@@ -145,6 +200,7 @@ impl Value {
             Value::Ref(RefVal::NullRef(_)) => wasmparser::ValType::FuncRef,
             Value::Ref(RefVal::FuncRef(_)) => wasmparser::ValType::FuncRef,
             Value::Ref(RefVal::ExternRef(_)) => wasmparser::ValType::ExternRef,
+            Value::V128(_) => wasmparser::ValType::V128,
         }
     }
 
@@ -213,6 +269,12 @@ impl From<f64> for Value {
     }
 }
 
+impl From<u128> for Value {
+    fn from(val: u128) -> Self {
+        Self::V128(val)
+    }
+}
+
 impl From<F32> for Value {
     fn from(val: F32) -> Self {
         Self::Num(NumVal::F32(val))
@@ -257,6 +319,39 @@ impl_native_value!(u64, I64);
 impl_native_value!(F32, F32);
 impl_native_value!(F64, F64);
 
+impl NativeValue for f32 {
+    fn from_value(val: Value) -> Option<Self> {
+        val.as_f32()
+    }
+
+    fn value_type() -> wasmparser::ValType {
+        wasmparser::ValType::F32
+    }
+}
+
+impl NativeValue for f64 {
+    fn from_value(val: Value) -> Option<Self> {
+        val.as_f64()
+    }
+
+    fn value_type() -> wasmparser::ValType {
+        wasmparser::ValType::F64
+    }
+}
+
+impl NativeValue for u128 {
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::V128(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    fn value_type() -> wasmparser::ValType {
+        wasmparser::ValType::V128
+    }
+}
+
 /// A trait to convert a basic number value into a bytes in little-endian byte order
 pub trait IntoLittleEndian {
     fn into_le_bytes(self) -> Vec<u8>;
@@ -303,6 +398,46 @@ impl F64 {
     }
 }
 
+impl Value {
+    /// Returns the bytes of a number or vector value in little-endian byte order, which is empty for references
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match *self {
+            Value::Num(NumVal::I32(v)) => v.into_le_bytes(),
+            Value::Num(NumVal::I64(v)) => v.into_le_bytes(),
+            Value::Num(NumVal::F32(v)) => v.into_le_bytes(),
+            Value::Num(NumVal::F64(v)) => v.into_le_bytes(),
+            Value::V128(v) => v.to_le_bytes().to_vec(),
+            Value::Ref(_) => vec![],
+        }
+    }
+
+    /// Reads a number value of `ty` from the head of `bytes` in little-endian byte order
+    pub fn from_le_bytes(ty: wasmparser::ValType, bytes: &[u8]) -> Result<Value, Error> {
+        use wasmparser::ValType;
+        let size = match ty {
+            ValType::I32 | ValType::F32 => 4,
+            ValType::I64 | ValType::F64 => 8,
+            ValType::V128 => 16,
+            ValType::FuncRef | ValType::ExternRef => return Err(Error::UnsupportedType(ty)),
+        };
+        if bytes.len() < size {
+            return Err(Error::InsufficientBytes {
+                expected: size,
+                actual: bytes.len(),
+            });
+        }
+        let value = match ty {
+            ValType::I32 => Value::from(<i32 as FromLittleEndian>::from_le(bytes)),
+            ValType::I64 => Value::from(<i64 as FromLittleEndian>::from_le(bytes)),
+            ValType::F32 => Value::F32(<u32 as FromLittleEndian>::from_le(bytes)),
+            ValType::F64 => Value::F64(<u64 as FromLittleEndian>::from_le(bytes)),
+            ValType::V128 => Value::V128(<u128 as FromLittleEndian>::from_le(bytes)),
+            _ => return Err(Error::UnsupportedType(ty)),
+        };
+        Ok(value)
+    }
+}
+
 macro_rules! impl_from_little_endian {
     ($type:ty, $size:expr) => {
         impl FromLittleEndian for $type {
@@ -319,6 +454,7 @@ impl_from_little_endian!(u8, 1);
 impl_from_little_endian!(u16, 2);
 impl_from_little_endian!(u32, 4);
 impl_from_little_endian!(u64, 8);
+impl_from_little_endian!(u128, 16);
 
 impl_from_little_endian!(i8, 1);
 impl_from_little_endian!(i16, 2);
@@ -753,7 +889,7 @@ impl I64 {
 mod tests {
     use crate::value::IEEE754;
 
-    use crate::F32;
+    use crate::{Value, F32};
 
     #[test]
     fn floating_value_min() {
@@ -762,4 +898,27 @@ mod tests {
             (-0.0_f32).to_bits()
         );
     }
+
+    #[test]
+    fn wasm_comparison() {
+        let nan = Value::from(f64::NAN);
+        assert!(!Value::wasm_eq(&nan, &nan));
+        assert!(!Value::wasm_le(&nan, &nan));
+        assert!(Value::wasm_eq(
+            &Value::from(0.0_f32),
+            &Value::from(-0.0_f32)
+        ));
+        assert!(Value::wasm_lt(&Value::from(-1_i32), &Value::from(1_i32)));
+        assert!(!Value::wasm_eq(&Value::from(1_i32), &Value::from(1_i64)));
+    }
+
+    #[test]
+    fn le_bytes_roundtrip() {
+        let value = Value::from(1.5_f64);
+        let bytes = value.to_le_bytes();
+        assert_eq!(bytes.len(), 8);
+        let ty = wasmparser::ValType::F64;
+        assert_eq!(Value::from_le_bytes(ty, &bytes).unwrap(), value);
+        assert!(Value::from_le_bytes(ty, &bytes[..4]).is_err());
+    }
 }
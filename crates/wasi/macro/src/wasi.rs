@@ -85,9 +85,14 @@ fn emit_func_extern(
             let wasi_ctx = store.get_embed_context::<WasiContext>().unwrap();
             let mut wasi_ctx = wasi_ctx.ctx.borrow_mut();
             let bc = unsafe { borrow::BorrowChecker::new() };
+            // WASI functions only access the memory 0
+            let mem0: &mut [u8] = match ctx.mems.first_mut() {
+                Some(mem) => mem,
+                None => &mut [],
+            };
             let mem = WasiMemory {
-                mem: ctx.mem.as_mut_ptr(),
-                mem_size: ctx.mem.len() as u32,
+                mem: mem0.as_mut_ptr(),
+                mem_size: mem0.len() as u32,
                 bc,
             };
             #call_expr
@@ -14,6 +14,30 @@ use wasminspect_vm::{
     WasmValue, F32, F64,
 };
 
+/// The result of an assertion directive
+pub struct AssertionOutcome {
+    pub directive: &'static str,
+    pub line: usize,
+    pub column: usize,
+    /// The reason why the assertion failed, or `None` if passed
+    pub failure: Option<String>,
+}
+
+#[derive(Default)]
+pub struct WastReport {
+    pub outcomes: Vec<AssertionOutcome>,
+}
+
+impl WastReport {
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.failure.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.passed()
+    }
+}
+
 pub struct WastContext {
     module_index_by_name: HashMap<String, ModuleIndex>,
     instance: WasmInstance,
@@ -37,6 +61,14 @@ impl WastContext {
         self.run_buffer(path.to_str().unwrap(), &bytes)
     }
 
+    /// Runs all directives like `run_file`, but records the results of assertions instead of
+    /// stopping at the first failure
+    pub fn run_file_with_report(&mut self, path: &Path) -> Result<WastReport> {
+        let bytes = std::fs::read(path).with_context(|| format!("{}", path.display()))?;
+        let filename = path.to_string_lossy();
+        self.parse_and_run(&filename, &bytes, Self::report_directives)
+    }
+
     pub fn extract_start_section(bytes: &[u8]) -> Result<Option<u32>> {
         let parser = wasmparser::Parser::new(0);
         for payload in parser.parse_all(bytes) {
@@ -77,6 +109,15 @@ impl WastContext {
     }
 
     pub fn run_buffer(&mut self, filename: &str, wast: &[u8]) -> Result<()> {
+        self.parse_and_run(filename, wast, Self::run_directives)
+    }
+
+    fn parse_and_run<T>(
+        &mut self,
+        filename: &str,
+        wast: &[u8],
+        run: impl FnOnce(&mut Self, Vec<wast::WastDirective<'_>>, &str, &str) -> Result<T>,
+    ) -> Result<T> {
         let wast = str::from_utf8(wast)?;
 
         let adjust_wast = |mut err: wast::Error| {
@@ -90,7 +131,84 @@ impl WastContext {
         let buf = wast::parser::ParseBuffer::new_with_lexer(lexer).map_err(adjust_wast)?;
         let ast = wast::parser::parse::<wast::Wast>(&buf).map_err(adjust_wast)?;
 
-        self.run_directives(ast.directives, filename, wast)
+        run(self, ast.directives, filename, wast)
+    }
+
+    fn report_directives(
+        &mut self,
+        directives: Vec<wast::WastDirective<'_>>,
+        filename: &str,
+        wast: &str,
+    ) -> Result<WastReport> {
+        use wast::WastDirective::*;
+        let mut report = WastReport::default();
+        for directive in directives {
+            let (line, column) = directive.span().linecol_in(wast);
+            let (name, result) = match directive {
+                AssertReturn { exec, results, .. } => {
+                    ("assert_return", self.assert_return(exec, results))
+                }
+                AssertTrap { exec, message, .. } => {
+                    ("assert_trap", self.assert_trap(exec, message))
+                }
+                AssertMalformed { module, .. } => {
+                    ("assert_malformed", self.assert_malformed(module))
+                }
+                other => {
+                    self.run_directives(vec![other], filename, wast)?;
+                    continue;
+                }
+            };
+            report.outcomes.push(AssertionOutcome {
+                directive: name,
+                line: line + 1,
+                column,
+                failure: result.err().map(|err| format!("{:#}", err)),
+            });
+        }
+        Ok(report)
+    }
+
+    fn assert_return(
+        &mut self,
+        exec: wast::WastExecute<'_>,
+        results: Vec<wast::WastRet<'_>>,
+    ) -> Result<()> {
+        let values = self.perform_execute(exec)??;
+        if values.len() != results.len() {
+            bail!("expected {} values, got {:?}", results.len(), values)
+        }
+        for (v, e) in values.iter().zip(results) {
+            match &e {
+                wast::WastRet::Core(e) => {
+                    if !val_matches(v, e)? {
+                        bail!("expected {:?}, got {:?}", e, v)
+                    }
+                }
+                wast::WastRet::Component(_) => bail!("component is not supported yet"),
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_trap(&mut self, exec: wast::WastExecute<'_>, message: &str) -> Result<()> {
+        match self.perform_execute(exec)? {
+            Ok(values) => bail!("expected trap, got {:?}", values),
+            Err(trap) => {
+                let result = format!("{}", trap);
+                if !result.contains(message) {
+                    bail!("expected {}, got {}", message, result)
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn assert_malformed(&mut self, module: QuoteWat<'_>) -> Result<()> {
+        match self.module(module) {
+            Ok(()) => bail!("expected module to fail to instantiate"),
+            Err(_) => Ok(()),
+        }
     }
 
     fn run_directives(
@@ -130,49 +248,23 @@ impl WastContext {
                     span,
                     exec,
                     results,
-                } => match self.perform_execute(exec).with_context(|| context(span)) {
-                    Ok(Ok(values)) => {
-                        for (v, e) in values.iter().zip(results) {
-                            match &e {
-                                wast::WastRet::Core(e) => {
-                                    if val_matches(v, e)? {
-                                        continue;
-                                    }
-                                }
-                                wast::WastRet::Component(_) => {
-                                    todo!("component is not supported yet")
-                                }
-                            }
-                            bail!("expected {:?}, got {:?} {}", e, v, context(span))
-                        }
-                    }
-                    Ok(Err(e)) => panic!("unexpected err: {}, {}", e, context(span)),
-                    Err(e) => panic!("unexpected err: {}", e),
-                },
+                } => self
+                    .assert_return(exec, results)
+                    .with_context(|| context(span))?,
                 AssertTrap {
                     span,
                     exec,
                     message,
-                } => match self.perform_execute(exec).with_context(|| context(span)) {
-                    Ok(Ok(values)) => panic!("{}\nexpected trap, got {:?}", context(span), values),
-                    Ok(Err(t)) => {
-                        let result = format!("{}", t);
-                        if result.contains(message) {
-                            continue;
-                        }
-                        panic!("{}\nexpected {}, got {}", context(span), message, result,)
-                    }
-                    Err(err) => panic!("got wast level exception: {}", err),
-                },
+                } => self
+                    .assert_trap(exec, message)
+                    .with_context(|| context(span))?,
                 AssertMalformed {
                     span,
                     module,
                     message: _,
-                } => {
-                    if let Ok(()) = self.module(module) {
-                        panic!("{}\nexpected module to fail to instantiate", context(span))
-                    };
-                }
+                } => self
+                    .assert_malformed(module)
+                    .with_context(|| context(span))?,
                 AssertUnlinkable {
                     span,
                     module,
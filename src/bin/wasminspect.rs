@@ -28,18 +28,78 @@ struct Opts {
     /// Tells the debugger to read in and execute the debugger commands in given file, after wasm file has been loaded
     #[structopt(short, long)]
     source: Option<String>,
+    /// Provide WASI (wasi_snapshot_preview1 and wasi_unstable) to the program. This is the default
+    #[structopt(long)]
+    wasi: bool,
+    /// Do not provide WASI to the program
+    #[structopt(long, conflicts_with = "wasi")]
+    no_wasi: bool,
     /// Grant access to a guest directory mapped as a host directory
-    #[structopt(long = "mapdir", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR", parse(try_from_str = parse_map_dirs))]
+    #[structopt(long = "mapdir", conflicts_with = "no-wasi", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR", parse(try_from_str = parse_map_dirs))]
     map_dirs: Vec<(String, String)>,
 
     /// Pass an environment variable to the program
-    #[structopt(long = "env", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
+    #[structopt(long = "env", conflicts_with = "no-wasi", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
     envs: Vec<(String, String)>,
+
+    /// Launch the split-pane terminal interface
+    #[cfg(feature = "tui")]
+    #[structopt(long)]
+    tui: bool,
+}
+
+/// Attach the debugger to a running debugger server session
+#[cfg(feature = "remote-api")]
+#[derive(StructOpt)]
+struct AttachOpts {
+    /// The address of the server (e.g. 127.0.0.1:4000)
+    #[structopt(name = "ADDR")]
+    addr: String,
+}
+
+/// Run assertions of a WebAssembly spec test script
+#[derive(StructOpt)]
+struct TestOpts {
+    /// The .wast script file
+    #[structopt(name = "FILE")]
+    filepath: String,
+}
+
+/// Returns whether all assertions passed
+fn run_spec_test(filepath: &str) -> anyhow::Result<bool> {
+    let mut context = wast_spec::WastContext::new(wasminspect_vm::Config::default());
+    let report = context.run_file_with_report(std::path::Path::new(filepath))?;
+    for outcome in &report.outcomes {
+        let location = format!("{}:{}:{}", filepath, outcome.line, outcome.column);
+        match &outcome.failure {
+            None => println!("PASS {} {}", location, outcome.directive),
+            Some(reason) => println!("FAIL {} {}\n  {}", location, outcome.directive, reason),
+        }
+    }
+    println!("{} passed, {} failed", report.passed(), report.failed());
+    Ok(report.failed() == 0)
 }
 
 fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
 
+    #[cfg(feature = "remote-api")]
+    if std::env::args().nth(1).as_deref() == Some("attach") {
+        let opts = AttachOpts::from_iter(std::env::args().skip(1));
+        if let Err(err) = wasminspect_debugger::attach(&opts.addr) {
+            println!("{:?}", err)
+        }
+        return Ok(());
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("test") {
+        let opts = TestOpts::from_iter(std::env::args().skip(1));
+        if !run_spec_test(&opts.filepath)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let opts = Opts::from_args();
     let module_input = match opts.filepath {
         Some(filepath) => {
@@ -60,8 +120,19 @@ fn main() -> anyhow::Result<()> {
         }
         None => None,
     };
+    // `--wasi` only spells out the default, and `--no-wasi` conflicts with it
+    let wasi = opts.wasi || !opts.no_wasi;
+    #[cfg(feature = "tui")]
+    if opts.tui {
+        if let Err(err) =
+            wasminspect_debugger::run_tui(module_input, opts.source, opts.map_dirs, opts.envs, wasi)
+        {
+            println!("{:?}", err)
+        }
+        return Ok(());
+    }
     if let Err(err) =
-        wasminspect_debugger::run_loop(module_input, opts.source, opts.map_dirs, opts.envs)
+        wasminspect_debugger::run_loop(module_input, opts.source, opts.map_dirs, opts.envs, wasi)
     {
         println!("{:?}", err)
     }